@@ -16,7 +16,7 @@ export function formatDiagnostics(diagnostics, source, filename = 'input.htms')
   const output = [];
 
   for (const diag of diagnostics) {
-    const { severity, message, location, code } = diag;
+    const { severity, message, location, code, file } = diag;
     const { line, column } = location;
 
     // Header with severity
@@ -28,7 +28,7 @@ export function formatDiagnostics(diagnostics, source, filename = 'input.htms')
     output.push(`${severityStr}${codeStr}: ${message}`);
 
     // Location
-    output.push(pc.cyan(`  --> ${filename}:${line}:${column}`));
+    output.push(pc.cyan(`  --> ${file ?? filename}:${line}:${column}`));
     output.push('');
 
     // Source context (line before, error line, line after)
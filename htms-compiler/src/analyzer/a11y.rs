@@ -0,0 +1,367 @@
+//! Accessibility lints
+//!
+//! Flags common markup mistakes that make pages hard to use with assistive
+//! technology. Every finding is a warning in the `A0xx` code range.
+
+use std::collections::HashSet;
+
+use crate::ast::*;
+use crate::{Diagnostic, DiagnosticCode, Location};
+use super::actions::edit_distance;
+
+/// WAI-ARIA 1.2 roles authors may use, with the DPUB-ARIA (`doc-*`) and
+/// Graphics-ARIA (`graphics-*`) module roles
+const ARIA_ROLES: &[&str] = &[
+    "alert", "alertdialog", "application", "article", "banner", "blockquote", "button", "caption",
+    "cell", "checkbox", "code", "columnheader", "combobox", "complementary", "contentinfo",
+    "definition", "deletion", "dialog", "directory", "document", "emphasis", "feed", "figure",
+    "form", "generic", "grid", "gridcell", "group", "heading", "img", "insertion", "link", "list",
+    "listbox", "listitem", "log", "main", "marquee", "math", "menu", "menubar", "menuitem",
+    "menuitemcheckbox", "menuitemradio", "meter", "navigation", "none", "note", "option",
+    "paragraph", "presentation", "progressbar", "radio", "radiogroup", "region", "row", "rowgroup",
+    "rowheader", "scrollbar", "search", "searchbox", "separator", "slider", "spinbutton", "status",
+    "strong", "subscript", "superscript", "switch", "tab", "table", "tablist", "tabpanel", "term",
+    "textbox", "time", "timer", "toolbar", "tooltip", "tree", "treegrid", "treeitem",
+    "doc-abstract", "doc-acknowledgments", "doc-afterword", "doc-appendix", "doc-backlink",
+    "doc-biblioentry", "doc-bibliography", "doc-biblioref", "doc-chapter", "doc-colophon",
+    "doc-conclusion", "doc-cover", "doc-credit", "doc-credits", "doc-dedication", "doc-endnote",
+    "doc-endnotes", "doc-epigraph", "doc-epilogue", "doc-errata", "doc-example", "doc-footnote",
+    "doc-foreword", "doc-glossary", "doc-glossref", "doc-index", "doc-introduction", "doc-noteref",
+    "doc-notice", "doc-pagebreak", "doc-pagefooter", "doc-pageheader", "doc-pagelist", "doc-part",
+    "doc-preface", "doc-prologue", "doc-pullquote", "doc-qna", "doc-subtitle", "doc-tip", "doc-toc",
+    "graphics-document", "graphics-object", "graphics-symbol",
+];
+
+/// WAI-ARIA 1.2 abstract roles, which only organize the taxonomy and are
+/// never valid in markup
+const ABSTRACT_ROLES: &[&str] = &[
+    "command", "composite", "input", "landmark", "range", "roletype", "section", "sectionhead",
+    "select", "structure", "widget", "window",
+];
+
+/// WAI-ARIA 1.2 states and properties
+const ARIA_ATTRIBUTES: &[&str] = &[
+    "aria-activedescendant", "aria-atomic", "aria-autocomplete", "aria-braillelabel",
+    "aria-brailleroledescription", "aria-busy", "aria-checked", "aria-colcount", "aria-colindex",
+    "aria-colindextext", "aria-colspan", "aria-controls", "aria-current", "aria-describedby",
+    "aria-description", "aria-details", "aria-disabled", "aria-dropeffect", "aria-errormessage",
+    "aria-expanded", "aria-flowto", "aria-grabbed", "aria-haspopup", "aria-hidden", "aria-invalid",
+    "aria-keyshortcuts", "aria-label", "aria-labelledby", "aria-level", "aria-live", "aria-modal",
+    "aria-multiline", "aria-multiselectable", "aria-orientation", "aria-owns", "aria-placeholder",
+    "aria-posinset", "aria-pressed", "aria-readonly", "aria-relevant", "aria-required",
+    "aria-roledescription", "aria-rowcount", "aria-rowindex", "aria-rowindextext", "aria-rowspan",
+    "aria-selected", "aria-setsize", "aria-sort", "aria-valuemax", "aria-valuemin", "aria-valuenow",
+    "aria-valuetext",
+];
+
+/// Run the accessibility lints over every declaration
+pub fn lint_a11y(program: &Program) -> Vec<Diagnostic> {
+    let mut linter = A11yLinter {
+        labelled_ids: HashSet::new(),
+        diagnostics: Vec::new(),
+    };
+
+    for decl in &program.body {
+        linter.collect_label_targets(declaration_body(decl));
+    }
+    for decl in &program.body {
+        linter.lint_nodes(declaration_body(decl), false);
+    }
+
+    linter.diagnostics
+}
+
+struct A11yLinter {
+    /// Ids referenced by `label [for: "..."]` anywhere in the program
+    labelled_ids: HashSet<String>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl A11yLinter {
+    fn collect_label_targets(&mut self, nodes: &[Node]) {
+        for_each_element(nodes, &mut |el| {
+            if el.tag == "label" {
+                if let Some(Expression::String(target)) = attribute(el, "for") {
+                    self.labelled_ids.insert(target.value.clone());
+                }
+            }
+        });
+    }
+
+    fn lint_nodes(&mut self, nodes: &[Node], in_label: bool) {
+        for node in nodes {
+            match node {
+                Node::Element(el) => {
+                    self.lint_element(el, in_label);
+                    self.lint_nodes(&el.children, in_label || el.tag == "label");
+                }
+                Node::ComponentRef(r) => {
+                    self.lint_nodes(&r.children, in_label);
+                    for fill in &r.slot_fills {
+                        self.lint_nodes(&fill.children, in_label);
+                    }
+                }
+                Node::If(stmt) => self.lint_if(stmt, in_label),
+                Node::Each(stmt) => {
+                    self.lint_nodes(&stmt.body, in_label);
+                    if let Some(empty) = &stmt.empty {
+                        self.lint_nodes(empty, in_label);
+                    }
+                }
+                Node::Text(_) | Node::Slot(_) | Node::Let(_) | Node::Comment(_) | Node::RawHtml(_) => {}
+            }
+        }
+    }
+
+    fn lint_if(&mut self, stmt: &IfStatement, in_label: bool) {
+        self.lint_nodes(&stmt.consequent, in_label);
+        match &stmt.alternate {
+            Some(Alternate::Block(nodes)) => self.lint_nodes(nodes, in_label),
+            Some(Alternate::ElseIf(elif)) => self.lint_if(elif, in_label),
+            None => {}
+        }
+    }
+
+    fn lint_element(&mut self, el: &Element, in_label: bool) {
+        self.check_aria(el);
+
+        // A spread may supply any of the attributes checked below
+        if el.attributes.iter().any(|a| matches!(a.value, Expression::Spread(_))) {
+            return;
+        }
+
+        match el.tag.as_str() {
+            "img" if attribute(el, "alt").is_none() => {
+                self.warning(
+                    DiagnosticCode::MissingAlt,
+                    "<img> is missing an 'alt' attribute - use alt: \"\" for decorative images",
+                    el.loc,
+                );
+            }
+            "a" if attribute(el, "href").is_none() => {
+                self.warning(
+                    DiagnosticCode::MissingHref,
+                    "<a> without 'href' is not focusable - use a button for actions",
+                    el.loc,
+                );
+            }
+            "input" if !in_label && !is_self_labelled_input(el) && !self.has_label(el) => {
+                self.warning(
+                    DiagnosticCode::UnlabelledInput,
+                    "<input> has no label - wrap it in a label, point a label's 'for' at its id, or add 'aria-label'",
+                    el.loc,
+                );
+            }
+            "button" if !has_aria_label(el) && !has_text_content(&el.children) => {
+                self.warning(
+                    DiagnosticCode::EmptyButton,
+                    "<button> has no text content - add text or an 'aria-label'",
+                    el.loc,
+                );
+            }
+            _ => {}
+        }
+    }
+
+    /// Check literal `role` values and `aria-*` names against WAI-ARIA
+    fn check_aria(&mut self, el: &Element) {
+        for attr in &el.attributes {
+            if attr.name == "role" {
+                // A role may list fallbacks: role: "switch checkbox"
+                let Expression::String(roles) = &attr.value else {
+                    continue;
+                };
+                for role in roles.value.split_whitespace() {
+                    if ABSTRACT_ROLES.contains(&role) {
+                        let message = format!("ARIA role '{}' is abstract and can't be used in markup", role);
+                        self.warning(DiagnosticCode::UnknownRole, &message, attr.loc);
+                    } else if !ARIA_ROLES.contains(&role) {
+                        let message = with_suggestion(format!("Unknown ARIA role '{}'", role), role, ARIA_ROLES);
+                        self.warning(DiagnosticCode::UnknownRole, &message, attr.loc);
+                    }
+                }
+            } else if attr.name.starts_with("aria-") && !ARIA_ATTRIBUTES.contains(&attr.name.as_str()) {
+                let message = with_suggestion(format!("Unknown ARIA attribute '{}'", attr.name), &attr.name, ARIA_ATTRIBUTES);
+                self.warning(DiagnosticCode::UnknownAriaAttribute, &message, attr.loc);
+            }
+        }
+    }
+
+    fn has_label(&self, el: &Element) -> bool {
+        if has_aria_label(el) {
+            return true;
+        }
+        match attribute(el, "id") {
+            Some(Expression::String(id)) => self.labelled_ids.contains(&id.value),
+            // A dynamic id may well match a label, so give it the benefit of the doubt
+            Some(_) => true,
+            None => false,
+        }
+    }
+
+    fn warning(&mut self, code: DiagnosticCode, message: &str, location: Location) {
+        self.diagnostics.push(Diagnostic::new(code, message, location));
+    }
+}
+
+fn declaration_body(decl: &Declaration) -> &[Node] {
+    match decl {
+        Declaration::Component(c) => &c.body,
+        Declaration::Section(s) => &s.body,
+        Declaration::Layout(l) => &l.body,
+        Declaration::Page(p) => &p.body,
+    }
+}
+
+fn for_each_element(nodes: &[Node], f: &mut impl FnMut(&Element)) {
+    for node in nodes {
+        match node {
+            Node::Element(el) => {
+                f(el);
+                for_each_element(&el.children, f);
+            }
+            Node::ComponentRef(r) => {
+                for_each_element(&r.children, f);
+                for fill in &r.slot_fills {
+                    for_each_element(&fill.children, f);
+                }
+            }
+            Node::If(stmt) => {
+                for_each_element(&stmt.consequent, f);
+                let mut alternate = stmt.alternate.as_ref();
+                while let Some(alt) = alternate {
+                    match alt {
+                        Alternate::Block(nodes) => {
+                            for_each_element(nodes, f);
+                            alternate = None;
+                        }
+                        Alternate::ElseIf(elif) => {
+                            for_each_element(&elif.consequent, f);
+                            alternate = elif.alternate.as_ref();
+                        }
+                    }
+                }
+            }
+            Node::Each(stmt) => {
+                for_each_element(&stmt.body, f);
+                if let Some(empty) = &stmt.empty {
+                    for_each_element(empty, f);
+                }
+            }
+            Node::Text(_) | Node::Slot(_) | Node::Let(_) | Node::Comment(_) | Node::RawHtml(_) => {}
+        }
+    }
+}
+
+/// `message`, naming the entry of `known` one edit away from `name` if there is one
+fn with_suggestion(message: String, name: &str, known: &[&str]) -> String {
+    match known.iter().find(|candidate| edit_distance(name, candidate) == 1) {
+        Some(suggestion) => format!("{} - did you mean '{}'?", message, suggestion),
+        None => message,
+    }
+}
+
+fn attribute<'a>(el: &'a Element, name: &str) -> Option<&'a Expression> {
+    el.attributes.iter().find(|a| a.name == name).map(|a| &a.value)
+}
+
+fn has_aria_label(el: &Element) -> bool {
+    attribute(el, "aria-label").is_some() || attribute(el, "aria-labelledby").is_some()
+}
+
+/// Inputs whose type gives them a visible label or no visible control at all
+fn is_self_labelled_input(el: &Element) -> bool {
+    matches!(
+        attribute(el, "type"),
+        Some(Expression::String(t)) if matches!(t.value.as_str(), "hidden" | "submit" | "reset" | "button" | "image")
+    )
+}
+
+/// Whether any descendant could render text (components and slots are assumed to)
+fn has_text_content(nodes: &[Node]) -> bool {
+    nodes.iter().any(|node| match node {
+        Node::Text(t) => t.is_dynamic || !t.content.trim().is_empty(),
+        Node::Element(el) => has_text_content(&el.children) || (el.tag == "img" && attribute(el, "alt").is_some()),
+        Node::ComponentRef(_) | Node::Slot(_) | Node::RawHtml(_) => true,
+        Node::If(stmt) => {
+            has_text_content(&stmt.consequent)
+                || match &stmt.alternate {
+                    Some(Alternate::Block(nodes)) => has_text_content(nodes),
+                    Some(Alternate::ElseIf(elif)) => has_text_content(&elif.consequent),
+                    None => false,
+                }
+        }
+        Node::Each(stmt) => {
+            has_text_content(&stmt.body) || stmt.empty.as_deref().is_some_and(has_text_content)
+        }
+        Node::Let(_) | Node::Comment(_) => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+
+    fn lint_source(source: &str) -> Vec<String> {
+        let ast = parse(&tokenize(source).unwrap()).unwrap();
+        lint_a11y(&ast)
+            .into_iter()
+            .map(|d| d.code.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_flags_each_rule() {
+        let codes = lint_source(r#"
+            page home "/" {
+                img [src: "/logo.png"]
+                a {{ Home }}
+                input [type: "text"]
+                button [class: "close"] { }
+            }
+        "#);
+        assert_eq!(codes, vec!["A001", "A002", "A003", "A004"]);
+    }
+
+    #[test]
+    fn test_accessible_markup_passes() {
+        let codes = lint_source(r##"
+            page home "/" {
+                img [src: "/logo.png", alt: ""]
+                a [href: "#/"] {{ Home }}
+                label [for: "email"] {{ Email }}
+                input [id: "email", type: "email"]
+                label { input [type: "checkbox"] }
+                input [type: "text", aria-label: "Search"]
+                input [type: "submit", value: "Go"]
+                button { span {{ Save }} }
+                button [aria-label: "Close"] { }
+            }
+        "##);
+        assert!(codes.is_empty(), "unexpected {:?}", codes);
+    }
+
+    #[test]
+    fn test_aria_roles_and_attributes() {
+        let ast = parse(&tokenize(r#"
+            page home "/" {
+                div [role: "buton", aria-lable: "Close", aria-hidden: "true"] {{ x }}
+                div [role: "switch checkbox", aria-checked: "false", aria-bogus: "x"] {{ y }}
+                div [role: ctx.role] {{ z }}
+                article [role: "doc-chapter"] { svg [role: "graphics-document"] { } }
+                div [role: "widget"] {{ w }}
+                div [role: "doc-chaptr"] {{ c }}
+            }
+        "#).unwrap()).unwrap();
+        let messages: Vec<_> = lint_a11y(&ast).into_iter().map(|d| (d.code.unwrap(), d.message)).collect();
+        assert_eq!(messages, vec![
+            ("A005".to_string(), "Unknown ARIA role 'buton' - did you mean 'button'?".to_string()),
+            ("A006".to_string(), "Unknown ARIA attribute 'aria-lable' - did you mean 'aria-label'?".to_string()),
+            ("A006".to_string(), "Unknown ARIA attribute 'aria-bogus'".to_string()),
+            ("A005".to_string(), "ARIA role 'widget' is abstract and can't be used in markup".to_string()),
+            ("A005".to_string(), "Unknown ARIA role 'doc-chaptr' - did you mean 'doc-chapter'?".to_string()),
+        ]);
+    }
+}
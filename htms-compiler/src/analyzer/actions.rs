@@ -0,0 +1,172 @@
+//! Action name typo check
+//!
+//! Actions are supplied by the host application, so there is nothing to
+//! resolve an event handler's name against. Within a program, though, an
+//! action used once whose name is a small edit away from one used more
+//! often is most likely a misspelling of it.
+
+use std::collections::HashMap;
+
+use crate::ast::*;
+use crate::{Diagnostic, DiagnosticCode, Location};
+
+/// Warn (`W049`) on one-off action names that look like typos of another action
+pub fn check_action_names(program: &Program) -> Vec<Diagnostic> {
+    let mut uses: Vec<(&str, Location)> = Vec::new();
+    for decl in &program.body {
+        let body = match decl {
+            Declaration::Component(c) => &c.body,
+            Declaration::Section(s) => &s.body,
+            Declaration::Layout(l) => &l.body,
+            Declaration::Page(p) => &p.body,
+        };
+        collect_actions(body, &mut uses);
+    }
+
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for (name, _) in &uses {
+        *counts.entry(name).or_default() += 1;
+    }
+
+    let mut diagnostics = Vec::new();
+    for (name, loc) in &uses {
+        if counts[name] != 1 {
+            continue;
+        }
+        // Prefer the most used candidate, then the alphabetically first for stable output
+        let suggestion = counts.iter()
+            .filter(|(other, &count)| count > 1 && is_likely_typo(name, other))
+            .max_by(|(a, count_a), (b, count_b)| count_a.cmp(count_b).then(b.cmp(a)))
+            .map(|(other, _)| *other);
+
+        if let Some(suggestion) = suggestion {
+            diagnostics.push(Diagnostic::new(
+                DiagnosticCode::ActionTypo,
+                format!("Action '{}' is used only once - did you mean '{}'?", name, suggestion),
+                *loc,
+            ));
+        }
+    }
+    diagnostics
+}
+
+fn collect_actions<'a>(nodes: &'a [Node], uses: &mut Vec<(&'a str, Location)>) {
+    for node in nodes {
+        match node {
+            Node::Element(el) => {
+                for attr in el.attributes.iter().filter(|a| a.name.starts_with("on")) {
+                    match &attr.value {
+                        Expression::Identifier(id) => uses.push((&id.name, id.loc)),
+                        Expression::Call(call) => uses.push((&call.callee, call.loc)),
+                        _ => {}
+                    }
+                }
+                collect_actions(&el.children, uses);
+            }
+            Node::ComponentRef(r) => {
+                collect_actions(&r.children, uses);
+                for fill in &r.slot_fills {
+                    collect_actions(&fill.children, uses);
+                }
+            }
+            Node::If(stmt) => collect_if(stmt, uses),
+            Node::Each(stmt) => {
+                collect_actions(&stmt.body, uses);
+                if let Some(empty) = &stmt.empty {
+                    collect_actions(empty, uses);
+                }
+            }
+            Node::Text(_) | Node::Slot(_) | Node::Let(_) | Node::Comment(_) | Node::RawHtml(_) => {}
+        }
+    }
+}
+
+fn collect_if<'a>(stmt: &'a IfStatement, uses: &mut Vec<(&'a str, Location)>) {
+    collect_actions(&stmt.consequent, uses);
+    match &stmt.alternate {
+        Some(Alternate::Block(nodes)) => collect_actions(nodes, uses),
+        Some(Alternate::ElseIf(elif)) => collect_if(elif, uses),
+        None => {}
+    }
+}
+
+/// Only long names get two edits of slack: `share` and `save` are both plausible actions
+fn is_likely_typo(name: &str, other: &str) -> bool {
+    let max_distance = if name.chars().count() >= 8 { 2 } else { 1 };
+    name != other && edit_distance(name, other) <= max_distance
+}
+
+/// Optimal string alignment distance: Levenshtein plus adjacent transpositions,
+/// so `submti` is one edit from `submit`
+pub(super) fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+
+    fn check(source: &str) -> Vec<Diagnostic> {
+        let ast = parse(&tokenize(source).unwrap()).unwrap();
+        check_action_names(&ast)
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("submit", "submti"), 1);
+        assert_eq!(edit_distance("save", "same"), 1);
+        assert_eq!(edit_distance("share", "save"), 2);
+    }
+
+    #[test]
+    fn test_one_off_typo_is_flagged() {
+        let diagnostics = check(r#"
+            page home "/" {
+                form [onSubmit: submit] { button [onClick: submit(1)] {{ Go }} }
+                button [onClick: submti] {{ Again }}
+            }
+        "#);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code.as_deref(), Some("W049"));
+        assert_eq!(diagnostics[0].message, "Action 'submti' is used only once - did you mean 'submit'?");
+    }
+
+    #[test]
+    fn test_distinct_actions_pass() {
+        let diagnostics = check(r#"
+            page home "/" {
+                button [onClick: open] {{ Open }}
+                button [onClick: close] {{ Close }}
+                button [onClick: save] {{ Save }}
+                button [onClick: save] {{ Save again }}
+                button [onClick: share] {{ Share }}
+            }
+        "#);
+
+        assert!(diagnostics.is_empty(), "unexpected {:?}", diagnostics);
+    }
+}
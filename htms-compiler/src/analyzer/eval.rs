@@ -0,0 +1,262 @@
+//! Compile-time evaluation of constant expressions
+
+use std::fmt;
+
+use crate::ast::{BinaryOp, Expression, MAX_SAFE_INTEGER};
+
+/// Value of an expression known at compile time
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstValue {
+    String(String),
+    Number(f64),
+    Boolean(bool),
+}
+
+impl fmt::Display for ConstValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConstValue::String(s) => f.write_str(s),
+            ConstValue::Number(n) => f.write_str(&format_number(*n)),
+            ConstValue::Boolean(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+/// Format a computed number without float noise: `3` not `3.0`, `0.3` not
+/// `0.30000000000000004`
+///
+/// Literals keep their source text (`NumberLiteral::raw`); this is only for
+/// values produced by folding.
+pub fn format_number(value: f64) -> String {
+    if !value.is_finite() {
+        return value.to_string();
+    }
+    let rounded = format!("{:.12}", value);
+    let trimmed = rounded.trim_end_matches('0').trim_end_matches('.');
+    if trimmed == "-0" {
+        "0".to_string()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+/// Evaluate an expression built only from literals
+///
+/// Returns `None` when the expression depends on runtime data, or when
+/// folding would hide a mistake (division or modulo by zero) that should
+/// be left in the output for the runtime to handle.
+pub fn eval_const(expr: &Expression) -> Option<ConstValue> {
+    eval(expr, false)
+}
+
+/// Evaluate an expression the way static HTML output sees it, with an empty
+/// context
+///
+/// Everything read from the context is undefined there, so the default of
+/// `ctx.title ?? "Untitled"` is known: `"Untitled"`.
+pub fn eval_static(expr: &Expression) -> Option<ConstValue> {
+    eval(expr, true)
+}
+
+/// Most numbers a range expands to where it is unrolled: in static HTML
+/// output and when rendering with context
+pub const MAX_RANGE_LENGTH: u64 = 10_000;
+
+/// How many numbers a range steps through, or `None` when a bound isn't a
+/// safe integer, past which adding one no longer moves the count
+pub fn range_length(start: f64, end: f64, inclusive: bool) -> Option<u64> {
+    let safe = |n: f64| n.fract() == 0.0 && n.abs() <= MAX_SAFE_INTEGER as f64;
+    if !safe(start) || !safe(end) {
+        return None;
+    }
+    let last = if inclusive { end } else { end - 1.0 };
+    Some(if last < start { 0 } else { (last - start) as u64 + 1 })
+}
+
+/// The numbers a range steps through, one apart from `start`, as the
+/// counted loop generated for it does
+///
+/// A range whose bounds aren't safe integers is empty, and a longer one
+/// stops after `MAX_RANGE_LENGTH` numbers.
+pub fn range_values(start: f64, end: f64, inclusive: bool) -> Vec<f64> {
+    let length = range_length(start, end, inclusive).unwrap_or(0).min(MAX_RANGE_LENGTH);
+    (0..length).map(|i| start + i as f64).collect()
+}
+
+fn eval(expr: &Expression, empty_context: bool) -> Option<ConstValue> {
+    match expr {
+        Expression::String(s) => Some(ConstValue::String(s.value.clone())),
+        Expression::Number(n) => Some(ConstValue::Number(n.value)),
+        Expression::Boolean(b) => Some(ConstValue::Boolean(b.value)),
+        // Constants are never null, so the left side wins when it folds
+        Expression::Binary(bin) if bin.operator == BinaryOp::NullishCoalesce => {
+            match eval(&bin.left, empty_context) {
+                Some(value) => Some(value),
+                None if matches!(*bin.left, Expression::Null(_)) => eval(&bin.right, empty_context),
+                None if empty_context && reads_missing_value(&bin.left) => eval(&bin.right, empty_context),
+                None => None,
+            }
+        }
+        // `&&` and `||` short-circuit, so a boolean on the left settles them
+        // without the right side being known
+        Expression::Binary(bin) if matches!(bin.operator, BinaryOp::And | BinaryOp::Or) => {
+            match (bin.operator, eval(&bin.left, empty_context)?) {
+                (BinaryOp::And, ConstValue::Boolean(false)) => Some(ConstValue::Boolean(false)),
+                (BinaryOp::Or, ConstValue::Boolean(true)) => Some(ConstValue::Boolean(true)),
+                (_, ConstValue::Boolean(_)) => eval(&bin.right, empty_context),
+                _ => None,
+            }
+        }
+        Expression::Binary(bin) => {
+            eval_binary(bin.operator, eval(&bin.left, empty_context)?, eval(&bin.right, empty_context)?)
+        }
+        Expression::Ternary(t) => match eval(&t.condition, empty_context)? {
+            ConstValue::Boolean(true) => eval(&t.consequent, empty_context),
+            ConstValue::Boolean(false) => eval(&t.alternate, empty_context),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether an expression only reads a value, which an empty context leaves
+/// undefined; anything computed from one, such as `ctx.a || "b"`, may not be
+fn reads_missing_value(expr: &Expression) -> bool {
+    match expr {
+        Expression::ContextPath(_) | Expression::Identifier(_) => true,
+        Expression::OptionalMember(m) => reads_missing_value(&m.object),
+        _ => false,
+    }
+}
+
+fn eval_binary(op: BinaryOp, left: ConstValue, right: ConstValue) -> Option<ConstValue> {
+    use ConstValue::*;
+
+    let value = match (op, left, right) {
+        (BinaryOp::Add, Number(l), Number(r)) => Number(l + r),
+        // A string on either side makes `+` concatenate, as in JavaScript
+        (BinaryOp::Add, l @ String(_), r) | (BinaryOp::Add, l, r @ String(_)) => String(format!("{}{}", l, r)),
+        (BinaryOp::Sub, Number(l), Number(r)) => Number(l - r),
+        (BinaryOp::Mul, Number(l), Number(r)) => Number(l * r),
+        (BinaryOp::Div, Number(l), Number(r)) if r != 0.0 => Number(l / r),
+        (BinaryOp::Mod, Number(l), Number(r)) if r != 0.0 => Number(l % r),
+        // Equality only folds between values of the same type, where `===` and `==` agree
+        (BinaryOp::Eq, l, r) if same_type(&l, &r) => Boolean(l == r),
+        (BinaryOp::Ne, l, r) if same_type(&l, &r) => Boolean(l != r),
+        (BinaryOp::Lt, Number(l), Number(r)) => Boolean(l < r),
+        (BinaryOp::Le, Number(l), Number(r)) => Boolean(l <= r),
+        (BinaryOp::Gt, Number(l), Number(r)) => Boolean(l > r),
+        (BinaryOp::Ge, Number(l), Number(r)) => Boolean(l >= r),
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn same_type(left: &ConstValue, right: &ConstValue) -> bool {
+    std::mem::discriminant(left) == std::mem::discriminant(right)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{Declaration, Node};
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+
+    fn eval_attr(value: &str) -> Option<ConstValue> {
+        eval_attr_with(value, eval_const)
+    }
+
+    fn eval_attr_with(value: &str, eval: fn(&Expression) -> Option<ConstValue>) -> Option<ConstValue> {
+        let source = format!("component Test {{ div [data-n: {}] }}", value);
+        let ast = parse(&tokenize(&source).unwrap()).unwrap();
+        match &ast.body[0] {
+            Declaration::Component(c) => match &c.body[0] {
+                Node::Element(e) => eval(&e.attributes[0].value),
+                _ => panic!("Expected element"),
+            },
+            _ => panic!("Expected component"),
+        }
+    }
+
+    #[test]
+    fn test_fold_modulo() {
+        assert_eq!(eval_attr("5 % 2"), Some(ConstValue::Number(1.0)));
+        assert_eq!(eval_attr("1 + 5 % 2 * 3"), Some(ConstValue::Number(4.0)));
+    }
+
+    #[test]
+    fn test_computed_numbers_format_cleanly() {
+        assert_eq!(eval_attr("0.1 + 0.2").unwrap().to_string(), "0.3");
+        assert_eq!(eval_attr("2 * 1.5").unwrap().to_string(), "3");
+        assert_eq!(eval_attr("1 / 4").unwrap().to_string(), "0.25");
+    }
+
+    #[test]
+    fn test_modulo_by_zero_is_not_folded() {
+        assert_eq!(eval_attr("5 % 0"), None);
+    }
+
+    #[test]
+    fn test_fold_comparisons() {
+        assert_eq!(eval_attr("10 * 8 > 64"), Some(ConstValue::Boolean(true)));
+        assert_eq!(eval_attr("\"a\" == \"b\""), Some(ConstValue::Boolean(false)));
+        assert_eq!(eval_attr("1 < 2 && 2 <= 2"), Some(ConstValue::Boolean(true)));
+        assert_eq!(eval_attr("1 == \"1\""), None);
+        assert_eq!(eval_attr("\"a\" + \"b\""), Some(ConstValue::String("ab".to_string())));
+        assert_eq!(eval_attr("\"n\" + 1 + 2"), Some(ConstValue::String("n12".to_string())));
+        assert_eq!(eval_attr("1 + 2 + \"px\""), Some(ConstValue::String("3px".to_string())));
+    }
+
+    #[test]
+    fn test_fold_ternary_with_literal_condition() {
+        assert_eq!(eval_attr("true ? \"on\" : \"off\""), Some(ConstValue::String("on".to_string())));
+        assert_eq!(eval_attr("2 > 1 ? \"on\" : \"off\""), Some(ConstValue::String("on".to_string())));
+        assert_eq!(eval_attr("false ? \"on\" : null"), None);
+        assert_eq!(eval_attr("ctx.active ? \"on\" : \"off\""), None);
+    }
+
+    #[test]
+    fn test_fold_nullish_coalescing() {
+        assert_eq!(eval_attr("null ?? \"n\""), Some(ConstValue::String("n".to_string())));
+        assert_eq!(eval_attr("1 ?? 2"), Some(ConstValue::Number(1.0)));
+        assert_eq!(eval_attr("ctx.name ?? \"n\""), None);
+    }
+
+    #[test]
+    fn test_static_nullish_coalescing_only_defaults_reads() {
+        let eval = |value| eval_attr_with(value, eval_static);
+        assert_eq!(eval("ctx.name ?? \"n\""), Some(ConstValue::String("n".to_string())));
+        assert_eq!(eval("ctx.user?.name ?? \"n\""), Some(ConstValue::String("n".to_string())));
+        assert_eq!(eval("title ?? \"n\""), Some(ConstValue::String("n".to_string())));
+        // A computed left side may be defined even when the context is empty
+        assert_eq!(eval("(ctx.a || \"b\") ?? \"c\""), None);
+        assert_eq!(eval("ctx.name + \"!\" ?? \"fallback\""), None);
+    }
+
+    #[test]
+    fn test_fold_short_circuit() {
+        assert_eq!(eval_attr("false && ctx.ready"), Some(ConstValue::Boolean(false)));
+        assert_eq!(eval_attr("true || ctx.ready"), Some(ConstValue::Boolean(true)));
+        assert_eq!(eval_attr("true && \"yes\""), Some(ConstValue::String("yes".to_string())));
+        assert_eq!(eval_attr("true && ctx.ready"), None);
+        assert_eq!(eval_attr("ctx.ready && false"), None);
+    }
+
+    #[test]
+    fn test_range_values() {
+        assert_eq!(range_values(1.0, 3.0, true), vec![1.0, 2.0, 3.0]);
+        assert_eq!(range_values(0.0, 3.0, false), vec![0.0, 1.0, 2.0]);
+        assert!(range_values(3.0, 1.0, true).is_empty());
+        assert_eq!(range_length(1.0, 2_000_000.0, true), Some(2_000_000));
+        assert_eq!(range_values(1.0, 2_000_000.0, true).len() as u64, MAX_RANGE_LENGTH);
+        assert_eq!(range_length(9_007_199_254_740_992.0, 9_007_199_254_740_993.0, true), None);
+        assert_eq!(range_length(0.5, 3.0, true), None);
+        assert!(range_values(9_007_199_254_740_992.0, 9_007_199_254_740_993.0, true).is_empty());
+    }
+
+    #[test]
+    fn test_runtime_operand_is_not_folded() {
+        assert_eq!(eval_attr("ctx.count % 2"), None);
+    }
+}
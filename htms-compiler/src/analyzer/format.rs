@@ -0,0 +1,183 @@
+//! Output-format-aware checks
+//!
+//! Some constructs compile fine but lose their meaning in a particular output
+//! format. These checks tell authors about it rather than failing the build.
+
+use crate::ast::*;
+use crate::{Diagnostic, DiagnosticCode, Location, OutputFormat};
+use super::eval::{eval_static, range_length, ConstValue, MAX_RANGE_LENGTH};
+
+/// Report constructs the chosen output format can't express
+pub fn check_output_format(program: &Program, format: OutputFormat) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    if format == OutputFormat::Html {
+        let mut handlers = Vec::new();
+        for decl in &program.body {
+            let body = match decl {
+                Declaration::Component(c) => &c.body,
+                Declaration::Section(s) => &s.body,
+                Declaration::Layout(l) => &l.body,
+                Declaration::Page(p) => &p.body,
+            };
+            collect_event_handlers(body, &mut handlers);
+            check_ranges(body, &mut diagnostics);
+        }
+
+        // One summary note rather than one per button keeps the output readable
+        if let Some(first) = handlers.first() {
+            let count = handlers.len();
+            diagnostics.push(Diagnostic::new(
+                DiagnosticCode::DroppedEventHandlers,
+                format!(
+                    "{} event handler{} dropped - static HTML output has no script to run {}",
+                    count,
+                    if count == 1 { " was" } else { "s were" },
+                    if count == 1 { "it" } else { "them" }
+                ),
+                *first,
+            ));
+        }
+    }
+
+    diagnostics
+}
+
+fn collect_event_handlers(nodes: &[Node], handlers: &mut Vec<Location>) {
+    for node in nodes {
+        match node {
+            Node::Element(el) => {
+                for attr in &el.attributes {
+                    if attr.name.starts_with("on")
+                        && matches!(attr.value, Expression::Identifier(_) | Expression::Call(_) | Expression::Event(_))
+                    {
+                        handlers.push(attr.loc);
+                    }
+                }
+                collect_event_handlers(&el.children, handlers);
+            }
+            Node::ComponentRef(r) => {
+                collect_event_handlers(&r.children, handlers);
+                for fill in &r.slot_fills {
+                    collect_event_handlers(&fill.children, handlers);
+                }
+            }
+            Node::If(stmt) => collect_if(stmt, handlers),
+            Node::Each(stmt) => {
+                collect_event_handlers(&stmt.body, handlers);
+                if let Some(empty) = &stmt.empty {
+                    collect_event_handlers(empty, handlers);
+                }
+            }
+            Node::Text(_) | Node::Slot(_) | Node::Let(_) | Node::Comment(_) | Node::RawHtml(_) => {}
+        }
+    }
+}
+
+/// Static HTML unrolls `@each` over a constant range, so one that can't be
+/// unrolled in full is worth a warning
+fn check_ranges(nodes: &[Node], diagnostics: &mut Vec<Diagnostic>) {
+    for node in nodes {
+        match node {
+            Node::Element(el) => check_ranges(&el.children, diagnostics),
+            Node::ComponentRef(r) => {
+                check_ranges(&r.children, diagnostics);
+                for fill in &r.slot_fills {
+                    check_ranges(&fill.children, diagnostics);
+                }
+            }
+            Node::If(stmt) => check_if_ranges(stmt, diagnostics),
+            Node::Each(stmt) => {
+                if let Expression::Range(range) = &stmt.iterable {
+                    check_range(range, diagnostics);
+                }
+                check_ranges(&stmt.body, diagnostics);
+                if let Some(empty) = &stmt.empty {
+                    check_ranges(empty, diagnostics);
+                }
+            }
+            Node::Text(_) | Node::Slot(_) | Node::Let(_) | Node::Comment(_) | Node::RawHtml(_) => {}
+        }
+    }
+}
+
+fn check_if_ranges(stmt: &IfStatement, diagnostics: &mut Vec<Diagnostic>) {
+    check_ranges(&stmt.consequent, diagnostics);
+    match &stmt.alternate {
+        Some(Alternate::Block(nodes)) => check_ranges(nodes, diagnostics),
+        Some(Alternate::ElseIf(elif)) => check_if_ranges(elif, diagnostics),
+        None => {}
+    }
+}
+
+fn check_range(range: &RangeExpr, diagnostics: &mut Vec<Diagnostic>) {
+    let (Some(ConstValue::Number(start)), Some(ConstValue::Number(end))) = (eval_static(&range.start), eval_static(&range.end)) else {
+        return;
+    };
+    let message = match range_length(start, end, range.inclusive) {
+        None => "Range bounds must be whole numbers no larger than 2^53 - 1, so static HTML renders this loop as empty".to_string(),
+        Some(length) if length > MAX_RANGE_LENGTH => format!(
+            "Range has {} numbers; static HTML only renders the first {}",
+            length, MAX_RANGE_LENGTH
+        ),
+        Some(_) => return,
+    };
+    diagnostics.push(Diagnostic::new(DiagnosticCode::UnrolledRange, message, range.loc));
+}
+
+fn collect_if(stmt: &IfStatement, handlers: &mut Vec<Location>) {
+    collect_event_handlers(&stmt.consequent, handlers);
+    match &stmt.alternate {
+        Some(Alternate::Block(nodes)) => collect_event_handlers(nodes, handlers),
+        Some(Alternate::ElseIf(elif)) => collect_if(elif, handlers),
+        None => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+    use crate::Severity;
+
+    const SOURCE: &str = r#"
+        page home "/" {
+            button [onClick: save] {{ Save }}
+            @if ctx.open {
+                button [onClick: close(1)] {{ Close }}
+            }
+        }
+    "#;
+
+    #[test]
+    fn test_html_reports_dropped_handlers() {
+        let ast = parse(&tokenize(SOURCE).unwrap()).unwrap();
+        let diagnostics = check_output_format(&ast, OutputFormat::Html);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Info);
+        assert_eq!(diagnostics[0].code.as_deref(), Some("I002"));
+        assert!(diagnostics[0].message.starts_with("2 event handlers were dropped"));
+    }
+
+    #[test]
+    fn test_html_warns_about_ranges_it_cannot_unroll() {
+        let source = r#"page home "/" {
+            @each 1..2000000 as i { p {{ ${i} }} }
+            @each 9007199254740992..9007199254740993 as i { p {{ ${i} }} }
+            @each 1..10 as i { p {{ ${i} }} }
+        }"#;
+        let ast = parse(&tokenize(source).unwrap()).unwrap();
+        let diagnostics = check_output_format(&ast, OutputFormat::Html);
+        assert_eq!(diagnostics.len(), 2, "{:?}", diagnostics);
+        assert!(diagnostics.iter().all(|d| d.code.as_deref() == Some("W057")));
+        assert_eq!(diagnostics[0].message, "Range has 2000000 numbers; static HTML only renders the first 10000");
+        assert!(diagnostics[1].message.starts_with("Range bounds must be whole numbers"));
+    }
+
+    #[test]
+    fn test_typescript_keeps_handlers() {
+        let ast = parse(&tokenize(SOURCE).unwrap()).unwrap();
+        assert!(check_output_format(&ast, OutputFormat::Typescript).is_empty());
+    }
+}
@@ -4,6 +4,20 @@
 
 mod symbols;
 mod resolver;
+mod eval;
+mod a11y;
+mod tags;
+mod format;
+mod actions;
+mod normalize;
+mod semantics;
 
 pub use symbols::{Symbol, SymbolKind, SymbolTable};
 pub use resolver::analyze;
+pub use eval::{eval_const, eval_static, format_number, range_values, ConstValue};
+pub use a11y::lint_a11y;
+pub use format::check_output_format;
+pub use actions::check_action_names;
+pub use normalize::{drop_overridden_attributes, prune_empty};
+pub use semantics::suggest_semantic_html;
+pub(crate) use tags::{is_known_tag, is_svg_tag};
@@ -0,0 +1,207 @@
+//! AST passes that run before code generation
+//!
+//! An element with no attributes and no children renders as nothing in most
+//! cases, so it can be dropped before code generation. Only tags on an
+//! explicit allowlist are pruned: an empty `td` still holds its table cell,
+//! an empty `li` still draws a bullet, and void elements are never empty.
+//!
+//! An attribute set more than once, usually across `[...] [...]` blocks,
+//! keeps only its last value, so every generator agrees on which one wins.
+
+use crate::ast::*;
+
+/// Remove empty, attribute-less elements that have no effect on the page
+pub fn prune_empty(program: &mut Program) {
+    for decl in &mut program.body {
+        let body = match decl {
+            Declaration::Component(c) => &mut c.body,
+            Declaration::Section(s) => &mut s.body,
+            Declaration::Layout(l) => &mut l.body,
+            Declaration::Page(p) => &mut p.body,
+        };
+        prune_nodes(body);
+    }
+}
+
+fn prune_nodes(nodes: &mut Vec<Node>) {
+    for node in nodes.iter_mut() {
+        match node {
+            Node::Element(el) => prune_nodes(&mut el.children),
+            Node::ComponentRef(r) => {
+                prune_nodes(&mut r.children);
+                for fill in &mut r.slot_fills {
+                    prune_nodes(&mut fill.children);
+                }
+            }
+            Node::If(stmt) => prune_if(stmt),
+            Node::Each(stmt) => {
+                prune_nodes(&mut stmt.body);
+                if let Some(empty) = &mut stmt.empty {
+                    prune_nodes(empty);
+                }
+            }
+            Node::Text(_) | Node::Slot(_) | Node::Let(_) | Node::Comment(_) | Node::RawHtml(_) => {}
+        }
+    }
+    // Children go first, so `div { span { } }` prunes both
+    nodes.retain(|node| !matches!(node, Node::Element(el) if is_prunable(el)));
+}
+
+fn prune_if(stmt: &mut IfStatement) {
+    prune_nodes(&mut stmt.consequent);
+    match &mut stmt.alternate {
+        Some(Alternate::Block(nodes)) => prune_nodes(nodes),
+        Some(Alternate::ElseIf(elif)) => prune_if(elif),
+        None => {}
+    }
+}
+
+/// Drop each attribute that a later one with the same name overrides
+pub fn drop_overridden_attributes(program: &mut Program) {
+    for decl in &mut program.body {
+        let body = match decl {
+            Declaration::Component(c) => {
+                drop_overridden(&mut c.attributes);
+                &mut c.body
+            }
+            Declaration::Section(s) => &mut s.body,
+            Declaration::Layout(l) => &mut l.body,
+            Declaration::Page(p) => {
+                drop_overridden_in_nodes(&mut p.head);
+                &mut p.body
+            }
+        };
+        drop_overridden_in_nodes(body);
+    }
+}
+
+fn drop_overridden_in_nodes(nodes: &mut [Node]) {
+    for node in nodes {
+        match node {
+            Node::Element(el) => {
+                drop_overridden(&mut el.attributes);
+                drop_overridden_in_nodes(&mut el.children);
+            }
+            Node::ComponentRef(r) => {
+                drop_overridden_in_nodes(&mut r.children);
+                for fill in &mut r.slot_fills {
+                    drop_overridden_in_nodes(&mut fill.children);
+                }
+            }
+            Node::If(stmt) => {
+                let mut branch = Some(stmt);
+                while let Some(stmt) = branch {
+                    drop_overridden_in_nodes(&mut stmt.consequent);
+                    branch = match &mut stmt.alternate {
+                        Some(Alternate::Block(nodes)) => {
+                            drop_overridden_in_nodes(nodes);
+                            None
+                        }
+                        Some(Alternate::ElseIf(elif)) => Some(elif),
+                        None => None,
+                    };
+                }
+            }
+            Node::Each(stmt) => {
+                drop_overridden_in_nodes(&mut stmt.body);
+                if let Some(empty) = &mut stmt.empty {
+                    drop_overridden_in_nodes(empty);
+                }
+            }
+            Node::Text(_) | Node::Slot(_) | Node::Let(_) | Node::Comment(_) | Node::RawHtml(_) => {}
+        }
+    }
+}
+
+fn drop_overridden(attributes: &mut Vec<Attribute>) {
+    let mut seen = Vec::new();
+    // Walk from the end so the last of each name is the one kept
+    for i in (0..attributes.len()).rev() {
+        let name = attributes[i].name.clone();
+        if name == "..." {
+            continue;
+        }
+        if seen.contains(&name) {
+            attributes.remove(i);
+        } else {
+            seen.push(name);
+        }
+    }
+}
+
+fn is_prunable(el: &Element) -> bool {
+    el.attributes.is_empty()
+        && el.children.is_empty()
+        && el.for_directive.is_none()
+        && el.if_directive.is_none()
+        && matches!(
+            el.tag.as_str(),
+            "div" | "span" | "b" | "i" | "em" | "strong" | "small" | "u" | "s" |
+            "mark" | "code" | "sub" | "sup"
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+
+    fn pruned_tags(source: &str) -> Vec<String> {
+        let mut ast = parse(&tokenize(source).unwrap()).unwrap();
+        prune_empty(&mut ast);
+
+        let mut tags = Vec::new();
+        let Declaration::Page(page) = &ast.body[0] else { panic!("Expected page") };
+        collect_tags(&page.body, &mut tags);
+        tags
+    }
+
+    fn collect_tags(nodes: &[Node], tags: &mut Vec<String>) {
+        for node in nodes {
+            if let Node::Element(el) = node {
+                tags.push(el.tag.clone());
+                collect_tags(&el.children, tags);
+            }
+        }
+    }
+
+    #[test]
+    fn test_later_attribute_blocks_override() {
+        let mut ast = parse(&tokenize(r#"page home "/" { div [class: "a", title: "x"] [id: "b", class: "c"] { } }"#).unwrap()).unwrap();
+        drop_overridden_attributes(&mut ast);
+
+        let Declaration::Page(page) = &ast.body[0] else { panic!("Expected page") };
+        let Node::Element(el) = &page.body[0] else { panic!("Expected element") };
+        let attributes: Vec<(&str, &Expression)> = el.attributes.iter().map(|a| (a.name.as_str(), &a.value)).collect();
+        assert!(matches!(attributes[..], [
+            ("title", Expression::String(_)),
+            ("id", Expression::String(_)),
+            ("class", Expression::String(StringLiteral { ref value, .. })),
+        ] if value == "c"), "{:?}", attributes);
+    }
+
+    #[test]
+    fn test_prunes_empty_span_but_keeps_td() {
+        let tags = pruned_tags(r#"
+            page home "/" {
+                table { tr { td { } td { span { } } } }
+                p { span { } {{ Text }} }
+            }
+        "#);
+        assert_eq!(tags, vec!["table", "tr", "td", "td", "p"]);
+    }
+
+    #[test]
+    fn test_keeps_elements_with_attributes_or_content() {
+        let tags = pruned_tags(r#"
+            page home "/" {
+                span [class: "icon"] { }
+                div { div { } }
+                i {{ Note }}
+                br
+            }
+        "#);
+        assert_eq!(tags, vec!["span", "i", "br"]);
+    }
+}
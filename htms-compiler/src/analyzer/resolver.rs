@@ -1,8 +1,12 @@
 //! Reference resolver and validator
 
+use std::collections::HashMap;
+
 use crate::ast::*;
-use crate::{Diagnostic, Location, Severity};
+use crate::{Diagnostic, DiagnosticCode, Location};
+use super::eval::{eval_const, ConstValue};
 use super::symbols::{SymbolKind, SymbolTable};
+use super::tags::is_known_tag;
 
 /// Analyze the AST and return symbol table + diagnostics
 pub fn analyze(program: &Program) -> (SymbolTable, Vec<Diagnostic>) {
@@ -14,6 +18,29 @@ pub fn analyze(program: &Program) -> (SymbolTable, Vec<Diagnostic>) {
 struct Analyzer {
     symbols: SymbolTable,
     diagnostics: Vec<Diagnostic>,
+    /// Names in scope at each enclosing block, innermost last
+    scopes: Vec<Vec<Binding>>,
+    /// Number of enclosing `@each` bodies
+    loop_depth: usize,
+    /// Slots declared by each component and section, `None` for the default slot
+    component_slots: HashMap<String, Vec<Option<String>>>,
+    /// Routes of every page, which internal links must match
+    routes: Vec<String>,
+}
+
+/// A name introduced by a component parameter, loop or `@let`
+struct Binding {
+    name: String,
+    kind: BindingKind,
+    loc: Location,
+    used: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum BindingKind {
+    Parameter,
+    Loop,
+    Let,
 }
 
 impl Analyzer {
@@ -21,6 +48,10 @@ impl Analyzer {
         Self {
             symbols: SymbolTable::new(),
             diagnostics: Vec::new(),
+            scopes: Vec::new(),
+            loop_depth: 0,
+            component_slots: HashMap::new(),
+            routes: Vec::new(),
         }
     }
 
@@ -50,6 +81,22 @@ impl Analyzer {
                     ) {
                         self.error(&msg, c.loc);
                     }
+                    let tag = c.name.to_lowercase();
+                    if is_known_tag(&tag) {
+                        self.warning_with_code(
+                            DiagnosticCode::ComponentNamedLikeElement,
+                            &format!(
+                                "Component '{}' has the same name as the <{}> element, so references to it are easy to misread - give it a more specific name",
+                                c.name, tag
+                            ),
+                            c.loc,
+                        );
+                    }
+                    let slots = collect_slots(&c.body)
+                        .into_iter()
+                        .map(|slot| slot.name.clone())
+                        .collect();
+                    self.component_slots.insert(c.name.clone(), slots);
                 }
                 Declaration::Section(s) => {
                     if let Err(msg) = self.symbols.declare(
@@ -59,6 +106,26 @@ impl Analyzer {
                     ) {
                         self.error(&msg, s.loc);
                     }
+                    let slots = collect_slots(&s.body)
+                        .into_iter()
+                        .map(|slot| slot.name.clone())
+                        .collect();
+                    self.component_slots.insert(s.name.clone(), slots);
+                }
+                Declaration::Layout(l) => {
+                    if let Err(msg) = self.symbols.declare(
+                        l.name.clone(),
+                        SymbolKind::Layout,
+                        l.loc,
+                    ) {
+                        self.error(&msg, l.loc);
+                    }
+                    if !collect_slots(&l.body).iter().any(|slot| slot.name.is_none()) {
+                        self.error(
+                            &format!("Layout '{}' has no @slot to render its pages' content in", l.name),
+                            l.loc,
+                        );
+                    }
                 }
                 Declaration::Page(p) => {
                     if let Err(msg) = self.symbols.declare(
@@ -68,6 +135,7 @@ impl Analyzer {
                     ) {
                         self.error(&msg, p.loc);
                     }
+                    self.routes.push(p.route.clone());
                 }
             }
         }
@@ -80,57 +148,424 @@ impl Analyzer {
     fn resolve_references(&mut self, program: &Program) {
         for decl in &program.body {
             match decl {
-                Declaration::Component(c) => self.resolve_nodes(&c.body),
-                Declaration::Section(s) => self.resolve_nodes(&s.body),
-                Declaration::Page(p) => self.resolve_nodes(&p.body),
+                Declaration::Component(c) => {
+                    self.check_repeated_attributes(&c.attributes);
+                    self.resolve_with_parameters(&c.parameters, &c.body)
+                }
+                Declaration::Section(s) => self.resolve_with_parameters(&s.parameters, &s.body),
+                Declaration::Layout(l) => self.resolve_nodes(&l.body),
+                Declaration::Page(p) => {
+                    if let Some(layout) = &p.layout {
+                        self.resolve_layout(layout, p.loc);
+                    }
+                    self.resolve_nodes(&p.head);
+                    self.resolve_nodes(&p.body);
+                }
             }
         }
     }
 
+    /// A page's `uses` clause must name a layout
+    fn resolve_layout(&mut self, name: &str, loc: Location) {
+        match self.symbols.lookup(name).map(|symbol| symbol.kind) {
+            Some(SymbolKind::Layout) => self.symbols.add_usage(name, loc),
+            Some(_) => self.error(&format!("'{}' is not a layout, so pages can't use it", name), loc),
+            None => self.error(&format!("Undefined layout: '{}'", name), loc),
+        }
+    }
+
+    /// Resolve a component or section body with its parameters in scope
+    fn resolve_with_parameters(&mut self, parameters: &[Parameter], body: &[Node]) {
+        let params = parameters.iter()
+            .map(|p| (p.binding.as_str(), p.loc))
+            .collect::<Vec<_>>();
+        self.push_scope(BindingKind::Parameter, &params);
+        self.resolve_nodes(body);
+        self.scopes.pop();
+    }
+
     fn resolve_nodes(&mut self, nodes: &[Node]) {
+        self.scopes.push(Vec::new());
         for node in nodes {
             self.resolve_node(node);
         }
+
+        let scope = self.scopes.pop().unwrap_or_default();
+        for binding in scope.into_iter().filter(|b| b.kind == BindingKind::Let && !b.used) {
+            self.warning(
+                &format!("Let binding '{}' is declared but never used", binding.name),
+                binding.loc,
+            );
+        }
+    }
+
+    /// Open a scope holding loop variables or component parameters
+    fn push_scope(&mut self, kind: BindingKind, names: &[(&str, Location)]) {
+        for &(name, loc) in names {
+            self.check_ctx_shadowing(name, kind, loc);
+        }
+        if kind == BindingKind::Loop {
+            for &(name, loc) in names {
+                let parameter = self.scopes.iter()
+                    .flatten()
+                    .find(|b| b.kind == BindingKind::Parameter && b.name == name)
+                    .map(|b| b.loc);
+                if let Some(parameter) = parameter {
+                    self.warning_with_code(
+                        DiagnosticCode::LoopShadowsParameter,
+                        &format!(
+                            "Loop variable '{}' shadows the component parameter '{}' declared on line {} - rename one of them",
+                            name, name, parameter.line
+                        ),
+                        loc,
+                    );
+                }
+            }
+        }
+
+        let scope = names.iter()
+            .map(|&(name, loc)| Binding {
+                name: name.to_string(),
+                kind,
+                loc,
+                used: false,
+            })
+            .collect();
+        self.scopes.push(scope);
+    }
+
+    /// A binding named `ctx` hides the context, so every `ctx.` path in its
+    /// scope reads the binding instead
+    fn check_ctx_shadowing(&mut self, name: &str, kind: BindingKind, loc: Location) {
+        if name != "ctx" {
+            return;
+        }
+        let what = match kind {
+            BindingKind::Loop => "Loop variable",
+            BindingKind::Parameter => "Parameter",
+            BindingKind::Let => "@let binding",
+        };
+        self.warning_with_code(
+            DiagnosticCode::ShadowedContext,
+            &format!("{} 'ctx' shadows the context, so ctx paths in its scope read it instead - rename it", what),
+            loc,
+        );
+    }
+
+    /// The router handles every same-origin link, so a literal `href` to a
+    /// path no page serves renders nothing
+    ///
+    /// Files with no pages, such as component libraries, aren't checked.
+    fn check_internal_link(&mut self, el: &Element) {
+        let Some(attr) = el.attributes.iter().find(|a| a.name == "href") else {
+            return;
+        };
+        let Expression::String(href) = &attr.value else {
+            return;
+        };
+        if self.routes.is_empty() || !href.value.starts_with('/') || href.value.starts_with("//") {
+            return;
+        }
+        let path = href.value.split(['?', '#']).next().unwrap_or_default();
+        if !self.routes.iter().any(|route| route_matches(route, path)) {
+            self.warning_with_code(
+                DiagnosticCode::UnknownRoute,
+                &format!("Link to '{}' doesn't match any page route, so the router has nothing to render there", path),
+                attr.loc,
+            );
+        }
     }
 
     fn resolve_node(&mut self, node: &Node) {
         match node {
             Node::Element(e) => {
+                // Hyphenated names are custom elements, which are always allowed
+                if !e.tag.contains('-') && !is_known_tag(&e.tag) {
+                    self.warning_with_code(
+                        DiagnosticCode::UnknownTag,
+                        &format!("Unknown HTML tag '{}' - custom elements must contain a hyphen", e.tag),
+                        e.loc,
+                    );
+                }
+                if e.self_closing && !is_void_element(&e.tag) {
+                    self.warning_with_code(
+                        DiagnosticCode::SelfClosingNonVoid,
+                        &format!(
+                            "'{}' is not a void element, so browsers ignore the self-closing '/' - use an empty body '{{}}' instead",
+                            e.tag
+                        ),
+                        e.loc,
+                    );
+                }
+                if let Some(if_directive) = &e.if_directive {
+                    self.resolve_expression(&if_directive.condition);
+                }
+
+                // `@for` repeats the element itself, so its variables cover the attributes too
+                if let Some(for_directive) = &e.for_directive {
+                    self.resolve_expression(&for_directive.iterable);
+                    let mut names = vec![(for_directive.item_name.as_str(), for_directive.loc)];
+                    if let Some(index) = &for_directive.index_name {
+                        names.push((index.as_str(), for_directive.loc));
+                    }
+                    self.push_scope(BindingKind::Loop, &names);
+                }
+
+                if e.tag == "a" {
+                    self.check_internal_link(e);
+                }
+                self.check_repeated_attributes(&e.attributes);
+
+                for attr in &e.attributes {
+                    if attr.name == "bind" && !matches!(&attr.value, Expression::ContextPath(p) if p.root == PathRoot::Ctx) {
+                        self.error("'bind' needs a ctx path to write back to, such as bind: ctx.search", attr.loc);
+                    }
+                    self.resolve_attribute_value(&attr.name, &attr.value);
+                }
                 self.resolve_nodes(&e.children);
+
+                if e.for_directive.is_some() {
+                    self.scopes.pop();
+                }
             }
             Node::ComponentRef(r) => {
-                // Check if component exists
-                if !self.symbols.has(&r.name) {
+                // Components and sections are referenced the same way
+                let kind = self.symbols.lookup(&r.name).map(|symbol| symbol.kind);
+                let what = if kind == Some(SymbolKind::Section) { "Section" } else { "Component" };
+                if kind.is_none() {
+                    self.error(
+                        &format!("Undefined component or section: '{}'", r.name),
+                        r.loc,
+                    );
+                } else if kind == Some(SymbolKind::Layout) {
                     self.error(
-                        &format!("Undefined component: '{}'", r.name),
+                        &format!("'{}' is a layout - pages use it with `uses {}` rather than as a component", r.name, r.name),
                         r.loc,
                     );
                 } else {
                     self.symbols.add_usage(&r.name, r.loc);
                 }
+                for param in &r.parameters {
+                    self.resolve_attribute_value(&param.name, &param.value);
+                }
+                let slots = self.component_slots.get(&r.name).cloned();
+                if let Some(slots) = &slots {
+                    let has_children = r.children.iter().any(|child| !matches!(child, Node::Comment(_)));
+                    if has_children && !slots.contains(&None) {
+                        self.warning_with_code(
+                            DiagnosticCode::ChildrenWithoutSlot,
+                            &format!("{} '{}' has no @slot, so the children passed to it are dropped", what, r.name),
+                            r.loc,
+                        );
+                    }
+                }
                 self.resolve_nodes(&r.children);
-            }
-            Node::If(stmt) => {
-                self.resolve_nodes(&stmt.consequent);
-                if let Some(alt) = &stmt.alternate {
-                    match alt {
-                        Alternate::Block(nodes) => self.resolve_nodes(nodes),
-                        Alternate::ElseIf(elif) => {
-                            self.resolve_nodes(&elif.consequent);
-                            if let Some(a) = &elif.alternate {
-                                match a {
-                                    Alternate::Block(n) => self.resolve_nodes(n),
-                                    Alternate::ElseIf(_) => {} // Recursive handled above
-                                }
-                            }
+                for fill in &r.slot_fills {
+                    if let Some(slots) = &slots {
+                        if !slots.iter().any(|slot| slot.as_deref() == Some(fill.name.as_str())) {
+                            self.error(
+                                &format!("{} '{}' has no slot named '{}'", what, r.name, fill.name),
+                                fill.loc,
+                            );
                         }
                     }
+                    self.resolve_nodes(&fill.children);
                 }
             }
+            Node::If(stmt) => self.resolve_if(stmt),
             Node::Each(stmt) => {
+                if stmt.entries && matches!(stmt.iterable, Expression::Range(_)) {
+                    self.error("A range has no entries - drop 'in entries' to loop over its numbers", stmt.loc);
+                }
+                self.resolve_expression(&stmt.iterable);
+
+                let mut names = vec![(stmt.item_name.as_str(), stmt.loc)];
+                if let Some(index) = &stmt.index_name {
+                    names.push((index.as_str(), stmt.loc));
+                }
+                self.push_scope(BindingKind::Loop, &names);
+                self.loop_depth += 1;
                 self.resolve_nodes(&stmt.body);
+                self.loop_depth -= 1;
+                self.scopes.pop();
+
+                // The empty block runs outside the loop, so the item isn't in scope
+                if let Some(empty) = &stmt.empty {
+                    self.resolve_nodes(empty);
+                }
+            }
+            Node::Let(stmt) => {
+                // Resolve the value first so `@let x = x + 1` refers to an outer `x`
+                self.resolve_expression(&stmt.value);
+                self.check_ctx_shadowing(&stmt.name, BindingKind::Let, stmt.loc);
+
+                let scope = self.scopes.last_mut().expect("nodes are resolved inside a scope");
+                if scope.iter().any(|b| b.name == stmt.name) {
+                    self.error(
+                        &format!("'{}' is already declared in this block", stmt.name),
+                        stmt.loc,
+                    );
+                } else {
+                    scope.push(Binding {
+                        name: stmt.name.clone(),
+                        kind: BindingKind::Let,
+                        loc: stmt.loc,
+                        used: false,
+                    });
+                }
+            }
+            Node::Text(t) => {
+                for part in &t.parts {
+                    if let TextPart::Dynamic(expr) = part {
+                        self.resolve_expression(expr);
+                    }
+                }
+            }
+            Node::RawHtml(raw) => {
+                if let Expression::String(s) = &raw.value {
+                    if s.value.to_lowercase().contains("<script") {
+                        self.warning_with_code(
+                            DiagnosticCode::ScriptInRawHtml,
+                            "@html inserts markup unescaped, and a <script> tag in it is an XSS risk - build the element instead",
+                            raw.loc,
+                        );
+                    }
+                }
+                self.resolve_expression(&raw.value);
+            }
+            Node::Slot(_) | Node::Comment(_) => {}
+        }
+    }
+
+    fn resolve_attribute_value(&mut self, name: &str, value: &Expression) {
+        // `onClick: save` names an action, not a value in scope
+        if name.starts_with("on") && matches!(value, Expression::Identifier(_)) {
+            return;
+        }
+        self.resolve_expression(value);
+    }
+
+    fn resolve_if(&mut self, stmt: &IfStatement) {
+        self.resolve_expression(&stmt.condition);
+        self.resolve_nodes(&stmt.consequent);
+        match &stmt.alternate {
+            Some(Alternate::Block(nodes)) => self.resolve_nodes(nodes),
+            Some(Alternate::ElseIf(elif)) => self.resolve_if(elif),
+            None => {}
+        }
+    }
+
+    /// Resolve the root name of an identifier or member access against the scope stack
+    fn resolve_name(&mut self, name: &str, location: Location) {
+        if name == "ctx" {
+            return;
+        }
+
+        for scope in self.scopes.iter_mut().rev() {
+            if let Some(binding) = scope.iter_mut().find(|b| b.name == name) {
+                binding.used = true;
+                return;
+            }
+        }
+
+        self.warning(
+            &format!(
+                "Unknown identifier '{}' - expected a loop variable, component parameter, @let binding or ctx path",
+                name
+            ),
+            location,
+        );
+    }
+
+    fn resolve_expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Binary(bin) => {
+                if bin.operator == BinaryOp::Mod
+                    && eval_const(&bin.right) == Some(ConstValue::Number(0.0))
+                {
+                    self.warning(
+                        "Modulo by zero always yields NaN - the expression is left unfolded",
+                        bin.loc,
+                    );
+                }
+                if let Expression::Binary(inner) = bin.left.as_ref() {
+                    if bin.operator.chains_with(inner.operator) {
+                        self.warning_with_code(
+                            DiagnosticCode::ChainedComparison,
+                            &format!(
+                                "Chained comparison `a {} b {} c` is probably not what you want - it compares the result of `a {} b` with `c`; use `a {} b && b {} c` instead",
+                                inner.operator.symbol(), bin.operator.symbol(),
+                                inner.operator.symbol(),
+                                inner.operator.symbol(), bin.operator.symbol(),
+                            ),
+                            bin.operator_loc,
+                        );
+                    }
+                }
+                self.resolve_expression(&bin.left);
+                self.resolve_expression(&bin.right);
+            }
+            Expression::Ternary(t) => {
+                self.resolve_expression(&t.condition);
+                self.resolve_expression(&t.consequent);
+                self.resolve_expression(&t.alternate);
             }
-            Node::Text(_) | Node::Slot(_) => {}
+            Expression::MemberAccess(m) | Expression::OptionalMember(m) => self.resolve_expression(&m.object),
+            Expression::Call(call) => {
+                for arg in &call.arguments {
+                    self.resolve_expression(arg);
+                }
+            }
+            Expression::Event(event) => {
+                for arg in &event.arguments {
+                    self.resolve_expression(arg);
+                }
+            }
+            Expression::Spread(spread) => self.resolve_expression(&spread.expr),
+            Expression::Range(range) => {
+                for bound in [&range.start, &range.end] {
+                    if matches!(eval_const(bound), Some(value) if !matches!(value, ConstValue::Number(_))) {
+                        self.error("Range bounds must be numbers", range.loc);
+                    }
+                    self.resolve_expression(bound);
+                }
+            }
+            Expression::Array(array) => {
+                for element in &array.elements {
+                    self.resolve_expression(element);
+                }
+            }
+            Expression::Object(object) => {
+                for property in &object.properties {
+                    self.resolve_expression(&property.value);
+                }
+            }
+            Expression::Identifier(ident) => self.resolve_identifier(&ident.name, ident.loc),
+            // `item.name` depends only on `item` resolving
+            Expression::ContextPath(path) if path.root == PathRoot::Local => {
+                self.resolve_identifier(&path.segments[0], path.loc);
+            }
+            Expression::String(_)
+            | Expression::Number(_)
+            | Expression::Boolean(_)
+            | Expression::Null(_)
+            | Expression::ContextPath(_) => {}
+        }
+    }
+
+    fn resolve_identifier(&mut self, name: &str, loc: Location) {
+        if !name.starts_with('$') {
+            self.resolve_name(name, loc);
+        } else if !is_loop_helper(name) {
+            self.error(
+                &format!("Unknown loop helper '{}' (expected $even or $odd)", name),
+                loc,
+            );
+        } else if self.loop_depth == 0 {
+            self.error(
+                &format!("'{}' can only be used inside an @each body", name),
+                loc,
+            );
         }
     }
 
@@ -139,15 +574,17 @@ impl Analyzer {
     // =========================================================================
 
     fn validate(&mut self, program: &Program) {
-        // Check for duplicate routes
-        let mut routes: std::collections::HashMap<String, Location> = std::collections::HashMap::new();
+        // Check for duplicate routes, including ones that differ only in parameter names
+        let mut routes: HashMap<String, &str> = HashMap::new();
         for decl in &program.body {
             if let Declaration::Page(p) = decl {
-                if let Some(_existing_loc) = routes.get(&p.route) {
-                    self.error(
-                        &format!("Duplicate route: '{}' is already defined", p.route),
-                        p.loc,
-                    );
+                if let Some(existing) = routes.get(&route_pattern(&p.route)) {
+                    let message = if *existing == p.route {
+                        format!("Duplicate route: '{}' is already defined", p.route)
+                    } else {
+                        format!("Duplicate route: '{}' matches the same paths as '{}'", p.route, existing)
+                    };
+                    self.error(&message, p.loc);
                 } else {
                     // Validate route format
                     if !p.route.starts_with('/') {
@@ -156,8 +593,9 @@ impl Analyzer {
                             p.loc,
                         );
                     }
-                    routes.insert(p.route.clone(), p.loc);
+                    routes.insert(route_pattern(&p.route), &p.route);
                 }
+                self.validate_route_params(p);
             }
         }
 
@@ -188,29 +626,87 @@ impl Analyzer {
     // =========================================================================
 
     fn error(&mut self, message: &str, location: Location) {
-        self.diagnostics.push(Diagnostic {
-            severity: Severity::Error,
-            message: message.to_string(),
-            location,
-            code: Some("E003".to_string()),
-        });
+        self.diagnostics.push(Diagnostic::new(DiagnosticCode::ResolveError, message, location));
     }
 
     fn warning(&mut self, message: &str, location: Location) {
-        self.diagnostics.push(Diagnostic {
-            severity: Severity::Warning,
-            message: message.to_string(),
-            location,
-            code: Some("W001".to_string()),
-        });
+        self.warning_with_code(DiagnosticCode::ResolveWarning, message, location);
+    }
+
+    /// Each `:name` segment must be an identifier, used once per route
+    fn validate_route_params(&mut self, page: &PageDecl) {
+        let mut seen = Vec::new();
+        for segment in route_segments(&page.route) {
+            let RouteSegment::Param(name) = segment else {
+                continue;
+            };
+            let is_identifier = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !is_identifier {
+                self.error(
+                    &format!(
+                        "Invalid route parameter ':{}' in '{}' - parameter names must be identifiers",
+                        name, page.route
+                    ),
+                    page.loc,
+                );
+            } else if seen.contains(&name) {
+                self.error(
+                    &format!("Duplicate route parameter ':{}' in '{}'", name, page.route),
+                    page.loc,
+                );
+            } else {
+                seen.push(name);
+            }
+        }
+    }
+
+    /// Warn about each attribute that a later one with the same name overrides
+    fn check_repeated_attributes(&mut self, attributes: &[Attribute]) {
+        for (i, attr) in attributes.iter().enumerate() {
+            if attr.name != "..." && attributes[i + 1..].iter().any(|later| later.name == attr.name) {
+                self.warning_with_code(
+                    DiagnosticCode::RepeatedAttribute,
+                    &format!("Attribute '{}' is set again later on this element, so this value is ignored", attr.name),
+                    attr.loc,
+                );
+            }
+        }
+    }
+
+    fn warning_with_code(&mut self, code: DiagnosticCode, message: &str, location: Location) {
+        self.diagnostics.push(Diagnostic::new(code, message, location));
     }
 }
 
+/// A route with each parameter name and empty segment dropped: `/users/:id/` -> `/users/:`
+fn route_pattern(route: &str) -> String {
+    route_segments(route)
+        .into_iter()
+        .map(|segment| match segment {
+            RouteSegment::Static(text) => format!("/{}", text),
+            RouteSegment::Param(_) => "/:".to_string(),
+        })
+        .collect()
+}
+
+/// Whether `path` is served by `route`, where a `:param` segment matches any one segment
+fn route_matches(route: &str, path: &str) -> bool {
+    let route = route_segments(route);
+    let path: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+    route.len() == path.len()
+        && route.iter().zip(&path).all(|(segment, part)| match segment {
+            RouteSegment::Static(text) => text == part,
+            RouteSegment::Param(_) => true,
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::lexer::tokenize;
     use crate::parser::parse;
+    use crate::Severity;
 
     fn analyze_source(source: &str) -> (SymbolTable, Vec<Diagnostic>) {
         let tokens = tokenize(source).unwrap();
@@ -256,6 +752,27 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_routes_differing_only_in_param_names_collide() {
+        let source = r#"
+            page user "/users/:id" { }
+            page profile "/users/:userId" { }
+            page posts "/users/:id/posts" { }
+            page settings "/users/settings" { }
+            page account "/users/:id/" { }
+        "#;
+        let (_, diagnostics) = analyze_source(source);
+
+        let duplicates: Vec<_> = diagnostics.iter()
+            .filter(|d| d.message.starts_with("Duplicate route"))
+            .collect();
+        assert_eq!(duplicates.len(), 2);
+        assert_eq!(duplicates[0].message, "Duplicate route: '/users/:userId' matches the same paths as '/users/:id'");
+        assert_eq!(duplicates[0].location.start, source.find("page profile").unwrap());
+        // A trailing slash matches the same paths too
+        assert_eq!(duplicates[1].message, "Duplicate route: '/users/:id/' matches the same paths as '/users/:id'");
+    }
+
     #[test]
     fn test_invalid_route() {
         let (_, diagnostics) = analyze_source(r#"
@@ -280,4 +797,428 @@ mod tests {
             d.message.contains("never used")
         ));
     }
+
+    #[test]
+    fn test_self_closing_non_void_warning() {
+        let (_, diagnostics) = analyze_source(r#"
+            page home "/" { div [id:"x"] / input [type: "text"] / }
+        "#);
+
+        let warnings: Vec<_> = diagnostics.iter()
+            .filter(|d| d.code.as_deref() == Some("W047"))
+            .collect();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].message.contains("'div'"));
+    }
+
+    #[test]
+    fn test_modulo_by_zero_warning() {
+        let (_, diagnostics) = analyze_source(r#"
+            page home "/" { @if ctx.index % 0 == 0 { div { } } }
+        "#);
+
+        assert!(diagnostics.iter().any(|d| d.message.contains("Modulo by zero")));
+    }
+
+    #[test]
+    fn test_let_binding_usage() {
+        let (_, diagnostics) = analyze_source(r#"
+            page home "/" {
+                @let used = ctx.user.name
+                @let unused = ctx.user.email
+                p {{ ${used} }}
+            }
+        "#);
+
+        let warnings: Vec<_> = diagnostics.iter()
+            .filter(|d| d.message.contains("Let binding"))
+            .collect();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].message.contains("'unused'"));
+    }
+
+    #[test]
+    fn test_bind_target_must_be_context_path() {
+        let (_, diagnostics) = analyze_source(r#"
+            page home "/" {
+                input [bind: ctx.form.email, aria-label: "Email"]
+                input [bind: "email", aria-label: "Name"]
+            }
+        "#);
+
+        let errors: Vec<_> = diagnostics.iter()
+            .filter(|d| d.severity == Severity::Error)
+            .collect();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].message.contains("'bind' needs a ctx path"));
+    }
+
+    #[test]
+    fn test_section_parameters_are_in_scope() {
+        let (_, diagnostics) = analyze_source(r#"
+            section Hero(title: heading) { h1 {{ ${heading} }} }
+            section Footer { p {{ ${heading} }} }
+        "#);
+
+        let unknown: Vec<_> = diagnostics.iter()
+            .filter(|d| d.message.contains("Unknown identifier 'heading'"))
+            .collect();
+        assert_eq!(unknown.len(), 1);
+        assert_eq!(unknown[0].location.line, 3);
+    }
+
+    #[test]
+    fn test_section_references() {
+        let (symbols, diagnostics) = analyze_source(r#"
+            section Hero { h1 {{ Welcome }} }
+            page home "/" { Hero { footer: { p {{ Fine print }} } } Missing }
+        "#);
+
+        let errors: Vec<_> = diagnostics.iter()
+            .filter(|d| d.severity == Severity::Error)
+            .map(|d| d.message.as_str())
+            .collect();
+        assert_eq!(errors, vec![
+            "Section 'Hero' has no slot named 'footer'",
+            "Undefined component or section: 'Missing'",
+        ]);
+        assert_eq!(symbols.lookup("Hero").unwrap().usages.len(), 1);
+    }
+
+    #[test]
+    fn test_each_else_block_is_outside_the_loop() {
+        let (_, diagnostics) = analyze_source(r#"
+            page home "/" {
+                @each ctx.items as item { li {{ ${item} }} } @else { p {{ ${item} }} }
+            }
+        "#);
+
+        let unknown: Vec<_> = diagnostics.iter()
+            .filter(|d| d.message.contains("Unknown identifier 'item'"))
+            .collect();
+        assert_eq!(unknown.len(), 1);
+        // At the name inside the interpolation
+        assert_eq!(unknown[0].location.column, 77);
+    }
+
+    #[test]
+    fn test_every_name_in_an_interpolation_is_resolved() {
+        let (_, diagnostics) = analyze_source(r#"
+            page home "/" {
+                @each ctx.items as item { li {{ ${ctx.wide ? item : other} and ${item + missing} }} }
+            }
+        "#);
+
+        let unknown: Vec<_> = diagnostics.iter()
+            .filter(|d| d.message.contains("Unknown identifier"))
+            .map(|d| d.message.as_str())
+            .collect();
+        assert_eq!(unknown.len(), 2, "{:?}", unknown);
+        assert!(unknown[0].contains("'other'"));
+        assert!(unknown[1].contains("'missing'"));
+    }
+
+    #[test]
+    fn test_range_bounds_must_be_numbers() {
+        let (_, diagnostics) = analyze_source(r#"
+            page home "/" {
+                @each 1..ctx.count as i { li {{ ${i} }} }
+                @each "a".."z" as c { }
+                @each 1..5 as n, i in entries { }
+            }
+        "#);
+
+        let errors: Vec<_> = diagnostics.iter()
+            .filter(|d| d.severity == Severity::Error)
+            .map(|d| d.message.as_str())
+            .collect();
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert!(errors[..2].iter().all(|m| *m == "Range bounds must be numbers"));
+        assert!(errors[2].contains("A range has no entries"));
+    }
+
+    #[test]
+    fn test_let_redeclaration() {
+        let (_, diagnostics) = analyze_source(r#"
+            page home "/" {
+                @let name = ctx.first
+                div { @let name = ctx.last }
+                @let name = ctx.nick
+            }
+        "#);
+
+        let errors: Vec<_> = diagnostics.iter()
+            .filter(|d| d.severity == Severity::Error)
+            .collect();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].message.contains("'name' is already declared"));
+    }
+
+    #[test]
+    fn test_loop_helper_scope() {
+        let (_, diagnostics) = analyze_source(r#"
+            page home "/" {
+                @each ctx.rows as row { tr [class: $odd ? "odd" : "even"] { } }
+                div [class: $even ? "a" : "b"] { }
+                @each ctx.rows as row { tr [data-first: $first] { } }
+            }
+        "#);
+
+        let errors: Vec<_> = diagnostics.iter()
+            .filter(|d| d.severity == Severity::Error)
+            .collect();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].message.contains("'$even' can only be used inside an @each"));
+        assert!(errors[1].message.contains("Unknown loop helper '$first'"));
+    }
+
+    #[test]
+    fn test_unknown_identifier_warning() {
+        let (_, diagnostics) = analyze_source(r#"
+            component Card(item: user) {
+                p { user.name }
+            }
+            page home "/" {
+                Card(user: ctx.user)
+                @each ctx.items as item, i {
+                    div [data-i: i, onClick: select(item.id)] { itme.name }
+                }
+                button [onClick: save] {{ ${ctx.label} ${missing} \${price} }}
+            }
+        "#);
+
+        let unknown: Vec<_> = diagnostics.iter()
+            .filter(|d| d.message.starts_with("Unknown identifier"))
+            .collect();
+        assert_eq!(unknown.len(), 2);
+        assert!(unknown[0].message.contains("'itme'"));
+        assert!(unknown[1].message.contains("'missing'"));
+    }
+
+    #[test]
+    fn test_unknown_tag_warning() {
+        let (_, diagnostics) = analyze_source(r#"
+            page home "/" {
+                buton {{ Save }}
+                my-widget { }
+                svg { path [d: "M0 0"] }
+            }
+        "#);
+
+        let warnings: Vec<_> = diagnostics.iter()
+            .filter(|d| d.code.as_deref() == Some("W002"))
+            .collect();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].message.contains("'buton'"));
+    }
+
+    #[test]
+    fn test_ctx_shadowing_warning() {
+        let (_, diagnostics) = analyze_source(r#"
+            component Card(item: ctx) { p { ctx.name } }
+            page home "/" {
+                @each ctx.items as ctx { p { ctx.name } }
+                @each ctx.items as item, ctx { p { item.name } }
+                @let ctx = 1
+                p [data-n: ctx] { }
+                @each ctx.items as item { p { item.name } }
+            }
+        "#);
+
+        let warnings: Vec<_> = diagnostics.iter()
+            .filter(|d| d.code.as_deref() == Some("W052"))
+            .map(|d| d.message.split(' ').next().unwrap())
+            .collect();
+        assert_eq!(warnings, ["Parameter", "Loop", "Loop", "@let"]);
+    }
+
+    #[test]
+    fn test_internal_link_to_unknown_route() {
+        let (_, diagnostics) = analyze_source(r#"
+            component Nav { a [href: "/pricing"] {{ Pricing }} }
+            page home "/" {
+                Nav
+                a [href: "/"] {{ Home }}
+                a [href: "/users/42?tab=posts"] {{ User }}
+                a [href: "/users/42/posts"] {{ Posts }}
+                a [href: "//cdn.example.com/x"] {{ CDN }}
+                a [href: "https://example.com"] {{ Out }}
+            }
+            page user "/users/:id" { p {{ User }} }
+        "#);
+
+        let warnings: Vec<_> = diagnostics.iter()
+            .filter(|d| d.code.as_deref() == Some("W054"))
+            .map(|d| d.message.split('\'').nth(1).unwrap())
+            .collect();
+        assert_eq!(warnings, ["/pricing", "/users/42/posts"]);
+
+        // Without pages there are no routes to check against
+        let (_, diagnostics) = analyze_source(r#"component Nav { a [href: "/pricing"] {{ Pricing }} }"#);
+        assert!(!diagnostics.iter().any(|d| d.code.as_deref() == Some("W054")));
+    }
+
+    #[test]
+    fn test_raw_html_script_warning() {
+        let (_, diagnostics) = analyze_source(r#"
+            page home "/" {
+                @html "<b>bold</b>"
+                @html "<SCRIPT>alert(1)</SCRIPT>"
+                @html ctx.body
+            }
+        "#);
+
+        let warnings = diagnostics.iter()
+            .filter(|d| d.code.as_deref() == Some("W053"))
+            .count();
+        assert_eq!(warnings, 1);
+    }
+
+    #[test]
+    fn test_unknown_slot_fill() {
+        let (_, diagnostics) = analyze_source(r#"
+            component Card { header { @slot(header) } @slot }
+            page home "/" {
+                Card {
+                    header: { h1 {{ Title }} }
+                    footer: { p {{ Fine print }} }
+                }
+            }
+        "#);
+
+        let errors: Vec<_> = diagnostics.iter()
+            .filter(|d| d.severity == Severity::Error)
+            .collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "Component 'Card' has no slot named 'footer'");
+    }
+
+    #[test]
+    fn test_children_without_default_slot() {
+        let (_, diagnostics) = analyze_source(r#"
+            component Badge { span {{ New }} }
+            component Card { header { @slot(header) } }
+            page home "/" {
+                Badge { p {{ Dropped }} }
+                Card { header: { h1 {{ Kept }} } }
+            }
+        "#);
+
+        let warnings: Vec<_> = diagnostics.iter()
+            .filter(|d| d.code.as_deref() == Some("W055"))
+            .collect();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].message.starts_with("Component 'Badge'"));
+    }
+
+    #[test]
+    fn test_repeated_attributes() {
+        let (_, diagnostics) = analyze_source(r#"
+            page home "/" { div [class: "a", ...ctx.props] [class: "b", ...ctx.more, id: "x"] { } }
+        "#);
+
+        let warnings: Vec<_> = diagnostics.iter()
+            .filter(|d| d.code.as_deref() == Some("W056"))
+            .collect();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].message, "Attribute 'class' is set again later on this element, so this value is ignored");
+    }
+
+    #[test]
+    fn test_loop_variable_shadows_parameter() {
+        let (_, diagnostics) = analyze_source(r#"
+            component Card(item: user) {
+                @each user.friends as user { p { user.name } }
+                @each user.tags as tag { span { tag.label } }
+            }
+            page home "/" { Card(item: ctx.user) }
+        "#);
+
+        let warnings: Vec<_> = diagnostics.iter()
+            .filter(|d| d.code.as_deref() == Some("W048"))
+            .collect();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            warnings[0].message,
+            "Loop variable 'user' shadows the component parameter 'user' declared on line 2 - rename one of them"
+        );
+    }
+
+    #[test]
+    fn test_component_named_like_a_tag() {
+        let (_, diagnostics) = analyze_source(r#"
+            component Section { div { } }
+            component Table { table { } }
+            component UserCard { div { } }
+        "#);
+
+        let warnings: Vec<_> = diagnostics.iter()
+            .filter(|d| d.code.as_deref() == Some("W050"))
+            .collect();
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(
+            warnings[1].message,
+            "Component 'Table' has the same name as the <table> element, so references to it are easy to misread - give it a more specific name"
+        );
+    }
+
+    #[test]
+    fn test_chained_comparison() {
+        let (_, diagnostics) = analyze_source(r#"
+            page home "/" {
+                @if ctx.min < ctx.value < ctx.max { p {{ In range }} }
+                @if ctx.min < ctx.value && ctx.value < ctx.max { p {{ In range }} }
+            }
+        "#);
+
+        let warnings: Vec<_> = diagnostics.iter()
+            .filter(|d| d.code.as_deref() == Some("W051"))
+            .collect();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].message.starts_with("Chained comparison `a < b < c` is probably not what you want"));
+        // Points at the second `<`
+        assert_eq!((warnings[0].location.line, warnings[0].location.column), (3, 41));
+    }
+
+    #[test]
+    fn test_route_param_validation() {
+        let (_, diagnostics) = analyze_source(r#"
+            page user "/users/:id" { }
+            page bad "/posts/:1st" { }
+            page twice "/a/:id/b/:id" { }
+        "#);
+
+        let errors: Vec<_> = diagnostics.iter()
+            .filter(|d| d.severity == Severity::Error)
+            .map(|d| d.message.as_str())
+            .collect();
+        assert_eq!(errors, [
+            "Invalid route parameter ':1st' in '/posts/:1st' - parameter names must be identifiers",
+            "Duplicate route parameter ':id' in '/a/:id/b/:id'",
+        ]);
+    }
+
+    #[test]
+    fn test_layout_validation() {
+        let (_, diagnostics) = analyze_source(r#"
+            layout Bare { header { } }
+            layout Main { main { @slot } }
+            component Card { div { } }
+            page home "/" uses Main { Card }
+            page about "/about" uses Card { }
+            page help "/help" uses Missing { }
+            page blog "/blog" { Main }
+        "#);
+
+        let errors: Vec<_> = diagnostics.iter()
+            .filter(|d| d.severity == Severity::Error)
+            .map(|d| d.message.as_str())
+            .collect();
+        assert_eq!(errors, vec![
+            "Layout 'Bare' has no @slot to render its pages' content in",
+            "'Card' is not a layout, so pages can't use it",
+            "Undefined layout: 'Missing'",
+            "'Main' is a layout - pages use it with `uses Main` rather than as a component",
+        ]);
+    }
 }
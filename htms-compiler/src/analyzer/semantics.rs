@@ -0,0 +1,172 @@
+//! Semantic HTML hints
+//!
+//! Suggests elements that carry meaning of their own in place of generic
+//! ones, such as `nav` for a `div` classed as navigation, or `button` for a
+//! clickable `div`. These are suggestions rather than mistakes, so every
+//! finding is an info diagnostic in the `I0xx` code range.
+
+use crate::ast::*;
+use crate::codegen::is_event_attribute;
+use crate::{Diagnostic, DiagnosticCode};
+
+/// Landmark elements suggested for a `div` whose class names one
+const LANDMARKS: &[&str] = &["nav", "header", "footer", "main", "aside"];
+
+/// Suggest semantic elements and flag roles that repeat an element's default
+pub fn suggest_semantic_html(program: &Program) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for decl in &program.body {
+        let body = match decl {
+            Declaration::Component(c) => &c.body,
+            Declaration::Section(s) => &s.body,
+            Declaration::Layout(l) => &l.body,
+            Declaration::Page(p) => &p.body,
+        };
+        hint_nodes(body, &mut diagnostics);
+    }
+    diagnostics
+}
+
+fn hint_nodes(nodes: &[Node], diagnostics: &mut Vec<Diagnostic>) {
+    for node in nodes {
+        match node {
+            Node::Element(el) => {
+                hint_element(el, diagnostics);
+                hint_nodes(&el.children, diagnostics);
+            }
+            Node::ComponentRef(r) => {
+                hint_nodes(&r.children, diagnostics);
+                for fill in &r.slot_fills {
+                    hint_nodes(&fill.children, diagnostics);
+                }
+            }
+            Node::If(stmt) => hint_if(stmt, diagnostics),
+            Node::Each(stmt) => {
+                hint_nodes(&stmt.body, diagnostics);
+                if let Some(empty) = &stmt.empty {
+                    hint_nodes(empty, diagnostics);
+                }
+            }
+            Node::Text(_) | Node::Slot(_) | Node::Let(_) | Node::Comment(_) | Node::RawHtml(_) => {}
+        }
+    }
+}
+
+fn hint_if(stmt: &IfStatement, diagnostics: &mut Vec<Diagnostic>) {
+    hint_nodes(&stmt.consequent, diagnostics);
+    match &stmt.alternate {
+        Some(Alternate::Block(nodes)) => hint_nodes(nodes, diagnostics),
+        Some(Alternate::ElseIf(elif)) => hint_if(elif, diagnostics),
+        None => {}
+    }
+}
+
+fn hint_element(el: &Element, diagnostics: &mut Vec<Diagnostic>) {
+    let mut info = |code: DiagnosticCode, message: String| {
+        diagnostics.push(Diagnostic::new(code, message, el.loc));
+    };
+
+    // An explicit role already says what the element is
+    let role = el.attributes.iter().find(|a| a.name == "role");
+
+    if el.tag == "div" && role.is_none() {
+        if let Some(landmark) = class_landmark(el) {
+            info(
+                DiagnosticCode::LandmarkDiv,
+                format!("<div> with a '{0}' class could be a <{0}> element, which assistive technology can jump to", landmark),
+            );
+        }
+    }
+
+    if matches!(el.tag.as_str(), "div" | "span") && role.is_none() {
+        if let Some(handler) = el.attributes.iter().find(|a| is_event_attribute(a) && a.name.split('.').next() == Some("onClick")) {
+            info(
+                DiagnosticCode::ClickableElement,
+                format!("<{}> with '{}' could be a <button>, which is focusable and keyboard-operable", el.tag, handler.name),
+            );
+        }
+    }
+
+    if let Some(Expression::String(role)) = role.map(|a| &a.value) {
+        if implicit_role(&el.tag) == Some(role.value.as_str()) {
+            info(
+                DiagnosticCode::RedundantRole,
+                format!("role: \"{}\" is already the default for <{}> and can be removed", role.value, el.tag),
+            );
+        }
+    }
+}
+
+/// The first landmark named by a static class, matching whole words so
+/// `navbar` and `site-nav` count but `canvas` does not
+fn class_landmark(el: &Element) -> Option<&'static str> {
+    let class = el.attributes.iter().find(|a| a.name == "class")?;
+    let Expression::String(class) = &class.value else { return None };
+
+    class.value
+        .split(|c: char| c.is_whitespace() || c == '-' || c == '_')
+        .find_map(|word| {
+            LANDMARKS.iter().copied().find(|landmark| {
+                word == *landmark || (*landmark == "nav" && word.starts_with("navbar"))
+            })
+        })
+}
+
+/// The ARIA role an element has without a `role` attribute
+fn implicit_role(tag: &str) -> Option<&'static str> {
+    Some(match tag {
+        "nav" => "navigation",
+        "button" => "button",
+        "main" => "main",
+        "aside" => "complementary",
+        "ul" | "ol" => "list",
+        "li" => "listitem",
+        "table" => "table",
+        "form" => "form",
+        "article" => "article",
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => "heading",
+        "img" => "img",
+        "dialog" => "dialog",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+
+    fn hint_codes(source: &str) -> Vec<String> {
+        let ast = parse(&tokenize(source).unwrap()).unwrap();
+        suggest_semantic_html(&ast)
+            .into_iter()
+            .map(|d| d.code.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_flags_each_hint() {
+        let codes = hint_codes(r#"
+            page home "/" {
+                div [class: "site-nav"] { a [href: "/"] {{ Home }} }
+                div [onClick: open] {{ Open }}
+                nav [role: "navigation"] { a [href: "/about"] {{ About }} }
+            }
+        "#);
+        assert_eq!(codes, vec!["I001", "I004", "I003"]);
+    }
+
+    #[test]
+    fn test_semantic_markup_passes() {
+        let codes = hint_codes(r#"
+            page home "/" {
+                nav [class: "nav"] { a [class: "nav-link", href: "/"] {{ Home }} }
+                button [onClick: open] {{ Open }}
+                div [class: "canvas", role: "button", onClick: open] {{ Draw }}
+                ul [role: "menu"] { li {{ One }} }
+            }
+        "#);
+        assert!(codes.is_empty(), "unexpected {:?}", codes);
+    }
+}
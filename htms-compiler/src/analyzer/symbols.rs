@@ -8,6 +8,7 @@ use crate::Location;
 pub enum SymbolKind {
     Component,
     Section,
+    Layout,
     Page,
 }
 
@@ -91,6 +92,13 @@ impl SymbolTable {
             .collect()
     }
 
+    /// Get all layout names
+    pub fn layouts(&self) -> Vec<&str> {
+        self.by_kind(SymbolKind::Layout)
+            .map(|s| s.name.as_str())
+            .collect()
+    }
+
     /// Get all page names
     pub fn pages(&self) -> Vec<&str> {
         self.by_kind(SymbolKind::Page)
@@ -0,0 +1,50 @@
+//! Known element names
+
+/// Whether `tag` is a standard HTML element, or an SVG/MathML element that may
+/// appear inline in HTML
+pub fn is_known_tag(tag: &str) -> bool {
+    is_html_tag(tag) || is_svg_tag(tag) || tag == "math"
+}
+
+fn is_html_tag(tag: &str) -> bool {
+    matches!(
+        tag,
+        // Document and metadata
+        "html" | "head" | "title" | "base" | "link" | "meta" | "style" | "body" |
+        // Sections
+        "article" | "section" | "nav" | "aside" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" |
+        "hgroup" | "header" | "footer" | "address" | "main" | "search" |
+        // Grouping
+        "p" | "hr" | "pre" | "blockquote" | "ol" | "ul" | "menu" | "li" | "dl" | "dt" | "dd" |
+        "figure" | "figcaption" | "div" |
+        // Text-level
+        "a" | "em" | "strong" | "small" | "s" | "cite" | "q" | "dfn" | "abbr" | "ruby" | "rt" |
+        "rp" | "data" | "time" | "code" | "var" | "samp" | "kbd" | "sub" | "sup" | "i" | "b" |
+        "u" | "mark" | "bdi" | "bdo" | "span" | "br" | "wbr" |
+        // Edits
+        "ins" | "del" |
+        // Embedded content
+        "picture" | "source" | "img" | "iframe" | "embed" | "object" | "param" | "video" |
+        "audio" | "track" | "map" | "area" | "canvas" |
+        // Tables
+        "table" | "caption" | "colgroup" | "col" | "tbody" | "thead" | "tfoot" | "tr" | "td" | "th" |
+        // Forms
+        "form" | "label" | "input" | "button" | "select" | "datalist" | "optgroup" | "option" |
+        "textarea" | "output" | "progress" | "meter" | "fieldset" | "legend" |
+        // Interactive
+        "details" | "summary" | "dialog" |
+        // Scripting
+        "script" | "noscript" | "template" | "slot"
+    )
+}
+
+/// Whether `tag` is an SVG element, which the DOM creates in the SVG namespace
+pub fn is_svg_tag(tag: &str) -> bool {
+    matches!(
+        tag,
+        "svg" | "g" | "defs" | "symbol" | "use" | "path" | "rect" | "circle" | "ellipse" | "line" |
+        "polyline" | "polygon" | "text" | "tspan" | "image" | "clipPath" | "mask" | "pattern" |
+        "linearGradient" | "radialGradient" | "stop" | "filter" | "foreignObject" | "marker" |
+        "desc"
+    )
+}
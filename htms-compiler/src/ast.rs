@@ -1,26 +1,31 @@
 //! AST (Abstract Syntax Tree) node definitions for HTMS
+//!
+//! Every node implements `PartialEq`, and equality includes each node's
+//! `loc`, so compare trees parsed from the same source text.
 
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use crate::Location;
 
 /// Root node of the AST
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Program {
     pub body: Vec<Declaration>,
     pub loc: Location,
 }
 
 /// Top-level declarations
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum Declaration {
     Component(ComponentDecl),
     Section(SectionDecl),
+    Layout(LayoutDecl),
     Page(PageDecl),
 }
 
 /// Component declaration: `component NavBar { ... }`
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ComponentDecl {
     pub name: String,
     pub parameters: Vec<Parameter>,
@@ -30,24 +35,62 @@ pub struct ComponentDecl {
 }
 
 /// Section declaration: `section HeroSection { ... }`
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SectionDecl {
     pub name: String,
+    pub parameters: Vec<Parameter>,
     pub body: Vec<Node>,
     pub loc: Location,
 }
 
-/// Page declaration: `page home "/" { ... }`
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Layout declaration: `layout Main { header { } @slot footer { } }`
+///
+/// Pages that name it with `uses` render inside it, in place of its `@slot`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayoutDecl {
+    pub name: String,
+    pub body: Vec<Node>,
+    pub loc: Location,
+}
+
+/// Page declaration: `page home "/" { ... }` or `page home "/" uses Main { ... }`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PageDecl {
     pub name: String,
     pub route: String,
+    /// Layout named by `uses`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<String>,
+    /// Content for the document `<head>`, from a `head { }` block in the page
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub head: Vec<Node>,
     pub body: Vec<Node>,
     pub loc: Location,
 }
 
+/// One `/`-separated piece of a page route
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteSegment<'a> {
+    /// Literal text: `users` in `/users/:id`
+    Static(&'a str),
+    /// Dynamic segment, without the colon: `id` in `/users/:id`
+    Param(&'a str),
+}
+
+/// Split a route such as `/users/:id` into its segments
+pub fn route_segments(route: &str) -> Vec<RouteSegment<'_>> {
+    route
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| match segment.strip_prefix(':') {
+            Some(name) => RouteSegment::Param(name),
+            None => RouteSegment::Static(segment),
+        })
+        .collect()
+}
+
 /// Component parameter: `(item: user)`
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Parameter {
     pub name: String,
     pub binding: String,
@@ -55,8 +98,9 @@ pub struct Parameter {
 }
 
 /// Any node that can appear in a body
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type")]
+#[allow(clippy::large_enum_variant)]
 pub enum Node {
     Element(Element),
     ComponentRef(ComponentRef),
@@ -64,21 +108,48 @@ pub enum Node {
     If(IfStatement),
     Each(EachStatement),
     Slot(Slot),
+    Let(LetStatement),
+    Comment(CommentNode),
+    RawHtml(RawHtmlNode),
 }
 
 /// HTML element: `div [class: "container"] { ... }`
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Element {
     pub tag: String,
     pub attributes: Vec<Attribute>,
     pub children: Vec<Node>,
     pub for_directive: Option<ForDirective>,
     pub if_directive: Option<IfDirective>,
+    /// Written with an explicit self-closing slash: `br /`
+    pub self_closing: bool,
     pub loc: Location,
 }
 
+/// HTML void elements, which never have a closing tag
+pub fn is_void_element(tag: &str) -> bool {
+    matches!(
+        tag,
+        "area" | "base" | "br" | "col" | "embed" | "hr" | "img" | "input" |
+        "link" | "meta" | "param" | "source" | "track" | "wbr"
+    )
+}
+
+/// The largest integer a float counts to exactly, JavaScript's `Number.MAX_SAFE_INTEGER`
+pub const MAX_SAFE_INTEGER: i64 = 9_007_199_254_740_991;
+
+/// Built-in values available inside an `@each` body
+pub fn is_loop_helper(name: &str) -> bool {
+    matches!(name, "$even" | "$odd")
+}
+
+/// Tags whose text content is whitespace-significant and must not be trimmed or re-indented
+pub fn is_whitespace_sensitive(tag: &str) -> bool {
+    matches!(tag, "pre" | "textarea")
+}
+
 /// For directive: `@for(ctx.items as item, index)`
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ForDirective {
     pub iterable: Expression,
     pub item_name: String,
@@ -87,7 +158,7 @@ pub struct ForDirective {
 }
 
 /// If directive: `@if(ctx.condition)`
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct IfDirective {
     pub condition: Expression,
     pub else_element: Option<Box<Element>>,
@@ -95,7 +166,10 @@ pub struct IfDirective {
 }
 
 /// Attribute: `class: "container"` or `onClick: submit`
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Spread attributes (`...ctx.props`) are stored with the name `...` and an
+/// `Expression::Spread` value so they keep their position in the list.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Attribute {
     pub name: String,
     pub value: Expression,
@@ -103,16 +177,409 @@ pub struct Attribute {
 }
 
 /// Component reference: `NavBar` or `Card (item: ctx.user)`
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ComponentRef {
     pub name: String,
     pub parameters: Vec<ParameterBinding>,
+    /// Children for the default `@slot`
+    pub children: Vec<Node>,
+    /// Children for named slots: `Card { header: { h1 {{ Title }} } }`
+    pub slot_fills: Vec<SlotFill>,
+    pub loc: Location,
+}
+
+/// Every `@slot` in a component body, including ones forwarded into another
+/// component's slot content
+pub fn collect_slots(nodes: &[Node]) -> Vec<&Slot> {
+    let mut slots = Vec::new();
+    for node in nodes {
+        match node {
+            Node::Slot(slot) => slots.push(slot),
+            Node::Element(el) => slots.extend(collect_slots(&el.children)),
+            Node::ComponentRef(r) => {
+                slots.extend(collect_slots(&r.children));
+                for fill in &r.slot_fills {
+                    slots.extend(collect_slots(&fill.children));
+                }
+            }
+            Node::If(stmt) => {
+                let mut branch = Some(stmt);
+                while let Some(stmt) = branch {
+                    slots.extend(collect_slots(&stmt.consequent));
+                    branch = match &stmt.alternate {
+                        Some(Alternate::Block(nodes)) => {
+                            slots.extend(collect_slots(nodes));
+                            None
+                        }
+                        Some(Alternate::ElseIf(elif)) => Some(elif),
+                        None => None,
+                    };
+                }
+            }
+            Node::Each(stmt) => {
+                slots.extend(collect_slots(&stmt.body));
+                if let Some(empty) = &stmt.empty {
+                    slots.extend(collect_slots(empty));
+                }
+            }
+            Node::Text(_) | Node::Let(_) | Node::Comment(_) | Node::RawHtml(_) => {}
+        }
+    }
+    slots
+}
+
+/// The layout declared as `name`
+pub fn find_layout<'a>(program: &'a Program, name: &str) -> Option<&'a LayoutDecl> {
+    program.body.iter().find_map(|decl| match decl {
+        Declaration::Layout(l) if l.name == name => Some(l),
+        _ => None,
+    })
+}
+
+/// `nodes` with each default `@slot` replaced by `content`, which is how a
+/// page's body renders inside its layout
+pub fn fill_default_slot(nodes: &[Node], content: &[Node]) -> Vec<Node> {
+    substitute_slots(nodes, &|slot| slot.name.is_none().then_some(content))
+}
+
+/// A component body with its slots filled from a reference: the default
+/// `@slot` gets `children` and each named slot its fill, or nothing
+pub fn fill_slots(nodes: &[Node], children: &[Node], fills: &[SlotFill]) -> Vec<Node> {
+    substitute_slots(nodes, &|slot| match &slot.name {
+        None => Some(children),
+        Some(name) => Some(fills.iter().find(|fill| &fill.name == name).map_or(&[], |fill| fill.children.as_slice())),
+    })
+}
+
+/// `nodes` with each `@slot` that `content` has nodes for replaced by them
+fn substitute_slots<'a>(nodes: &[Node], content: &dyn Fn(&Slot) -> Option<&'a [Node]>) -> Vec<Node> {
+    let mut filled = Vec::new();
+    for node in nodes {
+        match node {
+            Node::Slot(slot) => match content(slot) {
+                Some(nodes) => filled.extend(nodes.iter().cloned()),
+                None => filled.push(node.clone()),
+            },
+            Node::Element(el) => filled.push(Node::Element(Element {
+                children: substitute_slots(&el.children, content),
+                ..el.clone()
+            })),
+            Node::ComponentRef(r) => filled.push(Node::ComponentRef(ComponentRef {
+                children: substitute_slots(&r.children, content),
+                slot_fills: r.slot_fills.iter()
+                    .map(|fill| SlotFill {
+                        children: substitute_slots(&fill.children, content),
+                        ..fill.clone()
+                    })
+                    .collect(),
+                ..r.clone()
+            })),
+            Node::If(stmt) => filled.push(Node::If(substitute_if(stmt, content))),
+            Node::Each(stmt) => filled.push(Node::Each(EachStatement {
+                body: substitute_slots(&stmt.body, content),
+                empty: stmt.empty.as_deref().map(|empty| substitute_slots(empty, content)),
+                ..stmt.clone()
+            })),
+            Node::Text(_) | Node::Let(_) | Node::Comment(_) | Node::RawHtml(_) => filled.push(node.clone()),
+        }
+    }
+    filled
+}
+
+fn substitute_if<'a>(stmt: &IfStatement, content: &dyn Fn(&Slot) -> Option<&'a [Node]>) -> IfStatement {
+    IfStatement {
+        consequent: substitute_slots(&stmt.consequent, content),
+        alternate: stmt.alternate.as_ref().map(|alternate| match alternate {
+            Alternate::Block(nodes) => Alternate::Block(substitute_slots(nodes, content)),
+            Alternate::ElseIf(elif) => Alternate::ElseIf(Box::new(substitute_if(elif, content))),
+        }),
+        ..stmt.clone()
+    }
+}
+
+/// Byte ranges `(start, end)` of every foldable block, for editor code folding
+///
+/// Covers declarations, elements and component references with a body, named
+/// slot content and `@if`/`@each` statements (an if/else chain folds as one
+/// range). Ranges nest and are ordered by start offset.
+pub fn folding_ranges(program: &Program) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    for decl in &program.body {
+        let (body, loc) = match decl {
+            Declaration::Component(c) => (&c.body, c.loc),
+            Declaration::Section(s) => (&s.body, s.loc),
+            Declaration::Layout(l) => (&l.body, l.loc),
+            Declaration::Page(p) => (&p.body, p.loc),
+        };
+        ranges.push((loc.start, loc.end));
+        collect_folding_ranges(body, &mut ranges);
+    }
+    ranges
+}
+
+fn collect_folding_ranges(nodes: &[Node], ranges: &mut Vec<(usize, usize)>) {
+    for node in nodes {
+        match node {
+            Node::Element(el) => {
+                if !el.children.is_empty() {
+                    ranges.push((el.loc.start, el.loc.end));
+                    collect_folding_ranges(&el.children, ranges);
+                }
+            }
+            Node::ComponentRef(r) => {
+                if !r.children.is_empty() || !r.slot_fills.is_empty() {
+                    ranges.push((r.loc.start, r.loc.end));
+                }
+                collect_folding_ranges(&r.children, ranges);
+                for fill in &r.slot_fills {
+                    ranges.push((fill.loc.start, fill.loc.end));
+                    collect_folding_ranges(&fill.children, ranges);
+                }
+            }
+            Node::If(stmt) => {
+                ranges.push((stmt.loc.start, stmt.loc.end));
+                let mut branch = Some(stmt);
+                while let Some(stmt) = branch {
+                    collect_folding_ranges(&stmt.consequent, ranges);
+                    branch = match &stmt.alternate {
+                        Some(Alternate::Block(nodes)) => {
+                            collect_folding_ranges(nodes, ranges);
+                            None
+                        }
+                        Some(Alternate::ElseIf(elif)) => Some(elif),
+                        None => None,
+                    };
+                }
+            }
+            Node::Each(stmt) => {
+                ranges.push((stmt.loc.start, stmt.loc.end));
+                collect_folding_ranges(&stmt.body, ranges);
+                if let Some(empty) = &stmt.empty {
+                    collect_folding_ranges(empty, ranges);
+                }
+            }
+            Node::Text(_) | Node::Slot(_) | Node::Let(_) | Node::Comment(_) | Node::RawHtml(_) => {}
+        }
+    }
+}
+
+/// An AST item found by [`Program::node_at`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NodeRef<'a> {
+    Declaration(&'a Declaration),
+    Node(&'a Node),
+    Attribute(&'a Attribute),
+    /// A component argument: `title: heading` in `Card(title: heading)`
+    Argument(&'a ParameterBinding),
+    Expression(&'a Expression),
+}
+
+impl<'a> NodeRef<'a> {
+    pub fn loc(&self) -> Location {
+        match self {
+            NodeRef::Declaration(decl) => decl.loc(),
+            NodeRef::Node(node) => node.loc(),
+            NodeRef::Attribute(attr) => attr.loc,
+            NodeRef::Argument(arg) => arg.loc,
+            NodeRef::Expression(expr) => expr.loc(),
+        }
+    }
+
+    /// The component or section a reference points to, for go-to-definition
+    pub fn referenced_name(&self) -> Option<&'a str> {
+        match self {
+            NodeRef::Node(Node::ComponentRef(r)) => Some(&r.name),
+            _ => None,
+        }
+    }
+}
+
+impl Program {
+    /// The innermost item whose span contains the byte `offset`
+    ///
+    /// Spans are half-open, so an offset at a node's end belongs to what
+    /// follows. Children win over their parents, and an expression wins over
+    /// the attribute or statement holding it.
+    ///
+    /// ```rust
+    /// use htms_compiler::{lexer::tokenize, parser::parse};
+    ///
+    /// let source = "component Logo { img }\npage home \"/\" { Logo }";
+    /// let program = parse(&tokenize(source).unwrap()).unwrap();
+    /// let node = program.node_at(source.rfind("Logo").unwrap()).unwrap();
+    /// assert_eq!(node.referenced_name(), Some("Logo"));
+    /// assert!(matches!(program.declaration("Logo"), Some(decl) if decl.loc().start == 0));
+    /// ```
+    pub fn node_at(&self, offset: usize) -> Option<NodeRef<'_>> {
+        let contains = |loc: Location| loc.start <= offset && offset < loc.end;
+        let decl = self.body.iter().find(|decl| contains(decl.loc()))?;
+        let mut found = NodeRef::Declaration(decl);
+
+        let (attributes, bodies): (&[Attribute], Vec<&[Node]>) = match decl {
+            Declaration::Component(c) => (&c.attributes, vec![&c.body]),
+            Declaration::Section(s) => (&[], vec![&s.body]),
+            Declaration::Layout(l) => (&[], vec![&l.body]),
+            Declaration::Page(p) => (&[], vec![&p.head, &p.body]),
+        };
+        if let Some(attr) = attributes.iter().find(|attr| contains(attr.loc)) {
+            return Some(attribute_at(attr, offset));
+        }
+        let mut nodes: Vec<&Node> = bodies.into_iter().flatten().collect();
+        while let Some(node) = nodes.iter().copied().find(|node| contains(node.loc())) {
+            found = NodeRef::Node(node);
+            let mut expressions: Vec<&Expression> = Vec::new();
+            nodes = Vec::new();
+            match node {
+                Node::Element(el) => {
+                    if let Some(attr) = el.attributes.iter().find(|attr| contains(attr.loc)) {
+                        return Some(attribute_at(attr, offset));
+                    }
+                    expressions.extend(el.for_directive.as_ref().map(|f| &f.iterable));
+                    expressions.extend(el.if_directive.as_ref().map(|i| &i.condition));
+                    nodes.extend(&el.children);
+                }
+                Node::ComponentRef(r) => {
+                    if let Some(arg) = r.parameters.iter().find(|arg| contains(arg.loc)) {
+                        return Some(expression_at(&arg.value, offset).unwrap_or(NodeRef::Argument(arg)));
+                    }
+                    nodes.extend(&r.children);
+                    nodes.extend(r.slot_fills.iter().flat_map(|fill| &fill.children));
+                }
+                Node::If(stmt) => {
+                    let mut branch = Some(stmt);
+                    while let Some(stmt) = branch {
+                        expressions.push(&stmt.condition);
+                        nodes.extend(&stmt.consequent);
+                        branch = match &stmt.alternate {
+                            Some(Alternate::Block(alternate)) => {
+                                nodes.extend(alternate);
+                                None
+                            }
+                            Some(Alternate::ElseIf(elif)) => Some(elif),
+                            None => None,
+                        };
+                    }
+                }
+                Node::Each(stmt) => {
+                    expressions.push(&stmt.iterable);
+                    nodes.extend(&stmt.body);
+                    nodes.extend(stmt.empty.iter().flatten());
+                }
+                Node::Let(stmt) => expressions.push(&stmt.value),
+                Node::RawHtml(raw) => expressions.push(&raw.value),
+                Node::Text(text) => expressions.extend(text.parts.iter().filter_map(|part| match part {
+                    TextPart::Dynamic(expr) => Some(expr),
+                    TextPart::Static(_) => None,
+                })),
+                Node::Slot(_) | Node::Comment(_) => {}
+            }
+            if let Some(expr) = expressions.into_iter().find_map(|expr| expression_at(expr, offset)) {
+                return Some(expr);
+            }
+        }
+        Some(found)
+    }
+
+    /// The component, section, layout or page declared as `name`
+    pub fn declaration(&self, name: &str) -> Option<&Declaration> {
+        self.body.iter().find(|decl| decl.name() == name)
+    }
+}
+
+fn attribute_at(attr: &Attribute, offset: usize) -> NodeRef<'_> {
+    expression_at(&attr.value, offset).unwrap_or(NodeRef::Attribute(attr))
+}
+
+/// The innermost expression within `expr` that contains `offset`
+fn expression_at(expr: &Expression, offset: usize) -> Option<NodeRef<'_>> {
+    let loc = expr.loc();
+    if !(loc.start <= offset && offset < loc.end) {
+        return None;
+    }
+    let children: Vec<&Expression> = match expr {
+        Expression::Array(array) => array.elements.iter().collect(),
+        Expression::Object(object) => object.properties.iter().map(|p| &p.value).collect(),
+        Expression::MemberAccess(member) | Expression::OptionalMember(member) => vec![&member.object],
+        Expression::Binary(binary) => vec![&binary.left, &binary.right],
+        Expression::Ternary(ternary) => vec![&ternary.condition, &ternary.consequent, &ternary.alternate],
+        Expression::Call(call) => call.arguments.iter().collect(),
+        Expression::Event(event) => event.arguments.iter().collect(),
+        Expression::Spread(spread) => vec![&spread.expr],
+        Expression::Range(range) => vec![&range.start, &range.end],
+        Expression::String(_) | Expression::Number(_) | Expression::Boolean(_) | Expression::Null(_)
+        | Expression::ContextPath(_) | Expression::Identifier(_) => Vec::new(),
+    };
+    Some(children.into_iter().find_map(|child| expression_at(child, offset)).unwrap_or(NodeRef::Expression(expr)))
+}
+
+impl Declaration {
+    pub fn name(&self) -> &str {
+        match self {
+            Declaration::Component(c) => &c.name,
+            Declaration::Section(s) => &s.name,
+            Declaration::Layout(l) => &l.name,
+            Declaration::Page(p) => &p.name,
+        }
+    }
+
+    pub fn loc(&self) -> Location {
+        match self {
+            Declaration::Component(c) => c.loc,
+            Declaration::Section(s) => s.loc,
+            Declaration::Layout(l) => l.loc,
+            Declaration::Page(p) => p.loc,
+        }
+    }
+}
+
+impl Node {
+    pub fn loc(&self) -> Location {
+        match self {
+            Node::Element(el) => el.loc,
+            Node::ComponentRef(r) => r.loc,
+            Node::Text(t) => t.loc,
+            Node::If(stmt) => stmt.loc,
+            Node::Each(stmt) => stmt.loc,
+            Node::Slot(slot) => slot.loc,
+            Node::Let(stmt) => stmt.loc,
+            Node::Comment(comment) => comment.loc,
+            Node::RawHtml(raw) => raw.loc,
+        }
+    }
+}
+
+impl Expression {
+    pub fn loc(&self) -> Location {
+        match self {
+            Expression::String(e) => e.loc,
+            Expression::Number(e) => e.loc,
+            Expression::Boolean(e) => e.loc,
+            Expression::Null(e) => e.loc,
+            Expression::Array(e) => e.loc,
+            Expression::Object(e) => e.loc,
+            Expression::ContextPath(e) => e.loc,
+            Expression::Identifier(e) => e.loc,
+            Expression::MemberAccess(e) | Expression::OptionalMember(e) => e.loc,
+            Expression::Binary(e) => e.loc,
+            Expression::Ternary(e) => e.loc,
+            Expression::Call(e) => e.loc,
+            Expression::Event(e) => e.loc,
+            Expression::Spread(e) => e.loc,
+            Expression::Range(e) => e.loc,
+        }
+    }
+}
+
+/// Content for a named slot at a component reference: `header: { ... }`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SlotFill {
+    pub name: String,
     pub children: Vec<Node>,
     pub loc: Location,
 }
 
 /// Parameter binding: `item: ctx.user`
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ParameterBinding {
     pub name: String,
     pub value: Expression,
@@ -120,21 +587,69 @@ pub struct ParameterBinding {
 }
 
 /// Text node: `{{ Hello "world" }}` or `ctx.user.name`
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TextNode {
     pub content: String,
     pub is_dynamic: bool,
+    /// `content` as literal runs and `${...}` expressions, in order. An
+    /// interpolation that doesn't parse stays in its literal run as written
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parts: Vec<TextPart>,
+    /// Whether leading and trailing whitespace was trimmed; `{{-` and `-}}` keep it
+    pub trim: (bool, bool),
     pub loc: Location,
 }
 
-/// Slot: `@slot`
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A run of text: `Hi ` and `ctx.name` in `{{ Hi ${ctx.name} }}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value")]
+pub enum TextPart {
+    Static(String),
+    Dynamic(Expression),
+}
+
+/// Match `${...}` interpolations whose contents match `inner`, capturing
+/// them, or a `\${` escape with no capture, which stands for a literal `${`
+pub fn interpolation_regex(inner: &str) -> Regex {
+    Regex::new(&format!(r"\\\$\{{|\$\{{({})\}}", inner)).unwrap()
+}
+
+/// Slot: `@slot` or the named `@slot(header)`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Slot {
+    /// `None` for the default slot
+    pub name: Option<String>,
+    pub loc: Location,
+}
+
+/// HTML comment passed through to the output: `@comment "Generated nav"`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommentNode {
+    pub text: String,
+    pub loc: Location,
+}
+
+/// Markup inserted without escaping: `@html ctx.article.body`
+///
+/// Only for trusted, already-sanitized HTML.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RawHtmlNode {
+    pub value: Expression,
+    pub loc: Location,
+}
+
+/// Local binding: `@let full = ctx.user.first + " " + ctx.user.last`
+///
+/// Visible to the nodes that follow it in the same block.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LetStatement {
+    pub name: String,
+    pub value: Expression,
     pub loc: Location,
 }
 
 /// If statement: `@if ctx.show { } @else { }`
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct IfStatement {
     pub condition: Expression,
     pub consequent: Vec<Node>,
@@ -143,25 +658,41 @@ pub struct IfStatement {
 }
 
 /// Else branch (either block or else-if)
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(tag = "type")]
+///
+/// Adjacently tagged because an internally tagged variant cannot wrap a sequence.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "body")]
 pub enum Alternate {
     Block(Vec<Node>),
     ElseIf(Box<IfStatement>),
 }
 
-/// Each statement: `@each ctx.items as item, index { }`
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Each statement: `@each ctx.items as item, index { } @else { }`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EachStatement {
     pub iterable: Expression,
     pub item_name: String,
+    /// The position, or the key when iterating entries
     pub index_name: Option<String>,
+    /// What the index counts from: `@each ctx.items as item, n from 1`
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub index_base: i64,
+    /// Iterates an object's entries: `@each ctx.scores as score, name in entries`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub entries: bool,
     pub body: Vec<Node>,
+    /// Rendered instead of the body when the list is empty
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub empty: Option<Vec<Node>>,
     pub loc: Location,
 }
 
+fn is_zero(n: &i64) -> bool {
+    *n == 0
+}
+
 /// Expression (attribute values, conditions, etc.)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum Expression {
     /// String literal: `"hello"`
@@ -170,12 +701,20 @@ pub enum Expression {
     Number(NumberLiteral),
     /// Boolean literal: `true` or `false`
     Boolean(BooleanLiteral),
-    /// Context path: `ctx.user.name`
+    /// Null literal: `null`
+    Null(NullLiteral),
+    /// Array literal: `["card", ctx.active ? "on" : null]`
+    Array(ArrayLiteral),
+    /// Object literal: `{ color: "red", fontSize: 14 }`
+    Object(ObjectLiteral),
+    /// Dotted path: `ctx.user.name`, or `item.name` on a local binding
     ContextPath(ContextPathExpr),
-    /// Identifier: `item` (loop variable) or `submit` (action)
+    /// Identifier: `item` (loop variable), `submit` (action) or `$even` (loop helper)
     Identifier(IdentifierExpr),
     /// Member access: `item.name`, `user.profile.bio`
     MemberAccess(MemberAccessExpr),
+    /// Optional member access: `ctx.user?.name`, undefined when the object is null or undefined
+    OptionalMember(MemberAccessExpr),
     /// Binary expression: `a + b`, `a == b`
     Binary(BinaryExpr),
     /// Ternary expression: `a ? b : c`
@@ -184,76 +723,195 @@ pub enum Expression {
     Call(CallExpr),
     /// Event handler: `onClick.prevent: submit`
     Event(EventExpr),
+    /// Attribute spread: `...ctx.buttonProps`
+    Spread(SpreadAttr),
+    /// Number range, only as an `@each` iterable: `1..5`, or `0..<ctx.count` without the end
+    Range(RangeExpr),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct StringLiteral {
     pub value: String,
     pub loc: Location,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct NumberLiteral {
     pub value: f64,
+    /// Source text of the literal, emitted verbatim so `1.50` stays `1.50`
+    pub raw: String,
     pub loc: Location,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BooleanLiteral {
     pub value: bool,
     pub loc: Location,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NullLiteral {
+    pub loc: Location,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArrayLiteral {
+    pub elements: Vec<Expression>,
+    pub loc: Location,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ObjectLiteral {
+    pub properties: Vec<ObjectProperty>,
+    pub loc: Location,
+}
+
+/// `key: value` in an object literal; the key is an identifier or a string
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ObjectProperty {
+    pub key: String,
+    pub value: Expression,
+    pub loc: Location,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ContextPathExpr {
-    /// Full path including "ctx." prefix
-    pub path: String,
+    pub root: PathRoot,
+    /// Property names in order: `["user", "name"]` for `ctx.user.name`.
+    /// A `Local` path starts with the binding: `["item", "name"]` for `item.name`
+    pub segments: Vec<String>,
+    /// Span of each entry in `segments`, in the same order; `ctx.` has none
+    pub segment_locs: Vec<Location>,
     pub loc: Location,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// What a dotted path starts from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PathRoot {
+    /// The shared context: `ctx.user.name`
+    Ctx,
+    /// A loop variable, `@let` binding or parameter: `item.name`
+    Local,
+}
+
+impl ContextPathExpr {
+    /// The path as written in source: `ctx.user.name` or `item.name`
+    pub fn path(&self) -> String {
+        match self.root {
+            PathRoot::Ctx => format!("ctx.{}", self.segments.join(".")),
+            PathRoot::Local => self.segments.join("."),
+        }
+    }
+
+    /// The segment whose span contains the byte `offset`, with its index, so
+    /// a click on `user` in `ctx.user.name` maps to `(0, "user", span)`
+    pub fn segment_at(&self, offset: usize) -> Option<(usize, &str, Location)> {
+        self.segment_locs.iter()
+            .position(|loc| loc.start <= offset && offset < loc.end)
+            .map(|i| (i, self.segments[i].as_str(), self.segment_locs[i]))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct IdentifierExpr {
     pub name: String,
     pub loc: Location,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct MemberAccessExpr {
     /// Object expression (can be nested member access)
     pub object: Box<Expression>,
     /// Property name
     pub property: String,
+    /// Location of the property name after the `.`
+    pub property_loc: Location,
     pub loc: Location,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BinaryExpr {
     pub operator: BinaryOp,
+    /// Location of the operator token itself
+    pub operator_loc: Location,
     pub left: Box<Expression>,
     pub right: Box<Expression>,
     pub loc: Location,
 }
 
+/// Binary operator, serialized as its source symbol so JSON reads `"=="`
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum BinaryOp {
     // Comparison
-    Eq,     // ==
-    Ne,     // !=
-    Lt,     // <
-    Le,     // <=
-    Gt,     // >
-    Ge,     // >=
+    #[serde(rename = "==")]
+    Eq,
+    #[serde(rename = "!=")]
+    Ne,
+    #[serde(rename = "<")]
+    Lt,
+    #[serde(rename = "<=")]
+    Le,
+    #[serde(rename = ">")]
+    Gt,
+    #[serde(rename = ">=")]
+    Ge,
     // Logical
-    And,    // &&
-    Or,     // ||
+    #[serde(rename = "&&")]
+    And,
+    #[serde(rename = "||")]
+    Or,
+    #[serde(rename = "??")]
+    NullishCoalesce,
     // Arithmetic
-    Add,    // +
-    Sub,    // -
-    Mul,    // *
-    Div,    // /
+    #[serde(rename = "+")]
+    Add,
+    #[serde(rename = "-")]
+    Sub,
+    #[serde(rename = "*")]
+    Mul,
+    #[serde(rename = "/")]
+    Div,
+    #[serde(rename = "%")]
+    Mod,
+}
+
+impl BinaryOp {
+    /// The operator as written in HTMS source
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinaryOp::Eq => "==",
+            BinaryOp::Ne => "!=",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => ">=",
+            BinaryOp::And => "&&",
+            BinaryOp::Or => "||",
+            BinaryOp::NullishCoalesce => "??",
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Mod => "%",
+        }
+    }
+
+    fn is_relational(self) -> bool {
+        matches!(self, BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge)
+    }
+
+    fn is_equality(self) -> bool {
+        matches!(self, BinaryOp::Eq | BinaryOp::Ne)
+    }
+
+    /// Whether `self` applied to the result of `inner` reads as a chain like
+    /// `a < b < c`, which compares a boolean rather than testing a range
+    pub fn chains_with(self, inner: BinaryOp) -> bool {
+        (self.is_relational() && inner.is_relational()) || (self.is_equality() && inner.is_equality())
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TernaryExpr {
     pub condition: Box<Expression>,
     pub consequent: Box<Expression>,
@@ -261,14 +919,14 @@ pub struct TernaryExpr {
     pub loc: Location,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CallExpr {
     pub callee: String,
     pub arguments: Vec<Expression>,
     pub loc: Location,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EventExpr {
     pub event: String,
     pub modifiers: Vec<String>,
@@ -276,3 +934,18 @@ pub struct EventExpr {
     pub arguments: Vec<Expression>,
     pub loc: Location,
 }
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpreadAttr {
+    pub expr: Box<Expression>,
+    pub loc: Location,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RangeExpr {
+    pub start: Box<Expression>,
+    pub end: Box<Expression>,
+    /// `..` includes the end, `..<` stops before it
+    pub inclusive: bool,
+    pub loc: Location,
+}
@@ -0,0 +1,322 @@
+//! Single-file bundle generator
+//!
+//! Inlines the TypeScript-mode output (templates, router, events) as plain
+//! JavaScript in a `<script type="module">` inside one HTML document, so an
+//! app can be deployed as a single file.
+
+use crate::ast::{Declaration, Program};
+use crate::analyzer::SymbolTable;
+use crate::{CompileOptions, GeneratedFile, Indent, ModuleFormat};
+use super::{events, html, router, templates, utils};
+
+/// Generate a single HTML file with all generated code inlined
+pub fn generate(program: &Program, symbols: &SymbolTable, options: &CompileOptions) -> GeneratedFile {
+    let analysis = utils::analyze_events(program);
+    let needs_events = analysis.has_events || analysis.has_bindings;
+
+    // Modules are concatenated with their imports and exports stripped, which expects ES module syntax.
+    // events.ts is bundled whenever it's needed, so templates use its binding helper
+    let options = &CompileOptions { module_format: ModuleFormat::Esm, generate_events: true, ..options.clone() };
+
+    // Templates reference `actions` and `setNestedValue` from events.ts, so bundle it whenever they're used
+    let mut modules = vec![templates::generate(program, symbols, options)];
+    modules.push(router::generate(program, symbols, ModuleFormat::Esm));
+    if needs_events {
+        modules.push(events::generate(program, symbols, ModuleFormat::Esm));
+    }
+
+    let (open, close) = html::script_tags(" type=\"module\"", options);
+    let mut script = open;
+    for module in &modules {
+        for line in strip_types(&module.content).lines() {
+            if line.trim_start().starts_with("//") {
+                continue;
+            }
+            if line.is_empty() {
+                // Modules are separated by blank lines already; keep at most one
+                if !script.ends_with(">\n") && !script.ends_with("\n\n") {
+                    script.push('\n');
+                }
+                continue;
+            }
+            script.push_str("    ");
+            script.push_str(line);
+            script.push('\n');
+        }
+    }
+
+    // Inline modules can't be imported, so expose the runtime API on window
+    if needs_events {
+        script.push_str("    initEvents();\n");
+    }
+    if analysis.has_events {
+        script.push_str("    window.htms = { getContext, setContext, rerender, setActions };\n");
+    } else {
+        script.push_str("    window.htms = { getContext, setContext, rerender };\n");
+    }
+    script.push_str(&close);
+
+    let mut body = String::new();
+    body.push_str("  <div id=\"app\"></div>\n\n");
+    body.push_str(&script);
+
+    let first_page = program.body.iter().find_map(|decl| match decl {
+        Declaration::Page(p) => Some(p.name.as_str()),
+        _ => None,
+    });
+
+    let content = if let Some(template) = options.template_html.as_deref() {
+        html::inject_into_body(template, &body)
+    } else {
+        // The bundled body is laid out with the default indent, so the shell is too
+        let options = CompileOptions { indent: Indent::default(), ..options.clone() };
+        html::standalone_document(&html::title_case(first_page.unwrap_or("app")), "", &body, &options)
+    };
+
+    let path = if let Some(filename) = options.source_filename.as_deref() {
+        filename.to_string()
+    } else {
+        format!("{}.html", first_page.unwrap_or("index").to_lowercase())
+    };
+
+    GeneratedFile { path, content }
+}
+
+/// Erase TypeScript-only syntax from generated code so it runs as plain JavaScript
+///
+/// This only understands the constructs the HTMS generators emit (imports,
+/// type aliases, interfaces, annotations, `as` casts and non-null assertions);
+/// it is not a general TypeScript compiler.
+pub fn strip_types(ts: &str) -> String {
+    let mut out = String::new();
+    let mut lines = ts.lines();
+
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim_start();
+
+        // Cross-module imports are resolved by concatenation
+        if trimmed.starts_with("import ")
+            || trimmed.starts_with("export type ")
+            || trimmed.starts_with("type ")
+        {
+            continue;
+        }
+
+        if trimmed.starts_with("interface ") || trimmed.starts_with("export interface ") {
+            for body_line in lines.by_ref() {
+                if body_line.trim() == "}" {
+                    break;
+                }
+            }
+            continue;
+        }
+
+        let line = strip_declaration_types(line);
+        out.push_str(&strip_expression_types(&line));
+        out.push('\n');
+    }
+
+    out
+}
+
+/// Strip `export`, parameter/return annotations on function headers and `let`/`const` annotations
+fn strip_declaration_types(line: &str) -> String {
+    let body = line.trim_start();
+    let indent = &line[..line.len() - body.len()];
+    // Exports have nothing to bind to once the modules are concatenated
+    let body = body.strip_prefix("export ").unwrap_or(body);
+
+    let is_function = body.starts_with("function ") || body.starts_with("constructor(");
+    if is_function {
+        if let Some(open) = body.find('(') {
+            let close = open + skip_type(&body[open..], &[]).max(1) - 1;
+            let params: Vec<String> = split_top_level(&body[open + 1..close])
+                .into_iter()
+                .map(|param| {
+                    let name = param.split(':').next().unwrap_or("").trim();
+                    // Keep default values: `slots: Slots = {}` -> `slots = {}`
+                    match param.find(" = ") {
+                        Some(eq) => format!("{}{}", name, &param[eq..]),
+                        None => name.to_string(),
+                    }
+                })
+                .filter(|param| !param.is_empty())
+                .collect();
+
+            let mut rest = &body[close + 1..];
+            if rest.starts_with(':') {
+                rest = rest.rfind(" {").map_or("", |p| &rest[p..]);
+            }
+            return format!("{}{}({}){}", indent, &body[..open], params.join(", "), rest);
+        }
+    }
+
+    for keyword in ["let ", "const "] {
+        if let Some(decl) = body.strip_prefix(keyword) {
+            let name_len = decl
+                .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+                .unwrap_or(decl.len());
+            let (name, rest) = decl.split_at(name_len);
+            if let Some(annotated) = rest.strip_prefix(": ") {
+                let end = skip_type(annotated, &[';', '=']);
+                return format!("{}{}{} {}", indent, keyword, name, annotated[end..].trim_start())
+                    .replace(" ;", ";");
+            }
+        }
+    }
+
+    format!("{}{}", indent, body)
+}
+
+/// Strip `as T` casts and `!` non-null assertions outside string literals
+fn strip_expression_types(line: &str) -> String {
+    let chars: Vec<char> = line.chars().collect();
+    let mut out = String::new();
+    // The quote that opened the string literal being copied
+    let mut quote = None;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if let Some(open) = quote {
+            out.push(c);
+            if c == '\\' && i + 1 < chars.len() {
+                out.push(chars[i + 1]);
+                i += 1;
+            } else if c == open {
+                quote = None;
+            }
+            i += 1;
+            continue;
+        }
+
+        if matches!(c, '\'' | '"' | '`') {
+            quote = Some(c);
+            out.push(c);
+            i += 1;
+            continue;
+        }
+
+        if c == ' ' && chars[i..].starts_with(&[' ', 'a', 's', ' ']) {
+            let rest: String = chars[i + 4..].iter().collect();
+            let end = skip_type(&rest, &[')', ';', ',', ']', '}']);
+            i += 4 + rest[..end].chars().count();
+            continue;
+        }
+
+        if c == '!' {
+            let prev = out.chars().last();
+            let next = chars.get(i + 1).copied();
+            let after_operand = prev.is_some_and(|p| p.is_alphanumeric() || p == '_' || p == ')' || p == ']');
+            let before_end = matches!(next, None | Some(']' | ';' | ',' | ')' | '.'));
+            if after_operand && before_end {
+                i += 1;
+                continue;
+            }
+        }
+
+        out.push(c);
+        i += 1;
+    }
+
+    out
+}
+
+/// Return the byte length of the type at the start of `text`, ending at a
+/// depth-0 stop character (a lone `=` when `'='` is a stop, `=>` never is).
+///
+/// With no stops, a leading bracket group is measured up to its closing bracket.
+fn skip_type(text: &str, stops: &[char]) -> usize {
+    let bytes = text.as_bytes();
+    let mut depth = 0i32;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let c = bytes[i] as char;
+        if c == '=' && bytes.get(i + 1) == Some(&b'>') {
+            i += 2;
+            continue;
+        }
+        if depth == 0 && stops.contains(&c) {
+            break;
+        }
+        match c {
+            '<' | '(' | '{' | '[' => depth += 1,
+            '>' | ')' | '}' | ']' => {
+                depth -= 1;
+                if depth == 0 && stops.is_empty() {
+                    return i + 1;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    i
+}
+
+/// Split a parameter list on commas that aren't nested inside brackets
+fn split_top_level(params: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0i32;
+    let mut start = 0;
+
+    for (i, c) in params.char_indices() {
+        match c {
+            '<' | '(' | '{' | '[' => depth += 1,
+            '>' | ')' | '}' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&params[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&params[start..]);
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_function_annotations() {
+        assert_eq!(
+            strip_types("export function List0(ctx: { items: any[] }, item: unknown): HTMLElement[] {\n"),
+            "function List0(ctx, item) {\n"
+        );
+        assert_eq!(strip_types("  constructor(config: RouterConfig) {\n"), "  constructor(config) {\n");
+        assert_eq!(
+            strip_types("export function Card(ctx: Context, slots: Slots = {}): HTMLElement {\n"),
+            "function Card(ctx, slots = {}) {\n"
+        );
+    }
+
+    #[test]
+    fn test_strip_variable_annotations() {
+        assert_eq!(
+            strip_types("const routes: Record<string, (ctx: Record<string, unknown>) => void> = {\n"),
+            "const routes = {\n"
+        );
+        assert_eq!(strip_types("  let el0: Node;\n"), "  let el0;\n");
+    }
+
+    #[test]
+    fn test_strip_casts_outside_strings() {
+        assert_eq!(
+            strip_types("for (const item of (ctx.items as unknown[])) {\n"),
+            "for (const item of (ctx.items)) {\n"
+        );
+        assert_eq!(
+            strip_types("const el0 = document.createTextNode('log in as admin!');\n"),
+            "const el0 = document.createTextNode('log in as admin!');\n"
+        );
+        assert_eq!(strip_types("el0.title = \"shown as is!\";\n"), "el0.title = \"shown as is!\";\n");
+        assert_eq!(strip_types("el0.title = `${a} as ${b}!`;\n"), "el0.title = `${a} as ${b}!`;\n");
+        assert_eq!(strip_types("const last = keys.pop()!;\n"), "const last = keys.pop();\n");
+        assert_eq!(strip_types("if (a !== b) {\n"), "if (a !== b) {\n");
+    }
+}
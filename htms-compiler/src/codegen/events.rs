@@ -2,13 +2,15 @@
 
 use crate::ast::Program;
 use crate::analyzer::SymbolTable;
-use crate::GeneratedFile;
-use super::utils::{analyze_events, EventAnalysis};
+use crate::{GeneratedFile, ModuleFormat};
+use serde::Serialize;
+
+use super::utils::{analyze_events, with_module_format, EventAnalysis, EventHandler};
 
 /// Generate events.ts based on actual usage
-pub fn generate(program: &Program, _symbols: &SymbolTable) -> GeneratedFile {
+pub fn generate(program: &Program, _symbols: &SymbolTable, module_format: ModuleFormat) -> GeneratedFile {
     let analysis = analyze_events(program);
-    let content = generate_events_code(&analysis);
+    let content = with_module_format(generate_events_code(&analysis), module_format);
 
     GeneratedFile {
         path: "events.ts".to_string(),
@@ -16,6 +18,38 @@ pub fn generate(program: &Program, _symbols: &SymbolTable) -> GeneratedFile {
     }
 }
 
+/// Generate events.json, listing every event attribute so a host framework
+/// can wire the handlers itself
+pub fn generate_manifest(program: &Program) -> GeneratedFile {
+    #[derive(Serialize)]
+    struct Manifest<'a> {
+        handlers: &'a [EventHandler],
+    }
+
+    let analysis = analyze_events(program);
+    let manifest = Manifest { handlers: &analysis.handlers };
+    GeneratedFile {
+        path: "events.json".to_string(),
+        content: serde_json::to_string_pretty(&manifest).expect("the manifest serializes") + "\n",
+    }
+}
+
+/// Write `value` at a dotted `path` in `obj`, creating missing objects on the way
+///
+/// templates.ts declares its own copy when events.ts isn't generated.
+pub(super) const SET_NESTED_VALUE: &str = "\
+function setNestedValue(obj: Record<string, unknown>, path: string, value: unknown): void {
+  const keys = path.split('.');
+  const last = keys.pop()!;
+  let current: Record<string, unknown> = obj;
+  for (const k of keys) {
+    if (!current[k] || typeof current[k] !== 'object') current[k] = {};
+    current = current[k] as Record<string, unknown>;
+  }
+  current[last] = value;
+}
+";
+
 fn generate_events_code(analysis: &EventAnalysis) -> String {
     let mut out = String::new();
 
@@ -24,7 +58,7 @@ fn generate_events_code(analysis: &EventAnalysis) -> String {
     out.push_str("// Do not edit manually\n\n");
 
     // Only import what we need
-    if analysis.has_events || analysis.has_bindings {
+    if analysis.has_events {
         out.push_str("import { getContext, setContext, rerender } from './router';\n\n");
     }
 
@@ -62,15 +96,11 @@ fn generate_events_code(analysis: &EventAnalysis) -> String {
         }
     }
 
-    if analysis.has_bindings {
-        out.push_str("  initBinding();\n");
-    }
-
     out.push_str("}\n");
 
     // handleEvent function - only if events are used
     if analysis.has_events {
-        out.push_str("\n");
+        out.push('\n');
         out.push_str("function handleEvent(event: Event): void {\n");
         out.push_str("  const target = event.target as HTMLElement;\n");
         out.push_str("  const actionEl = target.closest('[data-action]') as HTMLElement;\n");
@@ -92,39 +122,10 @@ fn generate_events_code(analysis: &EventAnalysis) -> String {
         out.push_str("}\n");
     }
 
-    // Binding functions - only if bindings are used
+    // Binding helper - templates call it from each bound element's listener
     if analysis.has_bindings {
-        out.push_str("\n");
-        out.push_str("function initBinding(): void {\n");
-        out.push_str("  document.addEventListener('input', (e) => {\n");
-        out.push_str("    const el = e.target as HTMLInputElement;\n");
-        out.push_str("    const bindPath = el.dataset.bind;\n");
-        out.push_str("    if (bindPath) {\n");
-        out.push_str("      const ctx = getContext();\n");
-        out.push_str("      setNestedValue(ctx, bindPath, el.value);\n");
-        out.push_str("      setContext(ctx);\n");
-        out.push_str("    }\n");
-        out.push_str("  });\n");
-        out.push_str("  document.addEventListener('change', (e) => {\n");
-        out.push_str("    const el = e.target as HTMLInputElement;\n");
-        out.push_str("    const bindPath = el.dataset.bind;\n");
-        out.push_str("    if (bindPath && el.type === 'checkbox') {\n");
-        out.push_str("      const ctx = getContext();\n");
-        out.push_str("      setNestedValue(ctx, bindPath, el.checked);\n");
-        out.push_str("      setContext(ctx);\n");
-        out.push_str("    }\n");
-        out.push_str("  });\n");
-        out.push_str("}\n\n");
-        out.push_str("function setNestedValue(obj: Record<string, unknown>, path: string, value: unknown): void {\n");
-        out.push_str("  const keys = path.split('.');\n");
-        out.push_str("  const last = keys.pop()!;\n");
-        out.push_str("  let current: Record<string, unknown> = obj;\n");
-        out.push_str("  for (const k of keys) {\n");
-        out.push_str("    if (!current[k] || typeof current[k] !== 'object') current[k] = {};\n");
-        out.push_str("    current = current[k] as Record<string, unknown>;\n");
-        out.push_str("  }\n");
-        out.push_str("  current[last] = value;\n");
-        out.push_str("}\n");
+        out.push_str("\nexport ");
+        out.push_str(SET_NESTED_VALUE);
     }
 
     out
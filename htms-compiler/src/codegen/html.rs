@@ -3,13 +3,52 @@
 //! Generates static HTML from HTMS templates.
 //! v1: Empty context - all dynamic data is blank.
 
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
 use crate::ast::*;
-use crate::{CompileOptions, GeneratedFile};
+use crate::{CompileOptions, Doctype, GeneratedFile, Indent};
+use crate::analyzer::{eval_static, format_number, is_svg_tag, range_values, ConstValue};
+use super::utils::{comment_text, css_declaration, is_event_attribute, route_regex};
 
 /// Generate HTML output
 pub fn generate(program: &Program, options: &CompileOptions) -> Vec<GeneratedFile> {
-    let mut files = Vec::new();
+    let mut document = Vec::new();
+    let output = generate_to_writer(program, options, &mut document)
+        .expect("writing to a Vec can't fail");
+    match output {
+        Some(output) => {
+            let content = String::from_utf8(document).expect("generated HTML is UTF-8");
+            let mut files = output.files;
+            // The document follows the page templates it loads, as index.html follows it
+            let position = files.iter().position(|f| f.path == "index.html").unwrap_or(files.len());
+            files.insert(position, GeneratedFile { path: output.path, content });
+            files
+        }
+        None => Vec::new(),
+    }
+}
+
+/// The files `generate_to_writer` built besides the document it streamed
+#[derive(Debug, Clone)]
+pub struct HtmlOutput {
+    /// Path of the streamed document, such as `app.html`
+    pub path: String,
+    /// Split page templates and `index.html`, which are one page at most
+    pub files: Vec<GeneratedFile>,
+}
 
+/// Stream the main HTML document to `w`
+///
+/// The document is written one page template at a time, so it is never
+/// held in memory whole. `generate` writes into a buffer with this, so both
+/// produce the same bytes. Returns `None`, having written nothing, when the
+/// program has no pages.
+pub fn generate_to_writer<W: Write>(
+    program: &Program,
+    options: &CompileOptions,
+    w: &mut W,
+) -> io::Result<Option<HtmlOutput>> {
     // Collect all pages
     let pages: Vec<&PageDecl> = program.body.iter()
         .filter_map(|decl| match decl {
@@ -19,138 +58,200 @@ pub fn generate(program: &Program, options: &CompileOptions) -> Vec<GeneratedFil
         .collect();
 
     if pages.is_empty() {
-        return files;
+        return Ok(None);
     }
 
+    // A layout every page shares renders once, around the app container;
+    // otherwise each page renders inside its own layout
+    let shared_layout = pages[0].layout.as_deref()
+        .filter(|name| pages.iter().all(|page| page.layout.as_deref() == Some(*name)))
+        .and_then(|name| find_layout(program, name));
+    let composed: Vec<PageDecl> = pages.iter()
+        .map(|page| match page.layout.as_deref().and_then(|name| find_layout(program, name)) {
+            Some(layout) if shared_layout.is_none() => PageDecl {
+                body: fill_default_slot(&layout.body, &page.body),
+                ..(*page).clone()
+            },
+            _ => (*page).clone(),
+        })
+        .collect();
+    let pages: Vec<&PageDecl> = composed.iter().collect();
+
+    let mut files = Vec::new();
     if options.split_templates {
         // Split mode: Generate separate template files + main HTML with lazy loading
-        generate_split_templates(program, &pages, options, &mut files);
+        generate_split_templates(program, &pages, shared_layout, options, &mut files, w)?;
     } else {
         // Inline mode: Generate single HTML file with all templates inline
-        generate_inline_templates(program, &pages, options, &mut files);
+        generate_inline_templates(program, &pages, shared_layout, options, w)?;
     }
 
-    files
+    // Use the source filename (e.g., "app.htms" -> "app.html"), or else the first page name
+    let path = match options.source_filename.as_deref() {
+        Some(filename) => filename.to_string(),
+        None => format!("{}.html", pages[0].name.to_lowercase()),
+    };
+
+    // The app shell may already be index.html, and it takes precedence
+    if options.emit_index && path != "index.html" {
+        files.push(GeneratedFile {
+            path: "index.html".to_string(),
+            content: generate_index(&pages, options),
+        });
+    }
+
+    Ok(Some(HtmlOutput { path, files }))
+}
+
+/// A page listing every route, sorted by route, linked by its page title
+///
+/// Routes with parameters have no single URL to link to, so they are listed
+/// as text.
+fn generate_index(pages: &[&PageDecl], options: &CompileOptions) -> String {
+    let mut sorted = pages.to_vec();
+    sorted.sort_by(|a, b| a.route.cmp(&b.route));
+
+    let mut body = String::new();
+    body.push_str("  <nav>\n");
+    body.push_str("    <ul>\n");
+    for page in sorted {
+        let title = escape_html(&title_case(&page.name));
+        let route = escape_html(&page.route);
+        if page.route.contains(':') {
+            body.push_str(&format!("      <li>{} <code>{}</code></li>\n", title, route));
+        } else {
+            body.push_str(&format!("      <li><a href=\"{}\">{}</a></li>\n", route, title));
+        }
+    }
+    body.push_str("    </ul>\n");
+    body.push_str("  </nav>\n");
+    standalone_document("Pages", "", &reindent(&body, options.indent), options)
 }
 
 /// Generate inline templates (all templates in single HTML file)
-fn generate_inline_templates(
+fn generate_inline_templates<W: Write>(
     program: &Program,
     pages: &[&PageDecl],
+    layout: Option<&LayoutDecl>,
     options: &CompileOptions,
-    files: &mut Vec<GeneratedFile>,
-) {
-    // Detect common components used in all pages (for hoisting)
-    let common_components = find_common_components(pages);
+    w: &mut W,
+) -> io::Result<()> {
+    // Detect common components used in all pages (for hoisting); an explicit layout replaces this
+    let common_components = if layout.is_some() { Vec::new() } else { find_common_components(pages) };
 
-    let mut templates_content = String::new();
-    let mut routes = Vec::new();
+    let pad = options.indent.level(1);
     let mut layout_content = String::new();
 
     // Generate common components (layout) once
     if !common_components.is_empty() {
         for component_name in &common_components {
-            if let Some(component) = find_component(program, component_name) {
-                generate_component_html(component, &mut layout_content, 2, program);
+            if let Some(body) = find_component(program, component_name) {
+                generate_component_html(body, &mut layout_content, 2, program, options);
             }
         }
     }
 
-    for page in pages {
-        let page_id = format!("page-{}", page.name.to_lowercase());
-        routes.push((page.route.clone(), page_id.clone()));
-
-        // Generate template tag
-        templates_content.push_str(&format!("  <template id=\"{}\">\n", page_id));
-
-        // Generate page content (excluding common components)
-        let mut page_content = String::new();
-        for node in &page.body {
-            if !is_common_component_ref(node, &common_components) {
-                generate_node(node, &mut page_content, 2, program);
-            }
-        }
-        templates_content.push_str(&page_content);
+    // Components rendered more than once are emitted once as their own
+    // template, and pages hold a reference the router expands
+    let page_bodies: Vec<Vec<Node>> = pages.iter()
+        .map(|page| page.body.iter()
+            .filter(|node| !is_common_component_ref(node, &common_components))
+            .cloned()
+            .collect())
+        .collect();
+    let shared = if options.dedupe_components { repeated_components(&page_bodies, program) } else { Vec::new() };
+    let referencing;
+    let page_program = if shared.is_empty() {
+        program
+    } else {
+        referencing = Program {
+            body: program.body.iter().map(|decl| with_component_references(decl, &shared)).collect(),
+            ..program.clone()
+        };
+        &referencing
+    };
 
-        templates_content.push_str("  </template>\n\n");
-    }
+    let routes: Vec<(String, String)> = pages.iter()
+        .map(|page| (page.route.clone(), format!("page-{}", page.name.to_lowercase())))
+        .collect();
+    let apply_heads = pages.iter().any(|page| !page.head.is_empty());
 
     // Generate routing script
-    let router_script = generate_router_script(&routes, false);
+    let router_script = reindent(&generate_router_script(&routes, false, !shared.is_empty(), apply_heads, options), options.indent);
+    let head = page_head(pages[0], 1, program, options);
 
-    // Generate final HTML
-    let html = if let Some(template) = options.template_html.as_deref() {
-        // Inject layout, templates, app container, and script into body
-        let mut combined = String::new();
-        if !layout_content.is_empty() {
-            combined.push_str("  <div id=\"layout\">\n");
-            combined.push_str(&layout_content);
-            combined.push_str("  </div>\n\n");
-        }
-        combined.push_str("  <div id=\"app\"></div>\n\n");
-        combined.push_str(&templates_content);
-        combined.push_str(&router_script);
-        inject_into_body(template, &combined)
-    } else {
-        // Generate standalone HTML5 document
-        let mut html = String::new();
-        html.push_str("<!DOCTYPE html>\n");
-        html.push_str("<html lang=\"en\">\n");
-        html.push_str("<head>\n");
-        html.push_str("  <meta charset=\"UTF-8\">\n");
-        html.push_str("  <meta name=\"viewport\" content=\"width=device-width, initial-scale=1.0\">\n");
-        html.push_str(&format!("  <title>{}</title>\n", title_case(&pages[0].name)));
-        html.push_str("</head>\n");
-        html.push_str("<body>\n");
-        if !layout_content.is_empty() {
-            html.push_str("  <div id=\"layout\">\n");
-            html.push_str(&layout_content);
-            html.push_str("  </div>\n\n");
-        }
-        html.push_str("  <div id=\"app\"></div>\n\n");
-        html.push_str(&templates_content);
-        html.push_str(&router_script);
-        html.push_str("</body>\n");
-        html.push_str("</html>\n");
-        html
+    let app = match layout {
+        Some(layout) => layout_shell(layout, None, program, options),
+        None => format!("{}<div id=\"app\"></div>\n", pad),
     };
+    write_document(w, &page_title(pages[0], program, options), &head, options, |w| {
+        if !layout_content.is_empty() {
+            writeln!(w, "{}<div id=\"layout\">", pad)?;
+            w.write_all(layout_content.as_bytes())?;
+            write!(w, "{}</div>\n\n", pad)?;
+        }
+        w.write_all(app.as_bytes())?;
+        w.write_all(b"\n")?;
+
+        for name in &shared {
+            if let Some(body) = find_component(page_program, name) {
+                let mut template = format!("{}<template id=\"{}\">\n", pad, component_template_id(name));
+                generate_component_html(body, &mut template, 2, page_program, options);
+                template.push_str(&format!("{}</template>\n\n", pad));
+                w.write_all(template.as_bytes())?;
+            }
+        }
 
-    // Determine output filename
-    let output_filename = if let Some(filename) = options.source_filename.as_deref() {
-        // Use source filename (e.g., "app.htms" -> "app.html")
-        filename.to_string()
-    } else {
-        // Fall back to first page name
-        format!("{}.html", pages[0].name.to_lowercase())
-    };
+        // Each page template is written as soon as it is generated
+        for ((page, body), (_, page_id)) in pages.iter().zip(&page_bodies).zip(&routes) {
+            let mut template = format!("{}<template id=\"{}\">\n", pad, page_id);
+            if apply_heads {
+                template.push_str(&head_template(page, 2, program, options));
+            }
 
-    files.push(GeneratedFile {
-        path: output_filename,
-        content: html,
-    });
+            // Generate page content (excluding common components)
+            for node in replace_component_refs(body, &shared) {
+                generate_node(&node, &mut template, 2, page_program, options);
+            }
+            template.push_str(&format!("{}</template>\n\n", pad));
+            w.write_all(template.as_bytes())?;
+        }
+
+        w.write_all(router_script.as_bytes())
+    })
 }
 
 /// Generate split templates (separate .template.html files for lazy loading)
-fn generate_split_templates(
+fn generate_split_templates<W: Write>(
     program: &Program,
     pages: &[&PageDecl],
+    layout: Option<&LayoutDecl>,
     options: &CompileOptions,
     files: &mut Vec<GeneratedFile>,
-) {
+    w: &mut W,
+) -> io::Result<()> {
     let mut routes = Vec::new();
+    let apply_heads = pages.iter().any(|page| !page.head.is_empty());
 
     // Generate individual template files
     for page in pages {
-        let page_name = page.name.to_lowercase();
-        let template_filename = format!("{}.template.html", page_name);
-        routes.push((page.route.clone(), template_filename.clone()));
-
-        // Generate page content
+        // Generate page content, led by its head when the router swaps heads
         let mut page_content = String::new();
+        if apply_heads {
+            page_content.push_str(&head_template(page, 0, program, options));
+        }
         for node in &page.body {
-            generate_node(node, &mut page_content, 0, program);
+            generate_node(node, &mut page_content, 0, program, options);
         }
 
+        let page_name = page.name.to_lowercase();
+        let template_filename = if options.hash_filenames {
+            format!("{}.template.{}.html", page_name, content_hash(&page_content))
+        } else {
+            format!("{}.template.html", page_name)
+        };
+        routes.push((page.route.clone(), template_filename.clone()));
+
         files.push(GeneratedFile {
             path: template_filename,
             content: page_content,
@@ -158,59 +259,198 @@ fn generate_split_templates(
     }
 
     // Generate routing script for lazy loading
-    let router_script = generate_router_script(&routes, true);
+    let router_script = reindent(&generate_router_script(&routes, true, false, apply_heads, options), options.indent);
+    let head = page_head(pages[0], 1, program, options) + &prefetch_links(&routes, &options.preload_routes, options);
 
     // Generate main HTML file
-    let html = if let Some(template) = options.template_html.as_deref() {
-        // Inject app container and script into body
-        let mut combined = String::new();
-        combined.push_str("  <div id=\"app\">Loading...</div>\n\n");
-        combined.push_str(&router_script);
-        inject_into_body(template, &combined)
-    } else {
-        // Generate standalone HTML5 document
-        let mut html = String::new();
-        html.push_str("<!DOCTYPE html>\n");
-        html.push_str("<html lang=\"en\">\n");
-        html.push_str("<head>\n");
-        html.push_str("  <meta charset=\"UTF-8\">\n");
-        html.push_str("  <meta name=\"viewport\" content=\"width=device-width, initial-scale=1.0\">\n");
-        html.push_str(&format!("  <title>{}</title>\n", title_case(&pages[0].name)));
-        html.push_str("</head>\n");
-        html.push_str("<body>\n");
-        html.push_str("  <div id=\"app\">Loading...</div>\n");
-        html.push_str(&router_script);
-        html.push_str("</body>\n");
-        html.push_str("</html>\n");
-        html
+    let app = match layout {
+        Some(layout) => layout_shell(layout, Some("Loading..."), program, options),
+        None => format!("{}<div id=\"app\">Loading...</div>\n", options.indent.level(1)),
     };
+    write_document(w, &page_title(pages[0], program, options), &head, options, |w| {
+        w.write_all(app.as_bytes())?;
+        if options.template_html.is_some() {
+            w.write_all(b"\n")?;
+        }
+        w.write_all(router_script.as_bytes())
+    })
+}
+
+/// Write the app shell around the body `write_body` writes: the template
+/// HTML when there is one, otherwise a standalone document
+///
+/// A template without a `<body>` tag is written as-is, without the body.
+fn write_document<W: Write>(
+    w: &mut W,
+    title: &str,
+    head: &str,
+    options: &CompileOptions,
+    write_body: impl FnOnce(&mut W) -> io::Result<()>,
+) -> io::Result<()> {
+    match options.template_html.as_deref() {
+        Some(template) => {
+            let template = inject_into_head(template, head);
+            match split_at_body(&template) {
+                Some((start, end)) => {
+                    w.write_all(start.as_bytes())?;
+                    w.write_all(b"\n")?;
+                    write_body(w)?;
+                    w.write_all(end.as_bytes())
+                }
+                None => w.write_all(template.as_bytes()),
+            }
+        }
+        None => {
+            w.write_all(document_start(title, head, options).as_bytes())?;
+            write_body(w)?;
+            w.write_all(DOCUMENT_END.as_bytes())
+        }
+    }
+}
 
-    // Determine output filename
-    let output_filename = if let Some(filename) = options.source_filename.as_deref() {
-        filename.to_string()
+/// A shared layout rendered once, with the app container in place of its `@slot`
+fn layout_shell(layout: &LayoutDecl, placeholder: Option<&str>, program: &Program, options: &CompileOptions) -> String {
+    let loc = layout.loc;
+    let container = Node::Element(Element {
+        tag: "div".to_string(),
+        attributes: vec![Attribute {
+            name: "id".to_string(),
+            value: Expression::String(StringLiteral { value: "app".to_string(), loc }),
+            loc,
+        }],
+        children: placeholder
+            .map(|text| Node::Text(TextNode {
+                content: text.to_string(),
+                is_dynamic: false,
+                parts: vec![TextPart::Static(text.to_string())],
+                trim: (true, true),
+                loc,
+            }))
+            .into_iter()
+            .collect(),
+        for_directive: None,
+        if_directive: None,
+        self_closing: false,
+        loc,
+    });
+
+    let mut html = String::new();
+    for node in fill_default_slot(&layout.body, &[container]) {
+        generate_node(&node, &mut html, 1, program, options);
+    }
+    html
+}
+
+/// A page's `<title>` text, from its head block or else its name
+fn page_title(page: &PageDecl, program: &Program, options: &CompileOptions) -> String {
+    let title = page.head.iter().find_map(|node| match node {
+        Node::Element(el) if el.tag == "title" => Some(el),
+        _ => None,
+    });
+    match title {
+        Some(title) => {
+            let mut text = String::new();
+            for child in &title.children {
+                generate_node(child, &mut text, 0, program, options);
+            }
+            text.trim().to_string()
+        }
+        None => title_case(&page.name),
+    }
+}
+
+/// A page's head content other than its title
+///
+/// Elements are marked `data-page-head`, so the router can remove them when
+/// another page renders.
+fn page_head(page: &PageDecl, indent: usize, program: &Program, options: &CompileOptions) -> String {
+    let mut html = String::new();
+    for node in &page.head {
+        match node {
+            Node::Element(el) if el.tag == "title" => {}
+            Node::Element(el) => {
+                let marker = Attribute {
+                    name: "data-page-head".to_string(),
+                    value: Expression::Boolean(BooleanLiteral { value: true, loc: el.loc }),
+                    loc: el.loc,
+                };
+                let marked = Element {
+                    attributes: el.attributes.iter().cloned().chain([marker]).collect(),
+                    ..el.clone()
+                };
+                generate_element(&marked, &mut html, indent, program, options);
+            }
+            node => generate_node(node, &mut html, indent, program, options),
+        }
+    }
+    html
+}
+
+/// `<template data-head>` holding a page's title and head content, which
+/// the router moves into the document head when the page renders
+fn head_template(page: &PageDecl, indent: usize, program: &Program, options: &CompileOptions) -> String {
+    let indent_str = options.indent.level(indent);
+    let start = format!("{}<template{} data-title=\"{}\">", indent_str, boolean_attribute("data-head", options), page_title(page, program, options));
+    let content = page_head(page, indent + 1, program, options);
+    if content.is_empty() {
+        format!("{}</template>\n", start)
     } else {
-        format!("{}.html", pages[0].name.to_lowercase())
-    };
+        format!("{}\n{}{}</template>\n", start, content, indent_str)
+    }
+}
 
-    files.push(GeneratedFile {
-        path: output_filename,
-        content: html,
+/// Stable short hash of a file's content for cache-busting filenames (32-bit FNV-1a)
+fn content_hash(content: &str) -> String {
+    let hash = content.bytes().fold(0x811c9dc5u32, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x01000193)
     });
+    format!("{:08x}", hash)
+}
+
+/// `<link rel="prefetch">` tags for the templates of the routes in `preload`,
+/// in the order given; routes that don't belong to a page are skipped
+fn prefetch_links(routes: &[(String, String)], preload: &[String], options: &CompileOptions) -> String {
+    let mut links = String::new();
+    for route in preload {
+        if let Some((_, template)) = routes.iter().find(|(path, _)| path == route) {
+            links.push_str(&format!(
+                "{}<link rel=\"prefetch\" href=\"{}\"{}\n",
+                options.indent.level(1),
+                escape_html(template),
+                void_tag_end(options)
+            ));
+        }
+    }
+    links
 }
 
 /// Generate client-side routing script
-fn generate_router_script(routes: &[(String, String)], lazy_load: bool) -> String {
-    let mut script = String::new();
-    script.push_str("  <script>\n");
+///
+/// With `expand_components`, pages hold `<template data-component>`
+/// references to shared component templates, which are replaced with a copy
+/// of the component before the page is shown. With `apply_heads`, each page
+/// starts with a `<template data-head>` that replaces the previous page's
+/// title and head content.
+fn generate_router_script(routes: &[(String, String)], lazy_load: bool, expand_components: bool, apply_heads: bool, options: &CompileOptions) -> String {
+    let (open, close) = script_tags("", options);
+    let mut script = open;
+
+    if apply_heads {
+        script.push_str("    // Move the rendered page's head content into the document head\n");
+        script.push_str("    function applyHead(root) {\n");
+        script.push_str("      const head = root.querySelector('template[data-head]');\n");
+        script.push_str("      if (!head) return;\n");
+        script.push_str("      head.remove();\n");
+        script.push_str("      document.title = head.dataset.title;\n");
+        script.push_str("      document.head.querySelectorAll('[data-page-head]').forEach((el) => el.remove());\n");
+        script.push_str("      document.head.appendChild(head.content);\n");
+        script.push_str("    }\n\n");
+    }
 
     if lazy_load {
         // Lazy loading router with fetch
         script.push_str("    // HTMS Router v2 - Lazy-loading template routing\n");
-        script.push_str("    const routes = {\n");
-        for (path, template_file) in routes {
-            script.push_str(&format!("      '{}': '{}',\n", path, template_file));
-        }
-        script.push_str("    };\n\n");
+        script.push_str(&route_table(routes));
 
         script.push_str("    // Cache loaded templates\n");
         script.push_str("    const templateCache = new Map();\n\n");
@@ -227,7 +467,7 @@ fn generate_router_script(routes: &[(String, String)], lazy_load: bool) -> Strin
 
         script.push_str("    async function renderPage() {\n");
         script.push_str("      const path = window.location.pathname;\n");
-        script.push_str("      const templateUrl = routes[path] || routes['/'];\n\n");
+        script.push_str("      const templateUrl = matchRoute(path);\n\n");
         script.push_str("      if (!templateUrl) {\n");
         script.push_str("        document.getElementById('app').innerHTML = '<h1>404 - Page Not Found</h1>';\n");
         script.push_str("        return;\n");
@@ -235,6 +475,9 @@ fn generate_router_script(routes: &[(String, String)], lazy_load: bool) -> Strin
         script.push_str("      try {\n");
         script.push_str("        const html = await loadTemplate(templateUrl);\n");
         script.push_str("        document.getElementById('app').innerHTML = html;\n");
+        if apply_heads {
+            script.push_str("        applyHead(document.getElementById('app'));\n");
+        }
         script.push_str("      } catch (error) {\n");
         script.push_str("        console.error('Failed to load template:', error);\n");
         script.push_str("        document.getElementById('app').innerHTML = '<h1>Error loading page</h1>';\n");
@@ -243,15 +486,22 @@ fn generate_router_script(routes: &[(String, String)], lazy_load: bool) -> Strin
     } else {
         // Inline template router
         script.push_str("    // HTMS Router v2 - Template-based client-side routing\n");
-        script.push_str("    const routes = {\n");
-        for (path, template_id) in routes {
-            script.push_str(&format!("      '{}': '{}',\n", path, template_id));
+        script.push_str(&route_table(routes));
+
+        if expand_components {
+            script.push_str("    // Replace each component reference with a copy of its template\n");
+            script.push_str("    function expandComponents(root) {\n");
+            script.push_str("      root.querySelectorAll('template[data-component]').forEach((ref) => {\n");
+            script.push_str("        const content = document.getElementById(ref.dataset.component).content.cloneNode(true);\n");
+            script.push_str("        expandComponents(content);\n");
+            script.push_str("        ref.replaceWith(content);\n");
+            script.push_str("      });\n");
+            script.push_str("    }\n\n");
         }
-        script.push_str("    };\n\n");
 
         script.push_str("    function renderPage() {\n");
         script.push_str("      const path = window.location.pathname;\n");
-        script.push_str("      const templateId = routes[path] || routes['/'];\n");
+        script.push_str("      const templateId = matchRoute(path);\n");
         script.push_str("      \n");
         script.push_str("      // Get or create app container\n");
         script.push_str("      let appContainer = document.getElementById('app');\n");
@@ -274,6 +524,12 @@ fn generate_router_script(routes: &[(String, String)], lazy_load: bool) -> Strin
         script.push_str("      \n");
         script.push_str("      // Clear container and render template content\n");
         script.push_str("      const content = template.content.cloneNode(true);\n");
+        if expand_components {
+            script.push_str("      expandComponents(content);\n");
+        }
+        if apply_heads {
+            script.push_str("      applyHead(content);\n");
+        }
         script.push_str("      appContainer.innerHTML = '';\n");
         script.push_str("      appContainer.appendChild(content);\n");
         script.push_str("    }\n\n");
@@ -295,43 +551,154 @@ fn generate_router_script(routes: &[(String, String)], lazy_load: bool) -> Strin
     script.push_str("    \n");
     script.push_str("    // Initial render on load\n");
     script.push_str("    window.addEventListener('load', renderPage);\n");
-    script.push_str("  </script>\n");
+    script.push_str(&close);
 
     script
 }
 
-/// Inject generated content into template's <body> tag
-fn inject_into_body(template: &str, content: &str) -> String {
-    // Find <body> tag and inject content
-    // We look for <body> or <body ...attributes...>
-    use regex::Regex;
+/// Emit the `routes` lookup table and `matchRoute`, which falls back to `/`
+///
+/// Routes with `:param` segments are matched by regex after exact paths.
+fn route_table(routes: &[(String, String)]) -> String {
+    let mut script = String::new();
+    script.push_str("    const routes = {\n");
+    for (path, target) in routes.iter().filter(|(path, _)| route_regex(path).is_none()) {
+        script.push_str(&format!("      '{}': '{}',\n", path, target));
+    }
+    script.push_str("    };\n");
 
-    let re = Regex::new(r"(?i)(<body[^>]*>)").unwrap();
+    script.push_str("    const routePatterns = [\n");
+    for (path, target) in routes {
+        if let Some((pattern, _)) = route_regex(path) {
+            script.push_str(&format!("      [{}, '{}'],\n", pattern, target));
+        }
+    }
+    script.push_str("    ];\n\n");
+
+    script.push_str("    function matchRoute(path) {\n");
+    script.push_str("      if (routes[path]) return routes[path];\n");
+    script.push_str("      const found = routePatterns.find(([pattern]) => pattern.test(path));\n");
+    script.push_str("      return found ? found[1] : routes['/'];\n");
+    script.push_str("    }\n\n");
+    script
+}
+
+/// Wrap body content in a standalone document, with `head` written after the title
+pub(super) fn standalone_document(title: &str, head: &str, body: &str, options: &CompileOptions) -> String {
+    document_start(title, head, options) + body + DOCUMENT_END
+}
+
+/// A standalone document up to and including its `<body>` tag, with the
+/// doctype and `lang` from `options`
+fn document_start(title: &str, head: &str, options: &CompileOptions) -> String {
+    let pad = options.indent.level(1);
+    let mut html = String::new();
+    let mut root = String::from("<html");
+    match options.doctype {
+        Doctype::Html5 => html.push_str("<!DOCTYPE html>\n"),
+        Doctype::Xhtml => {
+            html.push_str("<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Strict//EN\" \"http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd\">\n");
+            root.push_str(" xmlns=\"http://www.w3.org/1999/xhtml\"");
+        }
+        Doctype::None => {}
+    }
+    if let Some(lang) = &options.html_lang {
+        root.push_str(&format!(" lang=\"{}\"", escape_html(lang)));
+    }
+    html.push_str(&root);
+    html.push_str(">\n");
+    html.push_str("<head>\n");
+    let end = void_tag_end(options);
+    html.push_str(&format!("{}<meta charset=\"UTF-8\"{}\n", pad, end));
+    html.push_str(&format!("{}<meta name=\"viewport\" content=\"width=device-width, initial-scale=1.0\"{}\n", pad, end));
+    html.push_str(&format!("{}<title>{}</title>\n", pad, title));
+    html.push_str(head);
+    html.push_str("</head>\n");
+    html.push_str("<body>\n");
+    html
+}
 
-    if let Some(captures) = re.captures(template) {
-        let body_tag = captures.get(1).unwrap();
-        let insert_pos = body_tag.end();
+const DOCUMENT_END: &str = "</body>\n</html>\n";
 
-        let mut result = String::new();
-        result.push_str(&template[..insert_pos]);
-        result.push('\n');
-        result.push_str(content);
-        result.push_str(&template[insert_pos..]);
-        result
+/// How a void element's start tag ends: `>`, or ` />` in XHTML, which is XML
+pub(super) fn void_tag_end(options: &CompileOptions) -> &'static str {
+    if options.doctype == Doctype::Xhtml { " />" } else { ">" }
+}
+
+/// ` name` for a true boolean attribute; XML has no attributes without a
+/// value, so XHTML repeats the name as the value
+pub(super) fn boolean_attribute(name: &str, options: &CompileOptions) -> String {
+    if options.doctype == Doctype::Xhtml {
+        format!(" {}=\"{}\"", name, name)
+    } else {
+        format!(" {}", name)
+    }
+}
+
+/// The lines that open and close a `<script>` block at the first indent
+/// level. XHTML wraps the code in a CDATA section, where `<` and `&` aren't
+/// markup, commented out for the script itself.
+pub(super) fn script_tags(attributes: &str, options: &CompileOptions) -> (String, String) {
+    if options.doctype == Doctype::Xhtml {
+        (format!("  <script{}>\n    //<![CDATA[\n", attributes), "    //]]>\n  </script>\n".to_string())
     } else {
+        (format!("  <script{}>\n", attributes), "  </script>\n".to_string())
+    }
+}
+
+/// Re-indent text the generator lays out in two-space steps, such as the
+/// router script
+fn reindent(text: &str, indent: Indent) -> String {
+    if indent == Indent::default() {
+        return text.to_string();
+    }
+    text.split_inclusive('\n')
+        .map(|line| {
+            let content = line.trim_start_matches(' ');
+            indent.level((line.len() - content.len()) / 2) + content
+        })
+        .collect()
+}
+
+/// Inject generated content into template's <body> tag
+pub(super) fn inject_into_body(template: &str, content: &str) -> String {
+    match split_at_body(template) {
+        Some((start, end)) => format!("{}\n{}{}", start, content, end),
         // No <body> tag found, return template as-is
-        template.to_string()
+        None => template.to_string(),
+    }
+}
+
+/// Split a template just after its `<body>` or `<body ...attributes...>` tag
+fn split_at_body(template: &str) -> Option<(&str, &str)> {
+    use regex::Regex;
+
+    let re = Regex::new(r"(?i)(<body[^>]*>)").unwrap();
+    let insert_pos = re.find(template)?.end();
+    Some(template.split_at(insert_pos))
+}
+
+/// Insert generated tags just before the template's `</head>`
+///
+/// Templates without a head are returned as-is.
+pub(super) fn inject_into_head(template: &str, content: &str) -> String {
+    if content.is_empty() {
+        return template.to_string();
+    }
+    match template.to_ascii_lowercase().find("</head>") {
+        Some(pos) => format!("{}{}{}", &template[..pos], content, &template[pos..]),
+        None => template.to_string(),
     }
 }
 
-fn generate_node(node: &Node, html: &mut String, indent: usize, program: &Program) {
+fn generate_node(node: &Node, html: &mut String, indent: usize, program: &Program, options: &CompileOptions) {
     match node {
-        Node::Element(el) => generate_element(el, html, indent, program),
+        Node::Element(el) => generate_element(el, html, indent, program, options),
         Node::ComponentRef(comp_ref) => {
-            // Resolve and inline component body
-            if let Some(component) = find_component(program, &comp_ref.name) {
-                for child in &component.body {
-                    generate_node(child, html, indent, program);
+            // Resolve and inline component body, with the reference's content in its slots
+            if let Some(body) = find_component(program, &comp_ref.name) {
+                for child in fill_slots(body, &comp_ref.children, &comp_ref.slot_fills) {
+                    generate_node(&child, html, indent, program, options);
                 }
             }
         }
@@ -339,25 +706,162 @@ fn generate_node(node: &Node, html: &mut String, indent: usize, program: &Progra
         Node::If(_) => {
             // v1: @if assumes false, renders nothing
         }
-        Node::Each(_) => {
-            // v1: @each assumes empty array, renders nothing
+        Node::Each(stmt) => {
+            // v1: @each assumes empty array, so only the @else block renders,
+            // except over a constant range, whose numbers are known
+            let values = match &stmt.iterable {
+                Expression::Range(range) if !stmt.entries => match (eval_static(&range.start), eval_static(&range.end)) {
+                    (Some(ConstValue::Number(start)), Some(ConstValue::Number(end))) => range_values(start, end, range.inclusive),
+                    _ => Vec::new(),
+                },
+                _ => Vec::new(),
+            };
+            if values.is_empty() {
+                for child in stmt.empty.iter().flatten() {
+                    generate_node(child, html, indent, program, options);
+                }
+            }
+            for (index, value) in values.into_iter().enumerate() {
+                let mut bindings = vec![
+                    (stmt.item_name.as_str(), ConstValue::Number(value)),
+                    ("$even", ConstValue::Boolean(index % 2 == 0)),
+                    ("$odd", ConstValue::Boolean(index % 2 == 1)),
+                ];
+                if let Some(index_name) = &stmt.index_name {
+                    bindings.push((index_name.as_str(), ConstValue::Number((index as i64 + stmt.index_base) as f64)));
+                }
+                for child in bind_constants(&stmt.body, &bindings) {
+                    generate_node(&child, html, indent, program, options);
+                }
+            }
         }
         Node::Slot(_) => {
-            // v1: Slots are not rendered
+            // Filled when the component is inlined; one left over has no content
+        }
+        Node::Let(_) => {
+            // Bindings only come from dynamic data, which static HTML doesn't render
+        }
+        Node::RawHtml(raw) => {
+            // Markup known at compile time goes in as is; anything else comes from data
+            if let Some(ConstValue::String(markup)) = eval_static(&raw.value) {
+                html.push_str(&options.indent.level(indent));
+                html.push_str(&markup);
+                html.push('\n');
+            }
+        }
+        Node::Comment(comment) => {
+            html.push_str(&options.indent.level(indent));
+            html.push_str(&format!("<!-- {} -->\n", comment_text(&comment.text)));
+        }
+    }
+}
+
+/// Copy `nodes` with the names in `bindings` replaced by their values, in
+/// attributes and `${...}` interpolations, so they fold like constants
+fn bind_constants(nodes: &[Node], bindings: &[(&str, ConstValue)]) -> Vec<Node> {
+    let mut nodes = nodes.to_vec();
+    for node in &mut nodes {
+        bind_node(node, bindings);
+    }
+    nodes
+}
+
+fn bind_node(node: &mut Node, bindings: &[(&str, ConstValue)]) {
+    match node {
+        Node::Element(el) => {
+            for attr in &mut el.attributes {
+                bind_expression(&mut attr.value, bindings);
+            }
+            for child in &mut el.children {
+                bind_node(child, bindings);
+            }
+        }
+        Node::ComponentRef(r) => {
+            for child in r.children.iter_mut().chain(r.slot_fills.iter_mut().flat_map(|fill| &mut fill.children)) {
+                bind_node(child, bindings);
+            }
+        }
+        Node::Each(stmt) => {
+            bind_expression(&mut stmt.iterable, bindings);
+            for child in stmt.empty.iter_mut().flatten() {
+                bind_node(child, bindings);
+            }
+            // The inner loop's names hide the outer ones in its body
+            let inner: Vec<_> = bindings.iter()
+                .filter(|(name, _)| {
+                    *name != stmt.item_name
+                        && Some(*name) != stmt.index_name.as_deref()
+                        && !name.starts_with('$')
+                })
+                .cloned()
+                .collect();
+            for child in &mut stmt.body {
+                bind_node(child, &inner);
+            }
+        }
+        Node::Text(text) => {
+            for part in &mut text.parts {
+                if let TextPart::Dynamic(expr) = part {
+                    bind_expression(expr, bindings);
+                }
+            }
+        }
+        Node::RawHtml(raw) => bind_expression(&mut raw.value, bindings),
+        // Static HTML renders none of these
+        Node::If(_) | Node::Slot(_) | Node::Let(_) | Node::Comment(_) => {}
+    }
+}
+
+fn bind_expression(expr: &mut Expression, bindings: &[(&str, ConstValue)]) {
+    match expr {
+        Expression::Identifier(ident) => {
+            let loc = ident.loc;
+            if let Some((_, value)) = bindings.iter().find(|(name, _)| *name == ident.name) {
+                *expr = match value {
+                    ConstValue::Number(n) => Expression::Number(NumberLiteral { value: *n, raw: format_number(*n), loc }),
+                    ConstValue::Boolean(b) => Expression::Boolean(BooleanLiteral { value: *b, loc }),
+                    ConstValue::String(s) => Expression::String(StringLiteral { value: s.clone(), loc }),
+                };
+            }
+        }
+        Expression::Binary(b) => {
+            bind_expression(&mut b.left, bindings);
+            bind_expression(&mut b.right, bindings);
+        }
+        Expression::Ternary(t) => {
+            bind_expression(&mut t.condition, bindings);
+            bind_expression(&mut t.consequent, bindings);
+            bind_expression(&mut t.alternate, bindings);
+        }
+        Expression::Array(a) => {
+            for element in &mut a.elements {
+                bind_expression(element, bindings);
+            }
+        }
+        Expression::Object(o) => {
+            for property in &mut o.properties {
+                bind_expression(&mut property.value, bindings);
+            }
+        }
+        Expression::Range(r) => {
+            bind_expression(&mut r.start, bindings);
+            bind_expression(&mut r.end, bindings);
         }
+        _ => {}
     }
 }
 
-/// Find a component by name in the program
-fn find_component<'a>(program: &'a Program, name: &str) -> Option<&'a ComponentDecl> {
+/// Find the body of a component or section by name in the program
+fn find_component<'a>(program: &'a Program, name: &str) -> Option<&'a [Node]> {
     program.body.iter().find_map(|decl| match decl {
-        Declaration::Component(comp) if comp.name == name => Some(comp),
+        Declaration::Component(comp) if comp.name == name => Some(comp.body.as_slice()),
+        Declaration::Section(section) if section.name == name => Some(section.body.as_slice()),
         _ => None,
     })
 }
 
-fn generate_element(el: &Element, html: &mut String, indent: usize, program: &Program) {
-    let indent_str = "  ".repeat(indent);
+fn generate_element(el: &Element, html: &mut String, indent: usize, program: &Program, options: &CompileOptions) {
+    let indent_str = options.indent.level(indent);
 
     // v1: @if directive - skip rendering (assume false)
     if el.if_directive.is_some() {
@@ -367,14 +871,7 @@ fn generate_element(el: &Element, html: &mut String, indent: usize, program: &Pr
     // v1: @for directive - render empty container
     if el.for_directive.is_some() {
         html.push_str(&indent_str);
-        html.push('<');
-        html.push_str(&el.tag);
-
-        // Attributes
-        for attr in &el.attributes {
-            generate_attribute(attr, html);
-        }
-
+        generate_start_tag(el, html, indent, options);
         html.push_str("></");
         html.push_str(&el.tag);
         html.push_str(">\n");
@@ -382,20 +879,21 @@ fn generate_element(el: &Element, html: &mut String, indent: usize, program: &Pr
     }
 
     // Check if self-closing tag
-    let self_closing = is_self_closing(&el.tag);
+    let self_closing = is_void_element(&el.tag);
 
     // Opening tag
     html.push_str(&indent_str);
-    html.push('<');
-    html.push_str(&el.tag);
+    generate_start_tag(el, html, indent, options);
 
-    // Attributes
-    for attr in &el.attributes {
-        generate_attribute(attr, html);
+    if self_closing {
+        html.push_str(void_tag_end(options));
+        html.push('\n');
+        return;
     }
 
-    if self_closing {
-        html.push_str(">\n");
+    // Empty SVG elements self-close, which HTML allows inside <svg>: <circle r="4" />
+    if el.children.is_empty() && is_svg_tag(&el.tag) {
+        html.push_str(" />\n");
         return;
     }
 
@@ -407,9 +905,29 @@ fn generate_element(el: &Element, html: &mut String, indent: usize, program: &Pr
         html.push_str("</");
         html.push_str(&el.tag);
         html.push_str(">\n");
+    } else if is_whitespace_sensitive(&el.tag) {
+        // Whitespace-significant content: no added newlines or indentation
+        let mut content = String::new();
+        for child in &el.children {
+            let mut child_html = String::new();
+            generate_node(child, &mut child_html, 0, program, options);
+            if matches!(child, Node::Text(_)) {
+                content.push_str(&child_html);
+            } else {
+                content.push_str(child_html.trim_end_matches('\n'));
+            }
+        }
+        // Parsers drop one newline right after `<pre>`/`<textarea>`, so a leading one needs a spare
+        if content.starts_with('\n') {
+            html.push('\n');
+        }
+        html.push_str(&content);
+        html.push_str("</");
+        html.push_str(&el.tag);
+        html.push_str(">\n");
     } else if el.children.len() == 1 && matches!(el.children[0], Node::Text(_)) {
         // Single text child - inline
-        generate_node(&el.children[0], html, 0, program);
+        generate_node(&el.children[0], html, 0, program, options);
         html.push_str("</");
         html.push_str(&el.tag);
         html.push_str(">\n");
@@ -417,7 +935,7 @@ fn generate_element(el: &Element, html: &mut String, indent: usize, program: &Pr
         // Multiple children or complex content
         html.push('\n');
         for child in &el.children {
-            generate_node(child, html, indent + 1, program);
+            generate_node(child, html, indent + 1, program, options);
         }
         html.push_str(&indent_str);
         html.push_str("</");
@@ -426,86 +944,138 @@ fn generate_element(el: &Element, html: &mut String, indent: usize, program: &Pr
     }
 }
 
-fn generate_attribute(attr: &Attribute, html: &mut String) {
-    html.push(' ');
-    html.push_str(&attr.name);
-    html.push_str("=\"");
-
-    match &attr.value {
-        Expression::String(s) => {
-            html.push_str(&escape_html(&s.value));
-        }
-        Expression::Number(n) => {
-            html.push_str(&n.value.to_string());
+/// Emit `<tag` and its attributes, leaving the `>` to the caller
+///
+/// When the tag would run past `max_line_width`, each attribute goes on its
+/// own continuation line. Attributes are never split, so quoted values stay
+/// intact, and the whitespace added is inside the tag, not the content.
+fn generate_start_tag(el: &Element, html: &mut String, indent: usize, options: &CompileOptions) {
+    let mut attributes = String::new();
+    let mut wrapped = String::new();
+    let continuation = format!("\n{}", options.indent.level(indent + 2));
+    for attr in el.attributes.iter().filter(|a| is_markup_attribute(a)) {
+        let mut attribute = String::new();
+        generate_attribute(attr, &mut attribute, options);
+        if !attribute.is_empty() {
+            attributes.push_str(&attribute);
+            wrapped.push_str(&continuation);
+            wrapped.push_str(&attribute[1..]);
         }
-        Expression::Boolean(b) => {
-            if b.value {
-                // Boolean true - render attribute name only (HTML5 style)
-                html.pop(); // Remove ="
-                html.pop(); // Remove "
-                return;
-            } else {
-                // Boolean false - skip attribute entirely
-                html.pop(); // Remove ="
-                html.pop(); // Remove "
-                html.pop(); // Remove space
-                html.pop(); // Remove name
+    }
+
+    let column = html[html.rfind('\n').map_or(0, |i| i + 1)..].chars().count();
+    // `<`, the tag, the attributes and the closing `>`
+    let width = column + 1 + el.tag.len() + attributes.chars().count() + 1;
+    let too_long = options.max_line_width.is_some_and(|max| width > max);
+
+    html.push('<');
+    html.push_str(&el.tag);
+    html.push_str(if too_long { &wrapped } else { &attributes });
+}
+
+/// Event handlers and `bind:` need a script, so they never reach static markup
+pub(super) fn is_markup_attribute(attr: &Attribute) -> bool {
+    !is_event_attribute(attr) && attr.name != "bind"
+}
+
+/// Emit ` name="value"`; every value written between the quotes goes through `escape_html`
+///
+/// Whether the attribute appears at all is decided before anything is written.
+fn generate_attribute(attr: &Attribute, html: &mut String, options: &CompileOptions) {
+    let value = match &attr.value {
+        // v1: Spread attributes come from context, which is empty - nothing to emit
+        Expression::Spread(_) => return,
+        // v1: Event handlers need a script to run
+        Expression::Event(_) => return,
+        Expression::Array(list) if attr.name == "class" => {
+            let classes = static_class_list(list);
+            if classes.is_empty() {
                 return;
             }
+            classes
         }
-        Expression::ContextPath(_) => {
-            // v1: Context data is empty
-        }
-        Expression::Identifier(_) => {
-            // v1: Identifiers (like event handlers) are skipped
-        }
-        Expression::MemberAccess(_) => {
-            // v1: Member access (ctx.foo.bar) is empty
-        }
-        Expression::Binary(_) => {
-            // v1: Binary expressions can't be evaluated without context
-        }
-        Expression::Ternary(_) => {
-            // v1: Ternary expressions can't be evaluated without context
-        }
-        Expression::Call(_) => {
-            // v1: Function calls are skipped
-        }
-        Expression::Event(_) => {
-            // v1: Event handlers are skipped
-            html.pop(); // Remove ="
-            html.pop(); // Remove "
-            html.pop(); // Remove space
-            // Remove attribute name
-            for _ in 0..attr.name.len() {
-                html.pop();
+        Expression::Object(object) if attr.name == "style" => {
+            let style = static_style(object);
+            if style.is_empty() {
+                return;
             }
-            return;
+            style
         }
-    }
+        // Literals keep their source text so `0.50` stays `0.50`
+        Expression::Number(n) => n.raw.clone(),
+        // Literals and expressions built from them, like `10 * 8` or `true ? "a" : "b"`
+        expr => match eval_static(expr) {
+            // true renders the name only (HTML5 style), false drops the attribute
+            Some(ConstValue::Boolean(true)) => {
+                html.push_str(&boolean_attribute(&attr.name, options));
+                return;
+            }
+            Some(ConstValue::Boolean(false)) => return,
+            Some(ConstValue::String(s)) => s,
+            Some(ConstValue::Number(n)) => format_number(n),
+            // v1: Context data is empty, so anything that reads it is undefined,
+            // which drops the attribute like `false` and `null` do
+            None => return,
+        },
+    };
 
+    html.push(' ');
+    html.push_str(&attr.name);
+    html.push_str("=\"");
+    html.push_str(&escape_html(&value));
     html.push('"');
 }
 
+/// Join the entries of a class list that are known at compile time
+///
+/// Entries that depend on context, and conditions that are false or null,
+/// render nothing.
+fn static_class_list(list: &ArrayLiteral) -> String {
+    list.elements
+        .iter()
+        .filter_map(|entry| match eval_static(entry)? {
+            ConstValue::String(s) if !s.is_empty() => Some(s),
+            ConstValue::Number(n) => Some(format_number(n)),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Join the entries of a style object that are known at compile time, as
+/// `color:red;font-size:14px`
+fn static_style(object: &ObjectLiteral) -> String {
+    object.properties
+        .iter()
+        .filter_map(|property| css_declaration(&property.key, &eval_static(&property.value)?))
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
 fn generate_text(text: &TextNode, html: &mut String) {
-    // v1: Always remove ${...} interpolations (empty context)
+    // v1: Context is empty, so only constant ${...} interpolations render
     // This handles both is_dynamic=true and any missed dynamic content
-    let static_text = remove_interpolations(&text.content);
+    let static_text = render_interpolations(&text.parts);
 
-    // Only output if there's static text remaining
-    if !static_text.trim().is_empty() {
+    // Only output if there's static text remaining, or whitespace kept with `{{-`/`-}}`
+    let keeps_whitespace = text.trim != (true, true) && !static_text.is_empty();
+    if keeps_whitespace || !static_text.trim().is_empty() {
         html.push_str(&escape_html(&static_text));
     }
 }
 
-fn remove_interpolations(text: &str) -> String {
-    // Remove ${...} patterns
-    use regex::Regex;
-    let re = Regex::new(r"\$\{[^}]+\}").unwrap();
-    re.replace_all(text, "").to_string()
+/// The text with each `${...}` replaced by its value when it folds to a
+/// constant, and by nothing otherwise
+fn render_interpolations(parts: &[TextPart]) -> String {
+    parts.iter()
+        .map(|part| match part {
+            TextPart::Static(text) => text.clone(),
+            TextPart::Dynamic(expr) => eval_static(expr).map(|value| value.to_string()).unwrap_or_default(),
+        })
+        .collect()
 }
 
-fn escape_html(s: &str) -> String {
+pub(super) fn escape_html(s: &str) -> String {
     s.replace('&', "&amp;")
         .replace('<', "&lt;")
         .replace('>', "&gt;")
@@ -513,15 +1083,7 @@ fn escape_html(s: &str) -> String {
         .replace('\'', "&#39;")
 }
 
-fn is_self_closing(tag: &str) -> bool {
-    matches!(
-        tag,
-        "area" | "base" | "br" | "col" | "embed" | "hr" | "img" | "input" |
-        "link" | "meta" | "param" | "source" | "track" | "wbr"
-    )
-}
-
-fn title_case(s: &str) -> String {
+pub(super) fn title_case(s: &str) -> String {
     let mut c = s.chars();
     match c.next() {
         None => String::new(),
@@ -547,12 +1109,14 @@ fn find_common_components(pages: &[&PageDecl]) -> Vec<String> {
     common
 }
 
-/// Extract component reference names from nodes
+/// Extract the names of components referenced without slot content
 fn get_component_refs(nodes: &[Node]) -> Vec<String> {
     let mut components = Vec::new();
     for node in nodes {
         if let Node::ComponentRef(comp_ref) = node {
-            components.push(comp_ref.name.clone());
+            if !passes_content(comp_ref) {
+                components.push(comp_ref.name.clone());
+            }
         }
     }
     components
@@ -561,22 +1125,144 @@ fn get_component_refs(nodes: &[Node]) -> Vec<String> {
 /// Check if a node is a reference to a common component
 fn is_common_component_ref(node: &Node, common_components: &[String]) -> bool {
     if let Node::ComponentRef(comp_ref) = node {
-        common_components.contains(&comp_ref.name)
+        !passes_content(comp_ref) && common_components.contains(&comp_ref.name)
     } else {
         false
     }
 }
 
-/// Generate HTML for a component (used for layout rendering)
-fn generate_component_html(component: &ComponentDecl, html: &mut String, indent: usize, program: &Program) {
-    for node in &component.body {
-        generate_node(node, html, indent, program);
+/// Whether a reference fills slots, so it renders differently from others
+fn passes_content(comp_ref: &ComponentRef) -> bool {
+    !comp_ref.children.is_empty() || !comp_ref.slot_fills.is_empty()
+}
+
+/// Components whose markup would appear more than once across `bodies` when
+/// inlined, sorted by name
+///
+/// Static HTML renders a component the same way wherever it's used without
+/// slot content, since arguments read context, so one copy can serve every
+/// such reference.
+fn repeated_components(bodies: &[Vec<Node>], program: &Program) -> Vec<String> {
+    let mut counts = BTreeMap::new();
+    for body in bodies {
+        count_renders(body, program, &mut counts);
+    }
+    counts.into_iter()
+        .filter(|(_, count)| *count > 1)
+        .map(|(name, _)| name)
+        .collect()
+}
+
+/// Count the component references `generate_node` would inline for `nodes`
+fn count_renders(nodes: &[Node], program: &Program, counts: &mut BTreeMap<String, usize>) {
+    for node in nodes {
+        match node {
+            Node::Element(el) if el.if_directive.is_none() && el.for_directive.is_none() => {
+                count_renders(&el.children, program, counts);
+            }
+            Node::ComponentRef(comp_ref) => {
+                if let Some(body) = find_component(program, &comp_ref.name) {
+                    if passes_content(comp_ref) {
+                        count_renders(&fill_slots(body, &comp_ref.children, &comp_ref.slot_fills), program, counts);
+                    } else {
+                        *counts.entry(comp_ref.name.clone()).or_insert(0) += 1;
+                        count_renders(body, program, counts);
+                    }
+                }
+            }
+            Node::Each(stmt) => count_renders(stmt.empty.as_deref().unwrap_or_default(), program, counts),
+            _ => {}
+        }
+    }
+}
+
+fn component_template_id(name: &str) -> String {
+    format!("component-{}", name.to_lowercase())
+}
+
+/// `decl` with its references to `shared` components replaced
+fn with_component_references(decl: &Declaration, shared: &[String]) -> Declaration {
+    let mut decl = decl.clone();
+    let body = match &mut decl {
+        Declaration::Component(c) => &mut c.body,
+        Declaration::Section(s) => &mut s.body,
+        Declaration::Layout(l) => &mut l.body,
+        Declaration::Page(p) => &mut p.body,
+    };
+    *body = replace_component_refs(body, shared);
+    decl
+}
+
+/// Replace references to `shared` components with an empty
+/// `<template data-component="...">` for the router to expand
+fn replace_component_refs(nodes: &[Node], shared: &[String]) -> Vec<Node> {
+    nodes.iter()
+        .map(|node| match node {
+            Node::ComponentRef(comp_ref) if !passes_content(comp_ref) && shared.contains(&comp_ref.name) => {
+                let loc = comp_ref.loc;
+                Node::Element(Element {
+                    tag: "template".to_string(),
+                    attributes: vec![Attribute {
+                        name: "data-component".to_string(),
+                        value: Expression::String(StringLiteral { value: component_template_id(&comp_ref.name), loc }),
+                        loc,
+                    }],
+                    children: Vec::new(),
+                    for_directive: None,
+                    if_directive: None,
+                    self_closing: false,
+                    loc,
+                })
+            }
+            Node::Element(el) => Node::Element(Element {
+                children: replace_component_refs(&el.children, shared),
+                ..el.clone()
+            }),
+            Node::ComponentRef(comp_ref) => Node::ComponentRef(ComponentRef {
+                children: replace_component_refs(&comp_ref.children, shared),
+                slot_fills: comp_ref.slot_fills.iter()
+                    .map(|fill| SlotFill {
+                        children: replace_component_refs(&fill.children, shared),
+                        ..fill.clone()
+                    })
+                    .collect(),
+                ..comp_ref.clone()
+            }),
+            Node::Each(stmt) => Node::Each(EachStatement {
+                empty: stmt.empty.as_deref().map(|empty| replace_component_refs(empty, shared)),
+                ..stmt.clone()
+            }),
+            node => node.clone(),
+        })
+        .collect()
+}
+
+/// Generate HTML for a component or section body (used for layout rendering)
+fn generate_component_html(
+    body: &[Node],
+    html: &mut String,
+    indent: usize,
+    program: &Program,
+    options: &CompileOptions,
+) {
+    for node in body {
+        generate_node(node, html, indent, program, options);
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::codegen::check_well_formed_xml;
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+
+    fn generate_html(source: &str) -> String {
+        let tokens = tokenize(source).unwrap();
+        let ast = parse(&tokens).unwrap();
+        let files = generate(&ast, &CompileOptions::default());
+        files.into_iter().last().unwrap().content
+    }
 
     #[test]
     fn test_escape_html() {
@@ -585,9 +1271,490 @@ mod tests {
     }
 
     #[test]
-    fn test_remove_interpolations() {
-        assert_eq!(remove_interpolations("Hello ${ctx.name}!"), "Hello !");
-        assert_eq!(remove_interpolations("${ctx.title}"), "");
-        assert_eq!(remove_interpolations("Static text"), "Static text");
+    fn test_render_interpolations() {
+        let render = |text: &str| {
+            let ast = parse(&tokenize(&format!("component T {{ p {{{{ {} }}}} }}", text)).unwrap()).unwrap();
+            match &ast.body[0] {
+                Declaration::Component(c) => match &c.body[0] {
+                    Node::Element(el) => match &el.children[0] {
+                        Node::Text(t) => render_interpolations(&t.parts),
+                        other => panic!("Expected text, got {:?}", other),
+                    },
+                    other => panic!("Expected element, got {:?}", other),
+                },
+                other => panic!("Expected component, got {:?}", other),
+            }
+        };
+        assert_eq!(render("Hello ${ctx.name}!"), "Hello !");
+        assert_eq!(render("${ctx.title}"), "");
+        assert_eq!(render("Static text"), "Static text");
+        assert_eq!(render("Width: ${10 * 8}px"), "Width: 80px");
+    }
+
+    #[test]
+    fn test_presence_attribute() {
+        let output = generate_html(r#"page home "/" { input [type: "text", required] }"#);
+        assert!(output.contains(r#"<input type="text" required>"#));
+    }
+
+    #[test]
+    fn test_attribute_quotes_are_escaped() {
+        let output = generate_html(r#"page home "/" { div [title: 'Say "hi"', class: "x"] { } }"#);
+        assert!(output.contains(r#"<div title="Say &quot;hi&quot;" class="x"></div>"#));
+    }
+
+    #[test]
+    fn test_route_params_match_by_regex() {
+        let output = generate_html(r#"
+            page home "/" { }
+            page user "/users/:id" { }
+        "#);
+        assert!(output.contains("      '/': 'page-home',\n    };"));
+        assert!(output.contains(r"[/^\/users\/([^/]+)$/, 'page-user'],"));
+        assert!(output.contains("const templateId = matchRoute(path);"));
+    }
+
+    #[test]
+    fn test_preload_routes_add_prefetch_links() {
+        let source = r#"
+            page home "/" { h1 {{ Home }} }
+            page about "/about" { h1 {{ About }} }
+            page contact "/contact" { h1 {{ Contact }} }
+        "#;
+        let ast = parse(&tokenize(source).unwrap()).unwrap();
+        let options = CompileOptions {
+            split_templates: true,
+            preload_routes: vec!["/contact".to_string(), "/missing".to_string(), "/about".to_string()],
+            ..Default::default()
+        };
+        let files = generate(&ast, &options);
+        let index = &files.iter().find(|f| f.path == "home.html").unwrap().content;
+
+        let head = &index[..index.find("</head>").unwrap()];
+        assert!(head.ends_with(
+            "  <link rel=\"prefetch\" href=\"contact.template.html\">\n  <link rel=\"prefetch\" href=\"about.template.html\">\n"
+        ));
+        assert!(!index.contains("home.template.html\">"));
+
+        let options = CompileOptions {
+            template_html: Some("<html><head><title>App</title></head><body></body></html>".to_string()),
+            ..options
+        };
+        let files = generate(&ast, &options);
+        let index = &files.iter().find(|f| f.path == "home.html").unwrap().content;
+        assert!(index.contains("<title>App</title>  <link rel=\"prefetch\" href=\"contact.template.html\">"));
+    }
+
+    #[test]
+    fn test_page_head_blocks() {
+        let source = r#"
+            page home "/" {
+                head { title {{ Welcome & hi }} meta [name: "description", content: "Home"] }
+                h1 {{ Home }}
+            }
+            page about "/about" { h1 {{ About }} }
+        "#;
+        let ast = parse(&tokenize(source).unwrap()).unwrap();
+        let output = generate(&ast, &CompileOptions::default()).remove(0).content;
+
+        let head = &output[..output.find("</head>").unwrap()];
+        assert!(head.contains("<title>Welcome &amp; hi</title>\n  <meta name=\"description\" content=\"Home\" data-page-head>\n"));
+        assert!(output.contains(
+            "<template id=\"page-home\">\n    <template data-head data-title=\"Welcome &amp; hi\">\n      <meta name=\"description\" content=\"Home\" data-page-head>\n    </template>\n    <h1>Home</h1>"
+        ));
+        assert!(output.contains("<template id=\"page-about\">\n    <template data-head data-title=\"About\"></template>\n"));
+        assert!(output.contains("      applyHead(content);\n"));
+
+        let options = CompileOptions { split_templates: true, ..Default::default() };
+        let files = generate(&ast, &options);
+        let about = &files.iter().find(|f| f.path == "about.template.html").unwrap().content;
+        assert!(about.starts_with("<template data-head data-title=\"About\"></template>\n<h1>About</h1>"));
+        let index = &files.iter().find(|f| f.path == "home.html").unwrap().content;
+        assert!(index.contains("        applyHead(document.getElementById('app'));\n"));
+
+        // Without head blocks the router is unchanged
+        let ast = parse(&tokenize(r#"page home "/" { h1 {{ Home }} }"#).unwrap()).unwrap();
+        assert!(!generate(&ast, &CompileOptions::default())[0].content.contains("applyHead"));
+    }
+
+    #[test]
+    fn test_hash_filenames() {
+        let source = r#"
+            page home "/" { h1 {{ Home }} }
+            page about "/about" { h1 {{ About }} }
+        "#;
+        let ast = parse(&tokenize(source).unwrap()).unwrap();
+        let options = CompileOptions {
+            split_templates: true,
+            hash_filenames: true,
+            preload_routes: vec!["/about".to_string()],
+            ..Default::default()
+        };
+        let files = generate(&ast, &options);
+        let templates: Vec<&str> = files.iter()
+            .map(|f| f.path.as_str())
+            .filter(|path| path.contains(".template."))
+            .collect();
+
+        assert_eq!(templates.len(), 2);
+        let index = &files.iter().find(|f| f.path == "home.html").unwrap().content;
+        for template in &templates {
+            let hash = template.split('.').nth(2).unwrap();
+            assert_eq!(hash.len(), 8);
+            assert!(index.contains(&format!("'{}'", template)), "router is missing {}", template);
+        }
+        let about = templates.iter().find(|path| path.starts_with("about.")).unwrap();
+        assert!(index.contains(&format!("<link rel=\"prefetch\" href=\"{}\">", about)));
+
+        // Same content, same name
+        assert_eq!(content_hash("<h1>About</h1>\n"), content_hash("<h1>About</h1>\n"));
+        assert_ne!(content_hash("<h1>About</h1>\n"), content_hash("<h1>Home</h1>\n"));
+    }
+
+    #[test]
+    fn test_spread_attribute_skipped() {
+        let output = generate_html(r#"page home "/" { button [...ctx.buttonProps, class: "x"] }"#);
+        assert!(output.contains(r#"<button class="x"></button>"#));
+    }
+
+    #[test]
+    fn test_event_attributes_are_dropped_cleanly() {
+        let output = generate_html(r#"page home "/" { button [class: "a", onClick: save, id: "b"] {{ Save }} }"#);
+        assert!(output.contains(r#"<button class="a" id="b">Save</button>"#));
+
+        let output = generate_html(r#"page home "/" { input [disabled: false, type: "text", required: true] }"#);
+        assert!(output.contains(r#"<input type="text" required>"#));
+    }
+
+    #[test]
+    fn test_constant_attributes_are_folded() {
+        let output = generate_html(r#"page home "/" { canvas [width: 10 * 8, class: 2 > 1 ? "a" : "b", hidden: 1 == 2] }"#);
+        assert!(output.contains(r#"<canvas width="80" class="a"></canvas>"#));
+    }
+
+    #[test]
+    fn test_class_list_keeps_static_entries() {
+        let output = generate_html(r#"page home "/" { div [class: ["a", false ? "b" : null]] }"#);
+        assert!(output.contains(r#"<div class="a"></div>"#));
+
+        let output = generate_html(r#"page home "/" { div [class: ["a", true ? "b" : null, ctx.extra]] }"#);
+        assert!(output.contains(r#"<div class="a b"></div>"#));
+
+        // Shorthand classes are known even when the explicit class isn't
+        let output = generate_html(r#"page home "/" { div.tab [class: ctx.active ? "on" : null] span.x [class: ["a", ctx.b]] }"#);
+        assert!(output.contains(r#"<div class="tab"></div>"#), "{}", output);
+        assert!(output.contains(r#"<span class="x a"></span>"#));
+    }
+
+    #[test]
+    fn test_unknown_attribute_values_are_omitted() {
+        let output = generate_html(r#"page home "/" { button [disabled: ctx.loading, class: ctx.active ? "on" : "off", title: true ? "t" : null] }"#);
+        assert!(output.contains(r#"<button title="t"></button>"#), "{}", output);
+    }
+
+    #[test]
+    fn test_comments_pass_through_sanitized() {
+        let output = generate_html(r#"page home "/" { div { @comment "Nav starts here" @comment "--> <script>" } }"#);
+        assert!(output.contains("      <!-- Nav starts here -->\n"), "{}", output);
+        assert!(output.contains("<!-- - -> <script> -->"), "{}", output);
+    }
+
+    #[test]
+    fn test_style_object_keeps_static_entries() {
+        let output = generate_html(r#"page home "/" { div [style: { fontSize: 14, opacity: 0.5, color: ctx.color }] }"#);
+        assert!(output.contains(r#"<div style="font-size:14px;opacity:0.5"></div>"#), "{}", output);
+
+        let output = generate_html(r#"page home "/" { p [style: { content: '"><script>' }] }"#);
+        assert!(output.contains(r#"<p style="content:&quot;&gt;&lt;script&gt;"></p>"#), "{}", output);
+    }
+
+    #[test]
+    fn test_number_literal_keeps_source_text() {
+        let output = generate_html(r#"page home "/" { meter [value: 0.50, max: 1] }"#);
+        assert!(output.contains(r#"<meter value="0.50" max="1">"#));
+    }
+
+    #[test]
+    fn test_pre_preserves_whitespace() {
+        let output = generate_html("page home \"/\" { pre {{\n    indented\n  line\n}} }");
+        // The first newline after <pre> is dropped when parsed, so the kept one needs a spare
+        assert!(output.contains("<pre>\n\n    indented\n  line\n</pre>"), "{}", output);
+
+        let output = generate_html("page home \"/\" { pre {{-\n  indented\n    more-}} textarea {{ text }} }");
+        assert!(output.contains("<pre>\n\n  indented\n    more</pre>"), "{}", output);
+        assert!(output.contains("<textarea> text </textarea>"), "{}", output);
+    }
+
+    #[test]
+    fn test_each_renders_else_block() {
+        let output = generate_html(r#"page home "/" { ul { @each ctx.items as item { li { item.name } } @else { li {{ Empty }} } } }"#);
+        assert!(output.contains("<li>Empty</li>"));
+    }
+
+    #[test]
+    fn test_component_children_fill_slots() {
+        let output = generate_html(r#"
+            component Panel { header { @slot(title) } main { @slot } footer { @slot(footer) } }
+            component Wrapper { article { Panel { title: { @slot(title) } @slot } } }
+            page home "/" { Wrapper { title: { h1 {{ Hello }} } p {{ Body }} } }
+        "#);
+        assert!(output.contains("<header>\n        <h1>Hello</h1>\n      </header>"), "{}", output);
+        assert!(output.contains("<main>\n        <p>Body</p>\n      </main>"));
+        assert!(output.contains("<footer></footer>"));
+    }
+
+    #[test]
+    fn test_sections_are_inlined() {
+        let output = generate_html(r#"
+            section Hero { header { h1 {{ Welcome }} @slot } }
+            page home "/" { Hero { p {{ Intro }} } }
+        "#);
+        assert!(output.contains("<header>\n      <h1>Welcome</h1>\n      <p>Intro</p>\n    </header>"), "{}", output);
+    }
+
+    #[test]
+    fn test_each_unrolls_constant_range() {
+        let output = generate_html(r#"page home "/" {
+            ul { @each 1..3 as i, k { li [data-n: i * 10] {{ Item ${i} at ${k} }} } }
+            @each 0..<ctx.count as n { p {{ ${n} }} } @else { p {{ Unknown }} }
+        }"#);
+        assert!(output.contains(concat!(
+            "      <li data-n=\"10\">Item 1 at 0</li>\n",
+            "      <li data-n=\"20\">Item 2 at 1</li>\n",
+            "      <li data-n=\"30\">Item 3 at 2</li>\n",
+        )), "{}", output);
+        assert!(output.contains("<p>Unknown</p>"));
+    }
+
+    #[test]
+    fn test_each_stripes_alternate_per_item() {
+        let output = generate_html(r#"page home "/" {
+            ul { @each 1..4 as i { li [class: $even ? "row-even" : "row-odd"] {{ ${i} }} } }
+        }"#);
+        assert!(output.contains(concat!(
+            "      <li class=\"row-even\">1</li>\n",
+            "      <li class=\"row-odd\">2</li>\n",
+            "      <li class=\"row-even\">3</li>\n",
+            "      <li class=\"row-odd\">4</li>\n",
+        )), "{}", output);
+    }
+
+    #[test]
+    fn test_literal_raw_html_is_rendered() {
+        let output = generate_html(r#"page home "/" {
+            article { @html "<b>x</b>" @html ctx.body }
+        }"#);
+        assert!(output.contains("<article>\n      <b>x</b>\n    </article>"), "{}", output);
+    }
+
+    #[test]
+    fn test_each_over_long_or_unsafe_range() {
+        let output = generate_html(r#"page home "/" {
+            @each 1..2000000 as i { p {{ ${i} }} }
+            @each 9007199254740992..9007199254740993 as i { p {{ ${i} }} } @else { p {{ Too far }} }
+        }"#);
+        assert_eq!(output.matches("<p>").count(), 10_001);
+        assert!(output.contains("<p>10000</p>\n    <p>Too far</p>"));
+    }
+
+    #[test]
+    fn test_document_lang_and_doctype() {
+        let ast = parse(&tokenize(r#"page home "/" { p {{ Bonjour }} }"#).unwrap()).unwrap();
+        let html = |options: CompileOptions| {
+            generate(&ast, &options).into_iter().find(|f| f.content.contains("<html")).unwrap().content
+        };
+
+        let french = html(CompileOptions { html_lang: Some("fr".to_string()), ..CompileOptions::default() });
+        assert!(french.starts_with("<!DOCTYPE html>\n<html lang=\"fr\">\n"), "{}", french);
+
+        let bare = html(CompileOptions { html_lang: None, doctype: Doctype::None, ..CompileOptions::default() });
+        assert!(bare.starts_with("<html>\n<head>"), "{}", bare);
+
+        let split = html(CompileOptions { doctype: Doctype::Xhtml, split_templates: true, ..CompileOptions::default() });
+        assert!(split.starts_with("<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Strict//EN\""), "{}", split);
+        assert!(split.contains("<html xmlns=\"http://www.w3.org/1999/xhtml\" lang=\"en\">"));
+        let inline = html(CompileOptions { doctype: Doctype::Xhtml, ..CompileOptions::default() });
+        assert!(inline.contains("  <meta charset=\"UTF-8\" />\n"));
+        assert!(inline.contains("<script>\n    //<![CDATA[\n"));
+
+        // XHTML is XML: void elements self-close, attributes have values and scripts are CDATA
+        let source = r#"
+            page home "/" {
+                head { meta [name: "description", content: "Home"] }
+                p {{ a < b }} br img [src: "/a.png", alt: ""] input [type: "checkbox", disabled]
+            }
+            page about "/about" { hr }
+        "#;
+        let ast = parse(&tokenize(source).unwrap()).unwrap();
+        for split_templates in [false, true] {
+            let options = CompileOptions { doctype: Doctype::Xhtml, split_templates, preload_routes: vec!["/about".to_string()], ..CompileOptions::default() };
+            for file in generate(&ast, &options) {
+                assert_eq!(check_well_formed_xml(&file.content), Ok(()), "{}:\n{}", file.path, file.content);
+            }
+        }
+    }
+
+    #[test]
+    fn test_max_line_width_wraps_attributes() {
+        let source = r#"page home "/" { main { a [href: "/docs/getting-started", class: "nav-link primary", title: "Read the guide"] {{ Docs }} } }"#;
+        let options = CompileOptions {
+            max_line_width: Some(60),
+            ..CompileOptions::default()
+        };
+        let tokens = tokenize(source).unwrap();
+        let ast = parse(&tokens).unwrap();
+        let output = generate(&ast, &options)[0].content.clone();
+
+        assert!(output.contains(
+            "      <a\n          href=\"/docs/getting-started\"\n          class=\"nav-link primary\"\n          title=\"Read the guide\">Docs</a>"
+        ), "{}", output);
+        assert!(output.contains("    <main>\n"));
+        // Unlimited by default
+        assert!(generate_html(source).contains(r#"<a href="/docs/getting-started" class="nav-link primary" title="Read the guide">"#));
+    }
+
+    #[test]
+    fn test_custom_indent() {
+        let source = r#"page home "/" { main { ul { li {{ One }} } } }"#;
+        let ast = parse(&tokenize(source).unwrap()).unwrap();
+        let output = |indent| generate(&ast, &CompileOptions { indent, ..CompileOptions::default() })[0].content.clone();
+
+        let spaces = output(Indent::Spaces(4));
+        assert!(spaces.contains("\n    <template id=\"page-home\">\n        <main>\n            <ul>\n                <li>One</li>\n            </ul>\n"), "{}", spaces);
+        assert!(spaces.contains("\n    <meta charset=\"UTF-8\">\n"));
+        assert!(spaces.contains("\n        function renderPage() {\n"));
+
+        let tabs = output(Indent::Tabs);
+        assert!(tabs.contains("\n\t<template id=\"page-home\">\n\t\t<main>\n\t\t\t<ul>\n\t\t\t\t<li>One</li>\n\t\t\t</ul>\n"), "{}", tabs);
+        assert!(tabs.lines().all(|line| !line.starts_with(' ')), "{}", tabs);
+    }
+
+    #[test]
+    fn test_whitespace_markers() {
+        let output = generate_html(r#"page home "/" { p { b {{ Bold }} {{- -}} i {{ Italic }} } }"#);
+        assert!(output.contains("<b>Bold</b>\n       <i>Italic</i>"));
+
+        // A leading or trailing dash without whitespace around it is just text
+        let output = generate_html(r#"page home "/" { p {{-5 degrees}} p {{ a -}} }"#);
+        assert!(output.contains("<p>-5 degrees</p>") && output.contains("<p>a -</p>"), "{}", output);
+    }
+
+    #[test]
+    fn test_nullish_default_renders_statically() {
+        let output = generate_html(r#"page home "/" { a [title: ctx.user?.name ?? "Guest", data-n: null ?? 1] {{ Hi }} }"#);
+        assert!(output.contains(r#"<a title="Guest" data-n="1">Hi</a>"#), "{}", output);
+    }
+
+    #[test]
+    fn test_escaped_interpolation_is_literal() {
+        let output = generate_html(r#"page home "/" { p {{ cost is \${x} }} p {{ ${1 + 1} \${x} }} }"#);
+        assert!(output.contains("<p>cost is ${x}</p>"));
+        assert!(output.contains("<p>2 ${x}</p>"));
+    }
+
+    #[test]
+    fn test_empty_svg_elements_self_close() {
+        let output = generate_html(r#"page home "/" { svg [viewBox: "0 0 8 8"] { circle [r: 4] g { rect [width: 2] } } div { } }"#);
+        assert!(output.contains("<circle r=\"4\" />\n"), "{}", output);
+        assert!(output.contains("<rect width=\"2\" />\n"));
+        assert!(output.contains("<div></div>\n"));
+    }
+
+    #[test]
+    fn test_writer_matches_string_output() {
+        let source = r#"
+            component Nav { nav { a [href: "/"] {{ Home }} } }
+            page home "/" { head { title {{ Welcome }} } Nav p {{ Home }} }
+            page about "/about" { Nav p {{ About }} }
+        "#;
+        let ast = parse(&tokenize(source).unwrap()).unwrap();
+        for options in [
+            CompileOptions::default(),
+            CompileOptions { split_templates: true, emit_index: true, ..CompileOptions::default() },
+            CompileOptions { template_html: Some("<html><head></head><body class=\"app\"></body></html>".to_string()), ..CompileOptions::default() },
+        ] {
+            let mut document = Vec::new();
+            let output = generate_to_writer(&ast, &options, &mut document).unwrap().unwrap();
+            let files = generate(&ast, &options);
+            let file = files.iter().find(|f| f.path == output.path).unwrap();
+            assert_eq!(String::from_utf8(document).unwrap(), file.content);
+            assert_eq!(output.files.len() + 1, files.len());
+        }
+
+        let empty = parse(&tokenize("component Nav { nav { } }").unwrap()).unwrap();
+        let mut document = Vec::new();
+        assert!(generate_to_writer(&empty, &CompileOptions::default(), &mut document).unwrap().is_none());
+        assert!(document.is_empty());
+    }
+
+    #[test]
+    fn test_emit_index_lists_pages_by_route() {
+        let source = r#"
+            page users "/users/:id" { h1 {{ User }} }
+            page home "/" { h1 {{ Home }} }
+            page about "/about" { h1 {{ About }} }
+        "#;
+        let options = CompileOptions {
+            emit_index: true,
+            ..CompileOptions::default()
+        };
+        let ast = parse(&tokenize(source).unwrap()).unwrap();
+        let files = generate(&ast, &options);
+
+        assert_eq!(files.iter().map(|f| f.path.as_str()).collect::<Vec<_>>(), vec!["users.html", "index.html"]);
+        assert!(files[1].content.contains(concat!(
+            "      <li><a href=\"/\">Home</a></li>\n",
+            "      <li><a href=\"/about\">About</a></li>\n",
+            "      <li>Users <code>/users/:id</code></li>\n",
+        )), "{}", files[1].content);
+    }
+
+    #[test]
+    fn test_shared_layout_wraps_app_container() {
+        let output = generate_html(r#"
+            layout Main { header {{ Site }} main { @slot } }
+            page home "/" uses Main { p {{ Home }} }
+            page about "/about" uses Main { p {{ About }} }
+        "#);
+
+        assert!(output.contains("  <header>Site</header>\n  <main>\n    <div id=\"app\"></div>\n  </main>\n"), "{}", output);
+        assert!(output.contains("<template id=\"page-home\">\n    <p>Home</p>\n  </template>"));
+        assert_eq!(output.matches("<header>").count(), 1);
+    }
+
+    #[test]
+    fn test_pages_with_different_layouts_render_inside_them() {
+        let output = generate_html(r#"
+            layout Main { main { @slot } }
+            page home "/" uses Main { p {{ Home }} }
+            page about "/about" { p {{ About }} }
+        "#);
+
+        assert!(output.contains("  <div id=\"app\"></div>\n"));
+        assert!(output.contains("<template id=\"page-home\">\n    <main>\n      <p>Home</p>\n    </main>\n  </template>"), "{}", output);
+        assert!(output.contains("<template id=\"page-about\">\n    <p>About</p>\n  </template>"));
+    }
+
+    #[test]
+    fn test_dedupe_components_emits_repeated_ones_once() {
+        let source = r#"
+            component Badge { span [class: "badge"] {{ New }} }
+            component Logo { img [src: "/logo.png", alt: "Logo"] }
+            page home "/" { Logo div { Badge } }
+            page about "/about" { p { Badge } }
+        "#;
+        let options = CompileOptions {
+            dedupe_components: true,
+            ..CompileOptions::default()
+        };
+        let ast = parse(&tokenize(source).unwrap()).unwrap();
+        let html = generate(&ast, &options).remove(0).content;
+
+        assert_eq!(html.matches("<span class=\"badge\">New</span>").count(), 1, "{}", html);
+        assert!(html.contains("  <template id=\"component-badge\">\n"));
+        assert_eq!(html.matches("<template data-component=\"component-badge\"></template>").count(), 2);
+        // Used once, so still inlined
+        assert!(!html.contains("component-logo"));
+        assert!(html.contains("expandComponents(content);"));
     }
 }
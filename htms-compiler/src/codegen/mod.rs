@@ -7,41 +7,79 @@ mod templates;
 mod router;
 mod events;
 mod utils;
+mod bundle;
+mod types;
+mod validate;
+mod ssr;
 pub mod html;
 
+pub use validate::{check_well_formed, check_well_formed_xml};
+pub use templates::expr_to_js;
+pub use ssr::render_with_context;
+pub(crate) use templates::TemplateCache;
+pub(crate) use utils::is_event_attribute;
+
 use crate::ast::Program;
 use crate::analyzer::SymbolTable;
 use crate::{CompileOptions, GeneratedFile, OutputFormat};
 
-/// Generate output files from AST
+/// Generate output files from AST, sorted by path so builds are reproducible
 pub fn generate(
     program: &Program,
     symbols: &SymbolTable,
     options: &CompileOptions,
 ) -> Vec<GeneratedFile> {
-    match options.output_format {
+    generate_cached(program, symbols, options, None)
+}
+
+/// Generate output files, reusing the template code in `cache` for
+/// declarations that didn't change
+pub(crate) fn generate_cached(
+    program: &Program,
+    symbols: &SymbolTable,
+    options: &CompileOptions,
+    cache: Option<&mut TemplateCache>,
+) -> Vec<GeneratedFile> {
+    let program = &*utils::with_testids(program, options);
+
+    let mut files = match options.output_format {
         OutputFormat::Html => {
             // Generate static HTML files
             html::generate(program, options)
         }
+        OutputFormat::Typescript if options.single_file => {
+            // Inline everything into one HTML document
+            vec![bundle::generate(program, symbols, options)]
+        }
         OutputFormat::Typescript => {
             // Generate TypeScript/JavaScript files
             let mut files = Vec::new();
 
             // Generate template functions (components, sections, pages)
-            files.push(templates::generate(program, symbols));
+            files.push(templates::generate_cached(program, symbols, options, cache));
 
             // Generate router
             if options.generate_router {
-                files.push(router::generate(program, symbols));
+                files.push(router::generate(program, symbols, options.module_format));
+            }
+
+            // Generate events only if the program actually has events or bindings
+            if options.generate_events && utils::program_needs_events(program) {
+                files.push(events::generate(program, symbols, options.module_format));
             }
 
-            // Generate events only if the program actually has events
-            if options.generate_events && utils::program_has_events(program) {
-                files.push(events::generate(program, symbols));
+            if options.emit_context_types {
+                files.push(types::generate(program));
+            }
+
+            if options.emit_events_manifest {
+                files.push(events::generate_manifest(program));
             }
 
             files
         }
-    }
+    };
+
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    files
 }
@@ -2,12 +2,24 @@
 
 use crate::ast::{Declaration, Program};
 use crate::analyzer::SymbolTable;
-use crate::GeneratedFile;
+use crate::{GeneratedFile, ModuleFormat};
+use super::utils::{route_regex, with_module_format};
 
 /// Generate router.ts
-pub fn generate(program: &Program, _symbols: &SymbolTable) -> GeneratedFile {
+pub fn generate(program: &Program, _symbols: &SymbolTable, module_format: ModuleFormat) -> GeneratedFile {
     let mut output = String::new();
 
+    let pages: Vec<(&str, &str)> = program.body.iter()
+        .filter_map(|decl| {
+            if let Declaration::Page(p) = decl {
+                Some((p.name.as_str(), p.route.as_str()))
+            } else {
+                None
+            }
+        })
+        .collect();
+    let has_params = pages.iter().any(|(_, route)| route_regex(route).is_some());
+
     // Header
     output.push_str("// Generated by HTMS Compiler\n");
     output.push_str("// Do not edit manually\n\n");
@@ -16,13 +28,20 @@ pub fn generate(program: &Program, _symbols: &SymbolTable) -> GeneratedFile {
     output.push_str("interface RouterConfig {\n");
     output.push_str("  mode: 'hash' | 'history';\n");
     output.push_str("  routes: Record<string, () => void>;\n");
+    if has_params {
+        output.push_str("  match: (path: string) => (() => void) | undefined;\n");
+    }
     output.push_str("  notFound: () => void;\n");
     output.push_str("}\n\n");
     output.push_str("class Router {\n");
     output.push_str("  constructor(config: RouterConfig) {\n");
     output.push_str("    const handleRoute = () => {\n");
     output.push_str("      const hash = window.location.hash.slice(1) || '/';\n");
-    output.push_str("      const handler = config.routes[hash];\n");
+    if has_params {
+        output.push_str("      const handler = config.routes[hash] ?? config.match(hash);\n");
+    } else {
+        output.push_str("      const handler = config.routes[hash];\n");
+    }
     output.push_str("      if (handler) {\n");
     output.push_str("        handler();\n");
     output.push_str("      } else {\n");
@@ -35,17 +54,6 @@ pub fn generate(program: &Program, _symbols: &SymbolTable) -> GeneratedFile {
     output.push_str("  }\n");
     output.push_str("}\n\n");
 
-    // Collect page imports
-    let pages: Vec<(&str, &str)> = program.body.iter()
-        .filter_map(|decl| {
-            if let Declaration::Page(p) = decl {
-                Some((p.name.as_str(), p.route.as_str()))
-            } else {
-                None
-            }
-        })
-        .collect();
-
     // Import page functions
     let page_names: Vec<String> = pages.iter()
         .map(|(name, _)| format!("{}Page", capitalize(name)))
@@ -58,13 +66,20 @@ pub fn generate(program: &Program, _symbols: &SymbolTable) -> GeneratedFile {
         ));
     }
 
-    output.push_str("\n");
+    output.push('\n');
 
     // Context
     output.push_str("// Application context\n");
     output.push_str("let context: Record<string, unknown> = {};\n");
     output.push_str("let currentPage: string = '';\n");
-    output.push_str("let appContainer: HTMLElement | null = null;\n\n");
+    output.push_str("let appContainer: HTMLElement | null = null;\n");
+    if has_params {
+        output.push_str("// Values of the current route's :params, exposed to pages as ctx.params\n");
+        output.push_str("let currentParams: Record<string, string> = {};\n");
+    }
+    output.push('\n');
+    // Pages see the route params merged in, without touching the shared context
+    let page_ctx = if has_params { "{ ...context, params: currentParams }" } else { "context" };
 
     // Context functions
     output.push_str("export function getContext(): Record<string, unknown> {\n");
@@ -83,7 +98,7 @@ pub fn generate(program: &Program, _symbols: &SymbolTable) -> GeneratedFile {
     output.push_str("    const renderer = routes[currentPage];\n");
     output.push_str("    if (renderer) {\n");
     output.push_str("      appContainer.innerHTML = '';\n");
-    output.push_str("      renderer(context, appContainer);\n");
+    output.push_str(&format!("      renderer({}, appContainer);\n", page_ctx));
     output.push_str("    }\n");
     output.push_str("  }\n");
     output.push_str("}\n\n");
@@ -102,16 +117,41 @@ pub fn generate(program: &Program, _symbols: &SymbolTable) -> GeneratedFile {
 
     output.push_str("};\n\n");
 
+    // Routes by page name, so callers can navigate without hardcoding paths
+    output.push_str("// Page routes by name\n");
+    output.push_str("export const Routes = {\n");
+    for (name, route) in &pages {
+        let key = if name.contains('-') { format!("'{}'", name) } else { name.to_string() };
+        output.push_str(&format!("  {}: '{}',\n", key, route));
+    }
+    output.push_str("} as const;\n\n");
+
+    if has_params {
+        output.push_str("export function navigate(route: keyof typeof Routes, params: Record<string, string> = {}): void {\n");
+        output.push_str("  const path: string = Routes[route];\n");
+        output.push_str("  window.location.hash = path.replace(/:([A-Za-z_][A-Za-z0-9_]*)/g, (_, name) => encodeURIComponent(params[name] ?? ''));\n");
+    } else {
+        output.push_str("export function navigate(route: keyof typeof Routes): void {\n");
+        output.push_str("  window.location.hash = Routes[route];\n");
+    }
+    output.push_str("}\n\n");
+
     // Render function
-    output.push_str("function renderPage(route: string): void {\n");
-    output.push_str("  currentPage = route;\n");
+    if has_params {
+        output.push_str("function renderPage(route: string, params: Record<string, string> = {}): void {\n");
+        output.push_str("  currentPage = route;\n");
+        output.push_str("  currentParams = params;\n");
+    } else {
+        output.push_str("function renderPage(route: string): void {\n");
+        output.push_str("  currentPage = route;\n");
+    }
     output.push_str("  if (!appContainer) {\n");
     output.push_str("    appContainer = document.getElementById('app');\n");
     output.push_str("  }\n");
     output.push_str("  const renderer = routes[route];\n");
     output.push_str("  if (renderer && appContainer) {\n");
     output.push_str("    appContainer.innerHTML = '';\n");
-    output.push_str("    renderer(context, appContainer);\n");
+    output.push_str(&format!("    renderer({}, appContainer);\n", page_ctx));
     output.push_str("  } else if (appContainer) {\n");
     output.push_str("    const el = document.createElement('h1');\n");
     output.push_str("    el.textContent = '404 - Page Not Found';\n");
@@ -126,7 +166,7 @@ pub fn generate(program: &Program, _symbols: &SymbolTable) -> GeneratedFile {
     output.push_str("  mode: 'hash',\n");
     output.push_str("  routes: {\n");
 
-    for (_, route) in &pages {
+    for (_, route) in pages.iter().filter(|(_, route)| route_regex(route).is_none()) {
         output.push_str(&format!(
             "    '{}': () => renderPage('{}'),\n",
             route, route
@@ -134,12 +174,33 @@ pub fn generate(program: &Program, _symbols: &SymbolTable) -> GeneratedFile {
     }
 
     output.push_str("  },\n");
+    if has_params {
+        output.push_str("  match: (path) => {\n");
+        let patterns = pages.iter().filter_map(|(_, route)| Some((*route, route_regex(route)?)));
+        for (i, (route, (pattern, names))) in patterns.enumerate() {
+            let found = format!("match{}", i);
+            output.push_str(&format!("    const {} = path.match({});\n", found, pattern));
+            output.push_str(&format!("    if ({}) {{\n", found));
+            let params: Vec<String> = names.iter()
+                .enumerate()
+                .map(|(n, name)| format!("{}: decodeURIComponent({}[{}])", name, found, n + 1))
+                .collect();
+            output.push_str(&format!(
+                "      return () => renderPage('{}', {{ {} }});\n",
+                route,
+                params.join(", ")
+            ));
+            output.push_str("    }\n");
+        }
+        output.push_str("    return undefined;\n");
+        output.push_str("  },\n");
+    }
     output.push_str("  notFound: () => renderPage('__404__'),\n");
     output.push_str("});\n");
 
     GeneratedFile {
         path: "router.ts".to_string(),
-        content: output,
+        content: with_module_format(output, module_format),
     }
 }
 
@@ -150,3 +211,54 @@ fn capitalize(s: &str) -> String {
         Some(c) => c.to_uppercase().collect::<String>() + chars.as_str(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+
+    fn generate_router(source: &str) -> String {
+        let ast = parse(&tokenize(source).unwrap()).unwrap();
+        generate(&ast, &SymbolTable::new(), ModuleFormat::Esm).content
+    }
+
+    #[test]
+    fn test_route_params() {
+        let output = generate_router(r#"
+            page home "/" { }
+            page post "/users/:userId/posts/:postId" { }
+        "#);
+
+        assert!(output.contains("const handler = config.routes[hash] ?? config.match(hash);"));
+        assert!(output.contains(r"const match0 = path.match(/^\/users\/([^/]+)\/posts\/([^/]+)$/);"));
+        assert!(output.contains(
+            "return () => renderPage('/users/:userId/posts/:postId', { userId: decodeURIComponent(match0[1]), postId: decodeURIComponent(match0[2]) });"
+        ));
+        assert!(output.contains("renderer({ ...context, params: currentParams }, appContainer);"));
+        // Only static routes are looked up by exact path
+        assert!(output.contains("    '/': () => renderPage('/'),\n  },"));
+    }
+
+    #[test]
+    fn test_routes_by_page_name() {
+        let output = generate_router(r#"
+            page home "/" { }
+            page user-list "/users" { }
+        "#);
+
+        assert!(output.contains("export const Routes = {\n  home: '/',\n  'user-list': '/users',\n} as const;"), "{}", output);
+        assert!(output.contains("export function navigate(route: keyof typeof Routes): void {\n  window.location.hash = Routes[route];"));
+
+        let output = generate_router(r#"page post "/posts/:postId" { }"#);
+        assert!(output.contains("export function navigate(route: keyof typeof Routes, params: Record<string, string> = {}): void {"));
+        assert!(output.contains("(_, name) => encodeURIComponent(params[name] ?? '')"));
+    }
+
+    #[test]
+    fn test_static_routes_skip_matching() {
+        let output = generate_router(r#"page home "/" { }"#);
+        assert!(!output.contains("config.match"));
+        assert!(!output.contains("currentParams"));
+    }
+}
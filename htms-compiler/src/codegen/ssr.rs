@@ -0,0 +1,748 @@
+//! Server-side renderer
+//!
+//! Renders every page against real context data, producing complete static
+//! HTML: `@each` and `@for` repeat over the data, `@if` picks its branch,
+//! and interpolations and attributes show their values. Event handlers are
+//! left out, since there is no script to run them.
+
+use serde_json::{Number, Value};
+
+use crate::ast::*;
+use crate::analyzer::{drop_overridden_attributes, format_number, is_svg_tag, range_values, ConstValue};
+use crate::{CompileOptions, GeneratedFile};
+use super::html::{
+    boolean_attribute, escape_html, inject_into_body, inject_into_head, is_markup_attribute, standalone_document, title_case,
+    void_tag_end,
+};
+use super::utils::{comment_text, css_declaration, with_testids};
+
+/// Render each page to `<page>.html` with `context` as `ctx`
+pub fn render_with_context(program: &Program, context: &Value, options: &CompileOptions) -> Vec<GeneratedFile> {
+    let mut program = with_testids(program, options).into_owned();
+    drop_overridden_attributes(&mut program);
+    let program = &program;
+    program.body.iter()
+        .filter_map(|decl| match decl {
+            Declaration::Page(page) => Some(page),
+            _ => None,
+        })
+        .map(|page| {
+            // The page body renders in place of its layout's slot
+            let composed = page.layout.as_deref()
+                .and_then(|name| find_layout(program, name))
+                .map(|layout| fill_default_slot(&layout.body, &page.body));
+            let mut renderer = Renderer {
+                program,
+                context,
+                scopes: vec![Vec::new()],
+                slots: Vec::new(),
+                html: String::new(),
+                options,
+            };
+            let (title, head) = renderer.render_head(page);
+            renderer.render_nodes(composed.as_deref().unwrap_or(&page.body), 1);
+
+            let content = match options.template_html.as_deref() {
+                Some(template) => inject_into_head(&inject_into_body(template, &renderer.html), &head),
+                None => standalone_document(&title, &head, &renderer.html, options),
+            };
+            GeneratedFile {
+                path: format!("{}.html", page.name.to_lowercase()),
+                content,
+            }
+        })
+        .collect()
+}
+
+/// Loop variables, parameters and `@let` bindings declared in one block
+type Scope = Vec<(String, Value)>;
+
+/// Content passed to a component's slots, with the caller's bindings
+struct SlotFrame<'a> {
+    fills: Vec<(&'a str, &'a [Node])>,
+    scopes: Vec<Scope>,
+}
+
+struct Renderer<'a> {
+    program: &'a Program,
+    context: &'a Value,
+    /// Innermost scope last
+    scopes: Vec<Scope>,
+    /// Slot content of the components being rendered, innermost last
+    slots: Vec<SlotFrame<'a>>,
+    html: String,
+    options: &'a CompileOptions,
+}
+
+impl<'a> Renderer<'a> {
+    /// A page's title, from its head block or else its name, and its other head content
+    fn render_head(&mut self, page: &'a PageDecl) -> (String, String) {
+        let mut title = title_case(&page.name);
+        for node in &page.head {
+            match node {
+                Node::Element(el) if el.tag == "title" => {
+                    title = el.children.iter()
+                        .filter_map(|child| match child {
+                            Node::Text(text) => Some(self.render_text(text)),
+                            _ => None,
+                        })
+                        .collect::<String>()
+                        .trim()
+                        .to_string();
+                }
+                node => self.render_node(node, 1),
+            }
+        }
+        (title, std::mem::take(&mut self.html))
+    }
+
+    fn render_nodes(&mut self, nodes: &'a [Node], indent: usize) {
+        self.scopes.push(Vec::new());
+        for node in nodes {
+            self.render_node(node, indent);
+        }
+        self.scopes.pop();
+    }
+
+    fn render_node(&mut self, node: &'a Node, indent: usize) {
+        match node {
+            Node::Element(el) => self.render_element(el, indent),
+            Node::ComponentRef(r) => self.render_component(r, indent),
+            Node::Text(t) => {
+                let text = self.render_text(t);
+                if !text.is_empty() {
+                    self.html.push_str(&self.options.indent.level(indent));
+                    self.html.push_str(&text);
+                    self.html.push('\n');
+                }
+            }
+            Node::If(stmt) => self.render_if(stmt, indent),
+            Node::Each(stmt) => {
+                let items = if stmt.entries {
+                    self.entries(&stmt.iterable)
+                } else {
+                    self.items(&stmt.iterable).into_iter().enumerate().map(|(i, item)| (Value::from(i as i64 + stmt.index_base), item)).collect()
+                };
+                if items.is_empty() {
+                    if let Some(empty) = &stmt.empty {
+                        self.render_nodes(empty, indent);
+                    }
+                }
+                for (index, (key, item)) in items.into_iter().enumerate() {
+                    self.scopes.push(loop_scope(&stmt.item_name, stmt.index_name.as_deref(), item, key, index));
+                    self.render_nodes(&stmt.body, indent);
+                    self.scopes.pop();
+                }
+            }
+            Node::Let(stmt) => {
+                let value = self.eval(&stmt.value);
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.push((stmt.name.clone(), value));
+                }
+            }
+            Node::Slot(slot) => self.render_slot(slot.name.as_deref().unwrap_or("default"), indent),
+            Node::RawHtml(raw) => {
+                let markup = to_text(&self.eval(&raw.value));
+                if !markup.is_empty() {
+                    self.html.push_str(&self.options.indent.level(indent));
+                    self.html.push_str(&markup);
+                    self.html.push('\n');
+                }
+            }
+            Node::Comment(comment) => {
+                self.html.push_str(&self.options.indent.level(indent));
+                self.html.push_str(&format!("<!-- {} -->\n", comment_text(&comment.text)));
+            }
+        }
+    }
+
+    fn render_if(&mut self, stmt: &'a IfStatement, indent: usize) {
+        if truthy(&self.eval(&stmt.condition)) {
+            self.render_nodes(&stmt.consequent, indent);
+            return;
+        }
+        match &stmt.alternate {
+            Some(Alternate::Block(nodes)) => self.render_nodes(nodes, indent),
+            Some(Alternate::ElseIf(elif)) => self.render_if(elif, indent),
+            None => {}
+        }
+    }
+
+    fn render_element(&mut self, el: &'a Element, indent: usize) {
+        // `@for` repeats the element itself, so its variables cover the `@if` too
+        if let Some(directive) = &el.for_directive {
+            for (index, item) in self.items(&directive.iterable).into_iter().enumerate() {
+                self.scopes.push(loop_scope(&directive.item_name, directive.index_name.as_deref(), item, Value::from(index), index));
+                self.render_conditional_element(el, indent);
+                self.scopes.pop();
+            }
+        } else {
+            self.render_conditional_element(el, indent);
+        }
+    }
+
+    fn render_conditional_element(&mut self, el: &'a Element, indent: usize) {
+        match &el.if_directive {
+            Some(directive) if !truthy(&self.eval(&directive.condition)) => {
+                if let Some(else_element) = &directive.else_element {
+                    self.render_element(else_element, indent);
+                }
+            }
+            _ => self.render_tag(el, indent),
+        }
+    }
+
+    fn render_tag(&mut self, el: &'a Element, indent: usize) {
+        let indent_str = self.options.indent.level(indent);
+        self.html.push_str(&indent_str);
+        self.html.push('<');
+        self.html.push_str(&el.tag);
+        for attr in &el.attributes {
+            self.render_attribute(el, attr);
+        }
+        if el.children.is_empty() && is_svg_tag(&el.tag) {
+            self.html.push_str(" />\n");
+            return;
+        }
+        if is_void_element(&el.tag) {
+            self.html.push_str(void_tag_end(self.options));
+            self.html.push('\n');
+            return;
+        }
+        self.html.push('>');
+
+        let inline = match &el.children[..] {
+            [] => true,
+            [Node::Text(_)] => true,
+            _ => is_whitespace_sensitive(&el.tag),
+        };
+        if inline {
+            // Text goes right inside the tags; for `pre` and `textarea` so does everything else
+            let content_start = self.html.len();
+            for child in &el.children {
+                match child {
+                    Node::Text(t) => {
+                        let text = self.render_text(t);
+                        self.html.push_str(&text);
+                    }
+                    other => {
+                        let start = self.html.len();
+                        self.render_node(other, 0);
+                        let end = self.html.trim_end_matches('\n').len().max(start);
+                        self.html.truncate(end);
+                    }
+                }
+            }
+            // Parsers drop one newline right after `<pre>`/`<textarea>`, so a leading one needs a spare
+            if is_whitespace_sensitive(&el.tag) && self.html[content_start..].starts_with('\n') {
+                self.html.insert(content_start, '\n');
+            }
+        } else {
+            self.html.push('\n');
+            self.render_nodes(&el.children, indent + 1);
+            self.html.push_str(&indent_str);
+        }
+
+        self.html.push_str("</");
+        self.html.push_str(&el.tag);
+        self.html.push_str(">\n");
+    }
+
+    fn render_attribute(&mut self, el: &Element, attr: &Attribute) {
+        if attr.name == "bind" {
+            // Show the bound value; the input writes nothing back without a script
+            let value = self.eval(&attr.value);
+            let input_type = el.attributes.iter()
+                .find(|a| a.name == "type")
+                .map(|a| self.eval(&a.value));
+            match input_type.as_ref().and_then(Value::as_str) {
+                Some("checkbox") => self.push_attribute("checked", &Value::Bool(truthy(&value))),
+                // A radio is checked when the group's value is its own, which defaults to "on"
+                Some("radio") => {
+                    let own = el.attributes.iter()
+                        .find(|a| a.name == "value")
+                        .map_or("on".to_string(), |a| to_text(&self.eval(&a.value)));
+                    self.push_attribute("checked", &Value::Bool(strict_equals(&value, &Value::String(own))));
+                }
+                _ if el.tag == "input" => self.push_attribute("value", &value),
+                _ => {}
+            }
+            return;
+        }
+        if !is_markup_attribute(attr) {
+            return;
+        }
+
+        match &attr.value {
+            Expression::Spread(spread) => {
+                if let Value::Object(entries) = self.eval(&spread.expr) {
+                    for (name, value) in entries.iter().filter(|(name, _)| is_spread_attribute_name(name)) {
+                        self.push_attribute(name, value);
+                    }
+                }
+            }
+            expr => {
+                let value = match self.eval(expr) {
+                    // Class lists keep their truthy entries
+                    Value::Array(items) if attr.name == "class" => Value::String(
+                        items.iter()
+                            .filter(|item| truthy(item))
+                            .map(to_text)
+                            .collect::<Vec<_>>()
+                            .join(" "),
+                    ),
+                    // Style objects become `color:red;font-size:14px`
+                    Value::Object(entries) if attr.name == "style" => Value::String(
+                        entries.iter()
+                            .filter_map(|(key, value)| css_declaration(key, &match value {
+                                Value::Null => return None,
+                                Value::Bool(b) => ConstValue::Boolean(*b),
+                                Value::Number(n) => ConstValue::Number(n.as_f64()?),
+                                value => ConstValue::String(to_text(value)),
+                            }))
+                            .collect::<Vec<_>>()
+                            .join(";"),
+                    ),
+                    value => value,
+                };
+                self.push_attribute(&attr.name, &value);
+            }
+        }
+    }
+
+    /// Emit ` name="value"`: true renders the name only, false and null drop the attribute
+    fn push_attribute(&mut self, name: &str, value: &Value) {
+        match value {
+            Value::Null | Value::Bool(false) => {}
+            Value::Bool(true) => self.html.push_str(&boolean_attribute(name, self.options)),
+            value => self.html.push_str(&format!(" {}=\"{}\"", name, escape_html(&to_text(value)))),
+        }
+    }
+
+    fn render_component(&mut self, r: &'a ComponentRef, indent: usize) {
+        let declaration = self.program.body.iter().find_map(|decl| match decl {
+            Declaration::Component(c) if c.name == r.name => Some((&c.parameters, &c.body)),
+            Declaration::Section(s) if s.name == r.name => Some((&s.parameters, &s.body)),
+            _ => None,
+        });
+        let Some((parameters, body)) = declaration else {
+            return;
+        };
+
+        // Arguments bind to parameters by position, as in the generated templates
+        let arguments: Scope = parameters.iter()
+            .zip(&r.parameters)
+            .map(|(param, arg)| (param.binding.clone(), self.eval(&arg.value)))
+            .collect();
+
+        let mut fills: Vec<(&str, &[Node])> = Vec::new();
+        if !r.children.is_empty() {
+            fills.push(("default", &r.children));
+        }
+        fills.extend(r.slot_fills.iter().map(|f| (f.name.as_str(), f.children.as_slice())));
+
+        // The body only sees its own parameters; slot content keeps the caller's bindings
+        let caller_scopes = std::mem::replace(&mut self.scopes, vec![arguments]);
+        self.slots.push(SlotFrame {
+            fills,
+            scopes: caller_scopes,
+        });
+        self.render_nodes(body, indent);
+        let frame = self.slots.pop().expect("slot frame pushed above");
+        self.scopes = frame.scopes;
+    }
+
+    fn render_slot(&mut self, name: &str, indent: usize) {
+        let Some(mut frame) = self.slots.pop() else {
+            return;
+        };
+        let fill = frame.fills.iter().find(|(fill, _)| *fill == name).map(|(_, nodes)| *nodes);
+        if let Some(nodes) = fill {
+            // Render in the caller's scope, where a forwarded `@slot` means the caller's own slot
+            std::mem::swap(&mut self.scopes, &mut frame.scopes);
+            self.render_nodes(nodes, indent);
+            std::mem::swap(&mut self.scopes, &mut frame.scopes);
+        }
+        self.slots.push(frame);
+    }
+
+    fn render_text(&self, text: &TextNode) -> String {
+        let rendered: String = text.parts.iter()
+            .map(|part| match part {
+                TextPart::Static(text) => text.clone(),
+                TextPart::Dynamic(expr) => to_text(&self.eval(expr)),
+            })
+            .collect();
+
+        // Whitespace-only text only survives when `{{-`/`-}}` kept it
+        if rendered.trim().is_empty() && text.trim == (true, true) {
+            return String::new();
+        }
+        escape_html(&rendered)
+    }
+
+    /// The items an `@each` or `@for` iterates; anything but an array is empty
+    fn items(&self, iterable: &Expression) -> Vec<Value> {
+        match self.eval(iterable) {
+            Value::Array(items) => items,
+            _ => Vec::new(),
+        }
+    }
+
+    fn entries(&self, iterable: &Expression) -> Vec<(Value, Value)> {
+        match self.eval(iterable) {
+            Value::Object(map) => map.into_iter().map(|(key, value)| (Value::String(key), value)).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The innermost binding named `name`, or `null` when there is none
+    fn lookup(&self, name: &str) -> &Value {
+        self.scopes.iter()
+            .rev()
+            .flat_map(|scope| scope.iter().rev())
+            .find(|(binding, _)| *binding == name)
+            .map_or(&Value::Null, |(_, value)| value)
+    }
+
+    /// Evaluate an expression with JavaScript semantics, as the generated templates would
+    fn eval(&self, expr: &Expression) -> Value {
+        match expr {
+            Expression::String(s) => Value::String(s.value.clone()),
+            Expression::Number(n) => number(n.value),
+            Expression::Boolean(b) => Value::Bool(b.value),
+            Expression::Null(_) => Value::Null,
+            Expression::Array(a) => Value::Array(a.elements.iter().map(|e| self.eval(e)).collect()),
+            Expression::Object(o) => Value::Object(
+                o.properties.iter().map(|p| (p.key.clone(), self.eval(&p.value))).collect(),
+            ),
+            Expression::ContextPath(p) => {
+                match p.root {
+                    PathRoot::Ctx => path_value(self.context, &p.segments),
+                    PathRoot::Local => path_value(self.lookup(&p.segments[0]), &p.segments[1..]),
+                }
+            }
+            Expression::Identifier(id) => self.lookup(&id.name).clone(),
+            // Properties of null and undefined are already null here
+            Expression::MemberAccess(m) | Expression::OptionalMember(m) => property(&self.eval(&m.object), &m.property),
+            Expression::Binary(b) => {
+                let left = self.eval(&b.left);
+                match b.operator {
+                    BinaryOp::And if !truthy(&left) => left,
+                    BinaryOp::Or if truthy(&left) => left,
+                    BinaryOp::NullishCoalesce if !left.is_null() => left,
+                    BinaryOp::And | BinaryOp::Or | BinaryOp::NullishCoalesce => self.eval(&b.right),
+                    op => binary(op, &left, &self.eval(&b.right)),
+                }
+            }
+            Expression::Ternary(t) => {
+                if truthy(&self.eval(&t.condition)) {
+                    self.eval(&t.consequent)
+                } else {
+                    self.eval(&t.alternate)
+                }
+            }
+            Expression::Spread(s) => self.eval(&s.expr),
+            Expression::Range(r) => match (self.eval(&r.start).as_f64(), self.eval(&r.end).as_f64()) {
+                (Some(start), Some(end)) => Value::Array(range_values(start, end, r.inclusive).into_iter().map(number).collect()),
+                _ => Value::Array(Vec::new()),
+            },
+            // Actions run in the browser
+            Expression::Call(_) | Expression::Event(_) => Value::Null,
+        }
+    }
+}
+
+/// Bindings for one pass of a loop, where `key` is the position in an array
+/// or the key of an object entry
+fn loop_scope(item_name: &str, index_name: Option<&str>, item: Value, key: Value, index: usize) -> Scope {
+    let mut scope = vec![(item_name.to_string(), item)];
+    if let Some(index_name) = index_name {
+        scope.push((index_name.to_string(), key));
+    }
+    scope.push(("$even".to_string(), Value::Bool(index.is_multiple_of(2))));
+    scope.push(("$odd".to_string(), Value::Bool(!index.is_multiple_of(2))));
+    scope
+}
+
+/// Whether a key of a spread object can be written as an attribute
+///
+/// The keys come from context data, so only plain attribute names get
+/// through; event handlers and `bind` are left out as for written attributes.
+fn is_spread_attribute_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == ':')
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | ':' | '.' | '-'))
+        && !name.get(..2).is_some_and(|prefix| prefix.eq_ignore_ascii_case("on"))
+        && !name.eq_ignore_ascii_case("bind")
+}
+
+
+/// Follow `segments` from `root`, cloning only the value reached
+fn path_value(root: &Value, segments: &[String]) -> Value {
+    match segments.split_first() {
+        None => root.clone(),
+        Some((segment, rest)) => match child(root, segment) {
+            Some(value) => path_value(value, rest),
+            None => rest.iter().fold(property(root, segment), |value, segment| property(&value, segment)),
+        },
+    }
+}
+
+/// An array item or object entry that is stored in `value`
+fn child<'v>(value: &'v Value, name: &str) -> Option<&'v Value> {
+    match value {
+        Value::Array(items) => name.parse::<usize>().ok().and_then(|i| items.get(i)),
+        Value::Object(entries) => entries.get(name),
+        _ => None,
+    }
+}
+
+fn property(value: &Value, name: &str) -> Value {
+    match (value, name) {
+        (Value::Array(items), "length") => Value::from(items.len()),
+        (Value::String(s), "length") => Value::from(s.chars().count()),
+        (value, name) => child(value, name).cloned().unwrap_or(Value::Null),
+    }
+}
+
+fn binary(op: BinaryOp, left: &Value, right: &Value) -> Value {
+    let numbers = left.as_f64().zip(right.as_f64());
+    match op {
+        BinaryOp::Add => match numbers {
+            Some((a, b)) => number(a + b),
+            None => Value::String(to_text(left) + &to_text(right)),
+        },
+        BinaryOp::Sub => numbers.map_or(Value::Null, |(a, b)| number(a - b)),
+        BinaryOp::Mul => numbers.map_or(Value::Null, |(a, b)| number(a * b)),
+        BinaryOp::Div => numbers.map_or(Value::Null, |(a, b)| number(a / b)),
+        BinaryOp::Mod => numbers.map_or(Value::Null, |(a, b)| number(a % b)),
+        BinaryOp::Eq => Value::Bool(strict_equals(left, right)),
+        BinaryOp::Ne => Value::Bool(!strict_equals(left, right)),
+        BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+            let ordering = match (left, right) {
+                (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+                _ => numbers.and_then(|(a, b)| a.partial_cmp(&b)),
+            };
+            Value::Bool(ordering.is_some_and(|ordering| match op {
+                BinaryOp::Lt => ordering.is_lt(),
+                BinaryOp::Le => ordering.is_le(),
+                BinaryOp::Gt => ordering.is_gt(),
+                _ => ordering.is_ge(),
+            }))
+        }
+        // Short-circuiting operators are handled by `eval`
+        BinaryOp::And | BinaryOp::Or | BinaryOp::NullishCoalesce => Value::Null,
+    }
+}
+
+/// `===`: numbers compare by value, so `1` equals `1.0`
+fn strict_equals(left: &Value, right: &Value) -> bool {
+    match left.as_f64().zip(right.as_f64()) {
+        Some((a, b)) => a == b,
+        None => left == right,
+    }
+}
+
+fn number(value: f64) -> Value {
+    Number::from_f64(value).map_or(Value::Null, Value::Number)
+}
+
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().is_some_and(|n| n != 0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(_) | Value::Object(_) => true,
+    }
+}
+
+/// `String(value)`, except that null renders as nothing
+fn to_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.as_f64().map(format_number).unwrap_or_else(|| n.to_string()),
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items.iter().map(to_text).collect::<Vec<_>>().join(","),
+        Value::Object(_) => "[object Object]".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+    use serde_json::json;
+
+    fn render(source: &str, context: Value) -> String {
+        let ast = parse(&tokenize(source).unwrap()).unwrap();
+        render_with_context(&ast, &context, &CompileOptions::default()).remove(0).content
+    }
+
+    #[test]
+    fn test_expressions_follow_javascript() {
+        let output = render(
+            r#"page home "/" {
+                p {{ ${ctx.count + 1} ${ctx.name + "!"} ${ctx.items.length} ${ctx.missing || "none"} }}
+                p [class: ["card", ctx.active && "active", ctx.hidden && "hidden"], hidden: ctx.hidden] { }
+            }"#,
+            json!({ "count": 2, "name": "Ada", "items": [1, 2, 3], "active": true, "hidden": false }),
+        );
+
+        assert!(output.contains("<p>3 Ada! 3 none</p>"));
+        assert!(output.contains(r#"<p class="card active"></p>"#));
+    }
+
+    #[test]
+    fn test_shorthand_with_computed_class() {
+        let output = render(
+            r#"page home "/" {
+                div.tab [class: ctx.active ? "on" : null] { }
+                span.x [class: ["a", ctx.b]] { }
+            }"#,
+            json!({ "active": false, "b": "bee" }),
+        );
+
+        assert!(output.contains(r#"<div class="tab"></div>"#), "{}", output);
+        assert!(output.contains(r#"<span class="x a bee"></span>"#));
+    }
+
+    #[test]
+    fn test_components_get_parameters_and_slots() {
+        let output = render(
+            r#"
+            component Card(item: user) {
+                div [class: "card"] { h3 {{ ${user.name} }} @slot }
+            }
+            page home "/" {
+                @each ctx.users as u, i {
+                    Card(item: u) { p {{ #${i + 1} }} }
+                }
+            }"#,
+            json!({ "users": [{ "name": "Ada" }, { "name": "Grace" }] }),
+        );
+
+        assert!(output.contains("  <div class=\"card\">\n    <h3>Ada</h3>\n    <p>#1</p>\n  </div>\n"));
+        assert!(output.contains("<h3>Grace</h3>\n    <p>#2</p>"));
+    }
+
+    #[test]
+    fn test_if_and_for_directives() {
+        let output = render(
+            r#"page home "/" {
+                @if ctx.user { p {{ Hi ${ctx.user.name} }} } @else { p {{ Sign in }} }
+                li @for(ctx.tags as tag) @if(tag != "draft") {{ ${tag} }}
+                input [type: "checkbox", bind: ctx.agree]
+                input [type: "radio", bind: ctx.plan, value: "basic"]
+                input [type: "radio", bind: ctx.plan, value: "pro"]
+            }"#,
+            json!({ "user": { "name": "Ada" }, "tags": ["news", "draft", "rust"], "agree": true, "plan": "pro" }),
+        );
+
+        assert!(output.contains("<p>Hi Ada</p>"));
+        assert!(!output.contains("Sign in"));
+        assert!(output.contains("  <li>news</li>\n  <li>rust</li>\n"));
+        assert!(output.contains(r#"<input type="checkbox" checked>"#));
+        assert!(output.contains(r#"<input type="radio" value="basic">"#), "{}", output);
+        assert!(output.contains(r#"<input type="radio" checked value="pro">"#), "{}", output);
+    }
+
+    #[test]
+    fn test_each_over_entries() {
+        let output = render(
+            r#"page home "/" {
+                @each ctx.scores as score, name in entries { p {{ ${name}: ${score} }} }
+                @each ctx.empty as value in entries { } @else { p {{ No scores }} }
+            }"#,
+            json!({ "scores": { "grace": 5, "ada": 3, "linus": 1 }, "empty": {} }),
+        );
+
+        // Insertion order, as `Object.entries` gives in the browser
+        assert!(output.contains("  <p>grace: 5</p>\n  <p>ada: 3</p>\n  <p>linus: 1</p>\n"), "{}", output);
+        assert!(output.contains("<p>No scores</p>"));
+    }
+
+    #[test]
+    fn test_each_index_base() {
+        let output = render(
+            r#"page home "/" { @each ctx.steps as step, n from 1 { p {{ ${n}. ${step} }} } }"#,
+            json!({ "steps": ["Plan", "Build"] }),
+        );
+
+        assert!(output.contains("  <p>1. Plan</p>\n  <p>2. Build</p>\n"), "{}", output);
+    }
+
+    #[test]
+    fn test_each_over_range() {
+        let output = render(
+            r#"page home "/" {
+                @each 1..ctx.count as i { p {{ ${i} }} }
+                @each 3..<3 as i { } @else { p {{ Empty }} }
+                @each 1..ctx.huge as i { i {{ ${i} }} }
+                @each ctx.huge..ctx.unsafe as i { } @else { p {{ Unsafe }} }
+            }"#,
+            json!({ "count": 3, "huge": 1e12, "unsafe": 9007199254740993.0 }),
+        );
+
+        assert!(output.contains("  <p>1</p>\n  <p>2</p>\n  <p>3</p>\n"), "{}", output);
+        assert!(output.contains("<p>Empty</p>"));
+        // Context data can't make the server build an unbounded list
+        assert_eq!(output.matches("<i>").count(), 10_000);
+        assert!(output.contains("<p>Unsafe</p>"));
+    }
+
+    #[test]
+    fn test_spread_keeps_plain_attribute_names() {
+        let output = render(
+            r#"page home "/" { div [...ctx.props] { } }"#,
+            json!({ "props": {
+                "onclick": "alert(1)",
+                "OnMouseOver": "alert(2)",
+                "bind": "ctx.x",
+                "x><script>alert(3)</script><b y": "z",
+                "aria-label": "Card",
+                "data-id": 7,
+            } }),
+        );
+
+        assert!(output.contains(r#"<div aria-label="Card" data-id="7"></div>"#), "{}", output);
+        assert!(!output.contains("alert"));
+    }
+
+    #[test]
+    fn test_pre_keeps_its_leading_newline() {
+        let output = render("page home \"/\" { pre {{\n  ${ctx.code}\n}} textarea {{ note }} }", json!({ "code": "x" }));
+        assert!(output.contains("<pre>\n\n  x\n</pre>"), "{}", output);
+        assert!(output.contains("<textarea> note </textarea>"), "{}", output);
+    }
+
+    #[test]
+    fn test_xhtml_output_is_well_formed_xml() {
+        let ast = parse(&tokenize(r#"page home "/" {
+            head { meta [name: "description", content: ctx.bio] }
+            input [type: "checkbox", bind: ctx.agree] br
+        }"#).unwrap()).unwrap();
+        let options = CompileOptions { doctype: crate::Doctype::Xhtml, ..CompileOptions::default() };
+        let output = render_with_context(&ast, &json!({ "bio": "Hi", "agree": true }), &options).remove(0).content;
+
+        assert!(output.contains(r#"<input type="checkbox" checked="checked" />"#), "{}", output);
+        assert_eq!(crate::codegen::check_well_formed_xml(&output), Ok(()));
+    }
+
+    #[test]
+    fn test_page_head_renders_with_context() {
+        let output = render(
+            r#"page home "/" {
+                head { title {{ ${ctx.name}'s page }} meta [name: "description", content: ctx.bio] }
+                h1 {{ Hi }}
+            }"#,
+            json!({ "name": "Ada", "bio": "Mathematician" }),
+        );
+
+        assert!(output.contains("<title>Ada&#39;s page</title>\n  <meta name=\"description\" content=\"Mathematician\">\n</head>"), "{}", output);
+        assert!(output.contains("<body>\n  <h1>Hi</h1>"));
+    }
+}
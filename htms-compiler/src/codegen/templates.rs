@@ -3,19 +3,63 @@
 //! Generates TypeScript functions that return DOM elements.
 //! No innerHTML, no template strings - pure DOM API.
 
+use std::collections::HashMap;
+
 use crate::ast::*;
-use crate::analyzer::SymbolTable;
-use crate::GeneratedFile;
+use crate::analyzer::{eval_const, is_svg_tag, ConstValue, SymbolTable};
+use crate::incremental::hash;
+use crate::{CompileOptions, GeneratedFile, Location};
+use super::events::SET_NESTED_VALUE;
+use super::types::property_key;
 use super::utils;
 
+const SVG_NAMESPACE: &str = "http://www.w3.org/2000/svg";
+
 /// Generate templates.ts
-pub fn generate(program: &Program, _symbols: &SymbolTable) -> GeneratedFile {
+pub fn generate(program: &Program, symbols: &SymbolTable, options: &CompileOptions) -> GeneratedFile {
+    generate_cached(program, symbols, options, None)
+}
+
+/// Generated code kept between compiles, so only the declarations that
+/// changed are generated again
+#[derive(Debug, Default)]
+pub(crate) struct TemplateCache {
+    /// Fingerprint of each declaration's source and the declarations it uses
+    pub(crate) fingerprints: HashMap<String, u64>,
+    entries: HashMap<u64, DeclarationCode>,
+}
+
+/// One declaration's function, and the list and conditional functions it
+/// needs, which go at the end of the file
+#[derive(Debug, Clone)]
+struct DeclarationCode {
+    function: String,
+    lists: Vec<String>,
+    conditionals: Vec<String>,
+    /// Conditional functions for `@if` elements inside list items
+    nested_conditionals: Vec<String>,
+    /// How many list and conditional names it took, so later ones stay numbered the same
+    list_count: usize,
+    conditional_count: usize,
+}
+
+/// Generate templates.ts, reusing the code in `cache` for declarations whose
+/// fingerprint is unchanged
+pub(crate) fn generate_cached(
+    program: &Program,
+    _symbols: &SymbolTable,
+    options: &CompileOptions,
+    cache: Option<&mut TemplateCache>,
+) -> GeneratedFile {
     let mut gen = TemplateGenerator::new();
-    gen.generate(program);
+    gen.source_comments = options.source_comments;
+    gen.source_filename = options.source_filename.clone();
+    gen.events_module = options.generate_events;
+    gen.generate(program, cache);
 
     GeneratedFile {
         path: "templates.ts".to_string(),
-        content: gen.output,
+        content: utils::with_module_format(gen.output, options.module_format),
     }
 }
 
@@ -24,10 +68,23 @@ struct TemplateGenerator {
     indent: usize,
     var_counter: usize,
     has_events: bool,
-    list_functions: Vec<(String, Element, ForDirective)>, // (name, element, directive)
+    has_bindings: bool,
+    list_functions: Vec<(String, Element, ForDirective, Option<String>)>, // (name, element, directive, component)
     list_counter: usize,
-    conditional_functions: Vec<(String, Element, IfDirective)>, // (name, element, directive)
+    conditional_functions: Vec<(String, Element, IfDirective, Option<String>)>, // (name, element, directive, component)
     conditional_counter: usize,
+    /// Component whose body is being generated, used to namespace action lookups
+    component: Option<String>,
+    /// Parameter count of each component that declares `@slot`, whose slot content is passed after its parameters
+    slotted_components: HashMap<String, usize>,
+    /// Whether the function being generated has a `slots` parameter
+    slots_in_scope: bool,
+    source_comments: bool,
+    source_filename: Option<String>,
+    /// Whether events.ts is generated, so its binding helper can be imported
+    events_module: bool,
+    /// Inside an `<svg>` subtree, outside any `foreignObject`
+    in_svg: bool,
 }
 
 impl TemplateGenerator {
@@ -37,10 +94,18 @@ impl TemplateGenerator {
             indent: 0,
             var_counter: 0,
             has_events: false,
+            has_bindings: false,
             list_functions: Vec::new(),
             list_counter: 0,
             conditional_functions: Vec::new(),
             conditional_counter: 0,
+            component: None,
+            slotted_components: HashMap::new(),
+            slots_in_scope: false,
+            source_comments: false,
+            source_filename: None,
+            events_module: true,
+            in_svg: false,
         }
     }
 
@@ -50,13 +115,61 @@ impl TemplateGenerator {
         var
     }
 
+    /// Start a new generated function
     fn reset_vars(&mut self) {
         self.var_counter = 0;
+        self.in_svg = false;
+    }
+
+    /// Whether `el` belongs in the SVG namespace: everything inside `<svg>`,
+    /// and SVG-only tags anywhere, such as a component whose root is a `path`
+    fn is_svg(&self, el: &Element) -> bool {
+        self.in_svg || is_svg_tag(&el.tag)
+    }
+
+    /// Declare `var` as a new element for `el`
+    ///
+    /// SVG elements are typed as `HTMLElement` like every other node the
+    /// templates build; their attributes are always set with `setAttribute`.
+    fn create_element(&mut self, var: &str, el: &Element) {
+        if self.is_svg(el) {
+            self.writeln(&format!(
+                "const {} = document.createElementNS('{}', '{}') as Element as HTMLElement;",
+                var, SVG_NAMESPACE, el.tag
+            ));
+        } else {
+            self.writeln(&format!("const {} = document.createElement('{}');", var, el.tag));
+        }
     }
 
-    fn generate(&mut self, program: &Program) {
-        // First pass: check if we have any events
-        self.has_events = utils::program_has_events(program);
+    /// Append `el`'s children, switching namespace at `svg` and back at `foreignObject`
+    fn append_element_children(&mut self, target: &str, el: &Element) {
+        let outer = self.in_svg;
+        self.in_svg = self.is_svg(el) && el.tag != "foreignObject";
+        self.append_children(target, &el.children);
+        self.in_svg = outer;
+    }
+
+    fn generate(&mut self, program: &Program, mut cache: Option<&mut TemplateCache>) {
+        // First pass: check if we have any events or bindings
+        let analysis = utils::analyze_events(program);
+        self.has_events = analysis.has_events;
+        self.has_bindings = analysis.has_bindings;
+        for decl in &program.body {
+            match decl {
+                Declaration::Component(c) if !collect_slots(&c.body).is_empty() => {
+                    self.slotted_components.insert(c.name.clone(), c.parameters.len());
+                }
+                Declaration::Section(s) if !collect_slots(&s.body).is_empty() => {
+                    self.slotted_components.insert(s.name.clone(), s.parameters.len());
+                }
+                // Pages pass their body to a layout as its default slot
+                Declaration::Layout(l) => {
+                    self.slotted_components.insert(l.name.clone(), 0);
+                }
+                _ => {}
+            }
+        }
 
         // Header
         self.writeln("// Generated by HTMS Compiler");
@@ -65,69 +178,152 @@ impl TemplateGenerator {
         if self.has_events {
             self.writeln("import { actions } from './actions';");
             self.writeln("import { getContext, rerender } from './router';");
+        } else if self.has_bindings {
+            self.writeln("import { getContext } from './router';");
+        }
+        if self.has_bindings && self.events_module {
+            self.writeln("import { setNestedValue } from './events';");
+        }
+        if self.has_events || self.has_bindings {
+            self.writeln("");
+        }
+        if self.has_bindings && !self.events_module {
+            self.output.push_str(SET_NESTED_VALUE);
             self.writeln("");
         }
         self.writeln("export type Context = Record<string, unknown>;");
+        if !self.slotted_components.is_empty() {
+            self.writeln("export type Slots = Record<string, () => Node>;");
+        }
         self.writeln("");
 
-        // Generate each declaration
+        // Generate each declaration, reusing cached code for the unchanged ones
+        let mut slotted: Vec<_> = self.slotted_components.iter().collect();
+        slotted.sort();
+        let context = hash(&(self.has_events, self.has_bindings, self.events_module, slotted));
+        let mut entries = HashMap::new();
+        let mut declarations = Vec::new();
         for decl in &program.body {
-            match decl {
-                Declaration::Component(c) => self.generate_component(c),
-                Declaration::Section(s) => self.generate_section(s),
-                Declaration::Page(p) => self.generate_page(p),
+            let key = cache.as_ref()
+                .and_then(|cache| cache.fingerprints.get(decl.name()))
+                .map(|fingerprint| {
+                    // Source comments carry line numbers, which shift with edits elsewhere
+                    let line = self.source_comments.then(|| decl.loc().line);
+                    hash(&(decl.name(), fingerprint, line, self.list_counter, self.conditional_counter, context))
+                });
+            let cached = key.and_then(|key| cache.as_mut()?.entries.remove(&key));
+            let code = match cached {
+                Some(code) => {
+                    self.list_counter += code.list_count;
+                    self.conditional_counter += code.conditional_count;
+                    code
+                }
+                None => self.generate_declaration(decl),
+            };
+            if let Some(key) = key {
+                entries.insert(key, code.clone());
             }
-            self.writeln("");
+            declarations.push(code);
+        }
+        if let Some(cache) = cache {
+            // Only keep what the current program still contains
+            cache.entries = entries;
         }
 
-        // Generate list functions for @for directives
-        let list_fns = self.list_functions.clone(); // Clone to avoid borrow issues
-        for (name, element, directive) in list_fns {
-            self.generate_list_function(&name, &element, &directive);
+        // List functions for @for directives, then conditional functions for
+        // @if directives, including those inside list items
+        for function in declarations.iter().map(|code| &code.function)
+            .chain(declarations.iter().flat_map(|code| &code.lists))
+            .chain(declarations.iter().flat_map(|code| &code.conditionals))
+            .chain(declarations.iter().flat_map(|code| &code.nested_conditionals))
+        {
+            self.output.push_str(function);
             self.writeln("");
         }
+    }
 
-        // Generate conditional functions for @if directives
-        let conditional_fns = self.conditional_functions.clone();
-        for (name, element, directive) in conditional_fns {
-            self.generate_conditional_function(&name, &element, &directive);
-            self.writeln("");
+    /// Generate one declaration's function and the helpers it needs
+    fn generate_declaration(&mut self, decl: &Declaration) -> DeclarationCode {
+        let output = std::mem::take(&mut self.output);
+        let (list_start, conditional_start) = (self.list_counter, self.conditional_counter);
+
+        self.component = None;
+        self.slots_in_scope = false;
+        match decl {
+            Declaration::Component(c) => self.generate_component(c),
+            Declaration::Section(s) => self.generate_section(s),
+            Declaration::Layout(l) => self.generate_layout(l),
+            Declaration::Page(p) => self.generate_page(p),
+        }
+        let function = std::mem::take(&mut self.output);
+
+        let list_fns = std::mem::take(&mut self.list_functions);
+        let conditional_fns = std::mem::take(&mut self.conditional_functions);
+        self.slots_in_scope = false;
+        let lists = list_fns.into_iter()
+            .map(|(name, element, directive, component)| {
+                self.component = component;
+                self.generate_list_function(&name, &element, &directive);
+                std::mem::take(&mut self.output)
+            })
+            .collect();
+        // A list item's own @for isn't generated
+        self.list_functions.clear();
+
+        let nested_fns = std::mem::take(&mut self.conditional_functions);
+        let mut generate_conditionals = |functions: Vec<(String, Element, IfDirective, Option<String>)>| {
+            functions.into_iter()
+                .map(|(name, element, directive, component)| {
+                    self.component = component;
+                    self.generate_conditional_function(&name, &element, &directive);
+                    std::mem::take(&mut self.output)
+                })
+                .collect()
+        };
+        let conditionals = generate_conditionals(conditional_fns);
+        let nested_conditionals = generate_conditionals(nested_fns);
+        // Nor is anything a conditional element would need
+        self.list_functions.clear();
+        self.conditional_functions.clear();
+
+        self.output = output;
+        DeclarationCode {
+            function,
+            lists,
+            conditionals,
+            nested_conditionals,
+            list_count: self.list_counter - list_start,
+            conditional_count: self.conditional_counter - conditional_start,
         }
     }
 
     fn generate_component(&mut self, comp: &ComponentDecl) {
         self.reset_vars();
+        self.component = Some(comp.name.clone());
+        self.write_source_comment(&comp.name, comp.loc);
 
         // Function signature
-        if comp.parameters.is_empty() {
-            self.writeln(&format!(
-                "export function {}(ctx: Context): HTMLElement {{",
-                comp.name
-            ));
-        } else {
-            let params: Vec<String> = comp.parameters
-                .iter()
-                .map(|p| format!("{}: unknown", p.binding))
-                .collect();
-            self.writeln(&format!(
-                "export function {}(ctx: Context, {}): HTMLElement {{",
-                comp.name,
-                params.join(", ")
-            ));
+        let mut params = vec!["ctx: Context".to_string()];
+        params.extend(comp.parameters.iter().map(|p| format!("{}: unknown", p.binding)));
+        self.slots_in_scope = self.slotted_components.contains_key(&comp.name);
+        if self.slots_in_scope {
+            params.push("slots: Slots = {}".to_string());
         }
+        self.writeln(&format!(
+            "export function {}({}): HTMLElement {{",
+            comp.name,
+            params.join(", ")
+        ));
 
         self.indent += 1;
 
         // Create root fragment if multiple children
-        if comp.body.len() == 1 {
-            let root_var = self.generate_node(&comp.body[0], None);
+        if let Some(root) = single_root(&comp.body) {
+            let root_var = self.generate_node(root, None);
             self.writeln(&format!("return {};", root_var));
         } else {
             self.writeln("const fragment = document.createDocumentFragment();");
-            for node in &comp.body {
-                let var = self.generate_node(node, None);
-                self.writeln(&format!("fragment.appendChild({});", var));
-            }
+            self.append_children("fragment", &comp.body);
             // Wrap in a div since we need to return HTMLElement
             self.writeln("const root = document.createElement('div');");
             self.writeln("root.appendChild(fragment);");
@@ -140,23 +336,55 @@ impl TemplateGenerator {
 
     fn generate_section(&mut self, section: &SectionDecl) {
         self.reset_vars();
+        self.write_source_comment(&section.name, section.loc);
 
+        let mut params = vec!["ctx: Context".to_string()];
+        params.extend(section.parameters.iter().map(|p| format!("{}: unknown", p.binding)));
+        self.slots_in_scope = self.slotted_components.contains_key(&section.name);
+        if self.slots_in_scope {
+            params.push("slots: Slots = {}".to_string());
+        }
         self.writeln(&format!(
-            "export function {}(ctx: Context): HTMLElement {{",
-            section.name
+            "export function {}({}): HTMLElement {{",
+            section.name,
+            params.join(", ")
         ));
 
         self.indent += 1;
 
-        if section.body.len() == 1 {
-            let root_var = self.generate_node(&section.body[0], None);
+        if let Some(root) = single_root(&section.body) {
+            let root_var = self.generate_node(root, None);
             self.writeln(&format!("return {};", root_var));
         } else {
             self.writeln("const fragment = document.createDocumentFragment();");
-            for node in &section.body {
-                let var = self.generate_node(node, None);
-                self.writeln(&format!("fragment.appendChild({});", var));
-            }
+            self.append_children("fragment", &section.body);
+            self.writeln("const root = document.createElement('div');");
+            self.writeln("root.appendChild(fragment);");
+            self.writeln("return root;");
+        }
+
+        self.indent -= 1;
+        self.writeln("}");
+    }
+
+    fn generate_layout(&mut self, layout: &LayoutDecl) {
+        self.reset_vars();
+        self.write_source_comment(&layout.name, layout.loc);
+        self.slots_in_scope = true;
+
+        self.writeln(&format!(
+            "export function {}(ctx: Context, slots: Slots = {{}}): HTMLElement {{",
+            layout.name
+        ));
+
+        self.indent += 1;
+
+        if let Some(root) = single_root(&layout.body) {
+            let root_var = self.generate_node(root, None);
+            self.writeln(&format!("return {};", root_var));
+        } else {
+            self.writeln("const fragment = document.createDocumentFragment();");
+            self.append_children("fragment", &layout.body);
             self.writeln("const root = document.createElement('div');");
             self.writeln("root.appendChild(fragment);");
             self.writeln("return root;");
@@ -168,6 +396,7 @@ impl TemplateGenerator {
 
     fn generate_page(&mut self, page: &PageDecl) {
         self.reset_vars();
+        self.write_source_comment(&page.name, page.loc);
 
         self.writeln(&format!(
             "export function {}Page(ctx: Context, container: HTMLElement): void {{",
@@ -176,15 +405,23 @@ impl TemplateGenerator {
 
         self.indent += 1;
 
-        if page.body.len() == 1 {
-            let root_var = self.generate_node(&page.body[0], None);
+        if let Some(layout) = &page.layout {
+            // The body is the layout's default slot content
+            let layout_ref = Node::ComponentRef(ComponentRef {
+                name: layout.clone(),
+                parameters: Vec::new(),
+                children: page.body.clone(),
+                slot_fills: Vec::new(),
+                loc: page.loc,
+            });
+            let root_var = self.generate_node(&layout_ref, None);
+            self.writeln(&format!("container.appendChild({});", root_var));
+        } else if let Some(root) = single_root(&page.body) {
+            let root_var = self.generate_node(root, None);
             self.writeln(&format!("container.appendChild({});", root_var));
         } else {
             self.writeln("const fragment = document.createDocumentFragment();");
-            for node in &page.body {
-                let var = self.generate_node(node, None);
-                self.writeln(&format!("fragment.appendChild({});", var));
-            }
+            self.append_children("fragment", &page.body);
             self.writeln("container.appendChild(fragment);");
         }
 
@@ -200,12 +437,61 @@ impl TemplateGenerator {
             Node::Text(t) => self.generate_text(t, parent_var),
             Node::If(stmt) => self.generate_if(stmt, parent_var),
             Node::Each(stmt) => self.generate_each(stmt, parent_var),
-            Node::Slot(_) => {
-                // Slots are handled at component call site
+            Node::Let(_) => unreachable!("let bindings are emitted by append_children"),
+            Node::Comment(comment) => {
                 let var = self.next_var();
-                self.writeln(&format!("const {} = document.createComment('slot');", var));
+                let text = escape_js_string(&utils::comment_text(&comment.text));
+                self.writeln(&format!("const {} = document.createComment(' {} ');", var, text));
                 var
             }
+            Node::RawHtml(raw) => {
+                // Parsed by a <template> so the markup goes in without a wrapper element
+                let var = self.next_var();
+                self.writeln(&format!("const {} = document.createElement('template');", var));
+                self.writeln(&format!("{}.innerHTML = String({} ?? '');", var, expr_to_js(&raw.value)));
+                format!("{}.content", var)
+            }
+            Node::Slot(slot) => {
+                // Slot content is built by the caller; an unfilled slot leaves a placeholder
+                let var = self.next_var();
+                if self.slots_in_scope {
+                    self.writeln(&format!(
+                        "const {} = slots.{}?.() ?? document.createComment('slot');",
+                        var,
+                        slot.name.as_deref().unwrap_or("default")
+                    ));
+                } else {
+                    self.writeln(&format!("const {} = document.createComment('slot');", var));
+                }
+                var
+            }
+        }
+    }
+
+    /// Generate `nodes` and append each one to `target`
+    ///
+    /// `@let` bindings become constants instead of DOM nodes; a block that
+    /// declares any is wrapped in braces so sibling blocks can reuse names.
+    fn append_children(&mut self, target: &str, nodes: &[Node]) {
+        let scoped = nodes.iter().any(|n| matches!(n, Node::Let(_)));
+        if scoped {
+            self.writeln("{");
+            self.indent += 1;
+        }
+
+        for node in nodes {
+            if let Node::Let(binding) = node {
+                let value = expr_to_js(&binding.value);
+                self.writeln(&format!("const {} = {};", binding.name, value));
+            } else {
+                let var = self.generate_node(node, Some(target));
+                self.writeln(&format!("{}.appendChild({});", target, var));
+            }
+        }
+
+        if scoped {
+            self.indent -= 1;
+            self.writeln("}");
         }
     }
 
@@ -238,7 +524,7 @@ impl TemplateGenerator {
             self.conditional_counter += 1;
 
             // Store the conditional function for later generation
-            self.conditional_functions.push((function_name.clone(), el.clone(), if_directive.clone()));
+            self.conditional_functions.push((function_name.clone(), el.clone(), if_directive.clone(), self.component.clone()));
 
             // Create comment placeholder (element will be created conditionally)
             let var = self.next_var();
@@ -280,18 +566,15 @@ impl TemplateGenerator {
             self.list_counter += 1;
 
             // Store the list function for later generation
-            self.list_functions.push((function_name.clone(), el.clone(), for_directive.clone()));
+            self.list_functions.push((function_name.clone(), el.clone(), for_directive.clone(), self.component.clone()));
 
             // Create placeholder container
             let var = self.next_var();
-            self.writeln(&format!(
-                "const {} = document.createElement('{}');",
-                var, el.tag
-            ));
+            self.create_element(&var, el);
 
             // Set attributes (but not children - they're in the list function)
             for attr in &el.attributes {
-                self.generate_attribute(&var, attr);
+                self.generate_attribute(el, &var, attr);
             }
 
             return var;
@@ -300,77 +583,97 @@ impl TemplateGenerator {
         let var = self.next_var();
 
         // Create element
-        self.writeln(&format!(
-            "const {} = document.createElement('{}');",
-            var, el.tag
-        ));
+        self.create_element(&var, el);
 
         // Set attributes
         for attr in &el.attributes {
-            self.generate_attribute(&var, attr);
+            self.generate_attribute(el, &var, attr);
         }
 
         // Add children
-        for child in &el.children {
-            let child_var = self.generate_node(child, Some(&var));
-            self.writeln(&format!("{}.appendChild({});", var, child_var));
-        }
+        self.append_element_children(&var, el);
 
         var
     }
 
-    fn generate_attribute(&mut self, el_var: &str, attr: &Attribute) {
+    fn generate_attribute(&mut self, el: &Element, el_var: &str, attr: &Attribute) {
         let name = &attr.name;
 
+        // Spread attributes: copy every entry; attributes listed later override them
+        if let Expression::Spread(spread) = &attr.value {
+            let source = expr_to_js(&spread.expr);
+            self.writeln(&format!(
+                "for (const [key, value] of Object.entries({} ?? {{}})) {{",
+                source
+            ));
+            self.indent += 1;
+            self.writeln(&format!("{}.setAttribute(key, String(value));", el_var));
+            self.indent -= 1;
+            self.writeln("}");
+            return;
+        }
+
         // Check for event handlers
-        if name.starts_with("on") {
+        if utils::is_event_attribute(attr) {
             self.generate_event_attribute(el_var, attr);
             return;
         }
 
         // Check for bind
         if name == "bind" {
-            self.generate_bind_attribute(el_var, attr);
+            self.generate_bind_attribute(el, el_var, attr);
             return;
         }
 
-        // Handle class specially
+        // Handle class specially; an SVG element's `className` is read-only
         if name == "class" {
+            let svg = self.is_svg(el);
+            let set_class = |value: String| if svg {
+                format!("{}.setAttribute('class', {});", el_var, value)
+            } else {
+                format!("{}.className = {};", el_var, value)
+            };
             match &attr.value {
                 Expression::String(s) => {
-                    self.writeln(&format!("{}.className = '{}';", el_var, s.value));
+                    self.writeln(&set_class(format!("'{}'", escape_js_string(&s.value))));
                 }
-                Expression::ContextPath(p) => {
-                    let path = strip_ctx(&p.path);
-                    self.writeln(&format!("{}.className = String(ctx.{});", el_var, path));
+                Expression::Ternary(t) if is_never_omitted(&attr.value) => {
+                    let cond = expr_to_js(&t.condition);
+                    let cons = expr_to_js(&t.consequent);
+                    let alt = expr_to_js(&t.alternate);
+                    self.writeln(&set_class(format!("{} ? {} : {}", cond, cons, alt)));
                 }
-                Expression::Ternary(t) => {
-                    let cond = self.expr_to_js(&t.condition);
-                    let cons = self.expr_to_js(&t.consequent);
-                    let alt = self.expr_to_js(&t.alternate);
-                    self.writeln(&format!(
-                        "{}.className = {} ? {} : {};",
-                        el_var, cond, cons, alt
-                    ));
+                Expression::Array(_) => {
+                    // Falsy entries (null, false, '') drop out of the class list
+                    let val = expr_to_js(&attr.value);
+                    self.writeln(&set_class(format!("{}.filter(Boolean).join(' ')", val)));
                 }
-                _ => {
-                    let val = self.expr_to_js(&attr.value);
-                    self.writeln(&format!("{}.className = {};", el_var, val));
+                value if is_never_omitted(value) => {
+                    let val = expr_to_js(value);
+                    self.writeln(&set_class(val));
                 }
+                value => self.write_unless_omitted(value, &set_class("String(value)".to_string())),
             }
             return;
         }
 
+        // Style objects: constant entries fold into the attribute, the rest are set at runtime
+        if let (Expression::Object(object), "style") = (&attr.value, name.as_str()) {
+            self.generate_style_object(el_var, object);
+            return;
+        }
+
         // Handle id
         if name == "id" {
             match &attr.value {
                 Expression::String(s) => {
-                    self.writeln(&format!("{}.id = '{}';", el_var, s.value));
+                    self.writeln(&format!("{}.id = '{}';", el_var, escape_js_string(&s.value)));
                 }
-                _ => {
-                    let val = self.expr_to_js(&attr.value);
-                    self.writeln(&format!("{}.id = {};", el_var, val));
+                value if is_never_omitted(value) => {
+                    let val = expr_to_js(value);
+                    self.writeln(&format!("{}.id = String({});", el_var, val));
                 }
+                value => self.write_unless_omitted(value, &format!("{}.id = String(value);", el_var)),
             }
             return;
         }
@@ -383,21 +686,18 @@ impl TemplateGenerator {
             return;
         }
 
-        // Handle href, src, etc. as properties
+        // Handle href, src, etc. as properties, which SVG elements don't have
         let prop_attrs = ["href", "src", "value", "type", "name", "placeholder", "alt", "title"];
-        if prop_attrs.contains(&name.as_str()) {
+        if prop_attrs.contains(&name.as_str()) && !self.is_svg(el) {
             match &attr.value {
                 Expression::String(s) => {
-                    self.writeln(&format!("{}.{} = '{}';", el_var, name, s.value));
+                    self.writeln(&format!("{}.{} = '{}';", el_var, name, escape_js_string(&s.value)));
                 }
-                Expression::ContextPath(p) => {
-                    let path = strip_ctx(&p.path);
-                    self.writeln(&format!("{}.{} = String(ctx.{});", el_var, name, path));
-                }
-                _ => {
-                    let val = self.expr_to_js(&attr.value);
-                    self.writeln(&format!("{}.{} = {};", el_var, name, val));
+                value if is_never_omitted(value) => {
+                    let val = expr_to_js(value);
+                    self.writeln(&format!("{}.{} = String({});", el_var, name, val));
                 }
+                value => self.write_unless_omitted(value, &format!("{}.{} = String(value);", el_var, name)),
             }
             return;
         }
@@ -405,22 +705,76 @@ impl TemplateGenerator {
         // General setAttribute
         match &attr.value {
             Expression::String(s) => {
-                self.writeln(&format!("{}.setAttribute('{}', '{}');", el_var, name, s.value));
+                self.writeln(&format!("{}.setAttribute('{}', '{}');", el_var, name, escape_js_string(&s.value)));
             }
             Expression::Number(n) => {
-                self.writeln(&format!("{}.setAttribute('{}', '{}');", el_var, name, n.value));
-            }
-            Expression::ContextPath(p) => {
-                let path = strip_ctx(&p.path);
-                self.writeln(&format!(
-                    "{}.setAttribute('{}', String(ctx.{}));",
-                    el_var, name, path
-                ));
+                self.writeln(&format!("{}.setAttribute('{}', '{}');", el_var, name, n.raw));
             }
-            _ => {
-                let val = self.expr_to_js(&attr.value);
+            value if is_never_omitted(value) => {
+                let val = expr_to_js(value);
                 self.writeln(&format!("{}.setAttribute('{}', String({}));", el_var, name, val));
             }
+            // `true` renders the name only, as the HTML output does
+            value => self.write_unless_omitted(
+                value,
+                &format!("{}.setAttribute('{}', value === true ? '' : String(value));", el_var, name),
+            ),
+        }
+    }
+
+    /// Run `set`, which reads `value`, unless the attribute's value is
+    /// `false`, `null` or `undefined`, which leave the attribute off
+    fn write_unless_omitted(&mut self, value: &Expression, set: &str) {
+        self.writeln("{");
+        self.indent += 1;
+        self.writeln(&format!("const value = {};", expr_to_js(value)));
+        self.writeln(&format!("if (value != null && value !== false) {}", set));
+        self.indent -= 1;
+        self.writeln("}");
+    }
+
+    fn generate_style_object(&mut self, el_var: &str, object: &ObjectLiteral) {
+        let folded: Vec<Option<ConstValue>> = object.properties.iter()
+            .map(|p| eval_const(&p.value))
+            .collect();
+        if folded.iter().all(Option::is_some) {
+            let style: Vec<String> = object.properties.iter()
+                .zip(&folded)
+                .filter_map(|(p, value)| utils::css_declaration(&p.key, value.as_ref()?))
+                .collect();
+            if !style.is_empty() {
+                self.writeln(&format!("{}.setAttribute('style', '{}');", el_var, escape_js_string(&style.join(";"))));
+            }
+            return;
+        }
+
+        for (property, value) in object.properties.iter().zip(&folded) {
+            let css = utils::css_property(&property.key);
+            match value {
+                Some(value) => {
+                    if let Some(value) = utils::css_value(&css, value) {
+                        self.writeln(&format!(
+                            "{}.style.setProperty('{}', '{}');",
+                            el_var, escape_js_string(&css), escape_js_string(&value)
+                        ));
+                    }
+                }
+                None => {
+                    // Like static output, null and booleans drop the entry; an empty value removes it
+                    let text = "value == null || typeof value === 'boolean' ? '' : String(value)";
+                    let text = if utils::is_pixel_property(&css) {
+                        format!("typeof value === 'number' ? value + 'px' : {}", text)
+                    } else {
+                        text.to_string()
+                    };
+                    self.writeln("{");
+                    self.indent += 1;
+                    self.writeln(&format!("const value = {};", expr_to_js(&property.value)));
+                    self.writeln(&format!("{}.style.setProperty('{}', {});", el_var, escape_js_string(&css), text));
+                    self.indent -= 1;
+                    self.writeln("}");
+                }
+            }
         }
     }
 
@@ -459,10 +813,16 @@ impl TemplateGenerator {
         self.indent -= 1;
         self.writeln("};");
 
+        // Scoped names may be absent from the user's actions object, so look them up untyped
+        if self.component.is_some() {
+            self.writeln("const actionTable = actions as Record<string, any>;");
+        }
+
         // Call the action
         if args.is_empty() {
             // Simple action without arguments
-            self.writeln(&format!("const actionFn = actions.{};", action));
+            let action_ref = self.action_ref(&action);
+            self.writeln(&format!("const actionFn = {};", action_ref));
             self.writeln("if (typeof actionFn === 'function') {");
             self.indent += 1;
             self.writeln("actionFn(actionCtx, e);");
@@ -471,9 +831,13 @@ impl TemplateGenerator {
         } else {
             // Parameterized action - curry the arguments
             let args_str: Vec<String> = args.iter()
-                .map(|a| self.expr_to_js(a))
+                .map(expr_to_js)
                 .collect();
-            self.writeln(&format!("const actionFn = actions.{}({});", action, args_str.join(", ")));
+            let action_ref = match self.component {
+                Some(_) => format!("({})", self.action_ref(&action)),
+                None => self.action_ref(&action),
+            };
+            self.writeln(&format!("const actionFn = {}({});", action_ref, args_str.join(", ")));
             self.writeln("if (typeof actionFn === 'function') {");
             self.indent += 1;
             self.writeln("actionFn(actionCtx, e);");
@@ -491,19 +855,54 @@ impl TemplateGenerator {
         }
     }
 
-    fn generate_bind_attribute(&mut self, el_var: &str, attr: &Attribute) {
+    /// Look up an action, preferring the component-scoped `Component__action`
+    /// so two components can define the same action name without colliding
+    fn action_ref(&self, action: &str) -> String {
+        match &self.component {
+            Some(component) => format!("actionTable.{}__{} ?? actionTable.{}", component, action, action),
+            None => format!("actions.{}", action),
+        }
+    }
+
+    fn generate_bind_attribute(&mut self, el: &Element, el_var: &str, attr: &Attribute) {
         if let Expression::ContextPath(p) = &attr.value {
-            let path = strip_ctx(&p.path);
+            let path = p.segments.join(".");
+            // Intermediate objects may not exist yet; setNestedValue creates them on write
+            let read = format!("ctx.{}", p.segments.join("?."));
+
+            // Checkboxes bind their checked state, radios the value of the checked
+            // one in their group, and everything else its value
+            let input_type = el.attributes.iter().find_map(|a| match &a.value {
+                Expression::String(t) if a.name == "type" => Some(t.value.as_str()),
+                _ => None,
+            });
+            let (property, event, initial) = match input_type {
+                Some("checkbox") => ("checked", "change", format!("Boolean({})", read)),
+                Some("radio") => {
+                    // The value attribute may come after the binding, so compare with it directly
+                    let value = el.attributes.iter()
+                        .find(|a| a.name == "value")
+                        .map_or(format!("{}.value", el_var), |a| match &a.value {
+                            Expression::String(_) => expr_to_js(&a.value),
+                            value => format!("String({})", expr_to_js(value)),
+                        });
+                    ("checked", "change", format!("{} === {}", read, value))
+                }
+                _ => ("value", "input", format!("String({} ?? '')", read)),
+            };
+            // A radio group stores the chosen value, not whether each one is checked
+            let written = if input_type == Some("radio") { "value" } else { property };
 
             // Set initial value
-            self.writeln(&format!("{}.value = String(ctx.{} || '');", el_var, path));
+            self.writeln(&format!("{}.{} = {};", el_var, property, initial));
 
-            // Add input listener for two-way binding
-            self.writeln(&format!("{}.addEventListener('input', (e) => {{", el_var));
+            // Write changes back to the shared context
+            self.writeln(&format!("{}.addEventListener('{}', (e) => {{", el_var, event));
             self.indent += 1;
             self.writeln(&format!(
-                "setNestedValue(ctx, '{}', (e.target as HTMLInputElement).value);",
-                path
+                "setNestedValue(getContext(), '{}', (e.target as HTMLInputElement).{});",
+                escape_js_string(&path),
+                written
             ));
             self.indent -= 1;
             self.writeln("});");
@@ -513,11 +912,43 @@ impl TemplateGenerator {
     fn generate_component_ref(&mut self, r: &ComponentRef, _parent: Option<&str>) -> String {
         let var = self.next_var();
 
+        let has_content = !r.children.is_empty() || !r.slot_fills.is_empty();
+        let arity = self.slotted_components.get(&r.name).copied();
+        if let Some(arity) = arity.filter(|_| has_content) {
+            let mut args = vec!["ctx".to_string()];
+            args.extend(r.parameters.iter().map(|p| expr_to_js(&p.value)));
+            // Slots come after every declared parameter
+            while args.len() <= arity {
+                args.push("undefined".to_string());
+            }
+            self.writeln(&format!("const {} = {}({}, {{", var, r.name, args.join(", ")));
+            self.indent += 1;
+
+            let mut fills: Vec<(&str, &[Node])> = Vec::new();
+            if !r.children.is_empty() {
+                fills.push(("default", &r.children));
+            }
+            fills.extend(r.slot_fills.iter().map(|f| (f.name.as_str(), f.children.as_slice())));
+            for (name, nodes) in fills {
+                self.writeln(&format!("{}: () => {{", name));
+                self.indent += 1;
+                self.writeln("const fragment = document.createDocumentFragment();");
+                self.append_children("fragment", nodes);
+                self.writeln("return fragment;");
+                self.indent -= 1;
+                self.writeln("},");
+            }
+
+            self.indent -= 1;
+            self.writeln("});");
+            return var;
+        }
+
         if r.parameters.is_empty() {
             self.writeln(&format!("const {} = {}(ctx);", var, r.name));
         } else {
             let params: Vec<String> = r.parameters.iter()
-                .map(|p| self.expr_to_js(&p.value))
+                .map(|p| expr_to_js(&p.value))
                 .collect();
             self.writeln(&format!(
                 "const {} = {}(ctx, {});",
@@ -530,93 +961,28 @@ impl TemplateGenerator {
 
     fn generate_text(&mut self, text: &TextNode, _parent: Option<&str>) -> String {
         let var = self.next_var();
-
-        // Check if text contains ${...} interpolation patterns
-        let content = &text.content;
-
-        if content.contains("${") {
-            // Parse and handle variable interpolation
-            let generated = self.interpolate_variables(content);
-            self.writeln(&format!(
-                "const {} = document.createTextNode({});",
-                var, generated
-            ));
-        } else {
-            // Pure literal text - escape single quotes
-            let escaped = content.replace('\\', "\\\\").replace('\'', "\\'");
-            self.writeln(&format!(
-                "const {} = document.createTextNode('{}');",
-                var, escaped
-            ));
-        }
-
+        self.writeln(&format!(
+            "const {} = document.createTextNode({});",
+            var, text_to_js(&text.parts)
+        ));
         var
     }
 
-    fn interpolate_variables(&self, text: &str) -> String {
-        use regex::Regex;
-
-        // Regex to match ${ctx.foo.bar}, ${varname}, or ${item.property}
-        // Allows: ctx.path, identifier, or identifier.path
-        let re = Regex::new(r"\$\{([a-zA-Z_][a-zA-Z0-9_]*(?:\.[a-zA-Z0-9_.]+)?)\}").unwrap();
-
-        let mut parts = Vec::new();
-        let mut last_end = 0;
-
-        for cap in re.captures_iter(text) {
-            let full_match = cap.get(0).unwrap();
-            let var_path = cap.get(1).unwrap().as_str();
-
-            // Add literal text before this variable
-            if full_match.start() > last_end {
-                let literal = &text[last_end..full_match.start()];
-                let escaped = literal.replace('\\', "\\\\").replace('\'', "\\'");
-                parts.push(format!("'{}'", escaped));
-            }
-
-            // Add variable with null coalescing
-            parts.push(format!("String({} ?? 'null')", var_path));
-
-            last_end = full_match.end();
-        }
-
-        // Add remaining literal text
-        if last_end < text.len() {
-            let literal = &text[last_end..];
-            let escaped = literal.replace('\\', "\\\\").replace('\'', "\\'");
-            parts.push(format!("'{}'", escaped));
-        }
-
-        // Join with +
-        if parts.is_empty() {
-            // No variables matched, treat entire text as literal
-            let escaped = text.replace('\\', "\\\\").replace('\'', "\\'");
-            format!("'{}'", escaped)
-        } else if parts.len() == 1 {
-            parts[0].clone()
-        } else {
-            parts.join(" + ")
-        }
-    }
-
     fn generate_if(&mut self, stmt: &IfStatement, _parent: Option<&str>) -> String {
         let var = self.next_var();
-        let cond = self.expr_to_js(&stmt.condition);
+        let cond = expr_to_js(&stmt.condition);
 
         // Create a placeholder/container
         self.writeln(&format!("let {}: Node;", var));
         self.writeln(&format!("if ({}) {{", cond));
 
         self.indent += 1;
-        if stmt.consequent.len() == 1 {
-            let child_var = self.generate_node(&stmt.consequent[0], None);
+        if let Some(root) = single_root(&stmt.consequent) {
+            let child_var = self.generate_node(root, None);
             self.writeln(&format!("{} = {};", var, child_var));
         } else {
             self.writeln("const fragment = document.createDocumentFragment();");
-            for node in &stmt.consequent {
-                let child_var = self.generate_node(node, None);
-                self.writeln(&format!("fragment.appendChild({});", child_var));
-            }
+            self.append_children("fragment", &stmt.consequent);
             self.writeln(&format!("{} = fragment;", var));
         }
         self.indent -= 1;
@@ -625,33 +991,27 @@ impl TemplateGenerator {
             Some(Alternate::Block(nodes)) => {
                 self.writeln("} else {");
                 self.indent += 1;
-                if nodes.len() == 1 {
-                    let child_var = self.generate_node(&nodes[0], None);
+                if let Some(root) = single_root(nodes) {
+                    let child_var = self.generate_node(root, None);
                     self.writeln(&format!("{} = {};", var, child_var));
                 } else {
                     self.writeln("const fragment = document.createDocumentFragment();");
-                    for node in nodes {
-                        let child_var = self.generate_node(node, None);
-                        self.writeln(&format!("fragment.appendChild({});", child_var));
-                    }
+                    self.append_children("fragment", nodes);
                     self.writeln(&format!("{} = fragment;", var));
                 }
                 self.indent -= 1;
                 self.writeln("}");
             }
             Some(Alternate::ElseIf(elif)) => {
-                let elif_cond = self.expr_to_js(&elif.condition);
+                let elif_cond = expr_to_js(&elif.condition);
                 self.writeln(&format!("}} else if ({}) {{", elif_cond));
                 self.indent += 1;
-                if elif.consequent.len() == 1 {
-                    let child_var = self.generate_node(&elif.consequent[0], None);
+                if let Some(root) = single_root(&elif.consequent) {
+                    let child_var = self.generate_node(root, None);
                     self.writeln(&format!("{} = {};", var, child_var));
                 } else {
                     self.writeln("const fragment = document.createDocumentFragment();");
-                    for node in &elif.consequent {
-                        let child_var = self.generate_node(node, None);
-                        self.writeln(&format!("fragment.appendChild({});", child_var));
-                    }
+                    self.append_children("fragment", &elif.consequent);
                     self.writeln(&format!("{} = fragment;", var));
                 }
                 self.indent -= 1;
@@ -675,15 +1035,52 @@ impl TemplateGenerator {
 
     fn generate_each(&mut self, stmt: &EachStatement, _parent: Option<&str>) -> String {
         let var = self.next_var();
-        let iterable = self.expr_to_js(&stmt.iterable);
+        let iterable = expr_to_js(&stmt.iterable);
         let item = &stmt.item_name;
+        let helpers = utils::uses_loop_helpers(&stmt.body);
 
         self.writeln(&format!("const {} = document.createDocumentFragment();", var));
 
-        if let Some(index) = &stmt.index_name {
+        // `$even`/`$odd` need a position, so they force the indexed form
+        let index = stmt.index_name.as_deref().or(helpers.then_some("$index"));
+        // An index counted from another number is offset from the loop's position,
+        // which `$even`/`$odd` still follow
+        let base = stmt.index_base;
+        let range = match &stmt.iterable {
+            Expression::Range(range) if !stmt.entries => Some(range),
+            _ => None,
+        };
+        // A range counts from its start, so it has no array to test for emptiness
+        let in_range = range.map(|range| {
+            let bound = |expr: &Expression| match expr {
+                Expression::Number(n) => n.raw.clone(),
+                expr => format!("({} as number)", expr_to_js(expr)),
+            };
+            let test = if range.inclusive { "<=" } else { "<" };
+            (bound(&range.start), test, bound(&range.end))
+        });
+
+        if let Some((start, test, end)) = &in_range {
+            match index {
+                Some(index) => self.writeln(&format!(
+                    "for (let {} = {}, {} = {}; {} {} {}; {}++, {}++) {{",
+                    item, start, index, base, item, test, end, item, index
+                )),
+                None => self.writeln(&format!("for (let {} = {}; {} {} {}; {}++) {{", item, start, item, test, end, item)),
+            }
+        } else if stmt.entries {
+            let entries = format!("Object.entries({} as Record<string, unknown>)", iterable);
+            let key = stmt.index_name.as_deref().unwrap_or("");
+            if helpers {
+                self.writeln(&format!("{}.forEach(([{}, {}], $index) => {{", entries, key, item));
+            } else {
+                self.writeln(&format!("{}.forEach(([{}, {}]) => {{", entries, key, item));
+            }
+        } else if let Some(index) = index {
+            let position = if base != 0 { "$index" } else { index };
             self.writeln(&format!(
                 "({} as unknown[]).forEach(({}, {}) => {{",
-                iterable, item, index
+                iterable, item, position
             ));
         } else {
             self.writeln(&format!(
@@ -693,33 +1090,56 @@ impl TemplateGenerator {
         }
 
         self.indent += 1;
-        for node in &stmt.body {
-            let child_var = self.generate_node(node, None);
-            self.writeln(&format!("{}.appendChild({});", var, child_var));
+        let position = match (index, base) {
+            (Some(index), base) if base != 0 && in_range.is_some() => Some(format!("({} - {})", index, base)),
+            (Some(index), base) if base != 0 => {
+                self.writeln(&format!("const {} = $index + {};", index, base));
+                Some("$index".to_string())
+            }
+            _ if stmt.entries => Some("$index".to_string()),
+            (index, _) => index.map(str::to_string),
+        };
+        if let Some(index) = position.filter(|_| helpers) {
+            self.writeln(&format!("const $even = {} % 2 === 0;", index));
+            self.writeln("const $odd = !$even;");
         }
+        self.append_children(&var, &stmt.body);
         self.indent -= 1;
 
-        if stmt.index_name.is_some() {
+        if in_range.is_none() && (index.is_some() || stmt.entries) {
             self.writeln("});");
         } else {
             self.writeln("}");
         }
 
+        if let Some(empty) = &stmt.empty {
+            if let Some((start, test, end)) = &in_range {
+                self.writeln(&format!("if (!({} {} {})) {{", start, test, end));
+            } else if stmt.entries {
+                self.writeln(&format!("if (Object.keys({} as Record<string, unknown>).length === 0) {{", iterable));
+            } else {
+                self.writeln(&format!("if (({} as unknown[]).length === 0) {{", iterable));
+            }
+            self.indent += 1;
+            self.append_children(&var, empty);
+            self.indent -= 1;
+            self.writeln("}");
+        }
+
         var
     }
 
     fn generate_list_function(&mut self, name: &str, el: &Element, directive: &ForDirective) {
         self.reset_vars();
 
-        let iterable = self.expr_to_js(&directive.iterable);
+        let iterable = expr_to_js(&directive.iterable);
         let item = &directive.item_name;
 
         // Generate function signature
         self.writeln(&format!(
-            "export function {}(ctx: {{ {}: any[] }}): HTMLElement[] {{",
+            "export function {}(ctx: {}): HTMLElement[] {{",
             name,
-            // Extract the array property name from the iterable expression
-            strip_ctx(&iterable)
+            list_context_type(&directive.iterable)
         ));
         self.indent += 1;
 
@@ -740,24 +1160,18 @@ impl TemplateGenerator {
 
         // Create the element for each item
         let var = self.next_var();
-        self.writeln(&format!(
-            "const {} = document.createElement('{}');",
-            var, el.tag
-        ));
+        self.create_element(&var, el);
 
         // Don't set ID or attributes that should be unique per item
         // Only set class and other repeatable attributes
         for attr in &el.attributes {
             if attr.name != "id" {  // Skip id attribute for list items
-                self.generate_attribute(&var, attr);
+                self.generate_attribute(el, &var, attr);
             }
         }
 
         // Add children
-        for child in &el.children {
-            let child_var = self.generate_node(child, Some(&var));
-            self.writeln(&format!("{}.appendChild({});", var, child_var));
-        }
+        self.append_element_children(&var, el);
 
         // Return the element
         self.writeln(&format!("return {};", var));
@@ -772,7 +1186,7 @@ impl TemplateGenerator {
     fn generate_conditional_function(&mut self, name: &str, el: &Element, directive: &IfDirective) {
         self.reset_vars();
 
-        let condition = self.expr_to_js(&directive.condition);
+        let condition = expr_to_js(&directive.condition);
 
         // Generate function signature
         self.writeln(&format!(
@@ -787,21 +1201,15 @@ impl TemplateGenerator {
 
         // Create the element
         let var = self.next_var();
-        self.writeln(&format!(
-            "const {} = document.createElement('{}');",
-            var, el.tag
-        ));
+        self.create_element(&var, el);
 
         // Set attributes
         for attr in &el.attributes {
-            self.generate_attribute(&var, attr);
+            self.generate_attribute(el, &var, attr);
         }
 
         // Add children
-        for child in &el.children {
-            let child_var = self.generate_node(child, Some(&var));
-            self.writeln(&format!("{}.appendChild({});", var, child_var));
-        }
+        self.append_element_children(&var, el);
 
         // Return the element
         self.writeln(&format!("return {};", var));
@@ -820,56 +1228,22 @@ impl TemplateGenerator {
     // Expression to JS
     // =========================================================================
 
-    fn expr_to_js(&self, expr: &Expression) -> String {
-        match expr {
-            Expression::String(s) => format!("'{}'", s.value.replace('\'', "\\'")),
-            Expression::Number(n) => format!("{}", n.value),
-            Expression::Boolean(b) => format!("{}", b.value),
-            Expression::ContextPath(p) => format!("ctx.{}", strip_ctx(&p.path)),
-            Expression::Identifier(id) => id.name.clone(),
-            Expression::MemberAccess(m) => {
-                let obj = self.expr_to_js(&m.object);
-                format!("{}.{}", obj, m.property)
-            }
-            Expression::Binary(b) => {
-                let left = self.expr_to_js(&b.left);
-                let right = self.expr_to_js(&b.right);
-                let op = match b.operator {
-                    BinaryOp::Eq => "===",
-                    BinaryOp::Ne => "!==",
-                    BinaryOp::Lt => "<",
-                    BinaryOp::Le => "<=",
-                    BinaryOp::Gt => ">",
-                    BinaryOp::Ge => ">=",
-                    BinaryOp::And => "&&",
-                    BinaryOp::Or => "||",
-                    BinaryOp::Add => "+",
-                    BinaryOp::Sub => "-",
-                    BinaryOp::Mul => "*",
-                    BinaryOp::Div => "/",
-                };
-                format!("({} {} {})", left, op, right)
-            }
-            Expression::Ternary(t) => {
-                let cond = self.expr_to_js(&t.condition);
-                let cons = self.expr_to_js(&t.consequent);
-                let alt = self.expr_to_js(&t.alternate);
-                format!("({} ? {} : {})", cond, cons, alt)
-            }
-            Expression::Call(c) => {
-                let args: Vec<String> = c.arguments.iter()
-                    .map(|a| self.expr_to_js(a))
-                    .collect();
-                format!("{}({})", c.callee, args.join(", "))
-            }
-            Expression::Event(_) => String::new(),
-        }
-    }
-
     // =========================================================================
     // Helpers
     // =========================================================================
 
+    /// Write a `// from Name (file:line:col)` comment pointing back to the source
+    fn write_source_comment(&mut self, name: &str, loc: Location) {
+        if !self.source_comments {
+            return;
+        }
+        let origin = match &self.source_filename {
+            Some(file) => format!("{}:{}:{}", file, loc.line, loc.column),
+            None => format!("{}:{}", loc.line, loc.column),
+        };
+        self.writeln(&format!("// from {} ({})", name, origin));
+    }
+
     fn writeln(&mut self, s: &str) {
         if !s.is_empty() {
             for _ in 0..self.indent {
@@ -881,11 +1255,164 @@ impl TemplateGenerator {
     }
 }
 
-fn strip_ctx(path: &str) -> &str {
-    path.strip_prefix("ctx.").unwrap_or(path)
+/// The string a text node shows: literal runs joined with each expression,
+/// which reads as `null` when it is null or undefined
+///
+/// Expressions over literals are written out as their value.
+fn text_to_js(parts: &[TextPart]) -> String {
+    let mut pieces = Vec::new();
+    let mut literal = String::new();
+    for part in parts {
+        match part {
+            TextPart::Static(text) => literal.push_str(text),
+            TextPart::Dynamic(expr) => match eval_const(expr) {
+                Some(value) => literal.push_str(&value.to_string()),
+                None => {
+                    if !literal.is_empty() {
+                        pieces.push(format!("'{}'", escape_js_string(&std::mem::take(&mut literal))));
+                    }
+                    pieces.push(format!("String({} ?? 'null')", expr_to_js(expr)));
+                }
+            },
+        }
+    }
+    if !literal.is_empty() || pieces.is_empty() {
+        pieces.push(format!("'{}'", escape_js_string(&literal)));
+    }
+    pieces.join(" + ")
+}
+
+/// Translate an expression to the TypeScript that evaluates it at runtime
+pub fn expr_to_js(expr: &Expression) -> String {
+    match expr {
+        Expression::String(s) => format!("'{}'", escape_js_string(&s.value)),
+        Expression::Number(n) => n.raw.clone(),
+        Expression::Boolean(b) => format!("{}", b.value),
+        Expression::Null(_) => "null".to_string(),
+        Expression::Array(a) => {
+            let elements: Vec<String> = a.elements.iter()
+                .map(expr_to_js)
+                .collect();
+            format!("[{}]", elements.join(", "))
+        }
+        Expression::Object(o) => {
+            let properties: Vec<String> = o.properties.iter()
+                .map(|p| format!("{}: {}", property_key(&p.key), expr_to_js(&p.value)))
+                .collect();
+            format!("{{ {} }}", properties.join(", "))
+        }
+        Expression::ContextPath(p) => p.path(),
+        Expression::Identifier(id) => id.name.clone(),
+        Expression::MemberAccess(m) => {
+            let obj = expr_to_js(&m.object);
+            format!("{}.{}", obj, m.property)
+        }
+        Expression::OptionalMember(m) => {
+            let obj = expr_to_js(&m.object);
+            format!("{}?.{}", obj, m.property)
+        }
+        Expression::Binary(b) => {
+            // Operators over literals fold to the value they produce
+            match eval_const(expr) {
+                Some(ConstValue::String(s)) => return format!("'{}'", escape_js_string(&s)),
+                Some(value) => return value.to_string(),
+                None => {}
+            }
+            let left = expr_to_js(&b.left);
+            let right = expr_to_js(&b.right);
+            let op = match b.operator {
+                BinaryOp::Eq => "===",
+                BinaryOp::Ne => "!==",
+                BinaryOp::Lt => "<",
+                BinaryOp::Le => "<=",
+                BinaryOp::Gt => ">",
+                BinaryOp::Ge => ">=",
+                BinaryOp::And => "&&",
+                BinaryOp::Or => "||",
+                BinaryOp::NullishCoalesce => "??",
+                BinaryOp::Add => "+",
+                BinaryOp::Sub => "-",
+                BinaryOp::Mul => "*",
+                BinaryOp::Div => "/",
+                BinaryOp::Mod => "%",
+            };
+            format!("({} {} {})", left, op, right)
+        }
+        Expression::Ternary(t) => {
+            let cond = expr_to_js(&t.condition);
+            let cons = expr_to_js(&t.consequent);
+            let alt = expr_to_js(&t.alternate);
+            format!("({} ? {} : {})", cond, cons, alt)
+        }
+        Expression::Call(c) => {
+            let args: Vec<String> = c.arguments.iter()
+                .map(expr_to_js)
+                .collect();
+            format!("{}({})", c.callee, args.join(", "))
+        }
+        Expression::Event(_) => String::new(),
+        Expression::Spread(s) => expr_to_js(&s.expr),
+        // `@each` counts through ranges itself; anywhere else they become the array
+        Expression::Range(r) => {
+            let (start, end) = (expr_to_js(&r.start), expr_to_js(&r.end));
+            let extra = if r.inclusive { " + 1" } else { "" };
+            format!(
+                "Array.from({{ length: Math.max(0, Math.ceil({} - {}){}) }}, (_, i) => {} + i)",
+                end, start, extra, start
+            )
+        }
+    }
+}
+
+/// Whether an attribute value can never be `false`, `null` or `undefined`,
+/// so it's set without a presence check
+fn is_never_omitted(expr: &Expression) -> bool {
+    match expr {
+        Expression::String(_) | Expression::Number(_) | Expression::Array(_) | Expression::Object(_) => true,
+        // Arithmetic and concatenation give a number or a string
+        Expression::Binary(b) => matches!(
+            b.operator,
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod
+        ),
+        Expression::Ternary(t) => is_never_omitted(&t.consequent) && is_never_omitted(&t.alternate),
+        _ => false,
+    }
 }
 
-fn capitalize(s: &str) -> String {
+/// Escape text for a single-quoted JS string literal
+///
+/// `</` becomes `<\/`, so a literal like `'</script>'` can't end the inline
+/// script a single-file bundle puts it in.
+fn escape_js_string(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('\'', "\\'")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace("</", "<\\/")
+}
+
+/// The node to use directly as a block's root, when it has exactly one.
+/// Raw HTML builds a fragment rather than an element, so it never qualifies.
+fn single_root(nodes: &[Node]) -> Option<&Node> {
+    match nodes {
+        [node] if !matches!(node, Node::Let(_) | Node::RawHtml(_)) => Some(node),
+        _ => None,
+    }
+}
+
+/// Context type for a list function: `{ user: { items: any[] } }` for `ctx.user.items`
+fn list_context_type(iterable: &Expression) -> String {
+    match iterable {
+        Expression::ContextPath(path) if path.root == PathRoot::Ctx && !path.segments.is_empty() => {
+            path.segments.iter().rev().fold("any[]".to_string(), |inner, segment| {
+                format!("{{ {}: {} }}", segment, inner)
+            })
+        }
+        _ => "Context".to_string(),
+    }
+}
+
+pub(super) fn capitalize(s: &str) -> String {
     let mut chars = s.chars();
     match chars.next() {
         None => String::new(),
@@ -901,13 +1428,92 @@ mod tests {
     use crate::analyzer::analyze;
 
     fn generate_templates(source: &str) -> String {
+        generate_templates_with_options(source, &CompileOptions::default())
+    }
+
+    fn generate_templates_with_options(source: &str, options: &CompileOptions) -> String {
         let tokens = tokenize(source).unwrap();
         let ast = parse(&tokens).unwrap();
         let (symbols, _) = analyze(&ast);
-        let file = generate(&ast, &symbols);
+        let file = generate(&ast, &symbols, options);
         file.content
     }
 
+    #[test]
+    fn test_cache_reuses_unchanged_declarations() {
+        let ast = parse(&tokenize(r#"
+            component Logo { img [src: "/logo.png", alt: ""] }
+            component Badge { span {{ New }} }
+        "#).unwrap()).unwrap();
+        let (symbols, _) = analyze(&ast);
+        let options = CompileOptions::default();
+        let mut cache = TemplateCache {
+            fingerprints: HashMap::from([("Logo".to_string(), 1), ("Badge".to_string(), 1)]),
+            ..TemplateCache::default()
+        };
+        let first = generate_cached(&ast, &symbols, &options, Some(&mut cache)).content;
+        assert_eq!(first, generate(&ast, &symbols, &options).content);
+
+        for code in cache.entries.values_mut() {
+            code.function = code.function.replace("'/logo.png'", "'/cached.png'").replace("'New'", "'Cached'");
+        }
+        cache.fingerprints.insert("Badge".to_string(), 2);
+        let second = generate_cached(&ast, &symbols, &options, Some(&mut cache)).content;
+        assert!(second.contains("'/cached.png'"), "{}", second);
+        assert!(second.contains("'New'"));
+        assert_eq!(cache.entries.len(), 2);
+    }
+
+    #[test]
+    fn test_svg_elements_use_svg_namespace() {
+        let output = generate_templates(r#"
+            component Dot { circle [r: 4, class: "dot"] }
+            page home "/" {
+                svg [viewBox: "0 0 8 8"] {
+                    a [href: "/x"] { Dot }
+                    foreignObject { p [class: "note", title: "t"] {{ Hi }} }
+                }
+                a [href: "/y"] {{ Out }}
+            }
+        "#);
+        let svg = "document.createElementNS('http://www.w3.org/2000/svg',";
+        assert!(output.contains(&format!("const el0 = {} 'svg') as Element as HTMLElement;", svg)), "{}", output);
+        assert!(output.contains(&format!("{} 'a')", svg)));
+        assert!(output.contains("el1.setAttribute('href', '/x');"));
+        // SVG-only tags are SVG anywhere, such as a component root
+        assert!(output.contains(&format!("{} 'circle')", svg)));
+        assert!(output.contains("el0.setAttribute('class', 'dot');"));
+        // foreignObject switches back to HTML
+        assert!(output.contains("document.createElement('p');"));
+        assert!(output.contains(".className = 'note';"));
+        assert!(output.contains(".title = 't';"));
+        assert!(output.contains(".href = '/y';"));
+    }
+
+    #[test]
+    fn test_raw_html_sets_inner_html() {
+        let output = generate_templates(r#"
+            page home "/" {
+                article { @html ctx.body }
+            }
+        "#);
+        assert!(output.contains("const el1 = document.createElement('template');"), "{}", output);
+        assert!(output.contains("el1.innerHTML = String(ctx.body ?? '');"));
+        assert!(output.contains("el0.appendChild(el1.content);"));
+    }
+
+    #[test]
+    fn test_raw_html_root_returns_element() {
+        let output = generate_templates(r#"
+            component Markup {
+                @html ctx.body
+            }
+        "#);
+        assert!(!output.contains("return el0.content;"), "{}", output);
+        assert!(output.contains("fragment.appendChild(el0.content);"), "{}", output);
+        assert!(output.contains("return root;"));
+    }
+
     #[test]
     fn test_simple_component() {
         let output = generate_templates(r#"
@@ -973,6 +1579,88 @@ mod tests {
         assert!(output.contains(r"createTextNode('She said \'hello\'')"));
     }
 
+    #[test]
+    fn test_attribute_quotes_are_escaped() {
+        let output = generate_templates(r#"
+            component Quote {
+                a [class: "it's", href: "/a'b", data-x: "c'd"] {{ Go }}
+            }
+        "#);
+
+        assert!(output.contains(r".className = 'it\'s';"));
+        assert!(output.contains(r".href = '/a\'b';"));
+        assert!(output.contains(r".setAttribute('data-x', 'c\'d');"));
+    }
+
+    #[test]
+    fn test_class_list_joins_truthy_entries() {
+        let output = generate_templates(r#"
+            component Tab {
+                button [class: ["tab", ctx.active ? "on" : null]] {{ Tab }}
+            }
+        "#);
+
+        assert!(output.contains(".className = ['tab', (ctx.active ? 'on' : null)].filter(Boolean).join(' ');"));
+    }
+
+    #[test]
+    fn test_shorthand_joins_computed_class_as_list() {
+        let output = generate_templates(r#"
+            component Tab {
+                div.tab [class: ctx.active ? "on" : null] { }
+                span.x [class: ["a", ctx.b]] { }
+            }
+        "#);
+
+        assert!(output.contains(".className = ['tab', (ctx.active ? 'on' : null)].filter(Boolean).join(' ');"), "{}", output);
+        assert!(output.contains(".className = ['x', 'a', ctx.b].filter(Boolean).join(' ');"));
+    }
+
+    #[test]
+    fn test_comment_node() {
+        let output = generate_templates(r#"
+            component Note {
+                div { @comment "it's -- here-" }
+            }
+        "#);
+
+        assert!(output.contains(r"document.createComment(' it\'s - - here- ');"), "{}", output);
+    }
+
+    #[test]
+    fn test_conditional_attributes() {
+        let output = generate_templates(r#"
+            component Toggle {
+                button [disabled: ctx.loading, class: ctx.active ? "on" : "off"] {{ Go }}
+                a [class: ctx.active ? "on" : null] {{ Link }}
+            }
+        "#);
+
+        assert!(output.contains("const value = ctx.loading;"));
+        assert!(output.contains("if (value != null && value !== false) el0.setAttribute('disabled', value === true ? '' : String(value));"));
+        // Both branches are strings, so the class is always set
+        assert!(output.contains("el0.className = ctx.active ? 'on' : 'off';"));
+        assert!(output.contains("const value = (ctx.active ? 'on' : null);"));
+        assert!(output.contains("if (value != null && value !== false) el2.className = String(value);"));
+    }
+
+    #[test]
+    fn test_style_object() {
+        let output = generate_templates(r#"
+            component Box {
+                div [style: { color: "red", fontSize: 14 }]
+                div [style: { color: ctx.color, marginTop: 4, "--it's": ctx.x }]
+            }
+        "#);
+
+        assert!(output.contains(".setAttribute('style', 'color:red;font-size:14px');"));
+        assert!(output.contains("const value = ctx.color;\n"), "{}", output);
+        // `false` drops the entry, as in static HTML, instead of rendering "false"
+        assert!(output.contains(".style.setProperty('color', value == null || typeof value === 'boolean' ? '' : String(value));"));
+        assert!(output.contains(".style.setProperty('margin-top', '4px');"));
+        assert!(output.contains(".style.setProperty('--it\\'s', "), "{}", output);
+    }
+
     #[test]
     fn test_literal_variable_name() {
         let output = generate_templates(r#"
@@ -1007,6 +1695,19 @@ mod tests {
         assert!(output.contains("createTextNode('Use var(${--color}) for CSS')"));
     }
 
+    #[test]
+    fn test_escaped_interpolation_is_literal() {
+        let output = generate_templates(r#"
+            component Price {
+                p {{ cost is \${x} }}
+                p {{ \${x} is ${ctx.x} }}
+            }
+        "#);
+
+        assert!(output.contains("createTextNode('cost is ${x}')"));
+        assert!(output.contains("createTextNode('${x} is ' + String(ctx.x ?? 'null'))"));
+    }
+
     #[test]
     fn test_each_statement() {
         let output = generate_templates(r#"
@@ -1022,4 +1723,314 @@ mod tests {
         assert!(output.contains("createDocumentFragment"));
         assert!(output.contains("for (const item of"));
     }
+
+    #[test]
+    fn test_list_function_context_type() {
+        let output = generate_templates("component A { ul { li @for(ctx.user.items as t) {{ ${t} }} li @for(ctx.rows as r) {{ x }} } }");
+        assert!(output.contains("export function LiList0(ctx: { user: { items: any[] } }): HTMLElement[] {"), "{}", output);
+        assert!(output.contains("export function LiList1(ctx: { rows: any[] }): HTMLElement[] {"), "{}", output);
+    }
+
+    #[test]
+    fn test_each_else_block() {
+        let output = generate_templates(r#"
+            component List {
+                @each ctx.items as item { li { item.name } } @else { p {{ No items }} }
+            }
+        "#);
+
+        assert!(output.contains("if ((ctx.items as unknown[]).length === 0) {"));
+        assert!(output.contains("createTextNode('No items')"));
+    }
+
+    #[test]
+    fn test_source_comments() {
+        let options = CompileOptions {
+            source_comments: true,
+            source_filename: Some("app.htms".to_string()),
+            ..Default::default()
+        };
+        let output = generate_templates_with_options(
+            "component Spacer { }\n\ncomponent NavBar {\n  nav { }\n}",
+            &options,
+        );
+
+        assert!(output.contains("// from NavBar (app.htms:3:1)\nexport function NavBar"));
+        assert!(!generate_templates("component NavBar { }").contains("// from"));
+    }
+
+    #[test]
+    fn test_spread_attribute() {
+        let output = generate_templates(r#"
+            component Action {
+                button [...ctx.buttonProps, class: "x"] {{ Go }}
+            }
+        "#);
+
+        let spread = output.find("Object.entries(ctx.buttonProps ?? {})").unwrap();
+        let class = output.find(".className = 'x'").unwrap();
+        assert!(spread < class, "explicit attributes must be applied after the spread");
+    }
+
+    #[test]
+    fn test_pre_preserves_whitespace() {
+        let output = generate_templates("component Code { pre {{\n  let x = 1;\n}} }");
+        assert!(output.contains("createTextNode('\\n  let x = 1;\\n')"));
+    }
+
+    #[test]
+    fn test_whitespace_markers() {
+        let output = generate_templates("component Name { span {{ ${ctx.first} }}{{-  ${ctx.last} }} }");
+        // Both sides of the seam keep their space instead of collapsing to one
+        assert!(output.contains("createTextNode(String(ctx.first ?? 'null') + '  ' + String(ctx.last ?? 'null'))"));
+    }
+
+    #[test]
+    fn test_two_way_binding() {
+        let output = generate_templates(r#"
+            component Signup {
+                input [bind: ctx.form.email]
+                input [type: "checkbox", bind: ctx.form.agree]
+            }
+        "#);
+
+        assert!(output.contains("import { setNestedValue } from './events';"));
+        assert!(output.contains(".value = String(ctx.form?.email ?? '');"));
+        assert!(output.contains("setNestedValue(getContext(), 'form.email', (e.target as HTMLInputElement).value);"));
+        assert!(output.contains(".checked = Boolean(ctx.form?.agree);"));
+        assert!(output.contains(".addEventListener('change', (e) => {"));
+    }
+
+    #[test]
+    fn test_radio_binding_stores_the_chosen_value() {
+        let output = generate_templates(r#"
+            component Plan {
+                input [type: "radio", bind: ctx.plan, value: "basic"]
+                input [type: "radio", bind: ctx.plan]
+            }
+        "#);
+
+        assert!(output.contains("el0.checked = ctx.plan === 'basic';"), "{}", output);
+        assert!(output.contains("el1.checked = ctx.plan === el1.value;"), "{}", output);
+        assert_eq!(output.matches("setNestedValue(getContext(), 'plan', (e.target as HTMLInputElement).value);").count(), 2);
+        assert!(!output.contains("Boolean(ctx.plan)"));
+    }
+
+    #[test]
+    fn test_binding_helper_without_events_module() {
+        let options = CompileOptions { generate_events: false, ..CompileOptions::default() };
+        let output = generate_templates_with_options(r#"component Signup { input [bind: ctx.form.email] }"#, &options);
+
+        assert!(!output.contains("from './events'"), "{}", output);
+        assert!(output.contains("\nfunction setNestedValue(obj: Record<string, unknown>, path: string, value: unknown): void {\n"));
+        assert!(output.contains("setNestedValue(getContext(), 'form.email', (e.target as HTMLInputElement).value);"));
+    }
+
+    #[test]
+    fn test_let_binding() {
+        let output = generate_templates(r#"
+            component Greeting {
+                div {
+                    @let full = ctx.user.first + " " + ctx.user.last
+                    p {{ Hello ${full} }}
+                }
+            }
+        "#);
+
+        let binding = output.find("const full = ((ctx.user.first + ' ') + ctx.user.last);").unwrap();
+        let usage = output.find("String(full ?? 'null')").unwrap();
+        assert!(binding < usage);
+        assert!(!output.contains("appendChild(full)"));
+    }
+
+    #[test]
+    fn test_optional_chaining_and_nullish() {
+        let output = generate_templates(r#"
+            component Greeting {
+                @if ctx.user?.active { a [title: ctx.user?.name ?? "Guest"] {{ Hi ${ctx.user?.name} }} }
+            }
+        "#);
+
+        assert!(output.contains("if (ctx.user?.active)"));
+        assert!(output.contains("(ctx.user?.name ?? 'Guest')"));
+        assert!(output.contains("String(ctx.user?.name ?? 'null')"));
+    }
+
+    #[test]
+    fn test_logical_operators_short_circuit() {
+        let output = generate_templates(r#"
+            component Admin {
+                @if ctx.user && isAdmin(ctx.user) { p {{ Admin }} }
+                @if ctx.cached || load(ctx.id) { p {{ Ready }} }
+                @if true && ctx.open { p {{ Open }} }
+            }
+        "#);
+
+        // Native operators, so the call only runs when the left side doesn't settle the result
+        assert!(output.contains("if ((ctx.user && isAdmin(ctx.user))) {"), "{}", output);
+        assert!(output.contains("if ((ctx.cached || load(ctx.id))) {"));
+        assert!(output.contains("if ((true && ctx.open)) {"));
+    }
+
+    #[test]
+    fn test_literal_concatenation_folds() {
+        let output = generate_templates(r#"
+            component Greeting {
+                p [class: "btn" + "-primary", title: ctx.name + "!"] {{ ${"Hello, " + "world"} x${2 * 3} ${ctx.name} }}
+            }
+        "#);
+
+        assert!(output.contains("el0.className = 'btn-primary';"), "{}", output);
+        assert!(output.contains("(ctx.name + '!')"));
+        assert!(output.contains("document.createTextNode('Hello, world x6 ' + String(ctx.name ?? 'null'))"));
+    }
+
+    #[test]
+    fn test_text_expressions_in_order() {
+        let output = generate_templates(r#"
+            component Counter {
+                p {{ ${ctx.count + 1} of ${ctx.done ? "done" : "left"} }}
+                p { ctx.user.name }
+            }
+        "#);
+
+        assert!(output.contains("createTextNode(String((ctx.count + 1) ?? 'null') + ' of ' + String((ctx.done ? 'done' : 'left') ?? 'null'))"), "{}", output);
+        assert!(output.contains("createTextNode(String(ctx.user.name ?? 'null'))"));
+    }
+
+    #[test]
+    fn test_each_over_entries() {
+        let output = generate_templates(r#"
+            component Scores {
+                @each ctx.scores as score, name in entries { li {{ ${name}: ${score} }} } @else { p {{ None }} }
+            }
+        "#);
+
+        assert!(output.contains("Object.entries(ctx.scores as Record<string, unknown>).forEach(([name, score]) => {"));
+        assert!(output.contains("if (Object.keys(ctx.scores as Record<string, unknown>).length === 0) {"));
+    }
+
+    #[test]
+    fn test_each_over_range_counts() {
+        let output = generate_templates(r#"
+            component Pages {
+                @each 1..5 as i { li {{ ${i} }} }
+                @each 0..<ctx.count as n, k { li {{ ${k} }} } @else { p {{ None }} }
+            }
+        "#);
+
+        assert!(output.contains("for (let i = 1; i <= 5; i++) {"), "{}", output);
+        assert!(output.contains("for (let n = 0, k = 0; n < (ctx.count as number); n++, k++) {"));
+        assert!(output.contains("if (!(0 < (ctx.count as number))) {"));
+    }
+
+    #[test]
+    fn test_each_index_base() {
+        let output = generate_templates(r#"
+            component Steps {
+                @each ctx.steps as step, n from 1 { li [class: $even ? "even" : "odd"] {{ ${n}. ${step} }} }
+                @each 0..<3 as i, k from 1 { p {{ ${k} }} }
+            }
+        "#);
+
+        assert!(output.contains("forEach((step, $index) => {"), "{}", output);
+        assert!(output.contains("const n = $index + 1;"));
+        assert!(output.contains("const $even = $index % 2 === 0;"));
+        assert!(output.contains("for (let i = 0, k = 1; i < 3; i++, k++) {"));
+    }
+
+    #[test]
+    fn test_loop_striping_helpers() {
+        let output = generate_templates(r#"
+            component Table {
+                @each ctx.rows as row {
+                    tr [class: $even ? "row-even" : "row-odd"] { }
+                }
+            }
+        "#);
+
+        assert!(output.contains("forEach((row, $index) => {"));
+        assert!(output.contains("const $even = $index % 2 === 0;"));
+        assert!(output.contains(".className = $even ? 'row-even' : 'row-odd';"));
+
+        // Loops that don't use the helpers keep the plain form
+        let plain = generate_templates("component List { @each ctx.rows as row { tr { } } }");
+        assert!(plain.contains("for (const row of"));
+        assert!(!plain.contains("$even"));
+    }
+
+    #[test]
+    fn test_component_scoped_actions() {
+        let output = generate_templates(r#"
+            component NavBar { button [onClick: submit] {{ Go }} }
+            component Footer { button [onClick: submit] {{ Send }} }
+            page home "/" { button [onClick: submit] {{ Save }} }
+        "#);
+
+        assert!(output.contains("const actionFn = actionTable.NavBar__submit ?? actionTable.submit;"));
+        assert!(output.contains("const actionFn = actionTable.Footer__submit ?? actionTable.submit;"));
+        // Pages aren't reusable, so they keep the global lookup
+        assert!(output.contains("const actionFn = actions.submit;"));
+    }
+
+    #[test]
+    fn test_section_parameters() {
+        let output = generate_templates(r#"
+            section Hero(title: heading) {
+                h1 {{ ${heading} }}
+            }
+            page home "/" { Hero(title: "Welcome") }
+        "#);
+
+        assert!(output.contains("export function Hero(ctx: Context, heading: unknown): HTMLElement {"));
+        assert!(output.contains("String(heading ?? 'null')"));
+        assert!(output.contains("= Hero(ctx, 'Welcome');"));
+    }
+
+    #[test]
+    fn test_section_slots() {
+        let output = generate_templates(r#"
+            section Hero(title: heading) { header { h1 {{ ${heading} }} @slot } }
+            page home "/" { Hero(title: "Welcome") { p {{ Intro }} } }
+        "#);
+
+        assert!(output.contains("export function Hero(ctx: Context, heading: unknown, slots: Slots = {}): HTMLElement {"), "{}", output);
+        assert!(output.contains("= Hero(ctx, 'Welcome', {"));
+    }
+
+    #[test]
+    fn test_named_slots() {
+        let output = generate_templates(r#"
+            component Card(title: heading) {
+                header { @slot(header) }
+                @slot
+            }
+            page home "/" {
+                Card {
+                    header: { h1 {{ Title }} }
+                    p {{ Body }}
+                }
+            }
+        "#);
+
+        assert!(output.contains("export type Slots = Record<string, () => Node>;"));
+        assert!(output.contains("export function Card(ctx: Context, heading: unknown, slots: Slots = {}): HTMLElement {"));
+        assert!(output.contains("slots.header?.() ?? document.createComment('slot');"));
+        assert!(output.contains("slots.default?.() ?? document.createComment('slot');"));
+        assert!(output.contains("= Card(ctx, undefined, {"));
+        let default = output.find("default: () => {").unwrap();
+        let header = output.find("header: () => {").unwrap();
+        assert!(default < header);
+    }
+
+    #[test]
+    fn test_page_renders_inside_layout() {
+        let output = generate_templates(r#"
+            layout Main { main { @slot } }
+            page home "/" uses Main { p {{ Home }} }
+        "#);
+
+        assert!(output.contains("export function Main(ctx: Context, slots: Slots = {}): HTMLElement {"));
+        assert!(output.contains("export function HomePage(ctx: Context, container: HTMLElement): void {\n  const el0 = Main(ctx, {\n    default: () => {"), "{}", output);
+    }
 }
@@ -0,0 +1,318 @@
+//! Context type generator
+//!
+//! Infers the shape of `ctx` from the paths each declaration reads and emits
+//! one TypeScript interface per component, section, layout and page. Leaf types are
+//! `unknown`; only the structure is inferred. A `Handlers` type lists the
+//! actions the program's event attributes refer to.
+
+use std::collections::BTreeMap;
+
+use crate::ast::*;
+use crate::GeneratedFile;
+use super::templates::capitalize;
+use super::utils::analyze_events;
+
+/// Generate context.d.ts
+pub fn generate(program: &Program) -> GeneratedFile {
+    let mut output = String::new();
+    output.push_str("// Generated by HTMS Compiler\n");
+    output.push_str("// Do not edit manually\n");
+
+    let actions = analyze_events(program).actions;
+    if !actions.is_empty() {
+        output.push_str("\nimport type { ActionContext } from './events';\n");
+    }
+
+    for decl in &program.body {
+        let (name, body) = match decl {
+            Declaration::Component(c) => (c.name.clone(), &c.body),
+            Declaration::Section(s) => (s.name.clone(), &s.body),
+            Declaration::Layout(l) => (l.name.clone(), &l.body),
+            Declaration::Page(p) => (format!("{}Page", capitalize(&p.name)), &p.body),
+        };
+
+        let mut shape = Fields::new();
+        collect_nodes(body, &mut shape);
+        // A page's context also feeds the layout it renders in
+        if let Declaration::Page(PageDecl { layout: Some(layout), .. }) = decl {
+            if let Some(layout) = find_layout(program, layout) {
+                collect_nodes(&layout.body, &mut shape);
+            }
+        }
+
+        output.push('\n');
+        if shape.is_empty() {
+            output.push_str(&format!("export interface {}Context {{}}\n", name));
+        } else {
+            output.push_str(&format!("export interface {}Context {{\n", name));
+            write_fields(&shape, 1, &mut output);
+            output.push_str("}\n");
+        }
+    }
+
+    if !actions.is_empty() {
+        output.push_str("\nexport type Handlers = {\n");
+        for action in &actions {
+            output.push_str(&format!(
+                "  {}: (ctx: ActionContext, event: Event) => void;\n",
+                property_key(action)
+            ));
+        }
+        output.push_str("};\n");
+    }
+
+    GeneratedFile {
+        path: "context.d.ts".to_string(),
+        content: output,
+    }
+}
+
+type Fields = BTreeMap<String, Shape>;
+
+/// Inferred type of one context property
+#[derive(Debug)]
+enum Shape {
+    Unknown,
+    Object(Fields),
+    /// Iterated by `@each` or `@for`
+    Array,
+    /// Iterated by `@each ... in entries`
+    Map,
+}
+
+/// Record a read of `ctx.a.b.c`, giving the last segment the shape it's iterated as
+fn insert_path(fields: &mut Fields, segments: &[impl AsRef<str>], iterated: Option<Shape>) {
+    let Some((last, parents)) = segments.split_last() else {
+        return;
+    };
+
+    let mut fields = fields;
+    for segment in parents {
+        let entry = fields.entry(segment.as_ref().to_string()).or_insert(Shape::Unknown);
+        match entry {
+            // Properties read off an array (`ctx.items.length`) don't change its type
+            Shape::Array | Shape::Map => return,
+            Shape::Unknown => *entry = Shape::Object(Fields::new()),
+            Shape::Object(_) => {}
+        }
+        let Shape::Object(inner) = entry else { unreachable!() };
+        fields = inner;
+    }
+
+    let entry = fields.entry(last.as_ref().to_string()).or_insert(Shape::Unknown);
+    if let Some(shape) = iterated {
+        *entry = shape;
+    }
+}
+
+/// `ctx.user.name` in text content -> `["user", "name"]`
+fn ctx_segments(path: &str) -> Vec<&str> {
+    path.split('.').skip(1).collect()
+}
+
+fn collect_nodes(nodes: &[Node], fields: &mut Fields) {
+    for node in nodes {
+        match node {
+            Node::Element(el) => {
+                for attr in &el.attributes {
+                    collect_expression(&attr.value, fields);
+                }
+                if let Some(directive) = &el.for_directive {
+                    collect_iterable(&directive.iterable, Shape::Array, fields);
+                }
+                if let Some(directive) = &el.if_directive {
+                    collect_expression(&directive.condition, fields);
+                }
+                collect_nodes(&el.children, fields);
+            }
+            Node::ComponentRef(r) => {
+                for param in &r.parameters {
+                    collect_expression(&param.value, fields);
+                }
+                collect_nodes(&r.children, fields);
+                for fill in &r.slot_fills {
+                    collect_nodes(&fill.children, fields);
+                }
+            }
+            Node::Text(t) => collect_text(t, fields),
+            Node::If(stmt) => collect_if(stmt, fields),
+            Node::Each(stmt) => {
+                let shape = if stmt.entries { Shape::Map } else { Shape::Array };
+                collect_iterable(&stmt.iterable, shape, fields);
+                collect_nodes(&stmt.body, fields);
+                if let Some(empty) = &stmt.empty {
+                    collect_nodes(empty, fields);
+                }
+            }
+            Node::Let(stmt) => collect_expression(&stmt.value, fields),
+            Node::RawHtml(raw) => collect_expression(&raw.value, fields),
+            Node::Slot(_) | Node::Comment(_) => {}
+        }
+    }
+}
+
+fn collect_if(stmt: &IfStatement, fields: &mut Fields) {
+    collect_expression(&stmt.condition, fields);
+    collect_nodes(&stmt.consequent, fields);
+    match &stmt.alternate {
+        Some(Alternate::Block(nodes)) => collect_nodes(nodes, fields),
+        Some(Alternate::ElseIf(elif)) => collect_if(elif, fields),
+        None => {}
+    }
+}
+
+fn collect_iterable(expr: &Expression, shape: Shape, fields: &mut Fields) {
+    match expr {
+        Expression::ContextPath(p) if p.root == PathRoot::Ctx => insert_path(fields, &p.segments, Some(shape)),
+        other => collect_expression(other, fields),
+    }
+}
+
+fn collect_text(text: &TextNode, fields: &mut Fields) {
+    if text.is_dynamic {
+        if text.content.starts_with("ctx.") {
+            insert_path(fields, &ctx_segments(&text.content), None);
+        }
+        return;
+    }
+
+    let re = interpolation_regex(r"ctx\.[a-zA-Z0-9_.]+");
+    for path in re.captures_iter(&text.content).filter_map(|cap| cap.get(1)) {
+        insert_path(fields, &ctx_segments(path.as_str()), None);
+    }
+}
+
+fn collect_expression(expr: &Expression, fields: &mut Fields) {
+    match expr {
+        Expression::ContextPath(p) if p.root == PathRoot::Ctx => insert_path(fields, &p.segments, None),
+        Expression::MemberAccess(m) | Expression::OptionalMember(m) => collect_expression(&m.object, fields),
+        Expression::Binary(b) => {
+            collect_expression(&b.left, fields);
+            collect_expression(&b.right, fields);
+        }
+        Expression::Ternary(t) => {
+            collect_expression(&t.condition, fields);
+            collect_expression(&t.consequent, fields);
+            collect_expression(&t.alternate, fields);
+        }
+        Expression::Call(c) => {
+            for arg in &c.arguments {
+                collect_expression(arg, fields);
+            }
+        }
+        Expression::Event(e) => {
+            for arg in &e.arguments {
+                collect_expression(arg, fields);
+            }
+        }
+        Expression::Spread(s) => collect_expression(&s.expr, fields),
+        Expression::Range(r) => {
+            collect_expression(&r.start, fields);
+            collect_expression(&r.end, fields);
+        }
+        Expression::Array(a) => {
+            for element in &a.elements {
+                collect_expression(element, fields);
+            }
+        }
+        Expression::Object(o) => {
+            for property in &o.properties {
+                collect_expression(&property.value, fields);
+            }
+        }
+        Expression::String(_)
+        | Expression::Number(_)
+        | Expression::Boolean(_)
+        | Expression::Null(_)
+        | Expression::Identifier(_)
+        | Expression::ContextPath(_) => {}
+    }
+}
+
+fn write_fields(fields: &Fields, depth: usize, output: &mut String) {
+    let indent = "  ".repeat(depth);
+    for (name, shape) in fields {
+        let key = property_key(name);
+        match shape {
+            Shape::Unknown => output.push_str(&format!("{}{}: unknown;\n", indent, key)),
+            Shape::Array => output.push_str(&format!("{}{}: unknown[];\n", indent, key)),
+            Shape::Map => output.push_str(&format!("{}{}: Record<string, unknown>;\n", indent, key)),
+            Shape::Object(inner) => {
+                output.push_str(&format!("{}{}: {{\n", indent, key));
+                write_fields(inner, depth + 1, output);
+                output.push_str(&format!("{}}};\n", indent));
+            }
+        }
+    }
+}
+
+/// Quote keys that aren't valid identifiers, such as `ctx.items.0`
+pub(super) fn property_key(name: &str) -> String {
+    let is_identifier = name.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '$');
+    if is_identifier {
+        name.to_string()
+    } else {
+        format!("'{}'", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::parser::parse;
+
+    fn generate_types(source: &str) -> String {
+        let ast = parse(&tokenize(source).unwrap()).unwrap();
+        generate(&ast).content
+    }
+
+    #[test]
+    fn test_infers_nested_paths_and_arrays() {
+        let output = generate_types(r#"
+            component Profile {
+                h1 {{ ${ctx.user.name} }}
+                img [src: ctx.user.avatar.url, alt: ""]
+                @each ctx.items as item {
+                    li {{ ${item.title} }}
+                }
+                @if ctx.items.length > 0 { p {{ Total ${ctx.total} }} }
+            }
+        "#);
+
+        assert!(output.contains(
+            "export interface ProfileContext {\n  items: unknown[];\n  total: unknown;\n  user: {\n    avatar: {\n      url: unknown;\n    };\n    name: unknown;\n  };\n}\n"
+        ));
+    }
+
+    #[test]
+    fn test_one_interface_per_declaration() {
+        let output = generate_types(r#"
+            component Logo { img [src: "/logo.png", alt: "Logo"] }
+            page home "/" { Logo }
+        "#);
+
+        assert!(output.contains("export interface LogoContext {}"));
+        assert!(output.contains("export interface HomePageContext {}"));
+        assert!(!output.contains("Handlers"));
+    }
+
+    #[test]
+    fn test_handler_signature_per_action() {
+        let output = generate_types(r#"
+            component Row(item: row) {
+                button [onClick: remove(row.id)] {{ Remove }}
+            }
+            page home "/" {
+                form [onSubmit: save] { input [onInput: search] }
+                button [onClick: save] {{ Save }}
+            }
+        "#);
+
+        assert!(output.contains("import type { ActionContext } from './events';"));
+        assert!(output.contains(
+            "export type Handlers = {\n  remove: (ctx: ActionContext, event: Event) => void;\n  save: (ctx: ActionContext, event: Event) => void;\n  search: (ctx: ActionContext, event: Event) => void;\n};\n"
+        ));
+    }
+}
@@ -1,73 +1,497 @@
 //! Shared utilities for code generation
 
 use crate::ast::*;
-use std::collections::HashSet;
+use crate::analyzer::{format_number, ConstValue};
+use crate::{CompileOptions, ModuleFormat};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap};
 
 /// Analysis results for event/binding usage
 #[derive(Default)]
 pub struct EventAnalysis {
     pub has_events: bool,
     pub has_bindings: bool,
-    pub event_types: HashSet<String>,  // click, submit, input, etc.
+    pub event_types: BTreeSet<String>,  // click, submit, input, etc. (sorted for stable output)
+    pub actions: BTreeSet<String>,      // handler names referenced by event attributes
+    /// Every event attribute, in declaration and source order
+    pub handlers: Vec<EventHandler>,
+}
+
+/// One event attribute, as listed in events.json
+#[derive(Debug, Clone, Serialize)]
+pub struct EventHandler {
+    /// Name of the component, section, layout or page it's in
+    pub declaration: String,
+    /// Index of each node on the way to the element, from the declaration
+    /// body down. An `@if` or `@each` adds its own index, then its branch
+    /// (0 for the body, 1 for the `@else`, or the position in an `@else @if`
+    /// chain), then the index within that branch. A slot fill adds the
+    /// component's index, then 0 for its children or 1 plus the position of a
+    /// named fill.
+    pub path: Vec<usize>,
+    pub tag: String,
+    pub event: String,
+    pub modifiers: Vec<String>,
+    pub action: String,
+    /// The arguments as the TypeScript that evaluates them
+    pub arguments: Vec<String>,
 }
 
 /// Analyze program for events and bindings
 pub fn analyze_events(program: &Program) -> EventAnalysis {
     let mut analysis = EventAnalysis::default();
     for decl in &program.body {
-        let nodes = match decl {
-            Declaration::Component(c) => &c.body,
-            Declaration::Section(s) => &s.body,
-            Declaration::Page(p) => &p.body,
+        let (name, nodes) = match decl {
+            Declaration::Component(c) => (&c.name, &c.body),
+            Declaration::Section(s) => (&s.name, &s.body),
+            Declaration::Layout(l) => (&l.name, &l.body),
+            Declaration::Page(p) => (&p.name, &p.body),
         };
-        analyze_nodes(nodes, &mut analysis);
+        analyze_nodes(nodes, name, &mut Vec::new(), &mut analysis);
     }
     analysis
 }
 
-fn analyze_nodes(nodes: &[Node], analysis: &mut EventAnalysis) {
-    for node in nodes {
+/// CSS property for a `style` object key: `fontSize` -> `font-size`
+///
+/// Keys already written in CSS form, like `"font-size"`, pass through.
+pub fn css_property(key: &str) -> String {
+    let mut property = String::new();
+    for c in key.chars() {
+        if c.is_ascii_uppercase() {
+            property.push('-');
+            property.push(c.to_ascii_lowercase());
+        } else {
+            property.push(c);
+        }
+    }
+    property
+}
+
+/// Whether a bare number for `property` means pixels, so `width: 10` is `10px`
+pub fn is_pixel_property(property: &str) -> bool {
+    matches!(
+        property,
+        "width" | "height" | "min-width" | "min-height" | "max-width" | "max-height" |
+        "top" | "right" | "bottom" | "left" | "font-size" |
+        "border-radius" | "border-width" | "outline-width" | "letter-spacing" |
+        "gap" | "row-gap" | "column-gap" | "flex-basis"
+    ) || property.starts_with("margin") || property.starts_with("padding")
+}
+
+/// CSS text for a constant `style` object value
+///
+/// Booleans have no CSS meaning, so `false` (from `ctx.x && "..."` style
+/// conditions) and `true` both drop the entry.
+pub fn css_value(property: &str, value: &ConstValue) -> Option<String> {
+    match value {
+        ConstValue::String(s) => Some(s.clone()),
+        ConstValue::Number(n) if is_pixel_property(property) && *n != 0.0 => Some(format!("{}px", format_number(*n))),
+        ConstValue::Number(n) => Some(format_number(*n)),
+        ConstValue::Boolean(_) => None,
+    }
+}
+
+/// `property:value` for a `style` object entry known at compile time
+pub fn css_declaration(key: &str, value: &ConstValue) -> Option<String> {
+    let property = css_property(key);
+    let value = css_value(&property, value)?;
+    Some(format!("{}:{}", property, value))
+}
+
+/// Make text safe inside `<!-- -->`
+///
+/// HTML comments can't contain `--`, which rules out an early `-->` or
+/// `--!>`, so runs of dashes are spaced apart. Callers pad the text with a
+/// space on each side, which covers the rules about how it starts and ends.
+pub fn comment_text(text: &str) -> String {
+    let mut safe = text.to_string();
+    while safe.contains("--") {
+        safe = safe.replace("--", "- -");
+    }
+    safe
+}
+
+/// Generated module code in the requested module syntax
+///
+/// Generators write ES modules; for CommonJS, each `import { a, b as c } from './x'`
+/// becomes TypeScript's `import xModule = require('./x')` followed by
+/// `const { a, b: c } = xModule`. The module binding carries the imported
+/// file's types, and the file stays a module without needing Node's type
+/// declarations for `require`. Exports keep their `export` keyword: compiled
+/// as CommonJS, the TypeScript compiler assigns exported functions to
+/// `exports` at the top of the file, before any `require` of the cycle
+/// between the generated modules can reach back into a half-loaded one.
+pub fn with_module_format(source: String, format: ModuleFormat) -> String {
+    if format == ModuleFormat::Esm {
+        return source;
+    }
+
+    let mut out = String::new();
+    let mut lines = source.lines();
+    while let Some(line) = lines.next() {
+        if line.starts_with("import ") && !line.starts_with("import type ") {
+            let mut statement = line.to_string();
+            while !statement.trim_end().ends_with(';') {
+                let Some(next) = lines.next() else { break };
+                statement.push(' ');
+                statement.push_str(next.trim());
+            }
+            out.push_str(&require_statement(&statement));
+        } else {
+            out.push_str(line);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// `import = require` for one ES `import` statement
+fn require_statement(import: &str) -> String {
+    let import = import.trim().trim_start_matches("import ").trim_end_matches(';').trim();
+    let Some((bindings, module)) = import.rsplit_once(" from ") else {
+        // `import './x'` only runs the module, which TypeScript compiles to a bare `require`
+        return format!("import {};", import);
+    };
+    let module = module.trim();
+
+    let mut bindings = bindings.trim();
+    if let Some(namespace) = bindings.strip_prefix("* as ") {
+        return format!("import {} = require({});", namespace.trim(), module);
+    }
+    let name = module.trim_matches(|c| c == '\'' || c == '"');
+    let name = name.rsplit('/').next().unwrap_or(name);
+    let binding = format!("{}Module", name);
+    let mut parts = vec![format!("import {} = require({});", binding, module)];
+    if !bindings.starts_with('{') {
+        // A default import, possibly followed by named ones: `import a, { b } from './x'`
+        let (default, rest) = bindings.split_once(',').unwrap_or((bindings, ""));
+        parts.push(format!("const {} = {}.default;", default.trim(), binding));
+        bindings = rest.trim();
+    }
+    if let Some(names) = bindings.strip_prefix('{').and_then(|b| b.strip_suffix('}')) {
+        let names: Vec<String> = names.split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty() && !name.starts_with("type "))
+            .map(|name| match name.split_once(" as ") {
+                Some((imported, local)) => format!("{}: {}", imported.trim(), local.trim()),
+                None => name.to_string(),
+            })
+            .collect();
+        if !names.is_empty() {
+            parts.push(format!("const {{ {} }} = {};", names.join(", "), binding));
+        }
+    }
+    parts.join("\n")
+}
+
+/// Whether an attribute is an event handler (`onClick`, `onSubmit`) rather than markup
+pub fn is_event_attribute(attr: &Attribute) -> bool {
+    attr.name.starts_with("on")
+}
+
+fn analyze_nodes(nodes: &[Node], decl: &str, path: &mut Vec<usize>, analysis: &mut EventAnalysis) {
+    for (index, node) in nodes.iter().enumerate() {
+        path.push(index);
         match node {
             Node::Element(el) => {
                 for attr in &el.attributes {
-                    if attr.name.starts_with("on") {
+                    if is_event_attribute(attr) {
                         analysis.has_events = true;
-                        // Extract event type: onClick -> click
-                        let event_name = attr.name
+                        // Extract event type and modifiers: onClick.prevent -> click, [prevent]
+                        let mut parts = attr.name.split('.');
+                        let event_name = parts.next()
+                            .unwrap_or("")
                             .strip_prefix("on")
-                            .unwrap_or(&attr.name)
-                            .split('.')
-                            .next()
                             .unwrap_or("")
                             .to_lowercase();
                         if !event_name.is_empty() {
-                            analysis.event_types.insert(event_name);
+                            analysis.event_types.insert(event_name.clone());
                         }
+                        let (action, arguments) = match &attr.value {
+                            Expression::Identifier(id) => (&id.name, &[][..]),
+                            Expression::Call(call) => (&call.callee, &call.arguments[..]),
+                            _ => continue,
+                        };
+                        analysis.actions.insert(action.clone());
+                        analysis.handlers.push(EventHandler {
+                            declaration: decl.to_string(),
+                            path: path.clone(),
+                            tag: el.tag.clone(),
+                            event: event_name,
+                            modifiers: parts.map(str::to_string).collect(),
+                            action: action.clone(),
+                            arguments: arguments.iter().map(super::expr_to_js).collect(),
+                        });
                     }
                     if attr.name == "bind" {
                         analysis.has_bindings = true;
                     }
                 }
-                analyze_nodes(&el.children, analysis);
+                analyze_nodes(&el.children, decl, path, analysis);
             }
             Node::If(stmt) => {
-                analyze_nodes(&stmt.consequent, analysis);
-                if let Some(alt) = &stmt.alternate {
-                    match alt {
-                        Alternate::Block(nodes) => analyze_nodes(nodes, analysis),
-                        Alternate::ElseIf(elif) => analyze_nodes(&elif.consequent, analysis),
+                let mut branch = 0;
+                let mut stmt = stmt;
+                loop {
+                    path.push(branch);
+                    analyze_nodes(&stmt.consequent, decl, path, analysis);
+                    path.pop();
+                    branch += 1;
+                    match &stmt.alternate {
+                        Some(Alternate::Block(nodes)) => {
+                            path.push(branch);
+                            analyze_nodes(nodes, decl, path, analysis);
+                            path.pop();
+                            break;
+                        }
+                        Some(Alternate::ElseIf(elif)) => stmt = elif,
+                        None => break,
                     }
                 }
             }
             Node::Each(stmt) => {
-                analyze_nodes(&stmt.body, analysis);
+                path.push(0);
+                analyze_nodes(&stmt.body, decl, path, analysis);
+                path.pop();
+                if let Some(empty) = &stmt.empty {
+                    path.push(1);
+                    analyze_nodes(empty, decl, path, analysis);
+                    path.pop();
+                }
+            }
+            // Slot content is rendered in the caller's template
+            Node::ComponentRef(r) => {
+                path.push(0);
+                analyze_nodes(&r.children, decl, path, analysis);
+                path.pop();
+                for (position, fill) in r.slot_fills.iter().enumerate() {
+                    path.push(position + 1);
+                    analyze_nodes(&fill.children, decl, path, analysis);
+                    path.pop();
+                }
             }
             _ => {}
         }
+        path.pop();
+    }
+}
+
+/// JS regex literal for a route with `:param` segments, and the param names
+/// in capture order; `None` for a static route
+pub fn route_regex(route: &str) -> Option<(String, Vec<&str>)> {
+    let segments = route_segments(route);
+    if !segments.iter().any(|s| matches!(s, RouteSegment::Param(_))) {
+        return None;
+    }
+
+    let mut pattern = String::from("/^");
+    let mut names = Vec::new();
+    for segment in segments {
+        pattern.push_str("\\/");
+        match segment {
+            RouteSegment::Static(text) => {
+                for c in text.chars() {
+                    if "\\^$.|?*+()[]{}".contains(c) {
+                        pattern.push('\\');
+                    }
+                    pattern.push(c);
+                }
+            }
+            RouteSegment::Param(name) => {
+                pattern.push_str("([^/]+)");
+                names.push(name);
+            }
+        }
     }
+    pattern.push_str("$/");
+    Some((pattern, names))
 }
 
-/// Check if a program has any event handlers
-pub fn program_has_events(program: &Program) -> bool {
-    analyze_events(program).has_events
+/// Check if a program needs events.ts: any event handler or `bind:`
+pub fn program_needs_events(program: &Program) -> bool {
+    let analysis = analyze_events(program);
+    analysis.has_events || analysis.has_bindings
+}
+
+/// `program` with test ids added if the options ask for them
+pub fn with_testids<'a>(program: &'a Program, options: &CompileOptions) -> Cow<'a, Program> {
+    if !options.auto_testids {
+        return Cow::Borrowed(program);
+    }
+    let mut program = program.clone();
+    add_testids(&mut program);
+    Cow::Owned(program)
+}
+
+/// Give every element without an explicit `data-testid` a stable one,
+/// `<declaration>-<tag>-<n>`, numbered per tag in source order
+///
+/// Explicit test ids are left alone and don't take a number, so adding one
+/// doesn't shift the ids of the elements after it.
+fn add_testids(program: &mut Program) {
+    for decl in &mut program.body {
+        let (name, body) = match decl {
+            Declaration::Component(c) => (&c.name, &mut c.body),
+            Declaration::Section(s) => (&s.name, &mut s.body),
+            Declaration::Layout(l) => (&l.name, &mut l.body),
+            Declaration::Page(p) => (&p.name, &mut p.body),
+        };
+        let prefix = kebab_case(name);
+        add_testids_to_nodes(body, &prefix, &mut HashMap::new());
+    }
+}
+
+fn add_testids_to_nodes(nodes: &mut [Node], prefix: &str, counts: &mut HashMap<String, usize>) {
+    for node in nodes {
+        match node {
+            Node::Element(el) => {
+                if !el.attributes.iter().any(|a| a.name == "data-testid") {
+                    let n = counts.entry(el.tag.clone()).or_default();
+                    *n += 1;
+                    el.attributes.push(Attribute {
+                        name: "data-testid".to_string(),
+                        value: Expression::String(StringLiteral {
+                            value: format!("{}-{}-{}", prefix, el.tag, n),
+                            loc: el.loc,
+                        }),
+                        loc: el.loc,
+                    });
+                }
+                add_testids_to_nodes(&mut el.children, prefix, counts);
+            }
+            Node::ComponentRef(r) => {
+                add_testids_to_nodes(&mut r.children, prefix, counts);
+                for fill in &mut r.slot_fills {
+                    add_testids_to_nodes(&mut fill.children, prefix, counts);
+                }
+            }
+            Node::If(stmt) => {
+                let mut branch = Some(stmt);
+                while let Some(stmt) = branch {
+                    add_testids_to_nodes(&mut stmt.consequent, prefix, counts);
+                    branch = match &mut stmt.alternate {
+                        Some(Alternate::Block(nodes)) => {
+                            add_testids_to_nodes(nodes, prefix, counts);
+                            None
+                        }
+                        Some(Alternate::ElseIf(elif)) => Some(elif),
+                        None => None,
+                    };
+                }
+            }
+            Node::Each(stmt) => {
+                add_testids_to_nodes(&mut stmt.body, prefix, counts);
+                if let Some(empty) = &mut stmt.empty {
+                    add_testids_to_nodes(empty, prefix, counts);
+                }
+            }
+            Node::Text(_) | Node::Slot(_) | Node::Let(_) | Node::Comment(_) | Node::RawHtml(_) => {}
+        }
+    }
+}
+
+/// `UserCard` -> `user-card`
+fn kebab_case(name: &str) -> String {
+    let mut kebab = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                kebab.push('-');
+            }
+            kebab.push(c.to_ascii_lowercase());
+        } else if c == '_' {
+            kebab.push('-');
+        } else {
+            kebab.push(c);
+        }
+    }
+    kebab
+}
+
+/// Check if an `@each` body reads `$even`/`$odd`, not counting nested loops' own helpers
+pub fn uses_loop_helpers(nodes: &[Node]) -> bool {
+    nodes.iter().any(|node| match node {
+        Node::Element(el) => {
+            el.attributes.iter().any(|a| expression_uses_loop_helpers(&a.value))
+                || uses_loop_helpers(&el.children)
+        }
+        Node::ComponentRef(r) => {
+            r.parameters.iter().any(|p| expression_uses_loop_helpers(&p.value))
+                || uses_loop_helpers(&r.children)
+                || r.slot_fills.iter().any(|fill| uses_loop_helpers(&fill.children))
+        }
+        Node::If(stmt) => if_uses_loop_helpers(stmt),
+        Node::Each(stmt) => {
+            // The empty block belongs to the enclosing loop
+            expression_uses_loop_helpers(&stmt.iterable)
+                || stmt.empty.as_deref().is_some_and(uses_loop_helpers)
+        }
+        Node::Let(stmt) => expression_uses_loop_helpers(&stmt.value),
+        Node::RawHtml(raw) => expression_uses_loop_helpers(&raw.value),
+        Node::Text(_) | Node::Slot(_) | Node::Comment(_) => false,
+    })
+}
+
+fn if_uses_loop_helpers(stmt: &IfStatement) -> bool {
+    expression_uses_loop_helpers(&stmt.condition)
+        || uses_loop_helpers(&stmt.consequent)
+        || match &stmt.alternate {
+            Some(Alternate::Block(nodes)) => uses_loop_helpers(nodes),
+            Some(Alternate::ElseIf(elif)) => if_uses_loop_helpers(elif),
+            None => false,
+        }
+}
+
+fn expression_uses_loop_helpers(expr: &Expression) -> bool {
+    match expr {
+        Expression::Identifier(ident) => is_loop_helper(&ident.name),
+        Expression::ContextPath(p) => p.root == PathRoot::Local && is_loop_helper(&p.segments[0]),
+        Expression::MemberAccess(m) | Expression::OptionalMember(m) => expression_uses_loop_helpers(&m.object),
+        Expression::Binary(b) => {
+            expression_uses_loop_helpers(&b.left) || expression_uses_loop_helpers(&b.right)
+        }
+        Expression::Ternary(t) => {
+            expression_uses_loop_helpers(&t.condition)
+                || expression_uses_loop_helpers(&t.consequent)
+                || expression_uses_loop_helpers(&t.alternate)
+        }
+        Expression::Call(c) => c.arguments.iter().any(expression_uses_loop_helpers),
+        Expression::Event(e) => e.arguments.iter().any(expression_uses_loop_helpers),
+        Expression::Spread(s) => expression_uses_loop_helpers(&s.expr),
+        Expression::Range(r) => expression_uses_loop_helpers(&r.start) || expression_uses_loop_helpers(&r.end),
+        Expression::Array(a) => a.elements.iter().any(expression_uses_loop_helpers),
+        Expression::Object(o) => o.properties.iter().any(|p| expression_uses_loop_helpers(&p.value)),
+        Expression::String(_)
+        | Expression::Number(_)
+        | Expression::Boolean(_)
+        | Expression::Null(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commonjs_imports() {
+        let source = concat!(
+            "import { a, b as c, type T } from './x';\n",
+            "import {\n  d,\n  e as f,\n} from './y';\n",
+            "import * as z from './z';\n",
+            "import type { Context } from './router';\n",
+            "export const items = [\n  1,\n];\n",
+        );
+        let output = with_module_format(source.to_string(), ModuleFormat::Cjs);
+        assert_eq!(output, concat!(
+            "import xModule = require('./x');\n",
+            "const { a, b: c } = xModule;\n",
+            "import yModule = require('./y');\n",
+            "const { d, e: f } = yModule;\n",
+            "import z = require('./z');\n",
+            "import type { Context } from './router';\n",
+            "export const items = [\n  1,\n];\n",
+        ));
+    }
 }
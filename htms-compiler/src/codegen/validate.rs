@@ -0,0 +1,274 @@
+//! Well-formedness check for generated HTML
+//!
+//! A safety net for the generators, not a full HTML parser: it checks that
+//! every element is closed in order (void elements excepted) and that every
+//! attribute value is quoted. Comments, doctypes and the bodies of `script`
+//! and `style` are skipped. The XHTML check adds the rules XML has on top:
+//! void elements self-close, every attribute has a value, and `<` and `&` in
+//! a `script` or `style` body sit in a CDATA section.
+
+use crate::ast::is_void_element;
+
+/// Check one generated HTML file, returning the first problem found with
+/// its 1-based `line:column` in the output
+pub fn check_well_formed(html: &str) -> Result<(), String> {
+    check(html, false)
+}
+
+/// Check one generated XHTML file as XML, returning the first problem found
+/// with its 1-based `line:column` in the output
+pub fn check_well_formed_xml(html: &str) -> Result<(), String> {
+    check(html, true)
+}
+
+fn check(html: &str, xml: bool) -> Result<(), String> {
+    let mut checker = Checker {
+        html,
+        pos: 0,
+        open: Vec::new(),
+        xml,
+    };
+    checker.run().map_err(|(pos, message)| {
+        let (line, column) = line_column(html, pos);
+        format!("{} at {}:{}", message, line, column)
+    })
+}
+
+type CheckError = (usize, String);
+
+struct Checker<'a> {
+    html: &'a str,
+    pos: usize,
+    /// Open elements with the offset of their start tag
+    open: Vec<(String, usize)>,
+    xml: bool,
+}
+
+impl Checker<'_> {
+    fn run(&mut self) -> Result<(), CheckError> {
+        while let Some(offset) = self.rest().find('<') {
+            self.pos += offset;
+            let rest = self.rest();
+
+            if rest.starts_with("<!--") {
+                self.skip_past("-->", "Unterminated comment")?;
+            } else if rest.starts_with("<![CDATA[") {
+                self.skip_past("]]>", "Unterminated CDATA section")?;
+            } else if rest.starts_with("<!") {
+                self.skip_past(">", "Unterminated doctype")?;
+            } else if rest.starts_with("</") {
+                self.end_tag()?;
+            } else if rest[1..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+                self.start_tag()?;
+            } else {
+                // A stray `<` in text
+                self.pos += 1;
+            }
+        }
+
+        match self.open.pop() {
+            Some((tag, start)) => Err((start, format!("Unclosed <{}>", tag))),
+            None => Ok(()),
+        }
+    }
+
+    fn start_tag(&mut self) -> Result<(), CheckError> {
+        let start = self.pos;
+        self.pos += 1;
+        let tag = self.name();
+
+        loop {
+            self.skip_whitespace();
+            let rest = self.rest();
+            if rest.is_empty() {
+                return Err((start, format!("Unterminated start tag <{}>", tag)));
+            }
+            if rest.starts_with("/>") {
+                self.pos += 2;
+                return Ok(());
+            }
+            if rest.starts_with('>') {
+                self.pos += 1;
+                break;
+            }
+            self.attribute(&tag)?;
+        }
+
+        if is_void_element(&tag) {
+            if self.xml {
+                return Err((start, format!("Void element <{}> must self-close in XHTML", tag)));
+            }
+            return Ok(());
+        }
+        if tag == "script" || tag == "style" {
+            // Raw text: the body can't contain markup, only the end tag
+            let end = format!("</{}", tag);
+            return match self.rest().find(&end) {
+                Some(offset) => {
+                    if self.xml && has_markup_outside_cdata(&self.rest()[..offset]) {
+                        return Err((start, format!("<{}> body has '<' or '&' outside a CDATA section", tag)));
+                    }
+                    self.pos += offset;
+                    self.end_tag_for(&tag)
+                }
+                None => Err((start, format!("Unclosed <{}>", tag))),
+            };
+        }
+
+        self.open.push((tag, start));
+        Ok(())
+    }
+
+    fn attribute(&mut self, tag: &str) -> Result<(), CheckError> {
+        let start = self.pos;
+        let name = self.name();
+        if name.is_empty() {
+            return Err((start, format!("Invalid character in start tag <{}>", tag)));
+        }
+
+        self.skip_whitespace();
+        if !self.rest().starts_with('=') {
+            // Boolean attribute
+            if self.xml {
+                return Err((start, format!("Attribute '{}' on <{}> has no value", name, tag)));
+            }
+            return Ok(());
+        }
+        self.pos += 1;
+        self.skip_whitespace();
+
+        let quote = match self.rest().chars().next() {
+            Some(q @ ('"' | '\'')) => q,
+            _ => return Err((start, format!("Unquoted value for attribute '{}' on <{}>", name, tag))),
+        };
+        self.pos += 1;
+        match self.rest().find(quote) {
+            Some(offset) => {
+                self.pos += offset + 1;
+                Ok(())
+            }
+            None => Err((start, format!("Unterminated value for attribute '{}' on <{}>", name, tag))),
+        }
+    }
+
+    fn end_tag(&mut self) -> Result<(), CheckError> {
+        let start = self.pos;
+        match self.open.pop() {
+            Some((tag, _)) => self.end_tag_for(&tag),
+            None => {
+                self.pos += 2;
+                let tag = self.name();
+                Err((start, format!("Closing tag </{}> has no matching start tag", tag)))
+            }
+        }
+    }
+
+    /// Consume `</tag>` at the current position, which must close `expected`
+    fn end_tag_for(&mut self, expected: &str) -> Result<(), CheckError> {
+        let at = self.pos;
+        self.pos += 2;
+        let tag = self.name();
+        if tag != expected {
+            return Err((at, format!("Expected </{}> but found </{}>", expected, tag)));
+        }
+        self.skip_whitespace();
+        if !self.rest().starts_with('>') {
+            return Err((at, format!("Unterminated closing tag </{}>", tag)));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn name(&mut self) -> String {
+        let len = self.rest()
+            .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':' | '.' | '@')))
+            .unwrap_or(self.rest().len());
+        let name = self.rest()[..len].to_string();
+        self.pos += len;
+        name
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn skip_past(&mut self, end: &str, message: &str) -> Result<(), CheckError> {
+        match self.rest().find(end) {
+            Some(offset) => {
+                self.pos += offset + end.len();
+                Ok(())
+            }
+            None => Err((self.pos, message.to_string())),
+        }
+    }
+
+    fn rest(&self) -> &str {
+        &self.html[self.pos..]
+    }
+}
+
+fn has_markup_outside_cdata(body: &str) -> bool {
+    let mut rest = body;
+    loop {
+        let (text, after) = match rest.split_once("<![CDATA[") {
+            Some((text, after)) => (text, Some(after)),
+            None => (rest, None),
+        };
+        if text.contains(['<', '&']) {
+            return true;
+        }
+        match after.and_then(|after| after.split_once("]]>")) {
+            Some((_, next)) => rest = next,
+            None => return false,
+        }
+    }
+}
+
+fn line_column(text: &str, pos: usize) -> (usize, usize) {
+    let before = &text[..pos];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accepts_well_formed_markup() {
+        let html = "<!DOCTYPE html>\n<html lang=\"en\">\n<!-- note -->\n<body>\n  <img src='a.png' alt=\"\">\n  <input disabled>\n  <script>if (a < b) {}</script>\n</body>\n</html>\n";
+        assert_eq!(check_well_formed(html), Ok(()));
+    }
+
+    #[test]
+    fn test_rejects_mismatched_tags() {
+        let err = check_well_formed("<div>\n  <span></div>").unwrap_err();
+        assert_eq!(err, "Expected </span> but found </div> at 2:9");
+    }
+
+    #[test]
+    fn test_rejects_unclosed_element() {
+        assert_eq!(check_well_formed("<main><p></p>").unwrap_err(), "Unclosed <main> at 1:1");
+    }
+
+    #[test]
+    fn test_xml_rules() {
+        let html = "<html>\n<body>\n  <img src=\"a.png\" alt=\"\" />\n  <input disabled=\"disabled\" />\n  <script>//<![CDATA[\nif (a < b) {}\n//]]></script>\n</body>\n</html>\n";
+        assert_eq!(check_well_formed_xml(html), Ok(()));
+
+        assert_eq!(check_well_formed_xml("<p><br></p>").unwrap_err(), "Void element <br> must self-close in XHTML at 1:4");
+        assert_eq!(check_well_formed_xml("<input disabled />").unwrap_err(), "Attribute 'disabled' on <input> has no value at 1:8");
+        assert_eq!(
+            check_well_formed_xml("<script>a && b</script>").unwrap_err(),
+            "<script> body has '<' or '&' outside a CDATA section at 1:1"
+        );
+    }
+
+    #[test]
+    fn test_rejects_unquoted_attribute() {
+        let err = check_well_formed("<a href=/home>Home</a>").unwrap_err();
+        assert_eq!(err, "Unquoted value for attribute 'href' on <a> at 1:4");
+    }
+}
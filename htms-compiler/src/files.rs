@@ -0,0 +1,172 @@
+//! Compiling several source files as one program
+//!
+//! Each file is lexed and parsed on its own, so an unterminated string or
+//! declaration can't run into the next file. The declarations are then
+//! joined into one program for analysis. Token offsets are moved past the
+//! files before them, which keeps every offset unique, while lines and
+//! columns stay relative to their own file. Diagnostics are mapped back to a
+//! file and a file-relative offset at the end.
+
+use crate::ast::Program;
+use crate::lexer::Token;
+use crate::{
+    compile_program, failed, lex_source, parse_options, parser, parser_diagnostic, CompileOptions,
+    CompileResult, Diagnostic, Location,
+};
+
+/// Compile several HTMS files, given as `(path, source)` pairs, as one program
+///
+/// Declarations from every file share one symbol table, so a page can use a
+/// component declared in another file, and a name declared twice is an error
+/// even across files. Each diagnostic's `file` is the path it points into.
+///
+/// ```rust
+/// use htms_compiler::{compile_files, CompileOptions};
+///
+/// let files = vec![
+///     ("logo.htms".to_string(), "component Logo { img [src: \"/logo.png\", alt: \"\"] }".to_string()),
+///     ("home.htms".to_string(), "page home \"/\" { Logo }".to_string()),
+/// ];
+/// let result = compile_files(&files, &CompileOptions::default());
+/// assert!(result.success);
+/// ```
+pub fn compile_files(files: &[(String, String)], options: &CompileOptions) -> CompileResult {
+    let mut offsets = FileOffsets::default();
+    let mut program = Program {
+        body: Vec::new(),
+        loc: Location { line: 1, column: 1, start: 0, end: 0 },
+    };
+    let mut errors = Vec::new();
+
+    for (path, source) in files {
+        let base = offsets.push(path);
+        // One byte of space between files, so an end offset never equals the next file's start
+        offsets.end = base + source.len() + 1;
+
+        let tokens = match lex_source(source) {
+            Ok(tokens) => tokens.into_iter().map(|token| shift(token, base)).collect::<Vec<_>>(),
+            Err(diagnostics) => {
+                errors.extend(diagnostics.into_iter().map(|mut d| {
+                    d.file = Some(path.clone());
+                    d
+                }));
+                continue;
+            }
+        };
+        match parser::parse_with_options(&tokens, parse_options(options)) {
+            Ok(ast) => {
+                program.body.extend(ast.body);
+                program.loc.end = ast.loc.end;
+            }
+            Err(parse_errors) => {
+                let mut diagnostics: Vec<_> = parse_errors.into_iter().map(parser_diagnostic).collect();
+                offsets.attribute(&mut diagnostics);
+                errors.extend(diagnostics);
+            }
+        }
+    }
+    if !errors.is_empty() {
+        return failed(errors);
+    }
+
+    compile_program(program, options, |diagnostics| offsets.attribute(diagnostics))
+}
+
+/// Where each file starts in the joined token stream
+#[derive(Default)]
+struct FileOffsets {
+    files: Vec<(String, usize)>,
+    end: usize,
+}
+
+impl FileOffsets {
+    /// Start a file after the ones before it, returning its base offset
+    fn push(&mut self, path: &str) -> usize {
+        self.files.push((path.to_string(), self.end));
+        self.end
+    }
+
+    /// Point each diagnostic at the file its offset falls in
+    fn attribute(&self, diagnostics: &mut [Diagnostic]) {
+        for diagnostic in diagnostics {
+            let index = self.files.partition_point(|(_, base)| *base <= diagnostic.location.start);
+            let Some((path, base)) = index.checked_sub(1).map(|index| &self.files[index]) else {
+                continue;
+            };
+            diagnostic.location.start -= base;
+            diagnostic.location.end = diagnostic.location.end.saturating_sub(*base);
+            diagnostic.file = Some(path.clone());
+        }
+    }
+}
+
+fn shift(mut token: Token, base: usize) -> Token {
+    token.location.start += base;
+    token.location.end += base;
+    token
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Severity;
+
+    fn sources(files: &[(&str, &str)]) -> Vec<(String, String)> {
+        files.iter().map(|(path, source)| (path.to_string(), source.to_string())).collect()
+    }
+
+    #[test]
+    fn test_components_resolve_across_files() {
+        let files = sources(&[
+            ("card.htms", "component Card { div [class: \"card\"] { @slot } }"),
+            ("home.htms", "page home \"/\" { Card { p {{ Hi }} } }"),
+        ]);
+        let result = compile_files(&files, &CompileOptions::default());
+        assert!(result.success, "{:?}", result.diagnostics);
+        assert!(result.files.iter().any(|f| f.content.contains("card")));
+    }
+
+    #[test]
+    fn test_duplicates_span_files() {
+        let home = "page home \"/\" { Card }";
+        let second = "\ncomponent Card { p {{ B }} }";
+        let files = sources(&[
+            ("a.htms", "component Card { p {{ A }} }"),
+            ("b.htms", second),
+            ("home.htms", home),
+        ]);
+        let result = compile_files(&files, &CompileOptions::default());
+        let errors: Vec<_> = result.diagnostics.iter().filter(|d| d.severity == Severity::Error).collect();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(errors[0].file.as_deref(), Some("b.htms"));
+        assert_eq!(errors[0].location.start, second.find("component").unwrap());
+        assert_eq!(errors[0].location.line, 2);
+    }
+
+    #[test]
+    fn test_lex_and_parse_errors_name_their_file() {
+        let files = sources(&[("ok.htms", "component A { }"), ("bad.htms", "component B { p {{ x }")]);
+        let result = compile_files(&files, &CompileOptions::default());
+        assert!(!result.success);
+        assert!(result.diagnostics.iter().all(|d| d.file.as_deref() == Some("bad.htms")));
+
+        let files = sources(&[("ok.htms", "component A { }"), ("bad.htms", "component B {")]);
+        let result = compile_files(&files, &CompileOptions::default());
+        assert!(!result.success);
+        assert!(result.diagnostics.iter().all(|d| d.file.as_deref() == Some("bad.htms")));
+
+        let files = sources(&[("bad.htms", "component B {"), ("ok.htms", "component A { }")]);
+        let result = compile_files(&files, &CompileOptions::default());
+        assert!(!result.success);
+        assert!(result.diagnostics.iter().all(|d| d.file.as_deref() == Some("bad.htms")), "{:?}", result.diagnostics);
+    }
+
+    #[test]
+    fn test_declarations_cannot_span_files() {
+        let files = sources(&[("a.htms", "component A { div {"), ("b.htms", "} }")]);
+        let result = compile_files(&files, &CompileOptions::default());
+        assert!(!result.success);
+        let files_with_errors: Vec<_> = result.diagnostics.iter().filter_map(|d| d.file.as_deref()).collect();
+        assert!(files_with_errors.contains(&"a.htms") && files_with_errors.contains(&"b.htms"), "{:?}", result.diagnostics);
+    }
+}
@@ -0,0 +1,122 @@
+//! Source formatting for `format`
+//!
+//! Only the whitespace between tokens changes: each line is re-indented to
+//! its bracket depth, trailing whitespace is dropped and runs of blank lines
+//! collapse to one. Text blocks and block comments are copied as written,
+//! since their whitespace can be part of the output.
+
+use crate::lexer::{self, TokenKind};
+use crate::{parse_source, parser, Diagnostic, Indent};
+
+/// Format HTMS source code, or return the diagnostics that stop it parsing
+///
+/// ```rust
+/// use htms_compiler::format;
+///
+/// let formatted = format("component Logo {\nimg [src: \"/logo.png\", alt: \"\"]   \n}").unwrap();
+/// assert_eq!(formatted, "component Logo {\n  img [src: \"/logo.png\", alt: \"\"]\n}\n");
+/// ```
+pub fn format(source: &str) -> Result<String, Vec<Diagnostic>> {
+    parse_source(source, parser::ParseOptions::default())?;
+
+    let (tokens, _) = lexer::tokenize_with_trivia(source);
+    let verbatim: Vec<(usize, usize)> = tokens.iter()
+        .filter(|t| matches!(t.kind, TokenKind::TextContent | TokenKind::BlockComment))
+        .map(|t| (t.location.start, t.location.end))
+        .collect();
+
+    let mut formatted = String::new();
+    let mut depth = 0usize;
+    let mut tokens = tokens.iter().peekable();
+    // Lines break where the lexer counts them, so a lone `\r` ends one too
+    let mut line_ends: Vec<usize> = lexer::line_break_ends(source).collect();
+    if line_ends.last().copied().unwrap_or(0) < source.len() {
+        line_ends.push(source.len());
+    }
+    let mut offset = 0;
+
+    for line_end in line_ends {
+        let start = offset;
+        let raw = &source[start..line_end];
+        offset = line_end;
+        let line = raw.trim_end_matches(['\n', '\r']);
+        let end = start + line.len();
+
+        while let Some(token) = tokens.next_if(|t| t.location.start < start) {
+            depth = step(depth, token.kind);
+        }
+
+        // Whitespace at either edge of a line inside a text block is text
+        let starts_inside = verbatim.iter().any(|&(s, e)| s < start && start <= e);
+        let ends_inside = verbatim.iter().any(|&(s, e)| s <= end && end < e);
+        let line = if ends_inside { line } else { line.trim_end() };
+
+        if starts_inside {
+            formatted.push_str(line);
+        } else if line.trim().is_empty() {
+            if formatted.is_empty() || formatted.ends_with("\n\n") {
+                continue;
+            }
+        } else {
+            // Closing brackets that open a line sit at the depth of their opener
+            let leading_closers = tokens.clone()
+                .take_while(|t| t.location.start < end && is_closer(t.kind))
+                .count();
+            formatted.push_str(&Indent::default().level(depth.saturating_sub(leading_closers)));
+            formatted.push_str(line.trim_start());
+        }
+        formatted.push('\n');
+    }
+
+    let trimmed = formatted.trim_end_matches('\n').len();
+    formatted.truncate(trimmed);
+    formatted.push('\n');
+    Ok(formatted)
+}
+
+fn step(depth: usize, kind: TokenKind) -> usize {
+    match kind {
+        TokenKind::LBrace | TokenKind::LBracket | TokenKind::LParen => depth + 1,
+        kind if is_closer(kind) => depth.saturating_sub(1),
+        _ => depth,
+    }
+}
+
+fn is_closer(kind: TokenKind) -> bool {
+    matches!(kind, TokenKind::RBrace | TokenKind::RBracket | TokenKind::RParen)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reindents_and_keeps_text() {
+        let source = "  // Header\n\n\ncomponent Card {\n        div [\n  class: \"card\",\n    id: \"main\"\n] {\nh1 {{ Title }}   \n    p {{\n   kept  as is\n    }}\n  /* note\n     here */\n        }\n    }\n\n";
+        let expected = "// Header\n\ncomponent Card {\n  div [\n    class: \"card\",\n    id: \"main\"\n  ] {\n    h1 {{ Title }}\n    p {{\n   kept  as is\n    }}\n    /* note\n     here */\n  }\n}\n";
+
+        let formatted = format(source).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(format(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_closers_on_one_line() {
+        let source = "page home \"/\" {\n@if ctx.open {\np {{ Open }}\n} @else {\np {{ Closed }} } }";
+        let expected = "page home \"/\" {\n  @if ctx.open {\n    p {{ Open }}\n  } @else {\n    p {{ Closed }} } }\n";
+        assert_eq!(format(source).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_carriage_return_line_breaks() {
+        assert_eq!(format("component A {\r p {{ x }}\r}").unwrap(), "component A {\n  p {{ x }}\n}\n");
+        assert_eq!(format("component A {\r\n p {{ x }}\r\n}\r\n").unwrap(), "component A {\n  p {{ x }}\n}\n");
+    }
+
+    #[test]
+    fn test_invalid_source_returns_diagnostics() {
+        let errors = format("component Card { div [ }").unwrap_err();
+        assert_eq!(errors[0].code.as_deref(), Some("E002"));
+        assert!(format("component Card { \u{1} }").is_err());
+    }
+}
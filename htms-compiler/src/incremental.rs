@@ -0,0 +1,590 @@
+//! Incremental compilation for watch servers
+//!
+//! Each top-level declaration is parsed on its own and cached by the hash of
+//! its source text, so an edit only re-parses the declaration it touches; a
+//! declaration that merely moved has its locations shifted instead.
+//! Analysis is deliberately not cached: it resolves references between
+//! declarations and checks names across all of them, so an edit to one can
+//! change the diagnostics of another, and it is cheap next to parsing and
+//! code generation. Template code is generated again only for declarations whose
+//! output could have changed: a declaration's fingerprint covers its own
+//! source and the fingerprints of every component or section it uses, so
+//! editing a component invalidates each page that references it.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashMap};
+use std::hash::{Hash, Hasher};
+
+use crate::ast::*;
+use crate::lexer::{Token, TokenKind};
+use crate::{
+    analyze_program, best_effort_program, count_stats, failed, finish, generate_files, generate_files_cached, has_errors,
+    lex_source, parse_options, parse_source, parser, with_source_filename, CompileOptions, CompileResult,
+    GeneratedFile, Location,
+};
+use crate::codegen::TemplateCache;
+
+/// A compiler that reuses work from its previous run
+///
+/// ```rust
+/// use htms_compiler::Compiler;
+///
+/// let mut compiler = Compiler::new();
+/// let result = compiler.compile("component Logo { img [src: \"/logo.png\", alt: \"\"] }");
+/// assert!(result.success);
+/// ```
+#[derive(Debug, Default)]
+pub struct Compiler {
+    options: CompileOptions,
+    /// Parsed declarations, with the location they were parsed at, by the hash of their source text
+    declarations: HashMap<u64, (Declaration, Location)>,
+    /// Fingerprint of each declaration's output as of the last successful compile
+    fingerprints: HashMap<String, u64>,
+    /// Files from the last successful compile
+    files: Option<Vec<GeneratedFile>>,
+    /// Declarations whose output changed in the last compile
+    changed: Vec<String>,
+    /// Template code for each declaration as of the last successful compile
+    templates: TemplateCache,
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_options(options: CompileOptions) -> Self {
+        Self {
+            options,
+            ..Self::default()
+        }
+    }
+
+    /// Compile `source`, reusing declarations and output that didn't change
+    ///
+    /// Parsing and template code are cached; analysis runs over the whole
+    /// program each time.
+    pub fn compile(&mut self, source: &str) -> CompileResult {
+        let result = self.compile_source(source);
+        with_source_filename(result, &self.options)
+    }
+
+    /// Names of the components, sections and pages whose output changed in the
+    /// last compile, including pages that use an edited component
+    ///
+    /// Every declaration counts as changed on the first compile.
+    pub fn changed(&self) -> &[String] {
+        &self.changed
+    }
+
+    fn compile_source(&mut self, source: &str) -> CompileResult {
+        self.changed.clear();
+        let ast = match self.parse(source) {
+            Ok(ast) => ast,
+            Err(errors) => return failed(errors),
+        };
+
+        // Not cached per declaration: renaming or removing a component changes the diagnostics of its users
+        let (symbols, diagnostics) = analyze_program(&ast, &self.options);
+        if has_errors(&diagnostics) {
+            // Partial output is never cached; the next clean compile compares against the last one
+            return match best_effort_program(&ast, &diagnostics, &self.options) {
+                Some(partial) => {
+                    let stats = count_stats(&partial, &self.options);
+                    finish(generate_files(partial, &symbols, &self.options), diagnostics, stats, &self.options)
+                }
+                None => failed(diagnostics),
+            };
+        }
+
+        let fingerprints = fingerprints(source, &ast);
+        self.changed = fingerprints.iter()
+            .filter(|(name, fingerprint)| self.fingerprints.get(*name) != Some(fingerprint))
+            .map(|(name, _)| name.clone())
+            .collect();
+        let removed = self.fingerprints.keys().any(|name| !fingerprints.contains_key(name));
+        self.changed.sort();
+
+        // Source comments carry line numbers, which shift with edits elsewhere
+        let reusable = !removed && self.changed.is_empty() && !self.options.source_comments;
+        let stats = count_stats(&ast, &self.options);
+        self.templates.fingerprints = fingerprints.clone();
+        let files = match self.files.take() {
+            Some(files) if reusable => files,
+            _ => generate_files_cached(ast, &symbols, &self.options, Some(&mut self.templates)),
+        };
+
+        self.fingerprints = fingerprints;
+        self.files = Some(files.clone());
+        finish(files, diagnostics, stats, &self.options)
+    }
+
+    /// Parse each declaration separately, reusing the ones seen before
+    fn parse(&mut self, source: &str) -> Result<Program, Vec<crate::Diagnostic>> {
+        let tokens = lex_source(source)?;
+        let eof = tokens.last().expect("the lexer always ends with Eof").clone();
+
+        let mut body = Vec::new();
+        let mut cache = HashMap::new();
+        for span in split_declarations(&tokens) {
+            let start = span[0].location;
+            let end = span[span.len() - 1].location.end;
+            let key = hash(&source[start.start..end]);
+
+            let declaration = match self.declarations.remove(&key) {
+                Some((mut declaration, parsed_at)) => {
+                    if parsed_at != start {
+                        relocate_declaration(&mut declaration, &Relocation { from: parsed_at, to: start });
+                    }
+                    declaration
+                }
+                None => {
+                    let mut span = span.to_vec();
+                    span.push(eof.clone());
+                    let parsed = parser::parse_with_options(&span, parse_options(&self.options))
+                        .ok()
+                        .and_then(|program| <[Declaration; 1]>::try_from(program.body).ok());
+                    match parsed {
+                        Some([declaration]) => declaration,
+                        // Let the full parser report errors with their usual context
+                        None => return parse_source(source, parse_options(&self.options)),
+                    }
+                }
+            };
+            cache.insert(key, (declaration.clone(), start));
+            body.push(declaration);
+        }
+
+        // Only keep what the current source still contains
+        self.declarations = cache;
+        Ok(Program {
+            body,
+            loc: Location {
+                end: eof.location.end,
+                ..tokens[0].location
+            },
+        })
+    }
+}
+
+/// Token runs for each top-level declaration, split at `component`, `section`
+/// and `page` keywords outside any braces
+fn split_declarations(tokens: &[Token]) -> Vec<&[Token]> {
+    let mut spans = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        match token.kind {
+            TokenKind::LBrace => depth += 1,
+            TokenKind::RBrace => depth = depth.saturating_sub(1),
+            TokenKind::Component | TokenKind::Section | TokenKind::Layout | TokenKind::Page | TokenKind::Eof
+                if depth == 0 && i > start =>
+            {
+                spans.push(&tokens[start..i]);
+                start = i;
+            }
+            _ => {}
+        }
+    }
+    spans
+}
+
+/// Fingerprint each declaration by its source and, transitively, the
+/// components, sections and layout it uses
+fn fingerprints(source: &str, program: &Program) -> HashMap<String, u64> {
+    let texts: HashMap<&str, &str> = program.body.iter()
+        .map(|decl| {
+            let (name, loc) = match decl {
+                Declaration::Component(c) => (c.name.as_str(), c.loc),
+                Declaration::Section(s) => (s.name.as_str(), s.loc),
+                Declaration::Layout(l) => (l.name.as_str(), l.loc),
+                Declaration::Page(p) => (p.name.as_str(), p.loc),
+            };
+            (name, &source[loc.start..loc.end])
+        })
+        .collect();
+    let bodies = declaration_bodies(program);
+
+    let layouts: HashMap<&str, &str> = program.body.iter()
+        .filter_map(|decl| match decl {
+            Declaration::Page(p) => Some((p.name.as_str(), p.layout.as_deref()?)),
+            _ => None,
+        })
+        .collect();
+
+    bodies.iter()
+        .map(|(&name, &body)| {
+            let mut used = BTreeSet::new();
+            if let Some(&layout) = layouts.get(name) {
+                used.insert(layout);
+                if let Some(body) = bodies.get(layout) {
+                    collect_references(body, &bodies, &mut used);
+                }
+            }
+            collect_references(body, &bodies, &mut used);
+            let sources: Vec<&str> = used.iter()
+                .filter_map(|dependency| texts.get(dependency).copied())
+                .collect();
+            (name.to_string(), hash(&(texts[name], sources)))
+        })
+        .collect()
+}
+
+/// Each declaration's body by name
+pub(crate) fn declaration_bodies(program: &Program) -> HashMap<&str, &[Node]> {
+    program.body.iter()
+        .map(|decl| match decl {
+            Declaration::Component(c) => (c.name.as_str(), c.body.as_slice()),
+            Declaration::Section(s) => (s.name.as_str(), s.body.as_slice()),
+            Declaration::Layout(l) => (l.name.as_str(), l.body.as_slice()),
+            Declaration::Page(p) => (p.name.as_str(), p.body.as_slice()),
+        })
+        .collect()
+}
+
+/// Every component or section reachable from `nodes`, cycles included once
+pub(crate) fn collect_references<'a>(
+    nodes: &'a [Node],
+    declarations: &HashMap<&str, &'a [Node]>,
+    used: &mut BTreeSet<&'a str>,
+) {
+    for node in nodes {
+        match node {
+            Node::Element(el) => collect_references(&el.children, declarations, used),
+            Node::ComponentRef(r) => {
+                if used.insert(r.name.as_str()) {
+                    if let Some(body) = declarations.get(r.name.as_str()) {
+                        collect_references(body, declarations, used);
+                    }
+                }
+                collect_references(&r.children, declarations, used);
+                for fill in &r.slot_fills {
+                    collect_references(&fill.children, declarations, used);
+                }
+            }
+            Node::If(stmt) => {
+                let mut branch = Some(stmt);
+                while let Some(stmt) = branch {
+                    collect_references(&stmt.consequent, declarations, used);
+                    branch = match &stmt.alternate {
+                        Some(Alternate::Block(nodes)) => {
+                            collect_references(nodes, declarations, used);
+                            None
+                        }
+                        Some(Alternate::ElseIf(elif)) => Some(elif),
+                        None => None,
+                    };
+                }
+            }
+            Node::Each(stmt) => {
+                collect_references(&stmt.body, declarations, used);
+                if let Some(empty) = &stmt.empty {
+                    collect_references(empty, declarations, used);
+                }
+            }
+            Node::Text(_) | Node::Slot(_) | Node::Let(_) | Node::Comment(_) | Node::RawHtml(_) => {}
+        }
+    }
+}
+
+/// Where a reused declaration started when it was parsed, and where it
+/// starts now
+struct Relocation {
+    from: Location,
+    to: Location,
+}
+
+impl Relocation {
+    /// Shift `loc` along with the declaration; columns only move on its first line
+    fn apply(&self, loc: &mut Location) {
+        if loc.line == self.from.line {
+            loc.column = loc.column - self.from.column + self.to.column;
+        }
+        loc.line = loc.line - self.from.line + self.to.line;
+        loc.start = loc.start - self.from.start + self.to.start;
+        loc.end = loc.end - self.from.start + self.to.start;
+    }
+}
+
+fn relocate_declaration(decl: &mut Declaration, r: &Relocation) {
+    match decl {
+        Declaration::Component(c) => {
+            r.apply(&mut c.loc);
+            for param in &mut c.parameters {
+                r.apply(&mut param.loc);
+            }
+            for attr in &mut c.attributes {
+                relocate_attribute(attr, r);
+            }
+            relocate_nodes(&mut c.body, r);
+        }
+        Declaration::Section(s) => {
+            r.apply(&mut s.loc);
+            for param in &mut s.parameters {
+                r.apply(&mut param.loc);
+            }
+            relocate_nodes(&mut s.body, r);
+        }
+        Declaration::Layout(l) => {
+            r.apply(&mut l.loc);
+            relocate_nodes(&mut l.body, r);
+        }
+        Declaration::Page(p) => {
+            r.apply(&mut p.loc);
+            relocate_nodes(&mut p.head, r);
+            relocate_nodes(&mut p.body, r);
+        }
+    }
+}
+
+fn relocate_nodes(nodes: &mut [Node], r: &Relocation) {
+    for node in nodes {
+        match node {
+            Node::Element(el) => relocate_element(el, r),
+            Node::ComponentRef(c) => {
+                r.apply(&mut c.loc);
+                for param in &mut c.parameters {
+                    r.apply(&mut param.loc);
+                    relocate_expression(&mut param.value, r);
+                }
+                relocate_nodes(&mut c.children, r);
+                for fill in &mut c.slot_fills {
+                    r.apply(&mut fill.loc);
+                    relocate_nodes(&mut fill.children, r);
+                }
+            }
+            Node::Text(t) => {
+                r.apply(&mut t.loc);
+                for part in &mut t.parts {
+                    if let TextPart::Dynamic(expr) = part {
+                        relocate_expression(expr, r);
+                    }
+                }
+            }
+            Node::If(stmt) => relocate_if(stmt, r),
+            Node::Each(stmt) => {
+                r.apply(&mut stmt.loc);
+                relocate_expression(&mut stmt.iterable, r);
+                relocate_nodes(&mut stmt.body, r);
+                if let Some(empty) = &mut stmt.empty {
+                    relocate_nodes(empty, r);
+                }
+            }
+            Node::Slot(slot) => r.apply(&mut slot.loc),
+            Node::Let(stmt) => {
+                r.apply(&mut stmt.loc);
+                relocate_expression(&mut stmt.value, r);
+            }
+            Node::Comment(comment) => r.apply(&mut comment.loc),
+            Node::RawHtml(raw) => {
+                r.apply(&mut raw.loc);
+                relocate_expression(&mut raw.value, r);
+            }
+        }
+    }
+}
+
+fn relocate_element(el: &mut Element, r: &Relocation) {
+    r.apply(&mut el.loc);
+    for attr in &mut el.attributes {
+        relocate_attribute(attr, r);
+    }
+    if let Some(directive) = &mut el.for_directive {
+        r.apply(&mut directive.loc);
+        relocate_expression(&mut directive.iterable, r);
+    }
+    if let Some(directive) = &mut el.if_directive {
+        r.apply(&mut directive.loc);
+        relocate_expression(&mut directive.condition, r);
+        if let Some(else_element) = &mut directive.else_element {
+            relocate_element(else_element, r);
+        }
+    }
+    relocate_nodes(&mut el.children, r);
+}
+
+fn relocate_if(stmt: &mut IfStatement, r: &Relocation) {
+    r.apply(&mut stmt.loc);
+    relocate_expression(&mut stmt.condition, r);
+    relocate_nodes(&mut stmt.consequent, r);
+    match &mut stmt.alternate {
+        Some(Alternate::Block(nodes)) => relocate_nodes(nodes, r),
+        Some(Alternate::ElseIf(elif)) => relocate_if(elif, r),
+        None => {}
+    }
+}
+
+fn relocate_attribute(attr: &mut Attribute, r: &Relocation) {
+    r.apply(&mut attr.loc);
+    relocate_expression(&mut attr.value, r);
+}
+
+fn relocate_expression(expr: &mut Expression, r: &Relocation) {
+    match expr {
+        Expression::String(e) => r.apply(&mut e.loc),
+        Expression::Number(e) => r.apply(&mut e.loc),
+        Expression::Boolean(e) => r.apply(&mut e.loc),
+        Expression::Null(e) => r.apply(&mut e.loc),
+        Expression::Array(e) => {
+            r.apply(&mut e.loc);
+            for element in &mut e.elements {
+                relocate_expression(element, r);
+            }
+        }
+        Expression::Object(e) => {
+            r.apply(&mut e.loc);
+            for property in &mut e.properties {
+                r.apply(&mut property.loc);
+                relocate_expression(&mut property.value, r);
+            }
+        }
+        Expression::ContextPath(e) => {
+            r.apply(&mut e.loc);
+            for loc in &mut e.segment_locs {
+                r.apply(loc);
+            }
+        }
+        Expression::Identifier(e) => r.apply(&mut e.loc),
+        Expression::MemberAccess(e) | Expression::OptionalMember(e) => {
+            r.apply(&mut e.loc);
+            r.apply(&mut e.property_loc);
+            relocate_expression(&mut e.object, r);
+        }
+        Expression::Binary(e) => {
+            r.apply(&mut e.loc);
+            r.apply(&mut e.operator_loc);
+            relocate_expression(&mut e.left, r);
+            relocate_expression(&mut e.right, r);
+        }
+        Expression::Ternary(e) => {
+            r.apply(&mut e.loc);
+            relocate_expression(&mut e.condition, r);
+            relocate_expression(&mut e.consequent, r);
+            relocate_expression(&mut e.alternate, r);
+        }
+        Expression::Call(e) => {
+            r.apply(&mut e.loc);
+            for argument in &mut e.arguments {
+                relocate_expression(argument, r);
+            }
+        }
+        Expression::Event(e) => {
+            r.apply(&mut e.loc);
+            for argument in &mut e.arguments {
+                relocate_expression(argument, r);
+            }
+        }
+        Expression::Spread(e) => {
+            r.apply(&mut e.loc);
+            relocate_expression(&mut e.expr, r);
+        }
+        Expression::Range(e) => {
+            r.apply(&mut e.loc);
+            relocate_expression(&mut e.start, r);
+            relocate_expression(&mut e.end, r);
+        }
+    }
+}
+
+pub(crate) fn hash(value: &(impl Hash + ?Sized)) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"component Badge { span [class: "badge"] {{ New }} }
+component Logo { img [src: "/logo.png", alt: "Logo"] }
+page home "/" { Logo Badge }
+page about "/about" { Logo }
+"#;
+
+    #[test]
+    fn test_editing_a_component_invalidates_its_pages() {
+        let mut compiler = Compiler::new();
+        let first = compiler.compile(SOURCE);
+        assert!(first.success);
+        assert_eq!(compiler.changed(), ["Badge", "Logo", "about", "home"]);
+
+        let edited = SOURCE.replace("{{ New }}", "{{ Fresh }}");
+        let second = compiler.compile(&edited);
+        assert!(second.success);
+        assert_eq!(compiler.changed(), ["Badge", "home"]);
+
+        let templates = second.files.iter().find(|f| f.path == "templates.ts").unwrap();
+        assert!(templates.content.contains("Fresh"));
+        assert_eq!(second.files.len(), compile_fresh(&edited).files.len());
+    }
+
+    #[test]
+    fn test_unchanged_source_reuses_output() {
+        let mut compiler = Compiler::new();
+        let first = compiler.compile(SOURCE);
+
+        // A comment shifts later declarations without changing them
+        let shifted = format!("// header\n{}", SOURCE);
+        let second = compiler.compile(&shifted);
+        assert!(compiler.changed().is_empty());
+        assert_eq!(
+            first.files.iter().map(|f| &f.content).collect::<Vec<_>>(),
+            second.files.iter().map(|f| &f.content).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_errors_match_a_full_compile() {
+        let mut compiler = Compiler::new();
+        compiler.compile(SOURCE);
+
+        let broken = SOURCE.replace("page about \"/about\" { Logo }", "page about \"/about\" { div [ }");
+        let result = compiler.compile(&broken);
+        assert!(!result.success);
+        assert_eq!(
+            result.diagnostics.iter().map(|d| &d.message).collect::<Vec<_>>(),
+            compile_fresh(&broken).diagnostics.iter().map(|d| &d.message).collect::<Vec<_>>()
+        );
+
+        // Fixing it recompiles
+        assert!(compiler.compile(SOURCE).success);
+    }
+
+    #[test]
+    fn test_declaration_parse_matches_a_full_parse() {
+        let mut compiler = Compiler::new();
+        compiler.compile(SOURCE);
+
+        let edited = SOURCE.replace("{{ New }}", "{{ Fresh }}");
+        let full = parse_source(&edited, parse_options(&CompileOptions::default())).unwrap();
+        assert_eq!(compiler.parse(&edited).unwrap(), full);
+    }
+
+    #[test]
+    fn test_moved_declaration_is_reused_at_its_new_location() {
+        let mut compiler = Compiler::new();
+        compiler.compile(SOURCE);
+
+        // Logo moves down a line and along, unchanged
+        let edited = SOURCE.replace("{{ New }}", "{{\n  Brand new }}");
+        let full = parse_source(&edited, parse_options(&CompileOptions::default())).unwrap();
+        assert_eq!(compiler.parse(&edited).unwrap(), full);
+        assert_eq!(full.body[1].loc().line, 3);
+
+        // It comes from the cache rather than a new parse
+        compiler.compile(SOURCE);
+        for (declaration, _) in compiler.declarations.values_mut() {
+            if let Declaration::Component(logo) = declaration {
+                logo.name = format!("Cached{}", logo.name);
+            }
+        }
+        let parsed = compiler.parse(&edited).unwrap();
+        assert_eq!(parsed.body[0].name(), "Badge");
+        assert_eq!(parsed.body[1].name(), "CachedLogo");
+    }
+
+    fn compile_fresh(source: &str) -> CompileResult {
+        crate::compile(source)
+    }
+}
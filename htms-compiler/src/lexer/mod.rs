@@ -6,4 +6,5 @@ mod tokens;
 mod scanner;
 
 pub use tokens::{Token, TokenKind};
-pub use scanner::tokenize;
+pub use scanner::{tokenize, tokenize_with_trivia};
+pub(crate) use scanner::line_break_ends;
@@ -7,6 +7,24 @@ use super::tokens::{Token, TokenKind};
 
 /// Tokenize HTMS source code
 pub fn tokenize(source: &str) -> Result<Vec<Token>, Vec<LexerError>> {
+    let (tokens, errors) = scan(source, false);
+    if errors.is_empty() {
+        Ok(tokens)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Tokenize HTMS source code for editors, keeping going past errors
+///
+/// Unlike `tokenize`, the stream also holds comments, newlines and an
+/// `Error` token for each unexpected character, so every byte a highlighter
+/// needs to color is covered.
+pub fn tokenize_with_trivia(source: &str) -> (Vec<Token>, Vec<LexerError>) {
+    scan(source, true)
+}
+
+fn scan(source: &str, keep_trivia: bool) -> (Vec<Token>, Vec<LexerError>) {
     let mut tokens = Vec::new();
     let mut errors = Vec::new();
     let mut lexer = TokenKind::lexer(source);
@@ -15,7 +33,13 @@ pub fn tokenize(source: &str) -> Result<Vec<Token>, Vec<LexerError>> {
     let mut line_start = 0;
     let mut in_text_content = false;
     let mut text_start = 0;
+    let mut text_line = 1;
+    let mut text_column = 1;
     let mut text_content = String::new();
+    // Whether the open text block ends in the whitespace-keeping `-}}`
+    let mut text_close_marker = false;
+    // Braces open outside text, so `}}` can close two blocks at once
+    let mut depth = 0usize;
 
     while let Some(result) = lexer.next() {
         let span = lexer.span();
@@ -37,31 +61,30 @@ pub fn tokenize(source: &str) -> Result<Vec<Token>, Vec<LexerError>> {
                 if in_text_content {
                     if kind == TokenKind::TextClose {
                         // End text content
+                        let close_start = span.start - usize::from(text_close_marker);
                         if !text_content.is_empty() {
                             tokens.push(Token {
                                 kind: TokenKind::TextContent,
                                 value: text_content.clone(),
                                 location: Location {
-                                    line,
-                                    column: text_start - line_start + 1,
+                                    line: text_line,
+                                    column: text_column,
                                     start: text_start,
-                                    end: span.start,
+                                    end: close_start,
                                 },
                             });
                             text_content.clear();
                         }
                         tokens.push(Token {
                             kind: TokenKind::TextClose,
-                            value: "}}".to_string(),
-                            location,
+                            value: if text_close_marker { "-}}" } else { "}}" }.to_string(),
+                            location: Location {
+                                column: location.column - (span.start - close_start),
+                                start: close_start,
+                                ..location
+                            },
                         });
                         in_text_content = false;
-                    } else {
-                        // Text content already captured, skip tokens until }}
-                        if kind == TokenKind::Newline {
-                            line += 1;
-                            line_start = span.end;
-                        }
                     }
                     continue;
                 }
@@ -69,43 +92,96 @@ pub fn tokenize(source: &str) -> Result<Vec<Token>, Vec<LexerError>> {
                 // Handle special tokens
                 match kind {
                     TokenKind::TextOpen => {
+                        // `{{-` and `-}}` keep the whitespace next to them instead of trimming it.
+                        // The opener needs whitespace after it so `{{-5 degrees}}` stays text,
+                        // and only text opened that way can end in `-}}`, so `{{ a -}}` keeps its dash.
+                        let mut after_open = source[span.end..].chars();
+                        let open_marker = after_open.next() == Some('-')
+                            && after_open.next().is_some_and(char::is_whitespace);
+                        let open_end = span.end + usize::from(open_marker);
                         tokens.push(Token {
                             kind: TokenKind::TextOpen,
-                            value: "{{".to_string(),
-                            location,
+                            value: if open_marker { "{{-" } else { "{{" }.to_string(),
+                            location: Location { end: open_end, ..location },
                         });
                         in_text_content = true;
-                        text_start = span.end;
+                        text_start = open_end;
+                        text_line = line;
+                        text_column = text_start - line_start + 1;
 
-                        // Manually capture text content until we find }}
-                        // Use character-based string operations for proper UTF-8 handling
+                        // Manually capture text content until we find }}, then
+                        // skip the lexer past it so quotes or comment markers in
+                        // the text can't swallow the close. `find` only returns
+                        // char boundaries, so this is safe for any UTF-8 tail.
                         let remaining = &source[text_start..];
-                        if let Some(end_pos) = remaining.find("}}") {
-                            text_content = remaining[..end_pos].to_string();
+                        let end_pos = remaining.find("}}").unwrap_or(remaining.len());
+                        text_content = remaining[..end_pos].to_string();
+                        text_close_marker = open_marker && text_content.ends_with('-');
+                        if text_close_marker && end_pos < remaining.len() {
+                            text_content.pop();
                         } else {
-                            // No closing }}, capture rest of content
-                            text_content = remaining.to_string();
+                            text_close_marker = false;
+                        }
+                        lexer.bump(text_start + end_pos - span.end);
+
+                        // Newlines inside the text never reach the lexer
+                        for end in line_break_ends(&remaining[..end_pos]) {
+                            line += 1;
+                            line_start = text_start + end;
+                        }
+                    }
+                    TokenKind::TextClose if depth >= 2 => {
+                        for i in 0..2 {
+                            tokens.push(Token {
+                                kind: TokenKind::RBrace,
+                                value: "}".to_string(),
+                                location: Location {
+                                    column: location.column + i,
+                                    start: span.start + i,
+                                    end: span.start + i + 1,
+                                    ..location
+                                },
+                            });
+                        }
+                        depth -= 2;
+                    }
+                    TokenKind::TextClose => {
+                        if keep_trivia {
+                            tokens.push(Token {
+                                kind: TokenKind::Error,
+                                value: slice.to_string(),
+                                location,
+                            });
                         }
+                        errors.push(LexerError::new("Unexpected '}}' outside text content", location));
+                        depth = 0;
                     }
                     TokenKind::Newline => {
+                        if keep_trivia {
+                            tokens.push(Token {
+                                kind,
+                                value: slice.to_string(),
+                                location,
+                            });
+                        }
                         line += 1;
                         line_start = span.end;
-                        // Don't add newline tokens
                     }
                     TokenKind::LineComment | TokenKind::BlockComment => {
+                        if keep_trivia {
+                            tokens.push(Token {
+                                kind,
+                                value: slice.to_string(),
+                                location,
+                            });
+                        }
                         // Count newlines in block comments
                         if kind == TokenKind::BlockComment {
-                            for c in slice.chars() {
-                                if c == '\n' {
-                                    line += 1;
-                                }
-                            }
-                            // Update line_start to after last newline
-                            if let Some(last_newline) = slice.rfind('\n') {
-                                line_start = span.start + last_newline + 1;
+                            for end in line_break_ends(slice) {
+                                line += 1;
+                                line_start = span.start + end;
                             }
                         }
-                        // Don't add comment tokens
                     }
                     TokenKind::String => {
                         // Remove quotes from string value
@@ -117,6 +193,11 @@ pub fn tokenize(source: &str) -> Result<Vec<Token>, Vec<LexerError>> {
                         });
                     }
                     _ => {
+                        match kind {
+                            TokenKind::LBrace => depth += 1,
+                            TokenKind::RBrace => depth = depth.saturating_sub(1),
+                            _ => {}
+                        }
                         tokens.push(Token {
                             kind,
                             value: slice.to_string(),
@@ -126,31 +207,40 @@ pub fn tokenize(source: &str) -> Result<Vec<Token>, Vec<LexerError>> {
                 }
             }
             Err(()) => {
-                // Handle text content mode for errors
-                if in_text_content {
-                    // Skip error tokens when in text content mode
-                    // Text is already captured manually at TextOpen
-                    if slice == "\n" {
-                        line += 1;
-                        line_start = span.end;
-                    }
-                } else {
-                    errors.push(LexerError::new(
-                        format!("Unexpected character: '{}'", slice),
+                if keep_trivia {
+                    tokens.push(Token {
+                        kind: TokenKind::Error,
+                        value: slice.to_string(),
                         location,
-                    ));
+                    });
                 }
+                errors.push(LexerError::new(
+                    format!("Unexpected character: '{}'", slice),
+                    location,
+                ));
             }
         }
     }
 
     // Check for unclosed text content
     if in_text_content {
+        if keep_trivia && !text_content.is_empty() {
+            tokens.push(Token {
+                kind: TokenKind::TextContent,
+                value: text_content.clone(),
+                location: Location {
+                    line: text_line,
+                    column: text_column,
+                    start: text_start,
+                    end: source.len(),
+                },
+            });
+        }
         errors.push(LexerError::new(
             "Unterminated text content: missing '}}'",
             Location {
-                line,
-                column: text_start - line_start + 1,
+                line: text_line,
+                column: text_column,
                 start: text_start,
                 end: source.len(),
             },
@@ -169,11 +259,19 @@ pub fn tokenize(source: &str) -> Result<Vec<Token>, Vec<LexerError>> {
         },
     });
 
-    if errors.is_empty() {
-        Ok(tokens)
-    } else {
-        Err(errors)
-    }
+    (tokens, errors)
+}
+
+/// Offset just past each line break in `text`, treating `\r\n` as one break
+pub(crate) fn line_break_ends(text: &str) -> impl Iterator<Item = usize> + '_ {
+    let bytes = text.as_bytes();
+    (0..bytes.len()).filter_map(move |i| match bytes[i] {
+        b'\n' if i > 0 && bytes[i - 1] == b'\r' => None,
+        b'\n' => Some(i + 1),
+        b'\r' if bytes.get(i + 1) == Some(&b'\n') => Some(i + 2),
+        b'\r' => Some(i + 1),
+        _ => None,
+    })
 }
 
 #[cfg(test)]
@@ -219,6 +317,60 @@ mod tests {
         assert_eq!(tokens[2].kind, TokenKind::TextClose);
     }
 
+    #[test]
+    fn test_whitespace_markers() {
+        let tokens = tokenize("{{- kept -}}").unwrap();
+
+        assert_eq!(tokens[0].value, "{{-");
+        assert_eq!(tokens[0].location.end, 3);
+        assert_eq!(tokens[1].value, " kept ");
+        assert_eq!(tokens[2].value, "-}}");
+        assert_eq!((tokens[2].location.start, tokens[2].location.column), (9, 10));
+
+        // A dash that isn't part of a marker stays in the text
+        for (source, text) in [("{{-5 degrees}}", "-5 degrees"), ("{{ a -}}", " a -"), ("{{-}}", "-")] {
+            let tokens = tokenize(source).unwrap();
+            assert_eq!((tokens[0].value.as_str(), tokens[2].value.as_str()), ("{{", "}}"), "{}", source);
+            assert_eq!(tokens[1].value, text);
+        }
+    }
+
+    #[test]
+    fn test_unterminated_text_at_eof() {
+        for source in ["x {{", "{{", "{{-", "p {{ caf\u{e9}", "p {{ \u{65e5}\u{672c}-"] {
+            let errors = tokenize(source).unwrap_err();
+            assert_eq!(errors.len(), 1, "{:?}", source);
+            assert_eq!(errors[0].message, "Unterminated text content: missing '}}'");
+            assert_eq!(errors[0].location.end, source.len());
+
+            let (tokens, _) = tokenize_with_trivia(source);
+            assert_eq!(tokens.last().unwrap().location.start, source.len());
+        }
+    }
+
+    #[test]
+    fn test_text_is_not_lexed() {
+        let source = "p {{ say \"hi\n}}\nspan {{ /* there */ }}";
+        let tokens = tokenize(source).unwrap();
+        let values: Vec<&str> = tokens.iter().map(|t| t.value.as_str()).collect();
+
+        assert_eq!(values, vec!["p", "{{", " say \"hi\n", "}}", "span", "{{", " /* there */ ", "}}", ""]);
+        assert_eq!((tokens[4].location.line, tokens[4].location.column), (3, 1));
+    }
+
+    #[test]
+    fn test_stray_text_close() {
+        let tokens = tokenize("div { p { br }}").unwrap();
+        let kinds: Vec<TokenKind> = tokens.iter().map(|t| t.kind).collect();
+        assert_eq!(&kinds[5..], [TokenKind::RBrace, TokenKind::RBrace, TokenKind::Eof]);
+        assert_eq!((tokens[6].location.start, tokens[6].location.column), (14, 15));
+
+        let errors = tokenize("component A {\n  p {{ hi }} }}\n}").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "Unexpected '}}' outside text content");
+        assert_eq!((errors[0].location.line, errors[0].location.column), (2, 14));
+    }
+
     #[test]
     fn test_context_path() {
         let source = "ctx.user.name";
@@ -250,6 +402,22 @@ mod tests {
         assert_eq!(tokens[3].location.line, 4);
     }
 
+    #[test]
+    fn test_crlf_line_endings() {
+        let positions = |source: &str| {
+            tokenize(source).unwrap()
+                .iter()
+                .map(|t| (t.location.line, t.location.column))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(positions("component\r\nFoo"), positions("component\nFoo"));
+        assert_eq!(positions("component\rFoo"), positions("component\nFoo"));
+
+        let lf = "// note\ncomponent /* a\nb */ Foo {\n p {{ x\ny }} }";
+        assert_eq!(positions(&lf.replace('\n', "\r\n")), positions(lf));
+    }
+
     #[test]
     fn test_comments_ignored() {
         let source = "// comment\ncomponent /* inline */ NavBar";
@@ -258,4 +426,24 @@ mod tests {
         assert_eq!(tokens[0].kind, TokenKind::Component);
         assert_eq!(tokens[1].kind, TokenKind::ComponentName);
     }
+
+    #[test]
+    fn test_trivia_and_errors_are_kept() {
+        let source = "// note\ndiv ~ /* x */ p";
+        let (tokens, errors) = tokenize_with_trivia(source);
+        let kinds: Vec<TokenKind> = tokens.iter().map(|t| t.kind).collect();
+
+        assert_eq!(kinds, vec![
+            TokenKind::LineComment,
+            TokenKind::Newline,
+            TokenKind::Identifier,
+            TokenKind::Error,
+            TokenKind::BlockComment,
+            TokenKind::Identifier,
+            TokenKind::Eof,
+        ]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(tokens[3].location.line, 2);
+        assert!(tokenize(source).is_err());
+    }
 }
@@ -1,10 +1,11 @@
 //! Token definitions for HTMS
 
 use logos::Logos;
+use serde::Serialize;
 use crate::Location;
 
 /// Token with location information
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Token {
     pub kind: TokenKind,
     pub value: String,
@@ -12,8 +13,11 @@ pub struct Token {
 }
 
 /// Token types generated by the lexer
-#[derive(Logos, Debug, Clone, Copy, PartialEq, Eq)]
-#[logos(skip r"[ \t\r]+")]  // Skip whitespace (but not newlines)
+///
+/// Serializes as the variant name (`"ComponentName"`), which is stable for tooling;
+/// `name()` is the human-readable form used in error messages.
+#[derive(Logos, Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[logos(skip r"[ \t]+")]  // Skip whitespace (but not newlines)
 pub enum TokenKind {
     // =========================================================================
     // Keywords
@@ -24,6 +28,9 @@ pub enum TokenKind {
     #[token("section")]
     Section,
 
+    #[token("layout")]
+    Layout,
+
     #[token("page")]
     Page,
 
@@ -36,6 +43,9 @@ pub enum TokenKind {
     #[token("false")]
     False,
 
+    #[token("null")]
+    Null,
+
     // =========================================================================
     // Directives
     // =========================================================================
@@ -54,6 +64,15 @@ pub enum TokenKind {
     #[token("@slot")]
     Slot,
 
+    #[token("@let")]
+    Let,
+
+    #[token("@comment")]
+    Comment,
+
+    #[token("@html")]
+    Html,
+
     // =========================================================================
     // Punctuation
     // =========================================================================
@@ -84,9 +103,27 @@ pub enum TokenKind {
     #[token(".")]
     Dot,
 
+    #[token("#")]
+    Hash,
+
+    #[token("...")]
+    Spread,
+
+    #[token("..")]
+    Range,
+
+    #[token("..<")]
+    RangeExclusive,
+
     #[token("?")]
     Question,
 
+    #[token("?.")]
+    QuestionDot,
+
+    #[token("??")]
+    DoubleQuestion,
+
     #[token("{{")]
     TextOpen,
 
@@ -99,6 +136,9 @@ pub enum TokenKind {
     // =========================================================================
     // Operators
     // =========================================================================
+    #[token("=")]
+    Assign,
+
     #[token("==")]
     Eq,
 
@@ -138,6 +178,9 @@ pub enum TokenKind {
     #[token("/")]
     Slash,
 
+    #[token("%")]
+    Percent,
+
     // =========================================================================
     // Literals and Identifiers
     // =========================================================================
@@ -162,19 +205,23 @@ pub enum TokenKind {
     #[regex(r"[A-Z][a-zA-Z0-9]*")]
     ComponentName,
 
+    /// Loop helper inside `@each`: $even, $odd
+    #[regex(r"\$[a-z][a-zA-Z0-9_]*")]
+    LoopHelper,
+
     // =========================================================================
     // Comments and Whitespace
     // =========================================================================
     /// Single-line comment: // ...
-    #[regex(r"//[^\n]*")]
+    #[regex(r"//[^\r\n]*")]
     LineComment,
 
     /// Block comment: /* ... */
     #[regex(r"/\*[^*]*\*+(?:[^/*][^*]*\*+)*/")]
     BlockComment,
 
-    /// Newline (tracked for line counting)
-    #[token("\n")]
+    /// Newline (tracked for line counting): `\n`, `\r\n` or a lone `\r`
+    #[regex(r"\r\n|\r|\n")]
     Newline,
 
     // =========================================================================
@@ -183,6 +230,9 @@ pub enum TokenKind {
     /// Text content between {{ and }}
     TextContent,
 
+    /// Unrecognized input, only produced by `tokenize_with_trivia`
+    Error,
+
     /// End of file
     Eof,
 }
@@ -203,15 +253,20 @@ impl TokenKind {
         match self {
             TokenKind::Component => "'component'",
             TokenKind::Section => "'section'",
+            TokenKind::Layout => "'layout'",
             TokenKind::Page => "'page'",
             TokenKind::As => "'as'",
             TokenKind::True => "'true'",
             TokenKind::False => "'false'",
+            TokenKind::Null => "'null'",
             TokenKind::If => "'@if'",
             TokenKind::Else => "'@else'",
             TokenKind::Each => "'@each'",
             TokenKind::For => "'@for'",
             TokenKind::Slot => "'@slot'",
+            TokenKind::Let => "'@let'",
+            TokenKind::Comment => "'@comment'",
+            TokenKind::Html => "'@html'",
             TokenKind::LBracket => "'['",
             TokenKind::RBracket => "']'",
             TokenKind::LBrace => "'{'",
@@ -221,10 +276,17 @@ impl TokenKind {
             TokenKind::Colon => "':'",
             TokenKind::Comma => "','",
             TokenKind::Dot => "'.'",
+            TokenKind::Hash => "'#'",
+            TokenKind::Spread => "'...'",
+            TokenKind::Range => "'..'",
+            TokenKind::RangeExclusive => "'..<'",
             TokenKind::Question => "'?'",
+            TokenKind::QuestionDot => "'?.'",
+            TokenKind::DoubleQuestion => "'??'",
             TokenKind::TextOpen => "'{{'",
             TokenKind::TextClose => "'}}'",
             TokenKind::InterpolationStart => "'${'",
+            TokenKind::Assign => "'='",
             TokenKind::Eq => "'=='",
             TokenKind::Ne => "'!='",
             TokenKind::Ge => "'>='",
@@ -238,15 +300,18 @@ impl TokenKind {
             TokenKind::Minus => "'-'",
             TokenKind::Star => "'*'",
             TokenKind::Slash => "'/'",
+            TokenKind::Percent => "'%'",
             TokenKind::String => "string",
             TokenKind::Number => "number",
             TokenKind::ContextPath => "context path",
             TokenKind::Identifier => "identifier",
             TokenKind::ComponentName => "component name",
+            TokenKind::LoopHelper => "loop helper",
             TokenKind::LineComment => "comment",
             TokenKind::BlockComment => "comment",
             TokenKind::Newline => "newline",
             TokenKind::TextContent => "text content",
+            TokenKind::Error => "invalid character",
             TokenKind::Eof => "end of file",
         }
     }
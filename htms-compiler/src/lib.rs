@@ -18,6 +18,16 @@ pub mod analyzer;
 pub mod codegen;
 pub mod error;
 pub mod ast;
+mod render;
+mod incremental;
+mod stats;
+mod files;
+mod formatter;
+
+pub use incremental::Compiler;
+pub use files::compile_files;
+pub use formatter::format;
+pub use stats::CompileStats;
 
 use serde::{Deserialize, Serialize};
 
@@ -42,8 +52,31 @@ pub enum Severity {
     Info,
 }
 
-/// Source location
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+impl Severity {
+    fn rank(self) -> u8 {
+        match self {
+            Severity::Info => 0,
+            Severity::Warning => 1,
+            Severity::Error => 2,
+        }
+    }
+}
+
+/// Ordered by how serious a diagnostic is: `Error > Warning > Info`
+impl Ord for Severity {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.rank().cmp(&other.rank())
+    }
+}
+
+impl PartialOrd for Severity {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Source location, which takes part in AST node equality
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Location {
     pub line: usize,
     pub column: usize,
@@ -59,32 +92,235 @@ pub struct Diagnostic {
     pub location: Location,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub code: Option<String>,
+    /// Source file the diagnostic belongs to, when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+}
+
+impl Diagnostic {
+    /// A diagnostic with `code`'s default severity
+    pub fn new(code: DiagnosticCode, message: impl Into<String>, location: Location) -> Self {
+        Diagnostic {
+            severity: code.default_severity(),
+            message: message.into(),
+            location,
+            code: Some(code.code().to_string()),
+            file: None,
+        }
+    }
+}
+
+/// Every diagnostic the compiler reports, serialized as its code string
+///
+/// `E` codes are errors, `W` and `A` (accessibility) codes are warnings,
+/// and `I` codes are informational hints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiagnosticCode {
+    /// The lexer hit a character or literal it can't tokenize
+    LexError,
+    /// The source doesn't match the grammar
+    ParseError,
+    /// A reference, binding or declaration that doesn't resolve
+    ResolveError,
+    /// Generated HTML that isn't well-formed, which means a generator bug
+    MalformedOutput,
+    /// Unused declarations, unknown identifiers and other likely mistakes
+    ResolveWarning,
+    /// A tag that is neither a known HTML element nor a custom element
+    UnknownTag,
+    /// A self-closing `/` on an element that isn't void
+    SelfClosingNonVoid,
+    /// A loop variable with the same name as a component parameter
+    LoopShadowsParameter,
+    /// An action used once that is one edit away from a common one
+    ActionTypo,
+    /// A component named like an HTML element
+    ComponentNamedLikeElement,
+    /// A chained comparison such as `a < b < c`
+    ChainedComparison,
+    /// A binding named `ctx`
+    ShadowedContext,
+    /// A `<script>` tag inside `@html`
+    ScriptInRawHtml,
+    /// An internal link that matches no page route
+    UnknownRoute,
+    /// Children passed to a component without a default `@slot`
+    ChildrenWithoutSlot,
+    /// An attribute set again later on the same element
+    RepeatedAttribute,
+    /// A constant range too long, or with bounds too large, to unroll
+    UnrolledRange,
+    /// An `<img>` without `alt`
+    MissingAlt,
+    /// An `<a>` without `href`
+    MissingHref,
+    /// An `<input>` without a label
+    UnlabelledInput,
+    /// A `<button>` without text or `aria-label`
+    EmptyButton,
+    /// A `role` that isn't a WAI-ARIA role
+    UnknownRole,
+    /// An `aria-*` attribute that isn't a WAI-ARIA state or property
+    UnknownAriaAttribute,
+    /// A `<div>` whose class names a landmark element
+    LandmarkDiv,
+    /// Event handlers dropped by static HTML output
+    DroppedEventHandlers,
+    /// A `role` that repeats the element's implicit role
+    RedundantRole,
+    /// A clickable `<div>` or `<span>` that could be a `<button>`
+    ClickableElement,
+}
+
+impl DiagnosticCode {
+    /// The stable string form, such as `"E001"`
+    pub fn code(&self) -> &'static str {
+        match self {
+            DiagnosticCode::LexError => "E001",
+            DiagnosticCode::ParseError => "E002",
+            DiagnosticCode::ResolveError => "E003",
+            DiagnosticCode::MalformedOutput => "E021",
+            DiagnosticCode::ResolveWarning => "W001",
+            DiagnosticCode::UnknownTag => "W002",
+            DiagnosticCode::SelfClosingNonVoid => "W047",
+            DiagnosticCode::LoopShadowsParameter => "W048",
+            DiagnosticCode::ActionTypo => "W049",
+            DiagnosticCode::ComponentNamedLikeElement => "W050",
+            DiagnosticCode::ChainedComparison => "W051",
+            DiagnosticCode::ShadowedContext => "W052",
+            DiagnosticCode::ScriptInRawHtml => "W053",
+            DiagnosticCode::UnknownRoute => "W054",
+            DiagnosticCode::ChildrenWithoutSlot => "W055",
+            DiagnosticCode::RepeatedAttribute => "W056",
+            DiagnosticCode::UnrolledRange => "W057",
+            DiagnosticCode::MissingAlt => "A001",
+            DiagnosticCode::MissingHref => "A002",
+            DiagnosticCode::UnlabelledInput => "A003",
+            DiagnosticCode::EmptyButton => "A004",
+            DiagnosticCode::UnknownRole => "A005",
+            DiagnosticCode::UnknownAriaAttribute => "A006",
+            DiagnosticCode::LandmarkDiv => "I001",
+            DiagnosticCode::DroppedEventHandlers => "I002",
+            DiagnosticCode::RedundantRole => "I003",
+            DiagnosticCode::ClickableElement => "I004",
+        }
+    }
+
+    /// Error for `E` codes, info for `I` codes, warning for the rest
+    pub fn default_severity(&self) -> Severity {
+        match self.code().as_bytes()[0] {
+            b'E' => Severity::Error,
+            b'I' => Severity::Info,
+            _ => Severity::Warning,
+        }
+    }
 }
 
 /// Compilation result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CompileResult {
-    /// Generated files
+    /// Generated files, sorted by path
     pub files: Vec<GeneratedFile>,
     /// Diagnostics (errors, warnings)
     pub diagnostics: Vec<Diagnostic>,
     /// Whether compilation succeeded (no errors)
     pub success: bool,
+    /// Output sizes and template counts, with `collect_stats`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<CompileStats>,
+}
+
+impl CompileResult {
+    /// The generated files, or the error diagnostics if compilation failed
+    ///
+    /// Warnings are dropped either way; read `diagnostics` first to keep them.
+    pub fn into_result(self) -> Result<Vec<GeneratedFile>, Vec<Diagnostic>> {
+        if self.success {
+            Ok(self.files)
+        } else {
+            Err(self.diagnostics
+                .into_iter()
+                .filter(|d| d.severity == Severity::Error)
+                .collect())
+        }
+    }
+
+    /// The most serious severity among the diagnostics, if there are any
+    pub fn max_severity(&self) -> Option<Severity> {
+        self.diagnostics.iter().map(|d| d.severity).max()
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.count(Severity::Error)
+    }
+
+    pub fn warning_count(&self) -> usize {
+        self.count(Severity::Warning)
+    }
+
+    fn count(&self, severity: Severity) -> usize {
+        self.diagnostics.iter().filter(|d| d.severity == severity).count()
+    }
 }
 
 /// Output format for compilation
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum OutputFormat {
     /// Generate TypeScript/JavaScript (default)
+    #[default]
     Typescript,
     /// Generate static HTML
     Html,
 }
 
-impl Default for OutputFormat {
+/// Module syntax for the generated TypeScript
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModuleFormat {
+    /// `import`/`export` (default)
+    #[default]
+    Esm,
+    /// `import = require`, which the TypeScript compiler turns into CommonJS
+    Cjs,
+}
+
+/// Doctype that starts a standalone HTML document
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Doctype {
+    /// `<!DOCTYPE html>` (default)
+    #[default]
+    Html5,
+    /// XHTML 1.0 Strict, with the XHTML namespace on `<html>`
+    Xhtml,
+    /// No doctype
+    None,
+}
+
+/// Indentation of generated HTML
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Indent {
+    /// This many spaces per level
+    Spaces(usize),
+    /// One tab per level
+    Tabs,
+}
+
+impl Default for Indent {
     fn default() -> Self {
-        Self::Typescript
+        Indent::Spaces(2)
+    }
+}
+
+impl Indent {
+    /// Leading whitespace for `depth` levels
+    pub fn level(self, depth: usize) -> String {
+        match self {
+            Indent::Spaces(width) => " ".repeat(width * depth),
+            Indent::Tabs => "\t".repeat(depth),
+        }
     }
 }
 
@@ -109,6 +345,69 @@ pub struct CompileOptions {
     /// Split templates into separate files for lazy loading (only for html output)
     #[serde(default)]
     pub split_templates: bool,
+    /// Routes whose template files get a `<link rel="prefetch">` in the head (only with split_templates)
+    #[serde(default)]
+    pub preload_routes: Vec<String>,
+    /// Allow `@if a, b { }` as shorthand for `@if a && b { }`
+    #[serde(default)]
+    pub comma_conditions: bool,
+    /// Prefix generated functions with a comment pointing back to the source (only for typescript output)
+    #[serde(default)]
+    pub source_comments: bool,
+    /// Emit one HTML file with the generated code inlined as a module script (only for typescript output)
+    #[serde(default)]
+    pub single_file: bool,
+    /// Run the accessibility lints (A0xx warnings)
+    #[serde(default = "default_true")]
+    pub lint_a11y: bool,
+    /// Suggest semantic elements, such as `nav` or `button`, in place of generic ones (I0xx info)
+    #[serde(default)]
+    pub semantic_hints: bool,
+    /// Emit context.d.ts with a context interface per component, section and page (only for typescript output)
+    #[serde(default)]
+    pub emit_context_types: bool,
+    /// Check every generated HTML file for balanced tags and quoted attributes (E021 on failure)
+    #[serde(default)]
+    pub validate_output: bool,
+    /// Drop empty, attribute-less elements such as `span { }` that render as nothing
+    #[serde(default)]
+    pub prune_empty: bool,
+    /// Add a content hash to split template filenames: `home.template.1a2b3c4d.html` (only with split_templates)
+    #[serde(default)]
+    pub hash_filenames: bool,
+    /// Wrap start tags with long attribute lists onto continuation lines (only for html output)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_line_width: Option<usize>,
+    /// Also emit index.html linking to every page, sorted by route (only for html output)
+    #[serde(default)]
+    pub emit_index: bool,
+    /// Emit components rendered more than once as a single shared `<template>` (only for inline html output)
+    #[serde(default)]
+    pub dedupe_components: bool,
+    /// Add `data-testid="<name>-<tag>-<n>"` to every element without one
+    #[serde(default)]
+    pub auto_testids: bool,
+    /// Module syntax for templates.ts, router.ts and events.ts (only for typescript output)
+    #[serde(default)]
+    pub module_format: ModuleFormat,
+    /// On errors, still generate output for the declarations that have none and use none that do
+    #[serde(default)]
+    pub best_effort: bool,
+    /// Report output sizes and template counts in `CompileResult::stats`
+    #[serde(default)]
+    pub collect_stats: bool,
+    /// Indentation of generated HTML, two spaces by default (only for html output)
+    #[serde(default)]
+    pub indent: Indent,
+    /// Emit events.json listing every event handler's element, event and action (only for typescript output)
+    #[serde(default)]
+    pub emit_events_manifest: bool,
+    /// `lang` of standalone documents' `<html>` tag, `"en"` by default; `None` leaves it out
+    #[serde(default = "default_html_lang")]
+    pub html_lang: Option<String>,
+    /// Doctype of standalone documents
+    #[serde(default)]
+    pub doctype: Doctype,
 }
 
 impl Default for CompileOptions {
@@ -120,6 +419,27 @@ impl Default for CompileOptions {
             template_html: None,
             source_filename: None,
             split_templates: false,
+            preload_routes: Vec::new(),
+            comma_conditions: false,
+            source_comments: false,
+            single_file: false,
+            lint_a11y: true,
+            semantic_hints: false,
+            emit_context_types: false,
+            validate_output: false,
+            prune_empty: false,
+            hash_filenames: false,
+            max_line_width: None,
+            emit_index: false,
+            dedupe_components: false,
+            auto_testids: false,
+            module_format: ModuleFormat::Esm,
+            best_effort: false,
+            collect_stats: false,
+            indent: Indent::Spaces(2),
+            emit_events_manifest: false,
+            html_lang: default_html_lang(),
+            doctype: Doctype::Html5,
         }
     }
 }
@@ -128,79 +448,296 @@ fn default_true() -> bool {
     true
 }
 
+fn default_html_lang() -> Option<String> {
+    Some("en".to_string())
+}
+
 /// Compile HTMS source code to HBS templates and TypeScript
 pub fn compile(source: &str) -> CompileResult {
     compile_with_options(source, &CompileOptions::default())
 }
 
+/// Parse HTMS source code and serialize the AST as JSON (for tooling)
+pub fn parse_to_json(source: &str) -> Result<String, Vec<Diagnostic>> {
+    let program = parse_source(source, parser::ParseOptions::default())?;
+    Ok(serde_json::to_string(&program).expect("AST serialization cannot fail"))
+}
+
+/// Tokenize HTMS source code and serialize the token stream as JSON (for tooling)
+///
+/// Each token carries its `kind` (the `TokenKind` variant name), `value` and `location`.
+pub fn tokenize_to_json(source: &str) -> Result<String, Vec<Diagnostic>> {
+    let tokens = lex_source(source)?;
+    Ok(serde_json::to_string(&tokens).expect("token serialization cannot fail"))
+}
+
+/// Compile a single HTMS expression, such as `ctx.count > 0 ? "some" : "none"`,
+/// to the TypeScript expression the template generator would emit for it
+pub fn compile_expression_to_ts(source: &str) -> Result<String, Vec<Diagnostic>> {
+    let tokens = lex_source(source)?;
+    let expr = parser::parse_expression(&tokens)
+        .map_err(|errors| errors.into_iter().map(parser_diagnostic).collect::<Vec<_>>())?;
+    Ok(codegen::expr_to_js(&expr))
+}
+
+/// A token for syntax highlighting, with a kind name that stays stable across releases
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenInfo {
+    /// `TokenKind` variant name, e.g. `"Identifier"`, `"LineComment"`, `"Error"`
+    pub kind: String,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Tokenize HTMS source code for an editor
+///
+/// Never stops at an error: unexpected characters become `Error` tokens and
+/// are also reported as diagnostics. Comments and newlines are included.
+pub fn lex(source: &str) -> (Vec<TokenInfo>, Vec<Diagnostic>) {
+    let (tokens, errors) = lexer::tokenize_with_trivia(source);
+    let tokens = tokens.into_iter()
+        .map(|token| TokenInfo {
+            kind: format!("{:?}", token.kind),
+            start: token.location.start,
+            end: token.location.end,
+            line: token.location.line,
+            column: token.location.column,
+        })
+        .collect();
+    (tokens, errors.into_iter().map(lexer_diagnostic).collect())
+}
+
+/// Serialize diagnostics as JSON Lines, one diagnostic per line
+pub fn diagnostics_to_jsonl(diagnostics: &[Diagnostic]) -> String {
+    diagnostics
+        .iter()
+        .map(|d| serde_json::to_string(d).expect("diagnostic serialization cannot fail") + "\n")
+        .collect()
+}
+
 /// Compile HTMS source code with options
 pub fn compile_with_options(source: &str, options: &CompileOptions) -> CompileResult {
-    let mut diagnostics = Vec::new();
+    with_source_filename(compile_source(source, options), options)
+}
 
-    // Phase 1: Lexing
-    let tokens = match lexer::tokenize(source) {
-        Ok(tokens) => tokens,
-        Err(errors) => {
-            for err in errors {
-                diagnostics.push(Diagnostic {
-                    severity: Severity::Error,
-                    message: err.message,
-                    location: err.location,
-                    code: Some("E001".to_string()),
-                });
-            }
-            return CompileResult {
-                files: vec![],
-                diagnostics,
-                success: false,
-            };
+fn with_source_filename(mut result: CompileResult, options: &CompileOptions) -> CompileResult {
+    if let Some(filename) = &options.source_filename {
+        for diagnostic in &mut result.diagnostics {
+            diagnostic.file.get_or_insert_with(|| filename.clone());
         }
-    };
+    }
+    result
+}
 
-    // Phase 2: Parsing
-    let ast = match parser::parse(&tokens) {
+fn compile_source(source: &str, options: &CompileOptions) -> CompileResult {
+    // Phase 1 & 2: Lexing and parsing
+    let ast = match parse_source(source, parse_options(options)) {
         Ok(ast) => ast,
-        Err(errors) => {
-            for err in errors {
-                diagnostics.push(Diagnostic {
-                    severity: Severity::Error,
-                    message: err.message,
-                    location: err.location,
-                    code: Some("E002".to_string()),
-                });
-            }
-            return CompileResult {
-                files: vec![],
-                diagnostics,
-                success: false,
-            };
-        }
+        Err(errors) => return failed(errors),
     };
 
+    compile_program(ast, options, |_| {})
+}
+
+/// Analyze and generate code for a parsed program
+///
+/// `attribute` sees the analysis diagnostics once best-effort output has
+/// picked the declarations to keep, so it may rewrite their locations.
+fn compile_program(ast: ast::Program, options: &CompileOptions, attribute: impl Fn(&mut [Diagnostic])) -> CompileResult {
     // Phase 3: Analysis
-    let (symbols, analysis_diagnostics) = analyzer::analyze(&ast);
-    diagnostics.extend(analysis_diagnostics);
-
-    // Check for errors
-    let has_errors = diagnostics.iter().any(|d| d.severity == Severity::Error);
-    if has_errors {
-        return CompileResult {
-            files: vec![],
-            diagnostics,
-            success: false,
-        };
-    }
+    let (symbols, mut diagnostics) = analyze_program(&ast, options);
+    let ast = if !has_errors(&diagnostics) {
+        Some(ast)
+    } else {
+        best_effort_program(&ast, &diagnostics, options)
+    };
+    attribute(&mut diagnostics);
+    let Some(ast) = ast else {
+        return failed(diagnostics);
+    };
 
     // Phase 4: Code Generation
-    let files = codegen::generate(&ast, &symbols, options);
+    let stats = count_stats(&ast, options);
+    let files = generate_files(ast, &symbols, options);
+    finish(files, diagnostics, stats, options)
+}
+
+fn parse_options(options: &CompileOptions) -> parser::ParseOptions {
+    parser::ParseOptions {
+        comma_conditions: options.comma_conditions,
+    }
+}
+
+/// Run the resolver and every lint the options enable
+fn analyze_program(ast: &ast::Program, options: &CompileOptions) -> (analyzer::SymbolTable, Vec<Diagnostic>) {
+    let (symbols, mut diagnostics) = analyzer::analyze(ast);
+    if options.lint_a11y {
+        diagnostics.extend(analyzer::lint_a11y(ast));
+    }
+    if options.semantic_hints {
+        diagnostics.extend(analyzer::suggest_semantic_html(ast));
+    }
+    diagnostics.extend(analyzer::check_output_format(ast, options.output_format));
+    diagnostics.extend(analyzer::check_action_names(ast));
+    (symbols, diagnostics)
+}
 
+fn generate_files(ast: ast::Program, symbols: &analyzer::SymbolTable, options: &CompileOptions) -> Vec<GeneratedFile> {
+    generate_files_cached(ast, symbols, options, None)
+}
+
+fn generate_files_cached(
+    mut ast: ast::Program,
+    symbols: &analyzer::SymbolTable,
+    options: &CompileOptions,
+    cache: Option<&mut codegen::TemplateCache>,
+) -> Vec<GeneratedFile> {
+    analyzer::drop_overridden_attributes(&mut ast);
+    if options.prune_empty {
+        analyzer::prune_empty(&mut ast);
+    }
+    codegen::generate_cached(&ast, symbols, options, cache)
+}
+
+fn count_stats(ast: &ast::Program, options: &CompileOptions) -> Option<CompileStats> {
+    options.collect_stats.then(|| CompileStats::count(ast))
+}
+
+/// Validate the output if asked, measure it for `stats`, and decide success
+/// from the final diagnostics
+fn finish(
+    files: Vec<GeneratedFile>,
+    mut diagnostics: Vec<Diagnostic>,
+    mut stats: Option<CompileStats>,
+    options: &CompileOptions,
+) -> CompileResult {
+    if options.validate_output {
+        diagnostics.extend(validate_files(&files, options));
+    }
+    if let Some(stats) = &mut stats {
+        stats.measure(&files);
+    }
+
+    let success = !has_errors(&diagnostics);
     CompileResult {
         files,
         diagnostics,
-        success: true,
+        success,
+        stats,
+    }
+}
+
+/// With `best_effort`, the program without the declarations that have errors
+/// or use one that does, directly or through a layout
+///
+/// `None` when the option is off, when an error lies outside every
+/// declaration, or when nothing is left to generate.
+fn best_effort_program(ast: &ast::Program, diagnostics: &[Diagnostic], options: &CompileOptions) -> Option<ast::Program> {
+    use std::collections::{BTreeSet, HashSet};
+
+    if !options.best_effort {
+        return None;
+    }
+
+    let spans: Vec<(&str, Location)> = ast.body.iter()
+        .map(|decl| match decl {
+            ast::Declaration::Component(c) => (c.name.as_str(), c.loc),
+            ast::Declaration::Section(s) => (s.name.as_str(), s.loc),
+            ast::Declaration::Layout(l) => (l.name.as_str(), l.loc),
+            ast::Declaration::Page(p) => (p.name.as_str(), p.loc),
+        })
+        .collect();
+
+    let mut broken = HashSet::new();
+    for diagnostic in diagnostics.iter().filter(|d| d.severity == Severity::Error) {
+        let offset = diagnostic.location.start;
+        let (name, _) = spans.iter().find(|(_, loc)| loc.start <= offset && offset < loc.end)?;
+        broken.insert(*name);
+    }
+
+    let bodies = incremental::declaration_bodies(ast);
+    let keep = |decl: &ast::Declaration| {
+        let mut used = BTreeSet::new();
+        let (name, body) = match decl {
+            ast::Declaration::Component(c) => (c.name.as_str(), &c.body),
+            ast::Declaration::Section(s) => (s.name.as_str(), &s.body),
+            ast::Declaration::Layout(l) => (l.name.as_str(), &l.body),
+            ast::Declaration::Page(p) => {
+                if let Some(layout) = &p.layout {
+                    used.insert(layout.as_str());
+                    if let Some(body) = bodies.get(layout.as_str()) {
+                        incremental::collect_references(body, &bodies, &mut used);
+                    }
+                }
+                (p.name.as_str(), &p.body)
+            }
+        };
+        incremental::collect_references(body, &bodies, &mut used);
+        !broken.contains(name) && used.iter().all(|dependency| !broken.contains(dependency))
+    };
+
+    let body: Vec<_> = ast.body.iter().filter(|decl| keep(decl)).cloned().collect();
+    if body.is_empty() {
+        return None;
+    }
+    Some(ast::Program { body, loc: ast.loc })
+}
+
+fn failed(diagnostics: Vec<Diagnostic>) -> CompileResult {
+    CompileResult {
+        files: vec![],
+        diagnostics,
+        success: false,
+        stats: None,
     }
 }
 
+fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(|d| d.severity == Severity::Error)
+}
+
+/// Report generated HTML that isn't well-formed, which means a generator bug
+fn validate_files(files: &[GeneratedFile], options: &CompileOptions) -> Vec<Diagnostic> {
+    let check = match options.doctype {
+        Doctype::Xhtml => codegen::check_well_formed_xml,
+        _ => codegen::check_well_formed,
+    };
+    files.iter()
+        .filter(|file| file.path.ends_with(".html"))
+        .filter_map(|file| {
+            let problem = check(&file.content).err()?;
+            Some(Diagnostic::new(
+                DiagnosticCode::MalformedOutput,
+                format!("Generated {} is malformed: {}", file.path, problem),
+                Location { line: 1, column: 1, start: 0, end: 0 },
+            ))
+        })
+        .collect()
+}
+
+/// Lex source code, converting failures to diagnostics
+fn lex_source(source: &str) -> Result<Vec<lexer::Token>, Vec<Diagnostic>> {
+    lexer::tokenize(source).map_err(|errors| errors.into_iter().map(lexer_diagnostic).collect())
+}
+
+fn lexer_diagnostic(err: error::LexerError) -> Diagnostic {
+    Diagnostic::new(DiagnosticCode::LexError, err.message, err.location)
+}
+
+/// Lex and parse source code, converting failures to diagnostics
+fn parse_source(source: &str, options: parser::ParseOptions) -> Result<ast::Program, Vec<Diagnostic>> {
+    let tokens = lex_source(source)?;
+
+    parser::parse_with_options(&tokens, options)
+        .map_err(|errors| errors.into_iter().map(parser_diagnostic).collect())
+}
+
+fn parser_diagnostic(err: error::ParseError) -> Diagnostic {
+    Diagnostic::new(DiagnosticCode::ParseError, err.message, err.location)
+}
+
 // ============================================================================
 // WASM Bindings
 // ============================================================================
@@ -237,12 +774,7 @@ pub fn check_wasm(source: &str) -> JsValue {
         Ok(tokens) => tokens,
         Err(errors) => {
             for err in errors {
-                diagnostics.push(Diagnostic {
-                    severity: Severity::Error,
-                    message: err.message,
-                    location: err.location,
-                    code: Some("E001".to_string()),
-                });
+                diagnostics.push(lexer_diagnostic(err));
             }
             return serde_wasm_bindgen::to_value(&diagnostics).unwrap();
         }
@@ -251,7 +783,62 @@ pub fn check_wasm(source: &str) -> JsValue {
     if let Ok(ast) = parser::parse(&tokens) {
         let (_, analysis_diagnostics) = analyzer::analyze(&ast);
         diagnostics.extend(analysis_diagnostics);
+        diagnostics.extend(analyzer::lint_a11y(&ast));
+        diagnostics.extend(analyzer::check_action_names(&ast));
     }
 
     serde_wasm_bindgen::to_value(&diagnostics).unwrap()
 }
+
+#[cfg(feature = "wasm")]
+#[derive(Serialize)]
+struct ParseWasmResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ast: Option<ast::Program>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn parse_wasm(source: &str) -> JsValue {
+    // Lex and parse only, returning the AST for tooling
+    let result = match parse_source(source, parser::ParseOptions::default()) {
+        Ok(ast) => ParseWasmResult { ast: Some(ast), diagnostics: Vec::new() },
+        Err(diagnostics) => ParseWasmResult { ast: None, diagnostics },
+    };
+    serde_wasm_bindgen::to_value(&result).unwrap()
+}
+
+#[cfg(feature = "wasm")]
+#[derive(Serialize)]
+struct FormatWasmResult {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diagnostics: Option<Vec<Diagnostic>>,
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn format_wasm(source: &str) -> JsValue {
+    let result = match format(source) {
+        Ok(output) => FormatWasmResult { ok: true, output: Some(output), diagnostics: None },
+        Err(diagnostics) => FormatWasmResult { ok: false, output: None, diagnostics: Some(diagnostics) },
+    };
+    serde_wasm_bindgen::to_value(&result).unwrap()
+}
+
+#[cfg(feature = "wasm")]
+#[derive(Serialize)]
+struct LexWasmResult {
+    tokens: Vec<TokenInfo>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn lex_wasm(source: &str) -> JsValue {
+    let (tokens, diagnostics) = lex(source);
+    serde_wasm_bindgen::to_value(&LexWasmResult { tokens, diagnostics }).unwrap()
+}
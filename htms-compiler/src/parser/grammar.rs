@@ -4,20 +4,53 @@ use crate::ast::*;
 use crate::error::ParseError;
 use crate::lexer::{Token, TokenKind};
 use crate::Location;
+use super::ParseOptions;
+use crate::analyzer::is_known_tag;
+
+/// Classes and id written as `div.card.active#main`
+#[derive(Default)]
+struct Shorthand {
+    classes: Vec<String>,
+    id: Option<(String, Location)>,
+    /// Span of the whole shorthand
+    loc: Option<Location>,
+}
+
+/// Tokens that can name a class or id in shorthand, keywords included: `div.page`
+fn is_selector_name(kind: TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::Identifier | TokenKind::ComponentName | TokenKind::Component | TokenKind::Section |
+        TokenKind::Layout | TokenKind::Page | TokenKind::As | TokenKind::True | TokenKind::False | TokenKind::Null
+    )
+}
 
 /// Parser state
 pub struct Parser<'a> {
     tokens: &'a [Token],
     current: usize,
     errors: Vec<ParseError>,
+    options: ParseOptions,
+    /// Inside a `pre`/`textarea`: text nodes keep their exact whitespace
+    preserve_whitespace: bool,
+    /// Parameters, loop variables and `@let` names in scope, which tell
+    /// `item.name` apart from `div.card`
+    locals: Vec<String>,
 }
 
 impl<'a> Parser<'a> {
     pub fn new(tokens: &'a [Token]) -> Self {
+        Self::with_options(tokens, ParseOptions::default())
+    }
+
+    pub fn with_options(tokens: &'a [Token], options: ParseOptions) -> Self {
         Self {
             tokens,
             current: 0,
             errors: Vec::new(),
+            options,
+            preserve_whitespace: false,
+            locals: Vec::new(),
         }
     }
 
@@ -51,6 +84,18 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Parse a single expression that must span every token
+    pub fn parse_expression(mut self) -> Result<Expression, Vec<ParseError>> {
+        let expr = match self.expression() {
+            Ok(expr) => expr,
+            Err(e) => return Err(vec![e]),
+        };
+        if !self.is_at_end() {
+            return Err(vec![self.error("Unexpected token after expression")]);
+        }
+        Ok(expr)
+    }
+
     // =========================================================================
     // Declarations
     // =========================================================================
@@ -60,10 +105,12 @@ impl<'a> Parser<'a> {
             self.component_decl().map(Declaration::Component)
         } else if self.check(TokenKind::Section) {
             self.section_decl().map(Declaration::Section)
+        } else if self.check(TokenKind::Layout) {
+            self.layout_decl().map(Declaration::Layout)
         } else if self.check(TokenKind::Page) {
             self.page_decl().map(Declaration::Page)
         } else {
-            Err(self.error("Expected 'component', 'section', or 'page'"))
+            Err(self.error("Expected 'component', 'section', 'layout', or 'page'"))
         }
     }
 
@@ -82,14 +129,10 @@ impl<'a> Parser<'a> {
         };
 
         // Optional attributes: [class: "foo"]
-        let attributes = if self.check(TokenKind::LBracket) {
-            self.attribute_list()?
-        } else {
-            Vec::new()
-        };
+        let attributes = self.attribute_blocks()?;
 
         // Body
-        let body = self.block()?;
+        let body = self.scoped(parameters.iter().map(|p| p.binding.clone()), Self::block)?;
 
         Ok(ComponentDecl {
             name,
@@ -107,9 +150,33 @@ impl<'a> Parser<'a> {
         let name = self.consume(TokenKind::ComponentName, "Expected section name")?;
         let name = name.value.clone();
 
-        let body = self.block()?;
+        // Optional parameters: (title: heading)
+        let parameters = if self.check(TokenKind::LParen) {
+            self.parameter_list()?
+        } else {
+            Vec::new()
+        };
+
+        let body = self.scoped(parameters.iter().map(|p| p.binding.clone()), Self::block)?;
 
         Ok(SectionDecl {
+            name,
+            parameters,
+            body,
+            loc: self.location_from(start),
+        })
+    }
+
+    fn layout_decl(&mut self) -> Result<LayoutDecl, ParseError> {
+        let start = self.current_location();
+        self.consume(TokenKind::Layout, "Expected 'layout'")?;
+
+        let name = self.consume(TokenKind::ComponentName, "Expected layout name")?;
+        let name = name.value.clone();
+
+        let body = self.block()?;
+
+        Ok(LayoutDecl {
             name,
             body,
             loc: self.location_from(start),
@@ -126,11 +193,36 @@ impl<'a> Parser<'a> {
         let route = self.consume(TokenKind::String, "Expected route string")?;
         let route = route.value.clone();
 
+        // Optional layout: uses Main
+        let layout = if self.check(TokenKind::Identifier) && self.peek().value == "uses" {
+            self.advance();
+            Some(self.consume(TokenKind::ComponentName, "Expected layout name after 'uses'")?.value.clone())
+        } else {
+            None
+        };
+
         let body = self.block()?;
 
+        // A top-level `head { }` holds the page's document head rather than markup
+        let (heads, body): (Vec<Node>, Vec<Node>) = body.into_iter()
+            .partition(|node| matches!(node, Node::Element(el) if el.tag == "head"));
+        let mut head = Vec::new();
+        for (i, node) in heads.into_iter().enumerate() {
+            let Node::Element(el) = node else { continue };
+            if i > 0 {
+                self.errors.push(ParseError::new("A page can only have one head block".to_string(), el.loc));
+            } else if !el.attributes.is_empty() || el.for_directive.is_some() || el.if_directive.is_some() {
+                self.errors.push(ParseError::new("A head block takes no attributes or directives".to_string(), el.loc));
+            } else {
+                head = el.children;
+            }
+        }
+
         Ok(PageDecl {
             name,
             route,
+            layout,
+            head,
             body,
             loc: self.location_from(start),
         })
@@ -147,7 +239,7 @@ impl<'a> Parser<'a> {
         if !self.check(TokenKind::RParen) {
             loop {
                 params.push(self.parameter()?);
-                if !self.match_token(TokenKind::Comma) {
+                if !self.list_continues(TokenKind::RParen) {
                     break;
                 }
             }
@@ -157,6 +249,17 @@ impl<'a> Parser<'a> {
         Ok(params)
     }
 
+    /// Whether another entry follows in a `[...]` or `(...)` list
+    ///
+    /// Entries are separated by a comma or a line break, and a trailing comma
+    /// before `close` is allowed. Two entries on one line still need a comma.
+    fn list_continues(&mut self, close: TokenKind) -> bool {
+        if self.match_token(TokenKind::Comma) {
+            return !self.check(close);
+        }
+        !self.check(close) && !self.is_at_end() && self.peek().location.line > self.previous().location.line
+    }
+
     fn parameter(&mut self) -> Result<Parameter, ParseError> {
         let start = self.current_location();
         let name = self.consume(TokenKind::Identifier, "Expected parameter name")?;
@@ -174,31 +277,80 @@ impl<'a> Parser<'a> {
         })
     }
 
+    /// Any number of attribute lists in a row, `[class: "a"] [id: "b"]`, joined
+    /// in order; a repeated name is resolved to its last value before output
+    fn attribute_blocks(&mut self) -> Result<Vec<Attribute>, ParseError> {
+        let mut attributes = Vec::new();
+        while self.check(TokenKind::LBracket) {
+            attributes.extend(self.attribute_list()?);
+        }
+        Ok(attributes)
+    }
+
     fn attribute_list(&mut self) -> Result<Vec<Attribute>, ParseError> {
         self.consume(TokenKind::LBracket, "Expected '['")?;
         let mut attrs = Vec::new();
 
         if !self.check(TokenKind::RBracket) {
             loop {
-                attrs.push(self.attribute()?);
-                if !self.match_token(TokenKind::Comma) {
+                match self.attribute() {
+                    Ok(attr) => attrs.push(attr),
+                    Err(e) => {
+                        // Record and skip to the closing ']' so the body still parses
+                        self.errors.push(e);
+                        self.recover_attribute_list();
+                        return Ok(attrs);
+                    }
+                }
+                if !self.list_continues(TokenKind::RBracket) {
                     break;
                 }
             }
         }
 
-        self.consume(TokenKind::RBracket, "Expected ']'")?;
+        if let Err(e) = self.consume(TokenKind::RBracket, "Expected ']'") {
+            self.errors.push(e);
+            self.recover_attribute_list();
+        }
         Ok(attrs)
     }
 
     fn attribute(&mut self) -> Result<Attribute, ParseError> {
         let start = self.current_location();
+
+        // Spread attribute: `...ctx.buttonProps`
+        if self.match_token(TokenKind::Spread) {
+            let expr = self.expression()?;
+            return Ok(Attribute {
+                name: "...".to_string(),
+                value: Expression::Spread(SpreadAttr {
+                    expr: Box::new(expr),
+                    loc: self.location_from(start),
+                }),
+                loc: self.location_from(start),
+            });
+        }
+
         let name = self.consume(TokenKind::Identifier, "Expected attribute name")?;
         let name = name.value.clone();
+        // Identifiers may contain hyphens, for `data-user-id` and `aria-hidden`,
+        // but a name can't end with one
+        if name.ends_with('-') {
+            self.errors.push(ParseError::new(
+                format!("Attribute name '{}' cannot end with '-'", name),
+                self.location_from(start),
+            ));
+        }
 
-        self.consume(TokenKind::Colon, "Expected ':'")?;
-
-        let value = self.expression()?;
+        // Presence attribute: `[required]` is shorthand for `[required: true]`
+        let value = if self.match_token(TokenKind::Colon) {
+            self.expression()?
+        } else {
+            Expression::Boolean(BooleanLiteral {
+                value: true,
+                loc: self.location_from(start),
+            })
+        };
 
         Ok(Attribute {
             name,
@@ -214,11 +366,20 @@ impl<'a> Parser<'a> {
     fn block(&mut self) -> Result<Vec<Node>, ParseError> {
         self.consume(TokenKind::LBrace, "Expected '{'")?;
         let mut nodes = Vec::new();
+        let outer_locals = self.locals.len();
 
         while !self.check(TokenKind::RBrace) && !self.is_at_end() {
-            nodes.push(self.node()?);
+            match self.node() {
+                Ok(node) => nodes.push(node),
+                Err(e) => {
+                    // Record and skip the malformed node so later siblings are still checked
+                    self.errors.push(e);
+                    self.recover_node();
+                }
+            }
         }
 
+        self.locals.truncate(outer_locals);
         self.consume(TokenKind::RBrace, "Expected '}'")?;
         Ok(nodes)
     }
@@ -230,6 +391,12 @@ impl<'a> Parser<'a> {
             self.each_statement().map(Node::Each)
         } else if self.check(TokenKind::Slot) {
             self.slot().map(Node::Slot)
+        } else if self.check(TokenKind::Let) {
+            self.let_statement().map(Node::Let)
+        } else if self.check(TokenKind::Comment) {
+            self.comment().map(Node::Comment)
+        } else if self.check(TokenKind::Html) {
+            self.raw_html().map(Node::RawHtml)
         } else if self.check(TokenKind::TextOpen) {
             self.text_node().map(Node::Text)
         } else if self.check(TokenKind::ContextPath) {
@@ -239,8 +406,7 @@ impl<'a> Parser<'a> {
         } else if self.check(TokenKind::Identifier) {
             // Check if this is dynamic text (identifier followed by . for member access)
             // or if it's an HTML element tag
-            let lookahead_pos = self.current + 1;
-            if lookahead_pos < self.tokens.len() && self.tokens[lookahead_pos].kind == TokenKind::Dot {
+            if self.check_next(TokenKind::Dot) && self.is_member_access(&self.peek().value) {
                 // This is member access like item.name - treat as dynamic text
                 self.dynamic_expression_text().map(Node::Text)
             } else {
@@ -252,16 +418,20 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Whether `name.` reads a property rather than starting `div.card`:
+    /// locals always win, and only element tags take class shorthand
+    fn is_member_access(&self, name: &str) -> bool {
+        self.locals.iter().any(|local| local == name) || !(is_known_tag(name) || name.contains('-'))
+    }
+
     fn element(&mut self) -> Result<Element, ParseError> {
         let start = self.current_location();
         let tag = self.consume(TokenKind::Identifier, "Expected tag name")?;
         let tag = tag.value.clone();
 
-        let attributes = if self.check(TokenKind::LBracket) {
-            self.attribute_list()?
-        } else {
-            Vec::new()
-        };
+        let shorthand = self.selector_shorthand()?;
+        let mut attributes = self.attribute_blocks()?;
+        self.merge_shorthand(shorthand, &mut attributes);
 
         // Check for @for directive: @for(ctx.todos as todo, index)
         let for_directive = if self.check(TokenKind::For) {
@@ -277,14 +447,19 @@ impl<'a> Parser<'a> {
             None
         };
 
-        // Check for shorthand text: button [onClick: submit] {{ Send }}
-        let children = if self.check(TokenKind::TextOpen) {
-            // Shorthand: text directly after element/attributes
-            vec![Node::Text(self.text_node()?)]
-        } else if self.check(TokenKind::LBrace) {
-            self.block()?
-        } else {
+        // Explicit self-closing: `input [type: "text"] /`
+        let self_closing = self.match_token(TokenKind::Slash);
+
+        let children = if self_closing {
             Vec::new()
+        } else {
+            let outer_preserve = self.preserve_whitespace;
+            self.preserve_whitespace |= is_whitespace_sensitive(&tag);
+            let loop_names = for_directive.iter()
+                .flat_map(|f| std::iter::once(f.item_name.clone()).chain(f.index_name.clone()));
+            let children = self.scoped(loop_names, Self::element_children);
+            self.preserve_whitespace = outer_preserve;
+            children?
         };
 
         Ok(Element {
@@ -293,10 +468,100 @@ impl<'a> Parser<'a> {
             children,
             for_directive,
             if_directive,
+            self_closing,
             loc: self.location_from(start),
         })
     }
 
+    /// Parse `.card.active#main` directly after a tag name
+    fn selector_shorthand(&mut self) -> Result<Shorthand, ParseError> {
+        let mut shorthand = Shorthand::default();
+        while (self.check(TokenKind::Dot) || self.check(TokenKind::Hash)) && self.is_adjacent() {
+            let marker = self.advance().kind;
+            let marker_loc = self.previous().location;
+            if !self.is_adjacent() || !is_selector_name(self.peek().kind) {
+                return Err(self.error(if marker == TokenKind::Dot { "Expected class name after '.'" } else { "Expected id after '#'" }));
+            }
+            let name = self.advance().value.clone();
+            let loc = self.location_from(marker_loc);
+            if marker == TokenKind::Dot {
+                shorthand.classes.push(name);
+            } else if shorthand.id.is_some() {
+                return Err(ParseError::new("An element can only have one #id", loc));
+            } else {
+                shorthand.id = Some((name, loc));
+            }
+            shorthand.loc = Some(match shorthand.loc {
+                Some(first) => Location { end: loc.end, ..first },
+                None => loc,
+            });
+        }
+        Ok(shorthand)
+    }
+
+    /// Turn shorthand into leading `class` and `id` attributes, adding the
+    /// classes to an explicit `class` instead when there is one
+    ///
+    /// A computed `class` becomes a class list, `["tab", value]`, so a null
+    /// or false value drops out as it does in any other class list.
+    fn merge_shorthand(&mut self, shorthand: Shorthand, attributes: &mut Vec<Attribute>) {
+        let mut leading = Vec::new();
+
+        if let Some(loc) = shorthand.loc.filter(|_| !shorthand.classes.is_empty()) {
+            let classes = shorthand.classes.join(" ");
+            match attributes.iter_mut().rev().find(|attr| attr.name == "class") {
+                Some(attr) => {
+                    let end = attr.loc.end;
+                    attr.value = match std::mem::replace(&mut attr.value, Expression::Null(NullLiteral { loc })) {
+                        Expression::String(s) => Expression::String(StringLiteral {
+                            value: format!("{} {}", classes, s.value),
+                            loc: s.loc,
+                        }),
+                        Expression::Array(mut list) => {
+                            list.elements.insert(0, Expression::String(StringLiteral { value: classes, loc }));
+                            Expression::Array(list)
+                        }
+                        value => Expression::Array(ArrayLiteral {
+                            elements: vec![Expression::String(StringLiteral { value: classes, loc }), value],
+                            loc: Location { end, ..loc },
+                        }),
+                    };
+                }
+                None => leading.push(Attribute {
+                    name: "class".to_string(),
+                    value: Expression::String(StringLiteral { value: classes, loc }),
+                    loc,
+                }),
+            }
+        }
+
+        if let Some((id, loc)) = shorthand.id {
+            if let Some(attr) = attributes.iter().find(|attr| attr.name == "id") {
+                self.errors.push(ParseError::new(format!("Element has both #{} and an id attribute", id), attr.loc));
+            } else {
+                leading.push(Attribute {
+                    name: "id".to_string(),
+                    value: Expression::String(StringLiteral { value: id, loc }),
+                    loc,
+                });
+            }
+        }
+
+        attributes.splice(0..0, leading);
+    }
+
+    fn element_children(&mut self) -> Result<Vec<Node>, ParseError> {
+        // Check for shorthand text: button [onClick: submit] {{ Send }}
+        if self.check(TokenKind::TextOpen) {
+            // Shorthand: text directly after element/attributes
+            Ok(vec![Node::Text(self.text_node()?)])
+        } else if self.check(TokenKind::LBrace) {
+            self.block()
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
     fn component_ref(&mut self) -> Result<ComponentRef, ParseError> {
         let start = self.current_location();
         let name = self.consume(TokenKind::ComponentName, "Expected component name")?;
@@ -308,16 +573,54 @@ impl<'a> Parser<'a> {
             Vec::new()
         };
 
-        let children = if self.check(TokenKind::LBrace) {
-            self.block()?
+        let (children, slot_fills) = if self.check(TokenKind::LBrace) {
+            self.component_children()?
         } else {
-            Vec::new()
+            (Vec::new(), Vec::new())
         };
 
         Ok(ComponentRef {
             name,
             parameters,
             children,
+            slot_fills,
+            loc: self.location_from(start),
+        })
+    }
+
+    /// Like `block`, but `name: { ... }` entries fill named slots
+    fn component_children(&mut self) -> Result<(Vec<Node>, Vec<SlotFill>), ParseError> {
+        self.consume(TokenKind::LBrace, "Expected '{'")?;
+        let mut nodes = Vec::new();
+        let mut slot_fills = Vec::new();
+
+        while !self.check(TokenKind::RBrace) && !self.is_at_end() {
+            let result = if self.check(TokenKind::Identifier) && self.check_next(TokenKind::Colon) {
+                self.slot_fill().map(|fill| slot_fills.push(fill))
+            } else {
+                self.node().map(|node| nodes.push(node))
+            };
+            if let Err(e) = result {
+                self.errors.push(e);
+                self.recover_node();
+            }
+        }
+
+        self.consume(TokenKind::RBrace, "Expected '}'")?;
+        Ok((nodes, slot_fills))
+    }
+
+    fn slot_fill(&mut self) -> Result<SlotFill, ParseError> {
+        let start = self.current_location();
+        let name = self.consume(TokenKind::Identifier, "Expected slot name")?;
+        let name = name.value.clone();
+
+        self.consume(TokenKind::Colon, "Expected ':'")?;
+        let children = self.block()?;
+
+        Ok(SlotFill {
+            name,
+            children,
             loc: self.location_from(start),
         })
     }
@@ -329,7 +632,7 @@ impl<'a> Parser<'a> {
         if !self.check(TokenKind::RParen) {
             loop {
                 bindings.push(self.parameter_binding()?);
-                if !self.match_token(TokenKind::Comma) {
+                if !self.list_continues(TokenKind::RParen) {
                     break;
                 }
             }
@@ -355,68 +658,218 @@ impl<'a> Parser<'a> {
         })
     }
 
+    /// Parse `{{ ... }}`, joining directly adjacent segments: `{{ a }}{{ b }}`
+    ///
+    /// Segments separated by whitespace stay separate text nodes, so
+    /// `h1 {{ Title }} {{ Footer }}` still puts the footer after the heading.
     fn text_node(&mut self) -> Result<TextNode, ParseError> {
         let start = self.current_location();
-        self.consume(TokenKind::TextOpen, "Expected '{{'")?;
+        let mut written = Vec::new();
+        let (mut content, trim_start, mut trim_end) = self.text_segment(&mut written)?;
+        while self.check(TokenKind::TextOpen) && self.peek().location.start == self.previous().location.end {
+            let (segment, segment_trim_start, segment_trim_end) = self.text_segment(&mut written)?;
+            if self.preserve_whitespace {
+                content.push_str(&segment);
+            } else if trim_end && segment_trim_start {
+                // Whitespace at the seam collapses to one space, as it would in HTML
+                let spaced = content.ends_with(char::is_whitespace) || segment.starts_with(char::is_whitespace);
+                content.truncate(content.trim_end().len());
+                if spaced {
+                    content.push(' ');
+                }
+                content.push_str(segment.trim_start());
+            } else {
+                if trim_end {
+                    content.truncate(content.trim_end().len());
+                }
+                content.push_str(if segment_trim_start { segment.trim_start() } else { &segment });
+            }
+            trim_end = segment_trim_end;
+        }
+
+        let trim = if self.preserve_whitespace {
+            (false, false)
+        } else {
+            (trim_start, trim_end)
+        };
+        if trim.1 {
+            content.truncate(content.trim_end().len());
+        }
+        if trim.0 {
+            content = content.trim_start().to_string();
+        }
+
+        Ok(TextNode {
+            parts: self.text_parts(&content, &written),
+            content,
+            is_dynamic: false,
+            trim,
+            loc: self.location_from(start),
+        })
+    }
+
+    /// One `{{ ... }}` block with whether each side should be trimmed; the
+    /// text as written and where it starts are added to `written`
+    fn text_segment(&mut self, written: &mut Vec<(String, Location)>) -> Result<(String, bool, bool), ParseError> {
+        let trim_start = self.consume(TokenKind::TextOpen, "Expected '{{'")?.value == "{{";
 
         let content = if self.check(TokenKind::TextContent) {
             let token = self.advance();
+            written.push((token.value.clone(), token.location));
             token.value.clone()
         } else {
             String::new()
         };
 
-        self.consume(TokenKind::TextClose, "Expected '}}'")?;
+        let trim_end = self.consume(TokenKind::TextClose, "Expected '}}'")?.value == "}}";
+        Ok((content, trim_start, trim_end))
+    }
 
-        Ok(TextNode {
-            content: content.trim().to_string(),
-            is_dynamic: false,
-            loc: self.location_from(start),
-        })
+    /// Split text into literal runs and `${...}` expressions
+    ///
+    /// Trimming and joining blocks only touch whitespace, so the n-th
+    /// interpolation in `content` is the n-th in the blocks as `written`,
+    /// which places its expression in the source.
+    fn text_parts(&self, content: &str, written: &[(String, Location)]) -> Vec<TextPart> {
+        let re = interpolation_regex(r"[^}]+");
+        let sources: Vec<Location> = written.iter()
+            .flat_map(|(text, loc)| {
+                re.captures_iter(text).map(|cap| location_within(text, *loc, cap.get(1).map_or(0, |m| m.start())))
+            })
+            .collect();
+
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut last_end = 0;
+        for (i, cap) in re.captures_iter(content).enumerate() {
+            let full_match = cap.get(0).unwrap();
+            literal.push_str(&content[last_end..full_match.start()]);
+            last_end = full_match.end();
+
+            // `\${` is an escaped, literal `${`
+            let Some(source) = cap.get(1) else {
+                literal.push_str("${");
+                continue;
+            };
+            match sources.get(i).and_then(|&loc| self.interpolation(source.as_str(), loc)) {
+                Some(expr) => {
+                    if !literal.is_empty() {
+                        parts.push(TextPart::Static(std::mem::take(&mut literal)));
+                    }
+                    parts.push(TextPart::Dynamic(expr));
+                }
+                None => literal.push_str(full_match.as_str()),
+            }
+        }
+        literal.push_str(&content[last_end..]);
+        if !literal.is_empty() {
+            parts.push(TextPart::Static(literal));
+        }
+        parts
+    }
+
+    /// Parse the inside of a `${...}` that starts at `loc`, with the names in scope here
+    fn interpolation(&self, source: &str, loc: Location) -> Option<Expression> {
+        let tokens: Vec<Token> = crate::lexer::tokenize(source).ok()?
+            .into_iter()
+            .map(|mut token| {
+                let len = token.location.end - token.location.start;
+                token.location = location_within(source, loc, token.location.start);
+                token.location.end += len;
+                token
+            })
+            .collect();
+        let mut parser = Parser::with_options(&tokens, self.options);
+        parser.locals = self.locals.clone();
+        parser.parse_expression().ok()
     }
 
     fn dynamic_text(&mut self) -> Result<TextNode, ParseError> {
         let start = self.current_location();
-        let token = self.consume(TokenKind::ContextPath, "Expected context path")?;
+        let content = self.peek().value.clone();
+        let path = self.primary()?;
 
         Ok(TextNode {
-            content: token.value.clone(),
+            content,
             is_dynamic: true,
+            parts: vec![TextPart::Dynamic(path)],
+            trim: (true, true),
             loc: self.location_from(start),
         })
     }
 
     fn dynamic_expression_text(&mut self) -> Result<TextNode, ParseError> {
         let start = self.current_location();
+        // Only reached for `name.`, which parses to a local path
         let expr = self.expression()?;
-
-        // Convert expression to string representation for the content
-        let content = self.expr_to_string(&expr);
+        let content = match &expr {
+            Expression::ContextPath(path) => path.path(),
+            _ => String::new(),
+        };
 
         Ok(TextNode {
             content,
             is_dynamic: true,
+            parts: vec![TextPart::Dynamic(expr)],
+            trim: (true, true),
             loc: self.location_from(start),
         })
     }
 
-    fn expr_to_string(&self, expr: &Expression) -> String {
-        match expr {
-            Expression::ContextPath(p) => p.path.clone(),
-            Expression::Identifier(id) => id.name.clone(),
-            Expression::MemberAccess(m) => {
-                let obj = self.expr_to_string(&m.object);
-                format!("{}.{}", obj, m.property)
-            }
-            _ => String::new(),
-        }
-    }
-
     fn slot(&mut self) -> Result<Slot, ParseError> {
         let start = self.current_location();
         self.consume(TokenKind::Slot, "Expected '@slot'")?;
 
+        let name = if self.match_token(TokenKind::LParen) {
+            let name = self.consume(TokenKind::Identifier, "Expected slot name")?.value.clone();
+            self.consume(TokenKind::RParen, "Expected ')'")?;
+            Some(name)
+        } else {
+            None
+        };
+
         Ok(Slot {
+            name,
+            loc: self.location_from(start),
+        })
+    }
+
+    fn let_statement(&mut self) -> Result<LetStatement, ParseError> {
+        let start = self.current_location();
+        self.consume(TokenKind::Let, "Expected '@let'")?;
+
+        let name = self.consume(TokenKind::Identifier, "Expected binding name")?;
+        let name = name.value.clone();
+
+        self.consume(TokenKind::Assign, "Expected '=' after binding name")?;
+        let value = self.expression()?;
+        self.locals.push(name.clone());
+
+        Ok(LetStatement {
+            name,
+            value,
+            loc: self.location_from(start),
+        })
+    }
+
+    fn comment(&mut self) -> Result<CommentNode, ParseError> {
+        let start = self.current_location();
+        self.consume(TokenKind::Comment, "Expected '@comment'")?;
+        let text = self.consume(TokenKind::String, "Expected comment text in quotes")?.value.clone();
+
+        Ok(CommentNode {
+            text,
+            loc: self.location_from(start),
+        })
+    }
+
+    fn raw_html(&mut self) -> Result<RawHtmlNode, ParseError> {
+        let start = self.current_location();
+        self.consume(TokenKind::Html, "Expected '@html'")?;
+        let value = self.expression()?;
+
+        Ok(RawHtmlNode {
+            value,
             loc: self.location_from(start),
         })
     }
@@ -429,7 +882,7 @@ impl<'a> Parser<'a> {
         let start = self.current_location();
         self.consume(TokenKind::If, "Expected '@if'")?;
 
-        let condition = self.expression()?;
+        let condition = self.if_condition()?;
         let consequent = self.block()?;
 
         let alternate = if self.match_token(TokenKind::Else) {
@@ -450,11 +903,33 @@ impl<'a> Parser<'a> {
         })
     }
 
+    /// Parse an `@if` condition, folding `a, b` into `a && b` when enabled
+    fn if_condition(&mut self) -> Result<Expression, ParseError> {
+        let start = self.current_location();
+        let mut condition = self.expression()?;
+
+        if self.options.comma_conditions {
+            while self.match_token(TokenKind::Comma) {
+                let operator_loc = self.previous().location;
+                let right = self.expression()?;
+                condition = Expression::Binary(BinaryExpr {
+                    operator: BinaryOp::And,
+                    operator_loc,
+                    left: Box::new(condition),
+                    right: Box::new(right),
+                    loc: self.location_from(start),
+                });
+            }
+        }
+
+        Ok(condition)
+    }
+
     fn each_statement(&mut self) -> Result<EachStatement, ParseError> {
         let start = self.current_location();
         self.consume(TokenKind::Each, "Expected '@each'")?;
 
-        let iterable = self.expression()?;
+        let iterable = self.each_iterable()?;
 
         self.consume(TokenKind::As, "Expected 'as'")?;
 
@@ -468,17 +943,72 @@ impl<'a> Parser<'a> {
             None
         };
 
-        let body = self.block()?;
+        let index_base = if index_name.is_some() && self.check(TokenKind::Identifier) && self.peek().value == "from" {
+            self.advance();
+            let token = self.consume(TokenKind::Number, "Expected a whole number after 'from'")?.clone();
+            let base: u64 = token.value.parse().map_err(|_| self.error("Expected a whole number after 'from'"))?;
+            // The index is a JavaScript number, which counts exactly only this far
+            if base > MAX_SAFE_INTEGER as u64 {
+                return Err(ParseError::new(format!("'from' can count from at most {}", MAX_SAFE_INTEGER), token.location));
+            }
+            base as i64
+        } else {
+            0
+        };
+
+        let entries = self.check(TokenKind::Identifier) && self.peek().value == "in";
+        if entries {
+            self.advance();
+            if self.peek().value != "entries" {
+                return Err(self.error("Expected 'entries' after 'in'"));
+            }
+            self.advance();
+            if index_base != 0 {
+                return Err(self.error("An entries loop's key can't count 'from' a number"));
+            }
+        }
+
+        let loop_names = std::iter::once(item_name.clone()).chain(index_name.clone());
+        let body = self.scoped(loop_names, Self::block)?;
+
+        let empty = if self.match_token(TokenKind::Else) {
+            Some(self.block()?)
+        } else {
+            None
+        };
 
         Ok(EachStatement {
             iterable,
             item_name,
             index_name,
+            index_base,
+            entries,
             body,
+            empty,
             loc: self.location_from(start),
         })
     }
 
+    /// An `@each` iterable, which may be a range: `1..5` or `0..<ctx.count`
+    fn each_iterable(&mut self) -> Result<Expression, ParseError> {
+        let start = self.current_location();
+        let expr = self.expression()?;
+        let inclusive = if self.match_token(TokenKind::Range) {
+            true
+        } else if self.match_token(TokenKind::RangeExclusive) {
+            false
+        } else {
+            return Ok(expr);
+        };
+        let end = self.expression()?;
+        Ok(Expression::Range(RangeExpr {
+            start: Box::new(expr),
+            end: Box::new(end),
+            inclusive,
+            loc: self.location_from(start),
+        }))
+    }
+
     fn for_directive(&mut self) -> Result<ForDirective, ParseError> {
         let start = self.current_location();
         self.consume(TokenKind::For, "Expected '@for'")?;
@@ -536,7 +1066,7 @@ impl<'a> Parser<'a> {
 
     fn ternary(&mut self) -> Result<Expression, ParseError> {
         let start = self.current_location();
-        let mut expr = self.or()?;
+        let mut expr = self.nullish()?;
 
         if self.match_token(TokenKind::Question) {
             let consequent = self.expression()?;
@@ -554,14 +1084,36 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
 
+    /// `a ?? b`, below `||` as in JavaScript
+    fn nullish(&mut self) -> Result<Expression, ParseError> {
+        let start = self.current_location();
+        let mut left = self.or()?;
+
+        while self.match_token(TokenKind::DoubleQuestion) {
+            let operator_loc = self.previous().location;
+            let right = self.or()?;
+            left = Expression::Binary(BinaryExpr {
+                operator: BinaryOp::NullishCoalesce,
+                operator_loc,
+                left: Box::new(left),
+                right: Box::new(right),
+                loc: self.location_from(start),
+            });
+        }
+
+        Ok(left)
+    }
+
     fn or(&mut self) -> Result<Expression, ParseError> {
         let start = self.current_location();
         let mut left = self.and()?;
 
         while self.match_token(TokenKind::Or) {
+            let operator_loc = self.previous().location;
             let right = self.and()?;
             left = Expression::Binary(BinaryExpr {
                 operator: BinaryOp::Or,
+                operator_loc,
                 left: Box::new(left),
                 right: Box::new(right),
                 loc: self.location_from(start),
@@ -576,9 +1128,11 @@ impl<'a> Parser<'a> {
         let mut left = self.equality()?;
 
         while self.match_token(TokenKind::And) {
+            let operator_loc = self.previous().location;
             let right = self.equality()?;
             left = Expression::Binary(BinaryExpr {
                 operator: BinaryOp::And,
+                operator_loc,
                 left: Box::new(left),
                 right: Box::new(right),
                 loc: self.location_from(start),
@@ -601,9 +1155,12 @@ impl<'a> Parser<'a> {
                 break;
             };
 
+            let operator_loc = self.previous().location;
+
             let right = self.comparison()?;
             left = Expression::Binary(BinaryExpr {
                 operator: op,
+                operator_loc,
                 left: Box::new(left),
                 right: Box::new(right),
                 loc: self.location_from(start),
@@ -630,9 +1187,12 @@ impl<'a> Parser<'a> {
                 break;
             };
 
+            let operator_loc = self.previous().location;
+
             let right = self.additive()?;
             left = Expression::Binary(BinaryExpr {
                 operator: op,
+                operator_loc,
                 left: Box::new(left),
                 right: Box::new(right),
                 loc: self.location_from(start),
@@ -644,7 +1204,7 @@ impl<'a> Parser<'a> {
 
     fn additive(&mut self) -> Result<Expression, ParseError> {
         let start = self.current_location();
-        let mut left = self.postfix()?;
+        let mut left = self.multiplicative()?;
 
         loop {
             let op = if self.match_token(TokenKind::Plus) {
@@ -655,9 +1215,42 @@ impl<'a> Parser<'a> {
                 break;
             };
 
+            let operator_loc = self.previous().location;
+
+            let right = self.multiplicative()?;
+            left = Expression::Binary(BinaryExpr {
+                operator: op,
+                operator_loc,
+                left: Box::new(left),
+                right: Box::new(right),
+                loc: self.location_from(start),
+            });
+        }
+
+        Ok(left)
+    }
+
+    fn multiplicative(&mut self) -> Result<Expression, ParseError> {
+        let start = self.current_location();
+        let mut left = self.postfix()?;
+
+        loop {
+            let op = if self.match_token(TokenKind::Star) {
+                BinaryOp::Mul
+            } else if self.match_token(TokenKind::Slash) {
+                BinaryOp::Div
+            } else if self.match_token(TokenKind::Percent) {
+                BinaryOp::Mod
+            } else {
+                break;
+            };
+
+            let operator_loc = self.previous().location;
+
             let right = self.postfix()?;
             left = Expression::Binary(BinaryExpr {
                 operator: op,
+                operator_loc,
                 left: Box::new(left),
                 right: Box::new(right),
                 loc: self.location_from(start),
@@ -673,10 +1266,37 @@ impl<'a> Parser<'a> {
 
         loop {
             if self.match_token(TokenKind::Dot) {
-                let property = self.consume(TokenKind::Identifier, "Expected property name after '.'")?;
-                expr = Expression::MemberAccess(MemberAccessExpr {
+                let token = self.consume(TokenKind::Identifier, "Expected property name after '.'")?;
+                let (property, property_loc) = (token.value.clone(), token.location);
+                let loc = self.location_from(start);
+                expr = match expr {
+                    // Dotted names stay paths: `item.name`, `ctx.user .name`
+                    Expression::Identifier(ident) => Expression::ContextPath(ContextPathExpr {
+                        root: PathRoot::Local,
+                        segments: vec![ident.name, property],
+                        segment_locs: vec![ident.loc, property_loc],
+                        loc,
+                    }),
+                    Expression::ContextPath(mut path) => {
+                        path.segments.push(property);
+                        path.segment_locs.push(property_loc);
+                        path.loc = loc;
+                        Expression::ContextPath(path)
+                    }
+                    object => Expression::MemberAccess(MemberAccessExpr {
+                        object: Box::new(object),
+                        property,
+                        property_loc,
+                        loc,
+                    }),
+                };
+            } else if self.match_token(TokenKind::QuestionDot) {
+                let token = self.consume(TokenKind::Identifier, "Expected property name after '?.'")?;
+                let (property, property_loc) = (token.value.clone(), token.location);
+                expr = Expression::OptionalMember(MemberAccessExpr {
                     object: Box::new(expr),
-                    property: property.value.clone(),
+                    property,
+                    property_loc,
                     loc: self.location_from(start),
                 });
             } else if self.check(TokenKind::LParen) {
@@ -729,6 +1349,7 @@ impl<'a> Parser<'a> {
             let value: f64 = token.value.parse().unwrap_or(0.0);
             return Ok(Expression::Number(NumberLiteral {
                 value,
+                raw: token.value.clone(),
                 loc: self.location_from(start),
             }));
         }
@@ -749,15 +1370,73 @@ impl<'a> Parser<'a> {
             }));
         }
 
+        if self.check(TokenKind::Null) {
+            self.advance();
+            return Ok(Expression::Null(NullLiteral {
+                loc: self.location_from(start),
+            }));
+        }
+
+        if self.check(TokenKind::LBracket) {
+            self.advance();
+            let mut elements = Vec::new();
+            if !self.check(TokenKind::RBracket) {
+                loop {
+                    elements.push(self.expression()?);
+                    if !self.match_token(TokenKind::Comma) {
+                        break;
+                    }
+                }
+            }
+            self.consume(TokenKind::RBracket, "Expected ']' after array elements")?;
+            return Ok(Expression::Array(ArrayLiteral {
+                elements,
+                loc: self.location_from(start),
+            }));
+        }
+
+        if self.check(TokenKind::LBrace) {
+            self.advance();
+            let mut properties = Vec::new();
+            if !self.check(TokenKind::RBrace) {
+                loop {
+                    let key_start = self.current_location();
+                    let key = if self.check(TokenKind::String) {
+                        self.advance().value.clone()
+                    } else {
+                        self.consume(TokenKind::Identifier, "Expected property name")?.value.clone()
+                    };
+                    self.consume(TokenKind::Colon, "Expected ':' after property name")?;
+                    let value = self.expression()?;
+                    properties.push(ObjectProperty {
+                        key,
+                        value,
+                        loc: self.location_from(key_start),
+                    });
+                    if !self.match_token(TokenKind::Comma) {
+                        break;
+                    }
+                }
+            }
+            self.consume(TokenKind::RBrace, "Expected '}' after object properties")?;
+            return Ok(Expression::Object(ObjectLiteral {
+                properties,
+                loc: self.location_from(start),
+            }));
+        }
+
         if self.check(TokenKind::ContextPath) {
             let token = self.advance();
+            let (segments, segment_locs) = path_segments(token);
             return Ok(Expression::ContextPath(ContextPathExpr {
-                path: token.value.clone(),
+                root: PathRoot::Ctx,
+                segments,
+                segment_locs,
                 loc: self.location_from(start),
             }));
         }
 
-        if self.check(TokenKind::Identifier) {
+        if self.check(TokenKind::Identifier) || self.check(TokenKind::LoopHelper) {
             let token = self.advance();
             let name = token.value.clone();
 
@@ -789,6 +1468,29 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Check the token after the current one
+    fn check_next(&self, kind: TokenKind) -> bool {
+        self.tokens.get(self.current + 1).is_some_and(|t| t.kind == kind)
+    }
+
+    /// Whether the current token directly follows the previous one, with no space between
+    fn is_adjacent(&self) -> bool {
+        self.peek().location.start == self.previous().location.end
+    }
+
+    /// Run `parse` with `names` in scope
+    fn scoped<T>(
+        &mut self,
+        names: impl IntoIterator<Item = String>,
+        parse: impl FnOnce(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        let outer = self.locals.len();
+        self.locals.extend(names);
+        let result = parse(self);
+        self.locals.truncate(outer);
+        result
+    }
+
     fn match_token(&mut self, kind: TokenKind) -> bool {
         if self.check(kind) {
             self.advance();
@@ -846,6 +1548,60 @@ impl<'a> Parser<'a> {
         )
     }
 
+    /// Skip tokens up to and including the `]` that closes the current attribute list
+    fn recover_attribute_list(&mut self) {
+        let mut depth = 0;
+        while !self.is_at_end() {
+            match self.peek().kind {
+                TokenKind::LBracket => depth += 1,
+                TokenKind::RBracket if depth == 0 => {
+                    self.advance();
+                    return;
+                }
+                TokenKind::RBracket => depth -= 1,
+                // Never run into the element body or the enclosing block
+                TokenKind::LBrace | TokenKind::RBrace => return,
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+
+    /// Skip the rest of a malformed node, stopping at the next node or the end of the block
+    fn recover_node(&mut self) {
+        if self.check(TokenKind::RBrace) {
+            return;
+        }
+        self.advance();
+
+        let mut depth = 0;
+        while !self.is_at_end() {
+            let kind = self.peek().kind;
+            if depth == 0 {
+                match kind {
+                    TokenKind::RBrace
+                    | TokenKind::If
+                    | TokenKind::Each
+                    | TokenKind::Slot
+                    | TokenKind::Let
+                    | TokenKind::Comment
+                    | TokenKind::Html
+                    | TokenKind::TextOpen
+                    | TokenKind::ContextPath
+                    | TokenKind::ComponentName
+                    | TokenKind::Identifier => return,
+                    _ => {}
+                }
+            }
+            match kind {
+                TokenKind::LBrace | TokenKind::LBracket | TokenKind::LParen => depth += 1,
+                TokenKind::RBrace | TokenKind::RBracket | TokenKind::RParen => depth -= 1,
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+
     fn synchronize(&mut self) {
         self.advance();
 
@@ -855,7 +1611,7 @@ impl<'a> Parser<'a> {
             }
 
             match self.peek().kind {
-                TokenKind::Component | TokenKind::Section | TokenKind::Page => return,
+                TokenKind::Component | TokenKind::Section | TokenKind::Layout | TokenKind::Page => return,
                 _ => {}
             }
 
@@ -864,6 +1620,34 @@ impl<'a> Parser<'a> {
     }
 }
 
+/// Where byte `offset` of `text`, which starts at `loc`, sits in the source
+fn location_within(text: &str, loc: Location, offset: usize) -> Location {
+    let before = &text[..offset];
+    let start = loc.start + offset;
+    let (line, column) = match before.rfind('\n') {
+        Some(newline) => (loc.line + before.matches('\n').count(), offset - newline),
+        None => (loc.line, loc.column + offset),
+    };
+    Location { line, column, start, end: start }
+}
+
+/// Segments of a `ctx.a.b` token after `ctx`, each with its own span
+fn path_segments(token: &Token) -> (Vec<String>, Vec<Location>) {
+    let mut offset = "ctx.".len();
+    token.value.split('.').skip(1)
+        .map(|segment| {
+            let loc = Location {
+                line: token.location.line,
+                column: token.location.column + offset,
+                start: token.location.start + offset,
+                end: token.location.start + offset + segment.len(),
+            };
+            offset += segment.len() + 1;
+            (segment.to_string(), loc)
+        })
+        .unzip()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -916,6 +1700,46 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_presence_attribute() {
+        let ast = parse_source(r#"component Test { input [type: "text", required] }"#).unwrap();
+        match &ast.body[0] {
+            Declaration::Component(c) => match &c.body[0] {
+                Node::Element(e) => {
+                    assert_eq!(e.attributes.len(), 2);
+                    assert_eq!(e.attributes[1].name, "required");
+                    assert!(matches!(
+                        e.attributes[1].value,
+                        Expression::Boolean(BooleanLiteral { value: true, .. })
+                    ));
+                }
+                _ => panic!("Expected element"),
+            },
+            _ => panic!("Expected component"),
+        }
+    }
+
+    #[test]
+    fn test_spread_attribute() {
+        let ast = parse_source(r#"component Test { button [...ctx.buttonProps, class: "x"] }"#).unwrap();
+        match &ast.body[0] {
+            Declaration::Component(c) => match &c.body[0] {
+                Node::Element(e) => {
+                    assert_eq!(e.attributes.len(), 2);
+                    match &e.attributes[0].value {
+                        Expression::Spread(spread) => {
+                            assert!(matches!(*spread.expr, Expression::ContextPath(_)));
+                        }
+                        _ => panic!("Expected spread"),
+                    }
+                    assert_eq!(e.attributes[1].name, "class");
+                }
+                _ => panic!("Expected element"),
+            },
+            _ => panic!("Expected component"),
+        }
+    }
+
     #[test]
     fn test_if_statement() {
         let ast = parse_source(r#"component Test { @if ctx.show { div { } } }"#).unwrap();
@@ -932,6 +1756,369 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_modulo_precedence() {
+        let ast = parse_source("component Test { @if ctx.index % 2 == 0 { } }").unwrap();
+        match &ast.body[0] {
+            Declaration::Component(c) => match &c.body[0] {
+                Node::If(stmt) => match &stmt.condition {
+                    Expression::Binary(eq) => {
+                        assert_eq!(eq.operator, BinaryOp::Eq);
+                        match &*eq.left {
+                            Expression::Binary(modulo) => assert_eq!(modulo.operator, BinaryOp::Mod),
+                            _ => panic!("Expected modulo on the left"),
+                        }
+                    }
+                    _ => panic!("Expected binary expression"),
+                },
+                _ => panic!("Expected if statement"),
+            },
+            _ => panic!("Expected component"),
+        }
+    }
+
+    #[test]
+    fn test_path_roots() {
+        let ast = parse_source("component Test { @if ctx.user.name == item.name { } }").unwrap();
+        let Declaration::Component(c) = &ast.body[0] else { panic!("Expected component") };
+        let Node::If(stmt) = &c.body[0] else { panic!("Expected if statement") };
+        let Expression::Binary(eq) = &stmt.condition else { panic!("Expected binary expression") };
+
+        match (&*eq.left, &*eq.right) {
+            (Expression::ContextPath(ctx), Expression::ContextPath(local)) => {
+                assert_eq!(ctx.root, PathRoot::Ctx);
+                assert_eq!(ctx.segments, ["user", "name"]);
+                assert_eq!(local.root, PathRoot::Local);
+                assert_eq!(local.segments, ["item", "name"]);
+                assert_eq!(ctx.path(), "ctx.user.name");
+                assert_eq!(local.path(), "item.name");
+            }
+            _ => panic!("Expected two paths"),
+        }
+    }
+
+    #[test]
+    fn test_segment_locations() {
+        let source = r#"component Test { p [title: ctx.user.name, data-a: item.tag.label, data-n: [1].length] }"#;
+        let ast = parse_source(source).unwrap();
+        let Declaration::Component(c) = &ast.body[0] else { panic!("Expected component") };
+        let Node::Element(p) = &c.body[0] else { panic!("Expected element") };
+        let text = |loc: Location| &source[loc.start..loc.end];
+
+        let Expression::ContextPath(ctx) = &p.attributes[0].value else { panic!("Expected path") };
+        assert_eq!(ctx.segment_locs.iter().map(|l| text(*l)).collect::<Vec<_>>(), ["user", "name"]);
+        assert_eq!(ctx.segment_locs[1].column, 37);
+        let user = source.find("user").unwrap();
+        assert_eq!(ctx.segment_at(user + 2).map(|(i, name, _)| (i, name)), Some((0, "user")));
+        assert!(ctx.segment_at(user - 1).is_none());
+
+        let Expression::ContextPath(local) = &p.attributes[1].value else { panic!("Expected path") };
+        assert_eq!(local.segment_locs.iter().map(|l| text(*l)).collect::<Vec<_>>(), ["item", "tag", "label"]);
+
+        let Expression::MemberAccess(m) = &p.attributes[2].value else { panic!("Expected member access") };
+        assert_eq!(text(m.property_loc), "length");
+        assert_eq!(text(m.loc), "[1].length");
+    }
+
+    #[test]
+    fn test_optional_member_and_nullish() {
+        let ast = parse_source(r#"component Test { p [title: ctx.user?.name ?? "Guest" || "x"] }"#).unwrap();
+        let Declaration::Component(c) = &ast.body[0] else { panic!("Expected component") };
+        let Node::Element(p) = &c.body[0] else { panic!("Expected element") };
+
+        // `??` binds looser than `||`
+        let Expression::Binary(b) = &p.attributes[0].value else { panic!("Expected binary") };
+        assert_eq!(b.operator, BinaryOp::NullishCoalesce);
+        assert!(matches!(&*b.right, Expression::Binary(r) if r.operator == BinaryOp::Or));
+        let Expression::OptionalMember(m) = &*b.left else { panic!("Expected optional member") };
+        assert_eq!(m.property, "name");
+        assert!(matches!(&*m.object, Expression::ContextPath(ctx) if ctx.path() == "ctx.user"));
+    }
+
+    #[test]
+    fn test_let_statement() {
+        let ast = parse_source(r#"component Test { @let total = ctx.a + ctx.b div { } }"#).unwrap();
+        match &ast.body[0] {
+            Declaration::Component(c) => {
+                assert_eq!(c.body.len(), 2);
+                match &c.body[0] {
+                    Node::Let(stmt) => {
+                        assert_eq!(stmt.name, "total");
+                        assert!(matches!(stmt.value, Expression::Binary(_)));
+                    }
+                    _ => panic!("Expected let statement"),
+                }
+            }
+            _ => panic!("Expected component"),
+        }
+    }
+
+    #[test]
+    fn test_adjacent_text_segments() {
+        let ast = parse_source(r#"
+            component Test {
+                h1 {{ Welcome ${ctx.name} }}
+                p {{ Hello, }}{{ ${ctx.name}! }}
+                span {{ Apart }} {{ Sibling }}
+            }
+        "#).unwrap();
+
+        let body = match &ast.body[0] {
+            Declaration::Component(c) => &c.body,
+            _ => panic!("Expected component"),
+        };
+        let text = |node: &Node| match node {
+            Node::Element(e) => match &e.children[..] {
+                [Node::Text(t)] => t.content.clone(),
+                _ => panic!("Expected one text child"),
+            },
+            Node::Text(t) => t.content.clone(),
+            _ => panic!("Unexpected node"),
+        };
+
+        assert_eq!(text(&body[0]), "Welcome ${ctx.name}");
+        assert_eq!(text(&body[1]), "Hello, ${ctx.name}!");
+        // Whitespace between segments keeps them as separate nodes
+        assert_eq!(body.len(), 4);
+        assert_eq!(text(&body[3]), "Sibling");
+    }
+
+    #[test]
+    fn test_whitespace_markers_keep_spaces() {
+        let ast = parse_source(r#"
+            component Test {
+                p { span {{ a }} {{- and -}} span {{ b }} }
+                p {{ Hello, }}{{-  world }}
+            }
+        "#).unwrap();
+
+        let body = match &ast.body[0] {
+            Declaration::Component(c) => &c.body,
+            _ => panic!("Expected component"),
+        };
+        let children = |node: &Node| match node {
+            Node::Element(e) => e.children.clone(),
+            _ => panic!("Expected element"),
+        };
+
+        match &children(&body[0])[1] {
+            Node::Text(t) => {
+                assert_eq!(t.content, " and ");
+                assert_eq!(t.trim, (false, false));
+            }
+            _ => panic!("Expected text"),
+        }
+        match &children(&body[1])[0] {
+            Node::Text(t) => {
+                assert_eq!(t.content, "Hello,  world");
+                assert_eq!(t.trim, (true, true));
+            }
+            _ => panic!("Expected text"),
+        }
+    }
+
+    #[test]
+    fn test_named_slot_fills() {
+        let ast = parse_source(r#"
+            component Card { @slot(header) @slot }
+            page home "/" {
+                Card {
+                    header: { h1 {{ Title }} }
+                    p {{ Body }}
+                }
+            }
+        "#).unwrap();
+
+        match &ast.body[0] {
+            Declaration::Component(c) => match &c.body[0] {
+                Node::Slot(slot) => assert_eq!(slot.name.as_deref(), Some("header")),
+                _ => panic!("Expected slot"),
+            },
+            _ => panic!("Expected component"),
+        }
+        match &ast.body[1] {
+            Declaration::Page(p) => match &p.body[0] {
+                Node::ComponentRef(r) => {
+                    assert_eq!(r.slot_fills.len(), 1);
+                    assert_eq!(r.slot_fills[0].name, "header");
+                    assert!(matches!(&r.slot_fills[0].children[0], Node::Element(e) if e.tag == "h1"));
+                    assert_eq!(r.children.len(), 1);
+                }
+                _ => panic!("Expected component reference"),
+            },
+            _ => panic!("Expected page"),
+        }
+    }
+
+    #[test]
+    fn test_comma_condition() {
+        fn condition(source: &str, options: ParseOptions) -> serde_json::Value {
+            let tokens = tokenize(source).unwrap();
+            let ast = Parser::with_options(&tokens, options).parse().unwrap();
+            match &ast.body[0] {
+                Declaration::Component(c) => match &c.body[0] {
+                    Node::If(stmt) => {
+                        let mut value = serde_json::to_value(&stmt.condition).unwrap();
+                        // `,` and `&&` differ in width
+                        value.as_object_mut().unwrap().remove("operator_loc");
+                        value
+                    }
+                    _ => panic!("Expected if statement"),
+                },
+                _ => panic!("Expected component"),
+            }
+        }
+
+        let options = ParseOptions { comma_conditions: true };
+        let comma = condition("component Test { @if ctx.a, ctx.b { } }", options);
+        let and = condition("component Test { @if ctx.a&&ctx.b { } }", options);
+        assert_eq!(comma, and);
+
+        // Without the flag the comma form is rejected
+        assert!(parse_source("component Test { @if ctx.a, ctx.b { } }").is_err());
+    }
+
+    #[test]
+    fn test_list_separators() {
+        let ast = parse_source(r#"
+            component Card(item: user, title: heading,) {
+                a [
+                    href: "/docs"
+                    class: "link",
+                    title: "Docs",
+                ] { }
+            }
+            page home "/" {
+                Card(
+                    item: ctx.user
+                    title: "Hi"
+                )
+            }
+        "#).unwrap();
+        let Declaration::Component(c) = &ast.body[0] else { panic!("Expected component") };
+        assert_eq!(c.parameters.len(), 2);
+        let Node::Element(a) = &c.body[0] else { panic!("Expected element") };
+        assert_eq!(a.attributes.iter().map(|a| a.name.as_str()).collect::<Vec<_>>(), ["href", "class", "title"]);
+        let Declaration::Page(p) = &ast.body[1] else { panic!("Expected page") };
+        let Node::ComponentRef(r) = &p.body[0] else { panic!("Expected component ref") };
+        assert_eq!(r.parameters.len(), 2);
+
+        // Entries on one line still need a comma
+        let errors = parse_source(r#"component Test { a [href: "/" class: "link"] { } }"#).unwrap_err();
+        assert!(errors[0].message.contains("Expected ']'"));
+        assert!(parse_source(r#"component Test { a [,] { } }"#).is_err());
+    }
+
+    #[test]
+    fn test_hyphenated_attribute_names() {
+        let ast = parse_source(r#"component Test { div [data-user-id: ctx.id, aria-hidden: true, aria-label] { } }"#).unwrap();
+        let Declaration::Component(c) = &ast.body[0] else { panic!("Expected component") };
+        let Node::Element(el) = &c.body[0] else { panic!("Expected element") };
+        let names: Vec<_> = el.attributes.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, ["data-user-id", "aria-hidden", "aria-label"]);
+        assert!(matches!(&el.attributes[0].value, Expression::ContextPath(_)));
+        assert!(matches!(&el.attributes[1].value, Expression::Boolean(b) if b.value));
+
+        let errors = parse_source(r#"component Test { div [data-: 1] { } }"#).unwrap_err();
+        assert!(errors[0].message.contains("'data-' cannot end with '-'"), "{:?}", errors);
+    }
+
+    #[test]
+    fn test_text_parts() {
+        let source = "component Test(item: user) { p {{ Hi ${item.name}, \\${x} ${ctx.n + 1}${ oops ) } }} }";
+        let ast = parse_source(source).unwrap();
+        let Declaration::Component(c) = &ast.body[0] else { panic!("Expected component") };
+        let Node::Element(el) = &c.body[0] else { panic!("Expected element") };
+        let Node::Text(text) = &el.children[0] else { panic!("Expected text") };
+
+        let [TextPart::Static(hi), TextPart::Dynamic(Expression::ContextPath(name)), TextPart::Static(escaped), TextPart::Dynamic(Expression::Binary(sum)), TextPart::Static(kept)] = &text.parts[..] else {
+            panic!("Unexpected parts: {:?}", text.parts);
+        };
+        assert_eq!((hi.as_str(), escaped.as_str(), kept.as_str()), ("Hi ", ", ${x} ", "${ oops ) }"));
+        assert_eq!(name.root, PathRoot::Local);
+        assert_eq!(&source[name.loc.start..name.loc.end], "item.name");
+        assert_eq!(&source[sum.loc.start..sum.loc.end], "ctx.n + 1");
+        assert_eq!(sum.loc.column, sum.loc.start + 1);
+
+        let ast = parse_source("page home \"/\" { p { ctx.user.name } }").unwrap();
+        let Declaration::Page(page) = &ast.body[0] else { panic!("Expected page") };
+        let Node::Element(el) = &page.body[0] else { panic!("Expected element") };
+        assert!(matches!(&el.children[0], Node::Text(t) if matches!(&t.parts[..], [TextPart::Dynamic(Expression::ContextPath(_))])));
+    }
+
+    #[test]
+    fn test_attribute_blocks_join() {
+        let ast = parse_source(r#"component Card [role: "group"] [title: "Card"] { div.box [class: "a"] [id: "b", class: "c"] { } }"#).unwrap();
+        let Declaration::Component(c) = &ast.body[0] else { panic!("Expected component") };
+        assert_eq!(c.attributes.iter().map(|a| a.name.as_str()).collect::<Vec<_>>(), ["role", "title"]);
+
+        let Node::Element(el) = &c.body[0] else { panic!("Expected element") };
+        let attrs: Vec<String> = el.attributes.iter()
+            .map(|a| match &a.value {
+                Expression::String(s) => format!("{}={}", a.name, s.value),
+                _ => a.name.clone(),
+            })
+            .collect();
+        // The shorthand joins the class attribute that wins
+        assert_eq!(attrs, ["class=a", "id=b", "class=box c"]);
+    }
+
+    #[test]
+    fn test_selector_shorthand() {
+        let ast = parse_source(r#"
+            component Card(item: option) {
+                div.card.active#main { }
+                p.lead [class: "intro"] { }
+                span.badge [class: ctx.tone] { }
+                option.label
+                @each ctx.items as label { label.text }
+            }
+        "#).unwrap();
+        let Declaration::Component(c) = &ast.body[0] else { panic!("Expected component") };
+        let attrs = |node: &Node| match node {
+            Node::Element(e) => e.attributes.iter()
+                .map(|a| match &a.value {
+                    Expression::String(s) => format!("{}={}", a.name, s.value),
+                    Expression::Array(list) => format!("{}=[{}]", a.name, list.elements.len()),
+                    _ => a.name.clone(),
+                })
+                .collect::<Vec<_>>(),
+            _ => panic!("Expected element"),
+        };
+        assert_eq!(attrs(&c.body[0]), ["class=card active", "id=main"]);
+        assert_eq!(attrs(&c.body[1]), ["class=lead intro"]);
+        assert_eq!(attrs(&c.body[2]), ["class=[2]"]);
+
+        // Locals keep member access, even when named like a tag
+        assert!(matches!(&c.body[3], Node::Text(t) if t.content == "option.label"));
+        let Node::Each(each) = &c.body[4] else { panic!("Expected @each") };
+        assert!(matches!(&each.body[0], Node::Text(t) if t.content == "label.text"));
+
+        let errors = parse_source(r#"component Test { div#a [id: "b"] { } }"#).unwrap_err();
+        assert!(errors[0].message.contains("both #a and an id attribute"));
+        assert!(parse_source("component Test { div#a#b { } }").is_err());
+        assert!(parse_source("component Test { div. card { } }").is_err());
+    }
+
+    #[test]
+    fn test_collects_independent_errors() {
+        let errors = parse_source(r#"
+            component A {
+                div [class "x"] { }
+                p { 42 }
+                span [id: ]
+            }
+        "#).unwrap_err();
+
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].message.contains("Expected ']'"));
+        assert!(errors[1].message.contains("Expected element"));
+        assert!(errors[2].message.contains("Expected expression"));
+        // Recovery resumes at the next element of the same component
+        let lines: Vec<usize> = errors.iter().map(|e| e.location.line).collect();
+        assert_eq!(lines, [3, 4, 5]);
+    }
+
     #[test]
     fn test_each_statement() {
         let ast = parse_source(r#"component Test { @each ctx.items as item { div { } } }"#).unwrap();
@@ -941,6 +2128,7 @@ mod tests {
                     Node::Each(stmt) => {
                         assert_eq!(stmt.item_name, "item");
                         assert!(stmt.index_name.is_none());
+                        assert!(stmt.empty.is_none());
                     }
                     _ => panic!("Expected each statement"),
                 }
@@ -948,4 +2136,115 @@ mod tests {
             _ => panic!("Expected component"),
         }
     }
+
+    #[test]
+    fn test_each_else_block() {
+        let source = r#"component Test { @each ctx.items as item { li { } } @else { p {{ No items }} } }"#;
+        let ast = parse_source(source).unwrap();
+        let Declaration::Component(c) = &ast.body[0] else { panic!("Expected component") };
+        let Node::Each(stmt) = &c.body[0] else { panic!("Expected each statement") };
+
+        assert_eq!(stmt.body.len(), 1);
+        assert!(matches!(stmt.empty.as_deref(), Some([Node::Element(p)]) if p.tag == "p"));
+        assert_eq!(stmt.loc.end, source.len() - 2);
+    }
+
+    #[test]
+    fn test_each_over_entries() {
+        let ast = parse_source(r#"component Test { @each ctx.scores as score, name in entries { li { } } }"#).unwrap();
+        let Declaration::Component(c) = &ast.body[0] else { panic!("Expected component") };
+        let Node::Each(stmt) = &c.body[0] else { panic!("Expected each statement") };
+        assert!(stmt.entries);
+        assert_eq!(stmt.item_name, "score");
+        assert_eq!(stmt.index_name.as_deref(), Some("name"));
+
+        let errors = parse_source(r#"component Test { @each ctx.scores as score in items { } }"#).unwrap_err();
+        assert!(errors[0].message.contains("Expected 'entries' after 'in'"), "{}", errors[0].message);
+    }
+
+    #[test]
+    fn test_each_index_base() {
+        let ast = parse_source(r#"component Test { @each ctx.items as item, n from 1 { li { } } }"#).unwrap();
+        let Declaration::Component(c) = &ast.body[0] else { panic!("Expected component") };
+        let Node::Each(stmt) = &c.body[0] else { panic!("Expected each statement") };
+        assert_eq!(stmt.index_name.as_deref(), Some("n"));
+        assert_eq!(stmt.index_base, 1);
+
+        let errors = parse_source(r#"component Test { @each ctx.items as item, n from 1.5 { } }"#).unwrap_err();
+        assert!(errors[0].message.contains("Expected a whole number after 'from'"), "{}", errors[0].message);
+        let errors = parse_source(r#"component Test { @each 0..<3 as i, k from 9223372036854775807 { } }"#).unwrap_err();
+        assert!(errors[0].message.contains("'from' can count from at most 9007199254740991"), "{}", errors[0].message);
+        assert!(parse_source(r#"component Test { @each 0..<3 as i, k from 9007199254740991 { } }"#).is_ok());
+        let errors = parse_source(r#"component Test { @each ctx.scores as score, name from 1 in entries { } }"#).unwrap_err();
+        assert!(errors[0].message.contains("can't count 'from'"), "{}", errors[0].message);
+    }
+
+    #[test]
+    fn test_each_over_range() {
+        let ast = parse_source(r#"component Test { @each 1..5 as i { } @each 0..<ctx.count as n { } }"#).unwrap();
+        let Declaration::Component(c) = &ast.body[0] else { panic!("Expected component") };
+        let Node::Each(stmt) = &c.body[0] else { panic!("Expected each statement") };
+        let Expression::Range(range) = &stmt.iterable else { panic!("Expected range") };
+        assert!(range.inclusive);
+        assert!(matches!(range.start.as_ref(), Expression::Number(n) if n.value == 1.0));
+        assert!(matches!(range.end.as_ref(), Expression::Number(n) if n.value == 5.0));
+
+        let Node::Each(stmt) = &c.body[1] else { panic!("Expected each statement") };
+        let Expression::Range(range) = &stmt.iterable else { panic!("Expected range") };
+        assert!(!range.inclusive);
+        assert!(matches!(range.end.as_ref(), Expression::ContextPath(_)));
+    }
+
+    #[test]
+    fn test_layout_and_uses() {
+        let ast = parse_source(r#"
+            layout Main { main { @slot } }
+            page home "/" uses Main { p {{ Home }} }
+            page about "/about" { p {{ About }} }
+        "#).unwrap();
+
+        let Declaration::Layout(layout) = &ast.body[0] else { panic!("Expected layout") };
+        assert_eq!(layout.name, "Main");
+        let Declaration::Page(home) = &ast.body[1] else { panic!("Expected page") };
+        assert_eq!(home.layout.as_deref(), Some("Main"));
+        let Declaration::Page(about) = &ast.body[2] else { panic!("Expected page") };
+        assert!(about.layout.is_none());
+    }
+
+    #[test]
+    fn test_page_head_block() {
+        let ast = parse_source(r#"
+            page home "/" {
+                h1 {{ Home }}
+                head { title {{ Welcome }} meta [name: "description", content: "Home"] }
+            }
+        "#).unwrap();
+        let Declaration::Page(home) = &ast.body[0] else { panic!("Expected page") };
+        assert_eq!(home.head.len(), 2);
+        assert!(matches!(&home.body[..], [Node::Element(h1)] if h1.tag == "h1"));
+
+        let errors = parse_source(r#"page home "/" { head { } head { } }"#).unwrap_err();
+        assert_eq!(errors[0].message, "A page can only have one head block");
+    }
+
+    #[test]
+    fn test_control_flow_spans_cover_whole_construct() {
+        let source = r#"component Test {
+    @if ctx.a { p {{ A }} } @else @if ctx.b { p {{ B }} } @else { p {{ C }} }
+    @each ctx.items as item { li {{ ${item} }} }
+}"#;
+        let ast = parse_source(source).unwrap();
+        let Declaration::Component(c) = &ast.body[0] else { panic!("Expected component") };
+
+        let Node::If(stmt) = &c.body[0] else { panic!("Expected if statement") };
+        let else_end = source.find("C }} }").unwrap() + "C }} }".len();
+        assert_eq!(&source[stmt.loc.start..stmt.loc.start + 3], "@if");
+        assert_eq!(stmt.loc.end, else_end);
+        let Some(Alternate::ElseIf(elif)) = &stmt.alternate else { panic!("Expected else-if") };
+        assert_eq!(elif.loc.end, else_end);
+
+        let Node::Each(each) = &c.body[1] else { panic!("Expected each statement") };
+        let each_src = &source[each.loc.start..each.loc.end];
+        assert!(each_src.starts_with("@each") && each_src.ends_with("${item} }} }"), "{:?}", each_src);
+    }
 }
@@ -4,11 +4,28 @@
 
 mod grammar;
 
-use crate::ast::Program;
+use crate::ast::{Expression, Program};
 use crate::error::ParseError;
 use crate::lexer::Token;
 
+/// Opt-in syntax extensions for the parser
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParseOptions {
+    /// Allow `@if a, b { }` as shorthand for `@if a && b { }`
+    pub comma_conditions: bool,
+}
+
 /// Parse tokens into an AST
 pub fn parse(tokens: &[Token]) -> Result<Program, Vec<ParseError>> {
     grammar::Parser::new(tokens).parse()
 }
+
+/// Parse tokens into an AST with syntax extensions enabled
+pub fn parse_with_options(tokens: &[Token], options: ParseOptions) -> Result<Program, Vec<ParseError>> {
+    grammar::Parser::with_options(tokens, options).parse()
+}
+
+/// Parse tokens holding a single expression, such as the inside of `${...}`
+pub fn parse_expression(tokens: &[Token]) -> Result<Expression, Vec<ParseError>> {
+    grammar::Parser::new(tokens).parse_expression()
+}
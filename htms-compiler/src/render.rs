@@ -0,0 +1,135 @@
+//! Rustc-style rendering of diagnostics against their source
+
+use crate::{Diagnostic, Severity};
+
+/// Columns a tab expands to in rendered snippets
+const TAB_WIDTH: usize = 4;
+
+impl Diagnostic {
+    /// Render the diagnostic as a source snippet with a caret underline
+    ///
+    /// ```text
+    /// error[E002]: Expected ']' (got string)
+    ///  --> 2:14
+    ///   |
+    /// 2 |   div [class "x"]
+    ///   |              ^^^
+    /// ```
+    pub fn render(&self, source: &str) -> String {
+        let label = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+        };
+
+        let mut out = String::new();
+        match &self.code {
+            Some(code) => out.push_str(&format!("{}[{}]: {}\n", label, code, self.message)),
+            None => out.push_str(&format!("{}: {}\n", label, self.message)),
+        }
+
+        let start = floor_char_boundary(source, self.location.start);
+        let end = floor_char_boundary(source, self.location.end).max(start);
+
+        // Collect every line the span touches: (line number, line start, line end)
+        let mut lines = Vec::new();
+        let mut line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let mut line_no = source[..line_start].matches('\n').count() + 1;
+        loop {
+            let line_end = source[line_start..]
+                .find('\n')
+                .map_or(source.len(), |i| line_start + i);
+            lines.push((line_no, line_start, line_end));
+            if line_end + 1 >= end || line_end == source.len() {
+                break;
+            }
+            line_start = line_end + 1;
+            line_no += 1;
+        }
+
+        let gutter = lines.last().map_or(1, |(n, _, _)| n.to_string().len());
+        let pad = " ".repeat(gutter);
+
+        match &self.file {
+            Some(file) => out.push_str(&format!("{}--> {}:{}:{}\n", pad, file, self.location.line, self.location.column)),
+            None => out.push_str(&format!("{}--> {}:{}\n", pad, self.location.line, self.location.column)),
+        }
+        out.push_str(&format!("{} |\n", pad));
+
+        for (line_no, line_start, line_end) in lines {
+            let text = source[line_start..line_end].trim_end_matches('\r');
+            out.push_str(&format!("{:>width$} | {}\n", line_no, expand_tabs(text), width = gutter));
+
+            let seg_start = start.max(line_start);
+            let seg_end = end.min(line_start + text.len()).max(seg_start);
+            let offset = display_width(&source[line_start..seg_start]);
+            let carets = display_width(&source[seg_start..seg_end]).max(1);
+            out.push_str(&format!("{} | {}{}\n", pad, " ".repeat(offset), "^".repeat(carets)));
+        }
+
+        out
+    }
+}
+
+fn floor_char_boundary(source: &str, index: usize) -> usize {
+    let mut index = index.min(source.len());
+    while !source.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+fn display_width(text: &str) -> usize {
+    text.chars().map(|c| if c == '\t' { TAB_WIDTH } else { 1 }).sum()
+}
+
+fn expand_tabs(text: &str) -> String {
+    text.replace('\t', &" ".repeat(TAB_WIDTH))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Diagnostic, Location, Severity};
+
+    fn diagnostic(start: usize, end: usize, line: usize, column: usize) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            message: "something is off".to_string(),
+            location: Location { line, column, start, end },
+            code: None,
+            file: None,
+        }
+    }
+
+    #[test]
+    fn test_multi_line_span() {
+        let source = "div {\n  span\n}";
+        let rendered = diagnostic(4, 14, 1, 5).render(source);
+
+        assert_eq!(
+            rendered,
+            "warning: something is off\n --> 1:5\n  |\n1 | div {\n  |     ^\n2 |   span\n  | ^^^^^^\n3 | }\n  | ^\n"
+        );
+    }
+
+    #[test]
+    fn test_tab_expansion() {
+        let source = "\tdiv";
+        let rendered = diagnostic(1, 4, 1, 2).render(source);
+
+        assert!(rendered.contains("1 |     div\n  |     ^^^\n"));
+    }
+
+    #[test]
+    fn test_span_past_end_of_source() {
+        let rendered = diagnostic(10, 10, 1, 4).render("abc");
+        assert!(rendered.contains("1 | abc\n  |    ^\n"));
+    }
+
+    #[test]
+    fn test_file_in_location_line() {
+        let mut d = diagnostic(0, 3, 1, 1);
+        d.file = Some("app.htms".to_string());
+        assert!(d.render("abc").contains(" --> app.htms:1:1\n"));
+    }
+}
@@ -0,0 +1,91 @@
+//! Output size and template metrics for `collect_stats`
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ast::{Alternate, Declaration, Node, Program};
+use crate::GeneratedFile;
+
+/// Sizes of the generated files and counts of what the source declares
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompileStats {
+    /// Sum of every generated file's size in bytes
+    pub total_bytes: usize,
+    /// Size in bytes of each generated file, by path
+    pub file_bytes: BTreeMap<String, usize>,
+    pub components: usize,
+    pub pages: usize,
+    /// `@each` blocks, not counting `@for` directives
+    pub each_blocks: usize,
+    /// `@if` blocks, counting each `@else @if` and not counting `@if` directives
+    pub if_blocks: usize,
+}
+
+impl CompileStats {
+    /// Count the declarations and control flow blocks in `program`
+    pub(crate) fn count(program: &Program) -> Self {
+        let mut stats = Self::default();
+        for decl in &program.body {
+            let body = match decl {
+                Declaration::Component(c) => {
+                    stats.components += 1;
+                    &c.body
+                }
+                Declaration::Page(p) => {
+                    stats.pages += 1;
+                    &p.body
+                }
+                Declaration::Section(s) => &s.body,
+                Declaration::Layout(l) => &l.body,
+            };
+            stats.count_nodes(body);
+        }
+        stats
+    }
+
+    /// Record the size of each generated file
+    pub(crate) fn measure(&mut self, files: &[GeneratedFile]) {
+        self.file_bytes = files.iter()
+            .map(|file| (file.path.clone(), file.content.len()))
+            .collect();
+        self.total_bytes = self.file_bytes.values().sum();
+    }
+
+    fn count_nodes(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            match node {
+                Node::Element(el) => self.count_nodes(&el.children),
+                Node::ComponentRef(r) => {
+                    self.count_nodes(&r.children);
+                    for fill in &r.slot_fills {
+                        self.count_nodes(&fill.children);
+                    }
+                }
+                Node::If(stmt) => {
+                    let mut branch = Some(stmt);
+                    while let Some(stmt) = branch {
+                        self.if_blocks += 1;
+                        self.count_nodes(&stmt.consequent);
+                        branch = match &stmt.alternate {
+                            Some(Alternate::Block(nodes)) => {
+                                self.count_nodes(nodes);
+                                None
+                            }
+                            Some(Alternate::ElseIf(elif)) => Some(elif),
+                            None => None,
+                        };
+                    }
+                }
+                Node::Each(stmt) => {
+                    self.each_blocks += 1;
+                    self.count_nodes(&stmt.body);
+                    if let Some(empty) = &stmt.empty {
+                        self.count_nodes(empty);
+                    }
+                }
+                Node::Text(_) | Node::Slot(_) | Node::Let(_) | Node::Comment(_) | Node::RawHtml(_) => {}
+            }
+        }
+    }
+}
@@ -20,6 +20,7 @@ component TodoItem {
         template_html: None,
         source_filename: None,
         split_templates: false,
+        ..Default::default()
     };
 
     let files = codegen::generate(&ast, &symbols, &options);
@@ -61,6 +62,7 @@ page home "/" {
         template_html: None,
         source_filename: None,
         split_templates: false,
+        ..Default::default()
     };
 
     let files = codegen::generate(&ast, &symbols, &options);
@@ -105,6 +107,7 @@ fn test_user_app_htms_reproduction() {
         template_html: None,
         source_filename: None,
         split_templates: false,
+        ..Default::default()
     };
 
     let files = codegen::generate(&ast, &symbols, &options);
@@ -120,3 +123,626 @@ fn test_user_app_htms_reproduction() {
     assert!(!templates.content.contains("× ×"),
             "Generated code should not contain duplicated times symbol '× ×'");
 }
+
+#[test]
+fn test_parse_to_json_keeps_type_tags() {
+    let source = r#"
+component Card(item: user) {
+  div [class: ctx.active ? "on" : "off", ...ctx.props, data-n: 1 + 2, data-size: [ctx.a, ctx.b].length] {
+    @slot
+    p { user.name }
+  }
+}
+
+page home "/" {
+  Card(user: ctx.user)
+  @if ctx.a { p {{ A }} } @else @if true { p {{ B }} } @else { p {{ C }} }
+  @each ctx.items as item { button [onClick: remove(item.id, item)] {{ x }} }
+}
+"#;
+
+    let json = parse_to_json(source).unwrap();
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert!(value["loc"]["line"].is_number());
+
+    for tag in [
+        "Component", "Page", "Element", "ComponentRef", "Text", "If", "Each", "Slot",
+        "Block", "ElseIf", "String", "Number", "Boolean", "ContextPath", "Identifier",
+        "MemberAccess", "Binary", "Ternary", "Call", "Spread",
+    ] {
+        assert!(
+            json.contains(&format!(r#""type":"{}""#, tag)),
+            "missing type tag {}",
+            tag
+        );
+    }
+}
+
+#[test]
+fn test_binary_operators_serialize_as_symbols() {
+    let json = parse_to_json(r#"page home "/" { p [data-a: 1 + 2 * 3, data-b: ctx.a ?? ctx.b, hidden: ctx.n >= 2 && ctx.n != 4] }"#).unwrap();
+    for symbol in ["+", "*", "??", ">=", "&&", "!="] {
+        assert!(json.contains(&format!(r#""operator":"{}""#, symbol)), "missing {}", symbol);
+    }
+
+    let loc = r#"{ "line": 1, "column": 1, "start": 0, "end": 5 }"#;
+    let tree = format!(
+        r#"{{ "type": "Binary", "operator": "||", "operator_loc": {loc},
+            "left": {{ "type": "Binary", "operator": "%", "operator_loc": {loc},
+                "left": {{ "type": "Number", "value": 5, "raw": "5", "loc": {loc} }},
+                "right": {{ "type": "Number", "value": 2, "raw": "2", "loc": {loc} }}, "loc": {loc} }},
+            "right": {{ "type": "Boolean", "value": true, "loc": {loc} }}, "loc": {loc} }}"#
+    );
+    let expr: ast::Expression = serde_json::from_str(&tree).unwrap();
+    let ast::Expression::Binary(or) = &expr else { panic!("Expected binary") };
+    assert_eq!(or.operator, ast::BinaryOp::Or);
+    let ast::Expression::Binary(modulo) = &*or.left else { panic!("Expected binary") };
+    assert_eq!(modulo.operator, ast::BinaryOp::Mod);
+
+    let reserialized: serde_json::Value = serde_json::to_value(&expr).unwrap();
+    assert_eq!(reserialized["operator"], "||");
+    assert_eq!(reserialized["left"]["operator"], "%");
+}
+
+#[test]
+fn test_parse_to_json_reports_errors() {
+    let errors = parse_to_json("component { }").unwrap_err();
+    assert_eq!(errors[0].code.as_deref(), Some("E002"));
+}
+
+#[test]
+fn test_single_file_inlines_script() {
+    let source = r#"
+component Card(item: user) {
+  div [class: "card"] { user.name }
+}
+
+page home "/" {
+  @each ctx.items as item {
+    Card(user: item)
+  }
+  button [onClick: save] {{ Save }}
+}
+"#;
+
+    let options = CompileOptions {
+        single_file: true,
+        ..Default::default()
+    };
+    let result = compile_with_options(source, &options);
+    assert!(result.success, "{:?}", result.diagnostics);
+    assert_eq!(result.files.len(), 1);
+
+    let file = &result.files[0];
+    assert_eq!(file.path, "home.html");
+    assert!(file.content.contains("<div id=\"app\"></div>"));
+    assert!(file.content.contains("<script type=\"module\">"));
+    assert!(file.content.contains("function HomePage(ctx, container) {"));
+    assert!(file.content.contains("initEvents();"));
+    assert!(!file.content.contains("import "));
+    assert!(!file.content.contains(": Context"));
+    assert!(!file.content.contains(" as unknown[]"));
+}
+
+#[test]
+fn test_single_file_with_route_params() {
+    let source = r#"
+page home "/" { h1 {{ Home }} }
+page post "/posts/:postId" { h1 {{ Post ${ctx.params.postId} }} }
+"#;
+
+    let options = CompileOptions {
+        single_file: true,
+        ..Default::default()
+    };
+    let result = compile_with_options(source, &options);
+    assert!(result.success, "{:?}", result.diagnostics);
+
+    let content = &result.files[0].content;
+    assert!(content.contains("function navigate(route, params = {}) {"), "{}", content);
+    assert!(content.contains("(_, name) => encodeURIComponent(params[name] ?? '')"));
+    assert!(!content.contains(": string"));
+    assert!(!content.contains(": Record<"));
+}
+
+/// Every router and events variant a bundle inlines, with strings and
+/// comments the type stripping must leave alone
+const BUNDLE_SOURCES: &[&str] = &[
+    r#"page home "/" { h1 {{ Home }} }"#,
+    r#"
+page home "/" {
+  form [onSubmit: send] {
+    input [onBlur: check, onFocus: check, placeholder: "Sign in as admin"]
+    button [onClick: save(ctx.id, "it's")] {{ Log in as "admin" }}
+  }
+}
+"#,
+    r#"page home "/" { input [bind: ctx.user.name] textarea [bind: ctx.note] }"#,
+    r#"
+component Card(item: user) {
+  div [class: ["card", user.active ? "on" : null], style: { color: "red" }] {
+    @slot
+    span {{ ${user.name ?? "Anonymous"} as of ${user.date} }}
+  }
+}
+layout Main { header {{ Site }} main { @slot } }
+page home "/" uses Main {
+  @let total = ctx.items.length * 2
+  @each ctx.items as item, i { Card(user: item) { p {{ #${i} of ${total} }} } } @else { p {{ None }} }
+  @if ctx.user { p {{ Hi }} } @else @if ctx.guest { p {{ Guest }} } @else { p {{ Who? }} }
+  ul { li [class: "x"] @for(ctx.rows as row) {{ ${row} }} }
+  p [id: "note-box"] @if(ctx.note) {{ Note: "//not a comment" }}
+  svg [viewBox: "0 0 10 10"] { circle [r: 4] }
+  @html ctx.body
+  input [bind: ctx.query, onInput: search]
+  a [href: "/posts/1", ...ctx.linkProps] {{ Post }}
+}
+page post "/posts/:postId" { h1 {{ Post ${ctx.params.postId} }} }
+page user "/users/:id/posts/:postId" { h1 {{ ${ctx.params.id} }} }
+"#,
+];
+
+#[test]
+fn test_single_file_scripts_are_plain_javascript() {
+    use std::process::Command;
+
+    // Parse each bundle's script with node, when it's installed
+    if Command::new("node").arg("--version").output().is_err() {
+        return;
+    }
+    for (i, source) in BUNDLE_SOURCES.iter().enumerate() {
+        for doctype in [Doctype::Html5, Doctype::Xhtml] {
+            let options = CompileOptions { single_file: true, doctype, ..Default::default() };
+            let result = compile_with_options(source, &options);
+            assert!(result.success, "{:?}", result.diagnostics);
+
+            let content = &result.files[0].content;
+            let start = content.find("<script type=\"module\">").unwrap() + "<script type=\"module\">".len();
+            let script = &content[start..start + content[start..].find("</script>").unwrap()];
+            let path = std::env::temp_dir().join(format!("htms-bundle-{}-{}.mjs", std::process::id(), i));
+            std::fs::write(&path, script).unwrap();
+            let output = Command::new("node").arg("--check").arg(&path).output().unwrap();
+            std::fs::remove_file(&path).ok();
+            assert!(output.status.success(), "{}\n{}", String::from_utf8_lossy(&output.stderr), script);
+        }
+    }
+}
+
+#[test]
+fn test_single_file_script_survives_closing_tags_in_text() {
+    let source = r#"page home "/" { p [title: "</script>"] {{ a </script><b>x }} }"#;
+    let result = compile_with_options(source, &CompileOptions { single_file: true, ..Default::default() });
+    assert!(result.success, "{:?}", result.diagnostics);
+
+    // The script only ends at its own closing tag, after the runtime setup
+    let content = &result.files[0].content;
+    let script = &content[content.find("<script type=\"module\">").unwrap()..];
+    let script = &script[..script.find("</script>").unwrap()];
+    assert!(script.contains("window.htms = "), "{}", content);
+    assert!(script.contains("'a <\\/script><b>x'"), "{}", script);
+    assert!(script.contains("'<\\/script>'"));
+}
+
+#[test]
+fn test_a11y_lints_can_be_disabled() {
+    let source = r#"page home "/" { img [src: "/logo.png"] }"#;
+    let has_a11y = |result: &CompileResult| {
+        result.diagnostics.iter().any(|d| d.code.as_deref() == Some("A001"))
+    };
+
+    assert!(has_a11y(&compile(source)));
+
+    let options = CompileOptions {
+        lint_a11y: false,
+        ..Default::default()
+    };
+    assert!(!has_a11y(&compile_with_options(source, &options)));
+}
+
+#[test]
+fn test_diagnostics_carry_source_filename() {
+    let options = CompileOptions {
+        source_filename: Some("home.htms".to_string()),
+        ..Default::default()
+    };
+    let result = compile_with_options("page home \"/\" { Missing }", &options);
+    assert!(!result.success);
+    assert!(result.diagnostics.iter().all(|d| d.file.as_deref() == Some("home.htms")));
+
+    let jsonl = diagnostics_to_jsonl(&result.diagnostics);
+    assert_eq!(jsonl.lines().count(), result.diagnostics.len());
+    assert!(jsonl.lines().all(|line| line.contains(r#""file":"home.htms""#)));
+
+    // Without a filename the field is omitted entirely
+    let result = compile("page home \"/\" { Missing }");
+    assert!(!diagnostics_to_jsonl(&result.diagnostics).contains("\"file\""));
+}
+
+#[test]
+fn test_html_mode_notes_dropped_event_handlers() {
+    let source = r#"page home "/" { button [onClick: save] {{ Save }} }"#;
+    let has_note = |result: &CompileResult| {
+        result.diagnostics.iter().any(|d| d.code.as_deref() == Some("I002"))
+    };
+
+    let options = CompileOptions {
+        output_format: OutputFormat::Html,
+        ..Default::default()
+    };
+    let result = compile_with_options(source, &options);
+    assert!(result.success);
+    assert!(has_note(&result));
+
+    assert!(!has_note(&compile(source)));
+}
+
+#[test]
+fn test_validate_output() {
+    let source = r##"
+        component Card {
+            div [class: "card"] {
+                h2 {{ ${ctx.title} }}
+                slot
+            }
+        }
+        page home "/" {
+            Card { p {{ Body & "more" }} }
+            img [src: "/a.png", alt: ""]
+            input [type: "checkbox", checked: true, disabled: false]
+            @each ctx.items as item { li {{ ${item} }} }
+            a [href: "#/about"] {{ About }}
+        }
+        page about "/about" { p {{ About }} }
+    "##;
+
+    for options in [
+        CompileOptions { output_format: OutputFormat::Html, ..Default::default() },
+        CompileOptions { output_format: OutputFormat::Html, split_templates: true, ..Default::default() },
+        CompileOptions { single_file: true, ..Default::default() },
+    ] {
+        let result = compile_with_options(source, &CompileOptions { validate_output: true, ..options });
+        assert!(result.success, "{:?}", result.diagnostics);
+        assert!(result.diagnostics.iter().all(|d| d.code.as_deref() != Some("E021")));
+    }
+
+    let broken = "<div class=\"card\">\n  <p>Text</div>\n";
+    assert!(htms_compiler::codegen::check_well_formed(broken).is_err());
+}
+
+#[test]
+fn test_emit_context_types() {
+    let source = r#"page home "/" { @each ctx.items as item { p {{ ${item.name} }} } }"#;
+    let has_types = |result: &CompileResult| result.files.iter().any(|f| f.path == "context.d.ts");
+
+    assert!(!has_types(&compile(source)));
+
+    let options = CompileOptions {
+        emit_context_types: true,
+        ..Default::default()
+    };
+    let result = compile_with_options(source, &options);
+    let types = result.files.iter().find(|f| f.path == "context.d.ts").unwrap();
+    assert!(types.content.contains("export interface HomePageContext {\n  items: unknown[];\n}"));
+}
+
+#[test]
+fn test_emit_events_manifest() {
+    let source = r#"
+        component Row(item: row) { li { button [onClick: remove(row.id)] {{ Remove }} } }
+        page home "/" {
+            @if ctx.empty { p {{ Nothing here }} } @else { button [onClick: refresh] {{ Refresh }} }
+            @each ctx.rows as row { Row(item: row) }
+        }
+    "#;
+    assert!(!compile(source).files.iter().any(|f| f.path == "events.json"));
+
+    let options = CompileOptions {
+        emit_events_manifest: true,
+        ..Default::default()
+    };
+    let result = compile_with_options(source, &options);
+    let manifest = result.files.iter().find(|f| f.path == "events.json").unwrap();
+    let manifest: serde_json::Value = serde_json::from_str(&manifest.content).unwrap();
+    assert_eq!(manifest["handlers"], serde_json::json!([
+        {
+            "declaration": "Row",
+            "path": [0, 0],
+            "tag": "button",
+            "event": "click",
+            "modifiers": [],
+            "action": "remove",
+            "arguments": ["row.id"],
+        },
+        {
+            "declaration": "home",
+            "path": [0, 1, 0],
+            "tag": "button",
+            "event": "click",
+            "modifiers": [],
+            "action": "refresh",
+            "arguments": [],
+        },
+    ]));
+}
+
+#[test]
+fn test_tokenize_to_json() {
+    let json = tokenize_to_json("component NavBar { }").unwrap();
+    let tokens: Vec<serde_json::Value> = serde_json::from_str(&json).unwrap();
+    let kinds: Vec<&str> = tokens.iter().map(|t| t["kind"].as_str().unwrap()).collect();
+
+    assert_eq!(kinds, ["Component", "ComponentName", "LBrace", "RBrace", "Eof"]);
+    assert_eq!(tokens[1]["value"], "NavBar");
+    assert_eq!(tokens[1]["location"]["column"], 11);
+}
+
+#[test]
+fn test_output_is_deterministic() {
+    let source = r##"
+component NavBar { nav { a [href: "#/"] {{ Home }} } }
+page home "/" {
+  NavBar
+  input [aria-label: "Name", onInput: typed, onFocus: focused, onBlur: blurred]
+  button [onClick: save, onMouseenter: hover] {{ Save }}
+}
+page about "/about" { NavBar p {{ About }} }
+"##;
+
+    for options in [
+        CompileOptions::default(),
+        CompileOptions {
+            output_format: OutputFormat::Html,
+            split_templates: true,
+            ..Default::default()
+        },
+    ] {
+        let first = compile_with_options(source, &options);
+        let second = compile_with_options(source, &options);
+        let listing = |result: &CompileResult| {
+            result.files.iter().map(|f| (f.path.clone(), f.content.clone())).collect::<Vec<_>>()
+        };
+
+        assert_eq!(listing(&first), listing(&second));
+        let paths: Vec<&String> = first.files.iter().map(|f| &f.path).collect();
+        let mut sorted = paths.clone();
+        sorted.sort();
+        assert_eq!(paths, sorted);
+    }
+}
+
+#[test]
+fn test_folding_ranges_nest() {
+    let source = "component Card {\n  div {\n    p {{ Hi }}\n  }\n  img [src: \"/a.png\", alt: \"\"]\n}\n";
+    let tokens = htms_compiler::lexer::tokenize(source).unwrap();
+    let program = htms_compiler::parser::parse(&tokens).unwrap();
+
+    let ranges = htms_compiler::ast::folding_ranges(&program);
+    let text: Vec<&str> = ranges.iter().map(|&(start, end)| &source[start..end]).collect();
+    assert_eq!(text, vec![source.trim_end(), "div {\n    p {{ Hi }}\n  }", "p {{ Hi }}"]);
+}
+
+#[test]
+fn test_node_at_finds_the_innermost_node() {
+    use htms_compiler::ast::{Expression, Node, NodeRef};
+
+    let source = "component Card(item: card) { div [class: item.tone] { p {{ Hi }} } }\npage home \"/\" { @if ctx.show { Card(item: ctx.card) } }";
+    let tokens = htms_compiler::lexer::tokenize(source).unwrap();
+    let program = htms_compiler::parser::parse(&tokens).unwrap();
+    let at = |text: &str| program.node_at(source.find(text).unwrap());
+
+    assert!(matches!(at("p {{"), Some(NodeRef::Node(Node::Element(el))) if el.tag == "p"));
+    assert!(matches!(at("Hi"), Some(NodeRef::Node(Node::Text(_)))));
+    assert!(matches!(at("item.tone"), Some(NodeRef::Expression(Expression::ContextPath(_)))));
+    assert!(matches!(at("ctx.show"), Some(NodeRef::Expression(_))));
+    assert!(matches!(at("ctx.card"), Some(NodeRef::Expression(e)) if e.loc().start == source.find("ctx.card").unwrap()));
+    assert!(matches!(at("component"), Some(NodeRef::Declaration(_))));
+    assert!(program.node_at(source.len()).is_none());
+
+    let reference = program.node_at(source.rfind("Card(item").unwrap()).unwrap();
+    assert_eq!(reference.referenced_name(), Some("Card"));
+    assert_eq!(program.declaration("Card").unwrap().loc().start, 0);
+}
+
+#[test]
+fn test_lex_keeps_going_past_errors() {
+    let source = "component A { // note\n  p ~ {{ Hi }}\n}";
+    let (tokens, diagnostics) = lex(source);
+    let kinds: Vec<&str> = tokens.iter().map(|t| t.kind.as_str()).collect();
+
+    assert_eq!(kinds, vec![
+        "Component", "ComponentName", "LBrace", "LineComment", "Newline",
+        "Identifier", "Error", "TextOpen", "TextContent", "TextClose", "Newline",
+        "RBrace", "Eof",
+    ]);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].code.as_deref(), Some("E001"));
+
+    let comment = &tokens[3];
+    assert_eq!(&source[comment.start..comment.end], "// note");
+    assert_eq!((comment.line, comment.column), (1, 15));
+}
+
+#[test]
+fn test_compile_expression_to_ts() {
+    let ts = compile_expression_to_ts(r#"ctx.a > 0 ? "yes" : "no""#).unwrap();
+    assert_eq!(ts, "((ctx.a > 0) ? 'yes' : 'no')");
+
+    let errors = compile_expression_to_ts("ctx.a >").unwrap_err();
+    assert_eq!(errors[0].code.as_deref(), Some("E002"));
+}
+
+#[test]
+fn test_render_with_context() {
+    let source = r#"
+        component Item(item: todo) {
+            li [class: todo.done ? "done" : "open"] {{ ${todo.title} }}
+        }
+        page home "/" {
+            h1 {{ ${ctx.title} }}
+            ul {
+                @each ctx.todos as todo { Item(item: todo) } @else { li {{ Nothing to do }} }
+            }
+        }
+    "#;
+    let ast = parser::parse(&lexer::tokenize(source).unwrap()).unwrap();
+    let context = serde_json::json!({
+        "title": "Today",
+        "todos": [{ "title": "Write docs", "done": true }, { "title": "Ship", "done": false }],
+    });
+    let files = codegen::render_with_context(&ast, &context, &CompileOptions::default());
+
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].path, "home.html");
+    let html = &files[0].content;
+    assert!(html.contains("<title>Home</title>"));
+    assert!(html.contains("<h1>Today</h1>"));
+    assert!(html.contains("<li class=\"done\">Write docs</li>\n    <li class=\"open\">Ship</li>"));
+    assert!(!html.contains("Nothing to do"));
+    assert_eq!(codegen::check_well_formed(html), Ok(()));
+}
+
+#[test]
+fn test_into_result() {
+    let files = compile(r#"page home "/" { h1 {{ Hello }} }"#).into_result().unwrap();
+    assert!(files.iter().any(|f| f.path == "templates.ts"));
+
+    let errors = compile(r#"page home "/" { img [src: "a.png"] Missing }"#).into_result().unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].severity, Severity::Error);
+    assert!(errors[0].message.contains("Missing"), "{}", errors[0].message);
+}
+
+#[test]
+fn test_severity_summary() {
+    assert!(Severity::Error > Severity::Warning);
+    assert!(Severity::Warning > Severity::Info);
+    assert_eq!([Severity::Warning, Severity::Error, Severity::Info].iter().max(), Some(&Severity::Error));
+
+    let clean = compile(r#"page home "/" { h1 {{ Hello }} }"#);
+    assert_eq!(clean.max_severity(), None);
+    assert_eq!((clean.error_count(), clean.warning_count()), (0, 0));
+
+    let warned = compile(r#"page home "/" { blink {{ Hello }} }"#);
+    assert_eq!(warned.max_severity(), Some(Severity::Warning));
+    assert_eq!((warned.error_count(), warned.warning_count()), (0, 1));
+
+    let failed = compile(r#"page home "/" { blink {{ Hello }} Missing }"#);
+    assert_eq!(failed.max_severity(), Some(Severity::Error));
+    assert_eq!((failed.error_count(), failed.warning_count()), (1, 1));
+}
+
+#[test]
+fn test_diagnostic_codes() {
+    assert_eq!(DiagnosticCode::ParseError.code(), "E002");
+    assert_eq!(DiagnosticCode::ParseError.default_severity(), Severity::Error);
+    assert_eq!(DiagnosticCode::MissingAlt.default_severity(), Severity::Warning);
+    assert_eq!(DiagnosticCode::RedundantRole.default_severity(), Severity::Info);
+
+    let location = Location { line: 1, column: 1, start: 0, end: 0 };
+    let diagnostic = Diagnostic::new(DiagnosticCode::UnknownTag, "Unknown HTML tag 'blink'", location);
+    assert_eq!(diagnostic.severity, Severity::Warning);
+    assert!(serde_json::to_string(&diagnostic).unwrap().contains(r#""code":"W002""#));
+
+    // The semantic hints and the dropped-handlers note have codes of their own
+    assert_ne!(DiagnosticCode::ClickableElement.code(), DiagnosticCode::DroppedEventHandlers.code());
+}
+
+#[test]
+fn test_auto_testids_match_across_outputs() {
+    let source = r#"
+        component UserCard { div [class: "card"] { h2 {{ Name }} p [data-testid: "bio"] {{ Bio }} p {{ Joined }} } }
+        page home "/" { UserCard }
+    "#;
+    let options = CompileOptions { auto_testids: true, ..Default::default() };
+    let templates = compile_with_options(source, &options);
+    let templates = &templates.files.iter().find(|f| f.path == "templates.ts").unwrap().content;
+    for id in ["user-card-div-1", "user-card-h2-1", "user-card-p-1"] {
+        assert!(templates.contains(&format!("'data-testid', '{}", id)), "{} missing:\n{}", id, templates);
+    }
+    assert!(templates.contains("'data-testid', 'bio'"));
+    assert!(!templates.contains("user-card-p-2"));
+
+    let html = compile_with_options(source, &CompileOptions { output_format: OutputFormat::Html, ..options });
+    let html = &html.files.iter().find(|f| f.path.ends_with(".html")).unwrap().content;
+    assert!(html.contains(r#"<div class="card" data-testid="user-card-div-1">"#), "{}", html);
+    assert!(html.contains(r#"<p data-testid="bio">Bio</p>"#));
+    assert!(html.contains(r#"<p data-testid="user-card-p-1">Joined</p>"#));
+}
+
+#[test]
+fn test_module_formats() {
+    let source = r#"
+        component Card { div [onClick: pick] { input [bind: ctx.name] } }
+        page home "/" { Card }
+    "#;
+    let file = |result: &CompileResult, path: &str| {
+        result.files.iter().find(|f| f.path == path).unwrap().content.clone()
+    };
+
+    let esm = compile(source);
+    assert!(file(&esm, "router.ts").contains("import { HomePage } from './templates';"));
+    assert!(file(&esm, "templates.ts").contains("export function Card("));
+    assert!(file(&esm, "events.ts").contains("import { getContext, setContext, rerender } from './router';"));
+    assert!(esm.files.iter().all(|f| !f.content.contains("require(") && !f.content.contains("module.exports")));
+
+    let cjs = compile_with_options(source, &CompileOptions { module_format: ModuleFormat::Cjs, ..Default::default() });
+    let router = file(&cjs, "router.ts");
+    // `import = require` gives the binding the templates module's types, where a bare `require` is `any`
+    assert!(router.contains("import templatesModule = require('./templates');\nconst { HomePage } = templatesModule;\n"));
+    // Exports stay declarations, which the TypeScript compiler assigns to `exports` before any require runs
+    assert!(router.contains("\nexport function getContext("));
+    assert!(router.contains("\nexport const Routes = {"));
+    let templates = file(&cjs, "templates.ts");
+    assert!(templates.contains("import actionsModule = require('./actions');\nconst { actions } = actionsModule;\n"));
+    assert!(templates.contains("import eventsModule = require('./events');\nconst { setNestedValue } = eventsModule;\n"));
+    assert!(file(&cjs, "events.ts").contains("import routerModule = require('./router');\nconst { getContext, setContext, rerender } = routerModule;\n"));
+    assert!(cjs.files.iter().all(|f| !f.content.contains("import {") && !f.content.contains("exports.")));
+}
+
+#[test]
+fn test_best_effort_skips_declarations_with_errors() {
+    let source = r#"
+        component Card { p {{ ok }} }
+        component Broken { Missing }
+        page home "/" { Card }
+        page about "/about" { Broken }
+    "#;
+
+    let strict = compile(source);
+    assert!(!strict.success);
+    assert!(strict.files.is_empty());
+
+    let result = compile_with_options(source, &CompileOptions { best_effort: true, ..Default::default() });
+    assert!(!result.success);
+    assert!(result.diagnostics.iter().any(|d| d.message.contains("Missing")));
+    let templates = &result.files.iter().find(|f| f.path == "templates.ts").unwrap().content;
+    assert!(templates.contains("export function Card("));
+    assert!(templates.contains("export function HomePage("));
+    assert!(!templates.contains("Broken"));
+    assert!(!templates.contains("AboutPage"));
+    let router = &result.files.iter().find(|f| f.path == "router.ts").unwrap().content;
+    assert!(!router.contains("/about"));
+}
+
+#[test]
+fn test_collect_stats() {
+    let source = r#"
+        component Card { p {{ ok }} }
+        page home "/" {
+            Card
+            @if ctx.a { p {{ A }} } @else @if ctx.b { p {{ B }} }
+            @each ctx.items as item { li { item.name } }
+        }
+    "#;
+
+    let plain = compile(source);
+    assert!(plain.stats.is_none());
+    assert!(!serde_json::to_string(&plain).unwrap().contains("\"stats\""));
+
+    let result = compile_with_options(source, &CompileOptions { collect_stats: true, ..Default::default() });
+    let stats = result.stats.unwrap();
+    assert_eq!((stats.components, stats.pages, stats.each_blocks, stats.if_blocks), (1, 1, 1, 2));
+    assert_eq!(stats.file_bytes.len(), result.files.len());
+    assert_eq!(stats.file_bytes["templates.ts"], result.files.iter().find(|f| f.path == "templates.ts").unwrap().content.len());
+    assert_eq!(stats.total_bytes, result.files.iter().map(|f| f.content.len()).sum::<usize>());
+}
@@ -0,0 +1,27 @@
+use htms_compiler::compile;
+
+#[test]
+fn test_render_parse_error_frame() {
+    let source = "component NavBar {\n  div [class \"x\"]\n}\n";
+    let result = compile(source);
+    assert!(!result.success);
+
+    let rendered: Vec<String> = result.diagnostics.iter()
+        .map(|d| d.render(source))
+        .collect();
+
+    assert_eq!(
+        rendered[0],
+        "error[E002]: Expected ']' (got string)\n --> 2:14\n  |\n2 |   div [class \"x\"]\n  |              ^^^\n"
+    );
+}
+
+#[test]
+fn test_render_lexer_error_frame() {
+    let source = "page home \"/\" {\n  p { {{ Hi }} } ~\n}\n";
+    let result = compile(source);
+
+    let rendered = result.diagnostics[0].render(source);
+    assert!(rendered.starts_with("error[E001]: Unexpected character: '~'\n --> 2:18\n"));
+    assert!(rendered.ends_with("2 |   p { {{ Hi }} } ~\n  |                  ^\n"));
+}
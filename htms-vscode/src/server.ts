@@ -166,6 +166,12 @@ connection.onCompletion(
         detail: 'Declare a section',
         insertText: 'section ${1:SectionName} {\n\t$0\n}'
       },
+      {
+        label: 'layout',
+        kind: CompletionItemKind.Keyword,
+        detail: 'Declare a layout that pages render inside',
+        insertText: 'layout ${1:LayoutName} {\n\t$0\n\t@slot\n}'
+      },
       {
         label: 'page',
         kind: CompletionItemKind.Keyword,